@@ -271,7 +271,7 @@ pub mod db {
 
         // Create collection
         let collection = collection_from_geojson(collection_id, &geojson, s_srs)?;
-        db.delete_collection(&collection.id).await?;
+        db.delete_collection(&collection.id, None).await?;
         db.create_collection(&collection).await?;
 
         // Load features
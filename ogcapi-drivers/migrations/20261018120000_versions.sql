@@ -0,0 +1,15 @@
+-- Row versions used as entity tags for optimistic concurrency
+ALTER TABLE meta.collections ADD COLUMN version bigint NOT NULL DEFAULT 1;
+
+DO $$
+DECLARE
+    t record;
+BEGIN
+    FOR t IN SELECT tablename FROM pg_tables WHERE schemaname = 'items' LOOP
+        EXECUTE format(
+            'ALTER TABLE items.%I ADD COLUMN IF NOT EXISTS version bigint NOT NULL DEFAULT 1',
+            t.tablename
+        );
+    END LOOP;
+END
+$$;
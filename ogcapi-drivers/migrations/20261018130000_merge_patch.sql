@@ -0,0 +1,32 @@
+-- JSON Merge Patch (RFC 7396) of jsonb values
+CREATE OR REPLACE FUNCTION jsonb_merge_patch(target jsonb, patch jsonb)
+RETURNS jsonb
+LANGUAGE plpgsql IMMUTABLE AS $$
+DECLARE
+    result jsonb;
+    member record;
+BEGIN
+    IF patch IS NULL OR jsonb_typeof(patch) <> 'object' THEN
+        RETURN patch;
+    END IF;
+
+    IF target IS NULL OR jsonb_typeof(target) <> 'object' THEN
+        result := '{}'::jsonb;
+    ELSE
+        result := target;
+    END IF;
+
+    FOR member IN SELECT key, value FROM jsonb_each(patch) LOOP
+        IF jsonb_typeof(member.value) = 'null' THEN
+            result := result - member.key;
+        ELSE
+            result := result || jsonb_build_object(
+                member.key,
+                jsonb_merge_patch(result -> member.key, member.value)
+            );
+        END IF;
+    END LOOP;
+
+    RETURN result;
+END
+$$;
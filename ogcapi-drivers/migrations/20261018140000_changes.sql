@@ -0,0 +1,41 @@
+-- Notifications of feature changes on the `ogcapi_items` channel
+CREATE OR REPLACE FUNCTION meta.notify_item_change() RETURNS trigger AS $$
+DECLARE
+    item record;
+    extent box2d;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        item := OLD;
+    ELSE
+        item := NEW;
+    END IF;
+
+    extent := ST_Transform(item.geom, 4326)::box2d;
+
+    -- payloads are limited in size, the feature is read by the listener
+    PERFORM pg_notify('ogcapi_items', json_build_object(
+        'type', CASE TG_OP WHEN 'INSERT' THEN 'created' WHEN 'UPDATE' THEN 'updated' ELSE 'deleted' END,
+        'collection', TG_TABLE_NAME,
+        'id', item.id,
+        'bbox', CASE WHEN extent IS NOT NULL THEN
+            json_build_array(ST_XMin(extent), ST_YMin(extent), ST_XMax(extent), ST_YMax(extent))
+        END
+    )::text);
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t record;
+BEGIN
+    FOR t IN SELECT tablename FROM pg_tables WHERE schemaname = 'items' LOOP
+        EXECUTE format(
+            'CREATE TRIGGER notify_change AFTER INSERT OR UPDATE OR DELETE ON items.%I '
+            'FOR EACH ROW EXECUTE FUNCTION meta.notify_item_change()',
+            t.tablename
+        );
+    END LOOP;
+END
+$$;
@@ -0,0 +1,20 @@
+-- History of the features of versioned collections, maintained by triggers
+CREATE SCHEMA IF NOT EXISTS history;
+
+CREATE OR REPLACE FUNCTION meta.record_item_history() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        EXECUTE format(
+            'UPDATE history.%I SET valid_to = now() WHERE id = $1 AND valid_to IS NULL',
+            TG_TABLE_NAME
+        ) USING OLD.id;
+    END IF;
+
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        EXECUTE format('INSERT INTO history.%I SELECT ($1).*, now(), NULL', TG_TABLE_NAME)
+            USING NEW;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
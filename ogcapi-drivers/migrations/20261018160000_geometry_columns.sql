@@ -0,0 +1,28 @@
+-- Record the history of features by column name, as the items tables of
+-- collections gain generated geometry columns after their history tables
+-- were created. Generated columns are computed by the history tables.
+CREATE OR REPLACE FUNCTION meta.record_item_history() RETURNS trigger AS $$
+DECLARE
+    columns text;
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        EXECUTE format(
+            'UPDATE history.%I SET valid_to = now() WHERE id = $1 AND valid_to IS NULL',
+            TG_TABLE_NAME
+        ) USING OLD.id;
+    END IF;
+
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        SELECT string_agg(quote_ident(attname), ', ' ORDER BY attnum) INTO columns
+        FROM pg_attribute
+        WHERE attrelid = TG_RELID AND attnum > 0 AND NOT attisdropped AND attgenerated = '';
+
+        EXECUTE format(
+            'INSERT INTO history.%I (%s, valid_from) SELECT %s, now() FROM (SELECT ($1).*) item',
+            TG_TABLE_NAME, columns, columns
+        ) USING NEW;
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
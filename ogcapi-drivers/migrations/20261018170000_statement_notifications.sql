@@ -0,0 +1,82 @@
+-- Notify feature changes once per statement instead of once per row, so bulk
+-- ingest with COPY does not queue a notification for every feature. Changes
+-- are batched into notifications below the payload limit of 8000 bytes.
+CREATE OR REPLACE FUNCTION meta.notify_item_changes() RETURNS trigger AS $$
+DECLARE
+    item record;
+    change jsonb;
+    changes jsonb := '[]'::jsonb;
+    size integer := 0;
+BEGIN
+    FOR item IN SELECT id, ST_Transform(geom, 4326)::box2d AS extent FROM changed_items LOOP
+        change := jsonb_build_object(
+            'id', item.id,
+            'bbox', CASE WHEN item.extent IS NOT NULL THEN
+                jsonb_build_array(ST_XMin(item.extent), ST_YMin(item.extent), ST_XMax(item.extent), ST_YMax(item.extent))
+            END
+        );
+        changes := changes || jsonb_build_array(change);
+        size := size + octet_length(change::text);
+
+        IF size > 6000 THEN
+            PERFORM meta.notify_items(TG_OP, TG_TABLE_NAME, changes);
+            changes := '[]'::jsonb;
+            size := 0;
+        END IF;
+    END LOOP;
+
+    IF size > 0 THEN
+        PERFORM meta.notify_items(TG_OP, TG_TABLE_NAME, changes);
+    END IF;
+
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- Notification of a batch of changes on the `ogcapi_items` channel, the
+-- features are read by the listener
+CREATE OR REPLACE FUNCTION meta.notify_items(op text, collection text, changes jsonb) RETURNS void AS $$
+    SELECT pg_notify('ogcapi_items', jsonb_build_object(
+        'type', CASE op WHEN 'INSERT' THEN 'created' WHEN 'UPDATE' THEN 'updated' ELSE 'deleted' END,
+        'collection', collection,
+        'changes', changes
+    )::text);
+$$ LANGUAGE sql;
+
+-- Create the statement triggers of an items table, transition tables allow
+-- a single event per trigger
+CREATE OR REPLACE FUNCTION meta.notify_item_changes_on(items regclass) RETURNS void AS $$
+BEGIN
+    EXECUTE format(
+        'CREATE TRIGGER notify_insert AFTER INSERT ON %s '
+        'REFERENCING NEW TABLE AS changed_items '
+        'FOR EACH STATEMENT EXECUTE FUNCTION meta.notify_item_changes()',
+        items
+    );
+    EXECUTE format(
+        'CREATE TRIGGER notify_update AFTER UPDATE ON %s '
+        'REFERENCING NEW TABLE AS changed_items '
+        'FOR EACH STATEMENT EXECUTE FUNCTION meta.notify_item_changes()',
+        items
+    );
+    EXECUTE format(
+        'CREATE TRIGGER notify_delete AFTER DELETE ON %s '
+        'REFERENCING OLD TABLE AS changed_items '
+        'FOR EACH STATEMENT EXECUTE FUNCTION meta.notify_item_changes()',
+        items
+    );
+END
+$$ LANGUAGE plpgsql;
+
+DO $$
+DECLARE
+    t record;
+BEGIN
+    FOR t IN SELECT tablename FROM pg_tables WHERE schemaname = 'items' LOOP
+        EXECUTE format('DROP TRIGGER IF EXISTS notify_change ON items.%I', t.tablename);
+        PERFORM meta.notify_item_changes_on(format('items.%I', t.tablename)::regclass);
+    END LOOP;
+END
+$$;
+
+DROP FUNCTION meta.notify_item_change();
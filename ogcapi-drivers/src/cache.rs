@@ -0,0 +1,420 @@
+//! Caching of collection metadata
+//!
+//! Collection documents and storage srids are looked up on almost every
+//! request. A [`CollectionCache`] keeps them for a time to live and is
+//! invalidated by the writes of the driver that owns it. Entries may be stale
+//! for up to the time to live if collections are modified by another process,
+//! so caching is disabled unless a time to live is configured.
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "features")]
+use futures::stream::BoxStream;
+use ogcapi_types::common::{Collection, Collections, Query as CollectionQuery};
+#[cfg(feature = "features")]
+use ogcapi_types::{
+    common::Crs,
+    features::{
+        Feature, FeatureCollection, FeatureVersion, Query as FeatureQuery, Queryables, Schema,
+    },
+};
+#[cfg(feature = "features")]
+use serde_json::{Map, Value};
+
+use crate::CollectionTransactions;
+#[cfg(feature = "features")]
+use crate::FeatureTransactions;
+
+/// Cache of collection documents and storage srids
+///
+/// Clones share their entries. The default cache is disabled.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionCache {
+    ttl: Duration,
+    entries: Arc<RwLock<HashMap<String, Entry>>>,
+    /// Incremented on invalidation, values loaded before are not inserted
+    generation: Arc<AtomicU64>,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    collection: Option<(Instant, Collection)>,
+    srid: Option<(Instant, i32)>,
+}
+
+impl CollectionCache {
+    /// Cache entries for a time to live, a zero duration disables caching
+    pub fn new(ttl: Duration) -> Self {
+        CollectionCache {
+            ttl,
+            ..Default::default()
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Cached collection document, loaded if it is missing or expired
+    ///
+    /// Collections that do not exist are not cached.
+    pub async fn collection<F>(&self, id: &str, load: F) -> anyhow::Result<Option<Collection>>
+    where
+        F: Future<Output = anyhow::Result<Option<Collection>>>,
+    {
+        if let Some(collection) = self.get(id, |entry| entry.collection.as_ref()) {
+            return Ok(Some(collection));
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let collection = load.await?;
+        if let Some(collection) = collection.as_ref() {
+            self.insert(id, generation, |entry| {
+                entry.collection = Some((Instant::now(), collection.to_owned()))
+            });
+        }
+
+        Ok(collection)
+    }
+
+    /// Cached storage srid of a collection, loaded if it is missing or expired
+    pub async fn srid<F>(&self, id: &str, load: F) -> anyhow::Result<i32>
+    where
+        F: Future<Output = anyhow::Result<i32>>,
+    {
+        if let Some(srid) = self.get(id, |entry| entry.srid.as_ref()) {
+            return Ok(srid);
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let srid = load.await?;
+        self.insert(id, generation, |entry| {
+            entry.srid = Some((Instant::now(), srid))
+        });
+
+        Ok(srid)
+    }
+
+    /// Remove the entries of a collection
+    pub fn invalidate(&self, id: &str) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.remove(id);
+    }
+
+    /// Remove all entries
+    pub fn clear(&self) {
+        let mut entries = self.entries.write().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    fn get<T: Clone>(
+        &self,
+        id: &str,
+        field: impl Fn(&Entry) -> Option<&(Instant, T)>,
+    ) -> Option<T> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(id)
+            .and_then(field)
+            .filter(|(cached, _)| cached.elapsed() < self.ttl)
+            .map(|(_, value)| value.to_owned())
+    }
+
+    /// Insert a value loaded at a generation, unless the cache was
+    /// invalidated in the meantime
+    fn insert(&self, id: &str, generation: u64, f: impl FnOnce(&mut Entry)) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.write().unwrap();
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+        f(entries.entry(id.to_owned()).or_default())
+    }
+}
+
+/// Driver decorator caching the collection documents of another driver
+///
+/// Writes through the decorator invalidate the cache, feature transactions
+/// are passed through.
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    inner: T,
+    cache: CollectionCache,
+}
+
+impl<T> Cached<T> {
+    /// Cache the collections of a driver for a time to live
+    pub fn new(inner: T, ttl: Duration) -> Self {
+        Cached {
+            inner,
+            cache: CollectionCache::new(ttl),
+        }
+    }
+
+    pub fn cache(&self) -> &CollectionCache {
+        &self.cache
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: CollectionTransactions> CollectionTransactions for Cached<T> {
+    async fn create_collection(&self, collection: &Collection) -> anyhow::Result<String> {
+        let id = self.inner.create_collection(collection).await?;
+        self.cache.invalidate(&id);
+        Ok(id)
+    }
+
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        self.cache
+            .collection(id, self.inner.read_collection(id))
+            .await
+    }
+
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let result = self.inner.update_collection(collection, if_match).await;
+        self.cache.invalidate(&collection.id);
+        result
+    }
+
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        let result = self.inner.delete_collection(id, if_match).await;
+        self.cache.invalidate(id);
+        result
+    }
+
+    async fn list_collections(&self, query: &CollectionQuery) -> anyhow::Result<Collections> {
+        self.inner.list_collections(query).await
+    }
+
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        // entity tags guard writes and are never cached
+        self.inner.collection_etag(id).await
+    }
+}
+
+#[cfg(feature = "features")]
+#[async_trait::async_trait]
+impl<T: FeatureTransactions> FeatureTransactions for Cached<T> {
+    async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String> {
+        self.inner.create_feature(feature).await
+    }
+
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        self.inner.create_features(collection_id, features).await
+    }
+
+    async fn read_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Feature>> {
+        self.inner.read_feature(collection_id, id, crs).await
+    }
+
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.inner.update_feature(feature, if_match).await
+    }
+
+    async fn patch_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .patch_feature(collection_id, id, patch, if_match)
+            .await
+    }
+
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.inner.delete_feature(collection_id, id, if_match).await
+    }
+
+    async fn feature_etag(&self, collection_id: &str, id: &str) -> anyhow::Result<Option<String>> {
+        self.inner.feature_etag(collection_id, id).await
+    }
+
+    async fn feature_versions(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Vec<FeatureVersion>>> {
+        self.inner.feature_versions(collection_id, id, crs).await
+    }
+
+    async fn list_items(
+        &self,
+        collection_id: &str,
+        query: &FeatureQuery,
+    ) -> anyhow::Result<FeatureCollection> {
+        self.inner.list_items(collection_id, query).await
+    }
+
+    fn stream_items<'a>(
+        &'a self,
+        collection_id: &'a str,
+        query: &'a FeatureQuery,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        self.inner.stream_items(collection_id, query)
+    }
+
+    fn keyset_pagination(&self, collection_id: &str) -> bool {
+        self.inner.keyset_pagination(collection_id)
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        self.inner.queryables(collection_id).await
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        self.inner.schema(collection_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(title: &str) -> Collection {
+        Collection {
+            id: "places".to_string(),
+            title: Some(title.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn expiry_and_invalidation() {
+        let cache = CollectionCache::new(Duration::from_millis(50));
+
+        let cached = cache
+            .collection("places", async { Ok(Some(collection("first"))) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("first"));
+
+        // served from the cache
+        let cached = cache
+            .collection("places", async { Ok(Some(collection("second"))) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("first"));
+        assert_eq!(
+            cache.srid("places", async { Ok(2056) }).await.unwrap(),
+            2056
+        );
+        assert_eq!(
+            cache.srid("places", async { Ok(4326) }).await.unwrap(),
+            2056
+        );
+
+        cache.invalidate("places");
+        assert_eq!(
+            cache.srid("places", async { Ok(4326) }).await.unwrap(),
+            4326
+        );
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let cached = cache
+            .collection("places", async { Ok(Some(collection("third"))) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("third"));
+
+        // missing collections are not cached
+        let cache = CollectionCache::new(Duration::from_secs(60));
+        assert!(
+            cache
+                .collection("places", async { Ok(None) })
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            cache
+                .collection("places", async { Ok(Some(collection("created"))) })
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn invalidation_during_load() {
+        let cache = CollectionCache::new(Duration::from_secs(60));
+
+        // a load that started before the invalidation does not fill the cache
+        let cached = cache
+            .collection("places", async {
+                let stale = collection("stale");
+                cache.invalidate("places");
+                Ok(Some(stale))
+            })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("stale"));
+
+        let cached = cache
+            .collection("places", async { Ok(Some(collection("fresh"))) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("fresh"));
+        let cached = cache
+            .collection("places", async { Ok(Some(collection("other"))) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap().title.as_deref(), Some("fresh"));
+    }
+
+    #[tokio::test]
+    async fn disabled() {
+        let cache = CollectionCache::default();
+        assert_eq!(
+            cache.srid("places", async { Ok(2056) }).await.unwrap(),
+            2056
+        );
+        assert_eq!(
+            cache.srid("places", async { Ok(4326) }).await.unwrap(),
+            4326
+        );
+    }
+}
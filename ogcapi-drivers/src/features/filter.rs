@@ -0,0 +1,428 @@
+use std::cmp::Ordering;
+
+use anyhow::bail;
+use chrono::{DateTime, NaiveDate, Utc};
+use geo::Relate;
+use serde_json::Value;
+
+use ogcapi_types::features::{
+    Feature,
+    cql2::{Expr, Operator},
+};
+
+use super::{compare, geometry};
+
+/// Whether a feature matches a CQL2 expression
+///
+/// Follows the semantics of the SQL translation of the postgres driver: a
+/// comparison with a missing property is unknown, and features only match
+/// if the whole expression is true.
+pub(crate) fn matches(expr: &Expr, feature: &Feature) -> anyhow::Result<bool> {
+    Ok(Evaluator { feature }.predicate(expr)? == Some(true))
+}
+
+/// Evaluates CQL2 expressions on a feature, with three-valued logic where
+/// `None` is unknown
+struct Evaluator<'a> {
+    feature: &'a Feature,
+}
+
+impl Evaluator<'_> {
+    fn predicate(&self, expr: &Expr) -> anyhow::Result<Option<bool>> {
+        match expr {
+            Expr::Bool(b) => Ok(Some(*b)),
+            Expr::Op { op, args } => self.operation(*op, args),
+            expr => bail!("Expected a boolean expression, found `{expr:?}`"),
+        }
+    }
+
+    fn operation(&self, op: Operator, args: &[Expr]) -> anyhow::Result<Option<bool>> {
+        match (op, args) {
+            (Operator::And, args) => {
+                let mut result = Some(true);
+                for arg in args {
+                    match self.predicate(arg)? {
+                        Some(false) => return Ok(Some(false)),
+                        None => result = None,
+                        Some(true) => {}
+                    }
+                }
+                Ok(result)
+            }
+            (Operator::Or, args) => {
+                let mut result = Some(false);
+                for arg in args {
+                    match self.predicate(arg)? {
+                        Some(true) => return Ok(Some(true)),
+                        None => result = None,
+                        Some(false) => {}
+                    }
+                }
+                Ok(result)
+            }
+            (Operator::Not, [arg]) => Ok(self.predicate(arg)?.map(|b| !b)),
+            (
+                Operator::Eq
+                | Operator::Neq
+                | Operator::Lt
+                | Operator::Lte
+                | Operator::Gt
+                | Operator::Gte,
+                [a, b],
+            ) => {
+                let domain = Domain::of(a, b);
+                let ordering = self.compare(a, b, domain)?;
+                Ok(ordering.map(|o| match op {
+                    Operator::Eq => o.is_eq(),
+                    Operator::Neq => o.is_ne(),
+                    Operator::Lt => o.is_lt(),
+                    Operator::Lte => o.is_le(),
+                    Operator::Gt => o.is_gt(),
+                    _ => o.is_ge(),
+                }))
+            }
+            (Operator::Like, [a, b]) => {
+                match (self.scalar(a, Domain::Text)?, self.scalar(b, Domain::Text)?) {
+                    (Some(Scalar::Text(a)), Some(Scalar::Text(b))) => Ok(Some(like(&a, &b))),
+                    _ => Ok(None),
+                }
+            }
+            (Operator::Between, [a, low, high]) => {
+                let domain = Domain::of(low, high);
+                let above = self.compare(a, low, domain)?.map(Ordering::is_ge);
+                let below = self.compare(a, high, domain)?.map(Ordering::is_le);
+                Ok(above.zip(below).map(|(above, below)| above && below))
+            }
+            (Operator::In, [a, Expr::Array(list)]) => {
+                let domain = list.first().map(|b| Domain::of(a, b)).unwrap_or_default();
+                let mut result = Some(false);
+                for item in list {
+                    match self.compare(a, item, domain)? {
+                        Some(Ordering::Equal) => return Ok(Some(true)),
+                        None => result = None,
+                        Some(_) => {}
+                    }
+                }
+                Ok(result)
+            }
+            (Operator::IsNull, [a]) => Ok(Some(matches!(
+                self.scalar(a, Domain::Json)?,
+                None | Some(Scalar::Json(Value::Null))
+            ))),
+            (op, [a, b]) if op.is_spatial() => {
+                let (Some(a), Some(b)) = (self.geometry(a)?, self.geometry(b)?) else {
+                    return Ok(None);
+                };
+                let matrix = a.relate(&b);
+                Ok(Some(match op {
+                    Operator::SContains => matrix.is_contains(),
+                    Operator::SCrosses => matrix.is_crosses(),
+                    Operator::SDisjoint => matrix.is_disjoint(),
+                    Operator::SEquals => matrix.is_equal_topo(),
+                    Operator::SIntersects => matrix.is_intersects(),
+                    Operator::SOverlaps => matrix.is_overlaps(),
+                    Operator::STouches => matrix.is_touches(),
+                    Operator::SWithin => matrix.is_within(),
+                    _ => unreachable!(),
+                }))
+            }
+            (op, [a, b]) if op.is_temporal() => {
+                let (Some((a_start, a_end)), Some((b_start, b_end))) =
+                    (self.interval(a)?, self.interval(b)?)
+                else {
+                    return Ok(None);
+                };
+                Ok(Some(match op {
+                    Operator::TAfter => a_start > b_end,
+                    Operator::TBefore => a_end < b_start,
+                    Operator::TContains => a_start < b_start && a_end > b_end,
+                    Operator::TDisjoint => !(a_start <= b_end && a_end >= b_start),
+                    Operator::TDuring => a_start > b_start && a_end < b_end,
+                    Operator::TEquals => a_start == b_start && a_end == b_end,
+                    Operator::TFinishedBy => a_start < b_start && a_end == b_end,
+                    Operator::TFinishes => a_start > b_start && a_end == b_end,
+                    Operator::TIntersects => a_start <= b_end && a_end >= b_start,
+                    Operator::TMeets => a_end == b_start,
+                    Operator::TMetBy => a_start == b_end,
+                    Operator::TOverlappedBy => {
+                        a_start > b_start && a_start < b_end && a_end > b_end
+                    }
+                    Operator::TOverlaps => a_start < b_start && a_end > b_start && a_end < b_end,
+                    Operator::TStartedBy => a_start == b_start && a_end > b_end,
+                    Operator::TStarts => a_start == b_start && a_end < b_end,
+                    _ => unreachable!(),
+                }))
+            }
+            (op, [a, b]) if op.is_array() => {
+                let (Some(Scalar::Json(a)), Some(Scalar::Json(b))) =
+                    (self.scalar(a, Domain::Json)?, self.scalar(b, Domain::Json)?)
+                else {
+                    return Ok(None);
+                };
+                let contains = |a: &Value, b: &Value| match (a, b) {
+                    (Value::Array(a), Value::Array(b)) => b.iter().all(|b| a.contains(b)),
+                    (a, b) => a == b,
+                };
+                Ok(Some(match op {
+                    Operator::AEquals => a == b,
+                    Operator::AContains => contains(&a, &b),
+                    Operator::AContainedBy => contains(&b, &a),
+                    Operator::AOverlaps => match (&a, &b) {
+                        (Value::Array(a), Value::Array(b)) => a.iter().any(|a| b.contains(a)),
+                        _ => false,
+                    },
+                    _ => unreachable!(),
+                }))
+            }
+            (op, args) => bail!("Invalid arguments for operator `{op}`: {args:?}"),
+        }
+    }
+
+    /// Compare two scalar expressions in the given domain
+    fn compare(&self, a: &Expr, b: &Expr, domain: Domain) -> anyhow::Result<Option<Ordering>> {
+        Ok(match (self.scalar(a, domain)?, self.scalar(b, domain)?) {
+            (Some(Scalar::Json(a)), Some(Scalar::Json(b))) => Some(compare(&a, &b)),
+            (Some(Scalar::Text(a)), Some(Scalar::Text(b))) => Some(a.cmp(&b)),
+            (Some(Scalar::Instant(a)), Some(Scalar::Instant(b))) => Some(a.cmp(&b)),
+            _ => None,
+        })
+    }
+
+    /// Evaluate a scalar expression in the given domain, `None` if unknown
+    fn scalar(&self, expr: &Expr, domain: Domain) -> anyhow::Result<Option<Scalar>> {
+        Ok(match (expr, domain) {
+            (Expr::Property(name), _) => self.property(name, domain),
+            (Expr::Function { name, args }, _) => match (name.as_str(), args.as_slice()) {
+                ("casei", [arg]) => match self.scalar(arg, Domain::Text)? {
+                    Some(Scalar::Text(s)) => Some(Scalar::Text(s.to_lowercase())),
+                    _ => None,
+                },
+                (name, _) => bail!("Unsupported function `{name}`"),
+            },
+            (Expr::Null, _) => Some(Scalar::Json(Value::Null)),
+            (Expr::Bool(b), Domain::Json) => Some(Scalar::Json((*b).into())),
+            (Expr::Number(n), Domain::Json) => Some(Scalar::Json((*n).into())),
+            (Expr::String(s), Domain::Json) => Some(Scalar::Json(s.as_str().into())),
+            (Expr::String(s), Domain::Text) => Some(Scalar::Text(s.to_owned())),
+            (Expr::Array(_), Domain::Json) => Some(Scalar::Json(json_literal(expr)?)),
+            (Expr::Date(_) | Expr::Timestamp(_), _) => self.instant(expr)?.map(Scalar::Instant),
+            (expr, domain) => bail!("Unable to use `{expr:?}` as {domain:?}"),
+        })
+    }
+
+    fn property(&self, name: &str, domain: Domain) -> Option<Scalar> {
+        let value = match name {
+            "id" => Value::from(self.feature.id.as_ref()?.to_string()),
+            name => self.feature.properties.as_ref()?.get(name)?.to_owned(),
+        };
+        match domain {
+            Domain::Json => Some(Scalar::Json(value)),
+            Domain::Text => match value {
+                Value::Null => None,
+                Value::String(s) => Some(Scalar::Text(s)),
+                value => Some(Scalar::Text(value.to_string())),
+            },
+            Domain::Instant => value.as_str().and_then(instant).map(Scalar::Instant),
+        }
+    }
+
+    fn instant(&self, expr: &Expr) -> anyhow::Result<Option<DateTime<Utc>>> {
+        match expr {
+            Expr::Date(d) => Ok(Some(d.and_time(Default::default()).and_utc())),
+            Expr::Timestamp(t) => Ok(Some(*t)),
+            Expr::Property(name) => Ok(match self.property(name, Domain::Instant) {
+                Some(Scalar::Instant(instant)) => Some(instant),
+                _ => None,
+            }),
+            expr => bail!("Expected an instant, found `{expr:?}`"),
+        }
+    }
+
+    /// Evaluate a temporal expression into its start and end
+    fn interval(&self, expr: &Expr) -> anyhow::Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        match expr {
+            Expr::Interval(start, end) => {
+                let start = match start.as_ref() {
+                    Expr::String(s) if s == ".." => Some(DateTime::<Utc>::MIN_UTC),
+                    start => self.instant(start)?,
+                };
+                let end = match end.as_ref() {
+                    Expr::String(s) if s == ".." => Some(DateTime::<Utc>::MAX_UTC),
+                    end => self.instant(end)?,
+                };
+                Ok(start.zip(end))
+            }
+            expr => Ok(self.instant(expr)?.map(|instant| (instant, instant))),
+        }
+    }
+
+    fn geometry(&self, expr: &Expr) -> anyhow::Result<Option<geo::Geometry>> {
+        match expr {
+            Expr::Property(_) => Ok(geometry(self.feature)),
+            Expr::Geometry(geometry) => Ok(Some(geo::Geometry::try_from(geometry)?)),
+            Expr::Bbox(bbox) => {
+                let (x1, y1, x2, y2) = match bbox.as_slice() {
+                    [x1, y1, x2, y2] | [x1, y1, _, x2, y2, _] => (*x1, *y1, *x2, *y2),
+                    _ => bail!("Invalid bbox `{bbox:?}`"),
+                };
+                Ok(Some(
+                    geo::Rect::new(geo::coord! { x: x1, y: y1 }, geo::coord! { x: x2, y: y2 })
+                        .into(),
+                ))
+            }
+            expr => bail!("Expected a geometry, found `{expr:?}`"),
+        }
+    }
+}
+
+/// Value of an operand
+enum Scalar {
+    Json(Value),
+    Text(String),
+    Instant(DateTime<Utc>),
+}
+
+/// Value domain in which operands are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Domain {
+    /// Compare as JSON, which orders numbers numerically and strings lexically
+    #[default]
+    Json,
+    Text,
+    Instant,
+}
+
+impl Domain {
+    fn of(a: &Expr, b: &Expr) -> Self {
+        match (a, b) {
+            (Expr::Date(_) | Expr::Timestamp(_), _) | (_, Expr::Date(_) | Expr::Timestamp(_)) => {
+                Domain::Instant
+            }
+            (Expr::Function { .. }, _) | (_, Expr::Function { .. }) => Domain::Text,
+            _ => Domain::Json,
+        }
+    }
+}
+
+fn json_literal(expr: &Expr) -> anyhow::Result<Value> {
+    Ok(match expr {
+        Expr::Null => Value::Null,
+        Expr::Bool(b) => (*b).into(),
+        Expr::Number(n) => (*n).into(),
+        Expr::String(s) => s.as_str().into(),
+        Expr::Array(items) => items
+            .iter()
+            .map(json_literal)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into(),
+        expr => bail!("Unsupported array item `{expr:?}`"),
+    })
+}
+
+/// Parse a timestamp or a date
+fn instant(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|datetime| datetime.to_utc())
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|date| date.and_time(Default::default()).and_utc())
+        })
+        .ok()
+}
+
+/// Match a `LIKE` pattern, where `%` matches any sequence of characters and
+/// `_` any single character, unless escaped with a backslash
+fn like(s: &str, pattern: &str) -> bool {
+    let s: Vec<char> = s.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+
+    fn matches(s: &[char], pattern: &[char]) -> bool {
+        match pattern {
+            [] => s.is_empty(),
+            ['%', rest @ ..] => (0..=s.len()).any(|i| matches(&s[i..], rest)),
+            ['_', rest @ ..] => !s.is_empty() && matches(&s[1..], rest),
+            ['\\', c, rest @ ..] | [c, rest @ ..] => s.first() == Some(c) && matches(&s[1..], rest),
+        }
+    }
+
+    matches(&s, &pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use ogcapi_types::features::Geometry;
+
+    use super::*;
+
+    fn feature() -> Feature {
+        let mut feature = Feature::new(Geometry::new_point([7.44, 46.95]));
+        feature.id = Some(ogcapi_types::features::FeatureId::String("1".to_string()));
+        feature.properties = Some(
+            serde_json::json!({
+                "name": "Bern",
+                "population": 134591,
+                "datetime": "2020-06-01T00:00:00Z",
+                "tags": ["capital", "city"]
+            })
+            .as_object()
+            .unwrap()
+            .to_owned(),
+        );
+        feature
+    }
+
+    fn eval(filter: &str) -> bool {
+        matches(&filter.parse().unwrap(), &feature()).unwrap()
+    }
+
+    #[test]
+    fn comparison() {
+        assert!(eval("name = 'Bern' AND population >= 100000"));
+        assert!(eval("population BETWEEN 100000 AND 200000"));
+        assert!(eval("name IN ('Bern', 'Zürich')"));
+        assert!(eval("name LIKE 'B_r%'"));
+        assert!(eval("CASEI(name) = casei('BERN')"));
+        assert!(!eval("name <> 'Bern'"));
+        assert!(eval("elevation IS NULL"));
+    }
+
+    #[test]
+    fn unknown() {
+        // comparisons with missing properties are neither true nor false
+        assert!(!eval("elevation > 500"));
+        assert!(!eval("NOT elevation > 500"));
+        assert!(eval("elevation > 500 OR name = 'Bern'"));
+    }
+
+    #[test]
+    fn spatial() {
+        assert!(eval("S_INTERSECTS(geometry, BBOX(6, 45, 9, 49))"));
+        assert!(eval(
+            "S_WITHIN(geometry, POLYGON((6 45, 9 45, 9 49, 6 49, 6 45)))"
+        ));
+        assert!(!eval("S_INTERSECTS(geometry, POINT(8 47))"));
+    }
+
+    #[test]
+    fn temporal() {
+        assert!(eval("T_INTERSECTS(datetime, INTERVAL('2020-01-01', '..'))"));
+        assert!(eval(
+            "T_BEFORE(datetime, TIMESTAMP('2021-01-01T00:00:00Z'))"
+        ));
+        assert!(!eval("T_AFTER(datetime, DATE('2021-01-01'))"));
+    }
+
+    #[test]
+    fn array() {
+        assert!(eval("A_CONTAINS(tags, ('city'))"));
+        assert!(eval("A_OVERLAPS(tags, ('village', 'city'))"));
+        assert!(!eval("A_EQUALS(tags, ('city'))"));
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(like("50%", "50\\%"));
+        assert!(!like("500", "50\\%"));
+        assert!(like("", "%"));
+    }
+}
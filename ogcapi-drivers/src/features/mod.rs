@@ -0,0 +1,386 @@
+//! Evaluation of queries on features in process
+//!
+//! Drivers without a query engine, e.g. the in-memory driver or drivers of
+//! files and objects, filter, sort and project features with these helpers,
+//! following the semantics of the postgres driver.
+mod filter;
+
+pub(crate) use self::filter::matches;
+
+use std::cmp::Ordering;
+#[cfg(any(feature = "memory", feature = "s3"))]
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+
+#[cfg(any(feature = "memory", feature = "s3"))]
+use ogcapi_types::features::Queryable;
+#[cfg(any(feature = "memory", feature = "files", feature = "s3"))]
+use ogcapi_types::features::{Direction, SortBy};
+use ogcapi_types::{
+    common::{AxisOrder, Bbox, Crs, Datetime, Exception, IntervalDatetime},
+    features::{Feature, Geometry},
+};
+
+#[cfg(any(feature = "memory", feature = "s3"))]
+use crate::SAMPLE_SIZE;
+
+/// Ensure that coordinates in `crs` need no transformation to `srid`
+pub(crate) fn untransformed(crs: Option<&Crs>, srid: i32) -> anyhow::Result<()> {
+    let crs = crs.cloned().unwrap_or_else(Crs::default2d);
+    if crs.as_srid() == srid {
+        Ok(())
+    } else {
+        Err(Exception::new_from_status(400)
+            .detail(format!(
+                "Coordinates in `{crs}` can not be transformed to EPSG:{srid}"
+            ))
+            .into())
+    }
+}
+
+/// Ensure that no past versions of features are requested, their history is
+/// not recorded
+pub(crate) fn unversioned(version_at: Option<&DateTime<Utc>>) -> anyhow::Result<()> {
+    match version_at {
+        Some(_) => Err(Exception::new_from_status(400)
+            .detail("The history of features is not recorded")
+            .into()),
+        None => Ok(()),
+    }
+}
+
+/// Ensure that the `bbox` is applied to the primary geometry, sources have
+/// no other geometries
+#[cfg(any(feature = "files", feature = "geopackage"))]
+pub(crate) fn primary_geometry(bbox_geometry: Option<&str>) -> anyhow::Result<()> {
+    match bbox_geometry {
+        Some(name) => Err(Exception::new_from_status(400)
+            .detail(format!("Features have no geometry `{name}`"))
+            .into()),
+        None => Ok(()),
+    }
+}
+
+/// Geometry of a feature
+pub(crate) fn geometry(feature: &Feature) -> Option<geo::Geometry> {
+    feature
+        .geometry
+        .as_ref()
+        .and_then(|geometry| geo::Geometry::try_from(geometry).ok())
+}
+
+/// Geometry property `name` of a feature, or its primary geometry if no name
+/// is given
+#[cfg(any(feature = "memory", feature = "s3"))]
+pub(crate) fn named_geometry(feature: &Feature, name: Option<&str>) -> Option<geo::Geometry> {
+    let Some(name) = name else {
+        return geometry(feature);
+    };
+    let value = feature.properties.as_ref()?.get(name)?;
+    serde_json::from_value::<Geometry>(value.to_owned())
+        .ok()
+        .and_then(|geometry| geo::Geometry::try_from(&geometry).ok())
+}
+
+/// Rectangles of the horizontal axes of a bounding box in `crs`, in
+/// longitude/easting first order
+///
+/// Boxes spanning the antimeridian are split in two.
+pub(crate) fn rects(bbox: &Bbox, crs: Option<&Crs>) -> Vec<geo::Rect> {
+    let bbox = match crs.map(Crs::axis_order) {
+        Some(AxisOrder::NorthEast) => bbox.swap_axes(),
+        _ => bbox.to_owned(),
+    };
+    bbox.split_antimeridian()
+        .iter()
+        .map(|bbox| {
+            let [x1, y1, x2, y2] = bbox.horizontal();
+            geo::Rect::new(geo::coord! { x: x1, y: y1 }, geo::coord! { x: x2, y: y2 })
+        })
+        .collect()
+}
+
+/// Feature with the axes of its geometry in the order of `crs`
+///
+/// Geometries are stored longitude/easting first like GeoJSON.
+pub(crate) fn oriented(mut feature: Feature, crs: Option<&Crs>) -> Feature {
+    if crs.is_some_and(|crs| crs.axis_order() == AxisOrder::NorthEast)
+        && let Some(geometry) = feature.geometry.as_mut()
+    {
+        swap_axes(geometry);
+    }
+    feature
+}
+
+/// Swap the first two axes of the positions of a geometry
+fn swap_axes(geometry: &mut Geometry) {
+    use geojson::{GeometryValue, Position};
+
+    fn swap(position: &mut Position) {
+        if position.len() >= 2 {
+            position.as_slice_mut().swap(0, 1);
+        }
+    }
+
+    match &mut geometry.value {
+        GeometryValue::Point { coordinates } => swap(coordinates),
+        GeometryValue::MultiPoint { coordinates } | GeometryValue::LineString { coordinates } => {
+            coordinates.iter_mut().for_each(swap)
+        }
+        GeometryValue::MultiLineString { coordinates } | GeometryValue::Polygon { coordinates } => {
+            coordinates.iter_mut().flatten().for_each(swap)
+        }
+        GeometryValue::MultiPolygon { coordinates } => {
+            coordinates.iter_mut().flatten().flatten().for_each(swap)
+        }
+        GeometryValue::GeometryCollection { geometries } => {
+            geometries.iter_mut().for_each(swap_axes)
+        }
+    }
+}
+
+/// Order of JSON values, following the order of `jsonb` in PostgreSQL
+///
+/// Values of different types are ordered by type: null < string < number <
+/// boolean < array < object.
+pub(crate) fn compare(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::String(_) => 1,
+            Value::Number(_) => 2,
+            Value::Bool(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    fn first_difference(mut orderings: impl Iterator<Item = Ordering>) -> Ordering {
+        orderings.find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+    }
+
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .len()
+            .cmp(&b.len())
+            .then_with(|| first_difference(a.iter().zip(b).map(|(a, b)| compare(a, b)))),
+        (Value::Object(a), Value::Object(b)) => a.len().cmp(&b.len()).then_with(|| {
+            first_difference(
+                a.iter()
+                    .zip(b)
+                    .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| compare(va, vb))),
+            )
+        }),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Whether the `datetime` or the `start_datetime` and `end_datetime`
+/// properties of a feature match the `datetime` parameter
+#[cfg(any(feature = "memory", feature = "files", feature = "geopackage"))]
+pub(crate) fn matches_datetime(feature: &Feature, datetime: &Datetime) -> bool {
+    matches_temporal(feature.properties.as_ref(), datetime)
+}
+
+/// Whether the temporal properties match the `datetime` parameter
+pub(crate) fn matches_temporal(
+    properties: Option<&Map<String, Value>>,
+    datetime: &Datetime,
+) -> bool {
+    let (from, to) = match datetime {
+        Datetime::Datetime(datetime) => (*datetime, *datetime),
+        Datetime::Interval { from, to } => (
+            match from {
+                IntervalDatetime::Datetime(from) => *from,
+                IntervalDatetime::Open => DateTime::<Utc>::MIN_UTC,
+            },
+            match to {
+                IntervalDatetime::Datetime(to) => *to,
+                IntervalDatetime::Open => Utc::now(),
+            },
+        ),
+    };
+
+    let instant = |key: &str| {
+        properties?
+            .get(key)?
+            .as_str()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|datetime| datetime.to_utc())
+    };
+
+    match (
+        instant("datetime"),
+        instant("start_datetime"),
+        instant("end_datetime"),
+    ) {
+        (Some(datetime), _, _) => from <= datetime && datetime <= to,
+        (None, Some(start), Some(end)) => from <= end && start <= to,
+        _ => true,
+    }
+}
+
+/// Whether a property equals the value of a key-value pair query parameter,
+/// features without the property match
+#[cfg(any(feature = "memory", feature = "files", feature = "s3"))]
+pub(crate) fn matches_value(feature: &Feature, key: &str, value: &str) -> bool {
+    match feature.properties.as_ref().and_then(|p| p.get(key)) {
+        Some(Value::String(s)) => s == value,
+        Some(Value::Number(n)) => value.parse::<f64>().ok() == n.as_f64(),
+        Some(Value::Bool(b)) => value.parse() == Ok(*b),
+        Some(v) => serde_json::from_str::<Value>(value).is_ok_and(|value| value == *v),
+        None => true,
+    }
+}
+
+/// Sort features by the `sortby` parameter, features are ordered by id otherwise
+#[cfg(any(feature = "memory", feature = "files", feature = "s3"))]
+pub(crate) fn sort(features: &mut [&Feature], sortby: &[SortBy]) -> anyhow::Result<()> {
+    if sortby.iter().any(|sortby| sortby.property() == "geometry") {
+        return Err(Exception::new_from_status(400)
+            .detail("Property geometry is not sortable!")
+            .into());
+    }
+
+    let key = |feature: &Feature, property: &str| -> Value {
+        match property {
+            "id" => feature.id.as_ref().map(|id| id.to_string()).into(),
+            "collection" => feature.collection.to_owned().into(),
+            // missing properties sort like JSON `null`
+            property => feature
+                .properties
+                .as_ref()
+                .and_then(|p| p.get(property))
+                .cloned()
+                .unwrap_or_default(),
+        }
+    };
+
+    // stable, so features with equal keys remain ordered by id
+    features.sort_by(|a, b| {
+        sortby
+            .iter()
+            .map(|sortby| {
+                let ordering = compare(&key(a, sortby.property()), &key(b, sortby.property()));
+                match sortby.direction {
+                    Direction::Asc => ordering,
+                    Direction::Desc => ordering.reverse(),
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Ok(())
+}
+
+/// Select properties and skip the geometry of a feature
+pub(crate) fn project(
+    mut feature: Feature,
+    properties: Option<&[String]>,
+    skip_geometry: bool,
+) -> Feature {
+    if let Some(selection) = properties {
+        feature.properties = Some(
+            feature
+                .properties
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(key, _)| selection.contains(key))
+                .collect(),
+        );
+    }
+    if skip_geometry {
+        feature.geometry = None;
+    }
+    feature
+}
+
+/// Derive property schemas from the json types of sampled features
+#[cfg(any(feature = "memory", feature = "s3"))]
+pub(crate) fn sample_properties<'a>(
+    features: impl Iterator<Item = &'a Feature>,
+) -> HashMap<String, Queryable> {
+    let mut types: HashMap<String, HashSet<&str>> = HashMap::new();
+
+    for feature in features.take(SAMPLE_SIZE) {
+        for (key, value) in feature.properties.iter().flatten() {
+            let r#type = match value {
+                Value::Null => continue,
+                Value::Bool(_) => "boolean",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            types.entry(key.to_owned()).or_default().insert(r#type);
+        }
+    }
+
+    let mut properties = HashMap::new();
+
+    for (key, types) in types {
+        let property = match Vec::from_iter(types).as_slice() {
+            [t] if *t == "string" && key.ends_with("datetime") => {
+                Queryable::new(t).format("date-time")
+            }
+            [t] => Queryable::new(t),
+            _ => Queryable::default(),
+        };
+        properties.insert(key, property);
+    }
+
+    let mut id = Queryable::new("string").title("Feature identifier");
+    id.additional_properties
+        .insert("x-ogc-role".to_string(), "id".into());
+    properties.insert("id".to_string(), id);
+
+    let mut geometry = Queryable::default().format("geometry-any");
+    geometry
+        .additional_properties
+        .insert("x-ogc-role".to_string(), "primary-geometry".into());
+    properties.insert("geometry".to_string(), geometry);
+
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn json_order() {
+        let mut values = vec![
+            json!(true),
+            json!("b"),
+            json!(10),
+            json!(null),
+            json!("a"),
+            json!(2.5),
+            json!([1]),
+        ];
+        values.sort_by(compare);
+        assert_eq!(
+            values,
+            [
+                json!(null),
+                json!("a"),
+                json!("b"),
+                json!(2.5),
+                json!(10),
+                json!(true),
+                json!([1])
+            ]
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use ogcapi_types::common::{Bbox, Collection, Collections, Extent, Query, SpatialExtent};
+
+use crate::CollectionTransactions;
+
+use super::{Files, Source, blocking, method_not_allowed};
+
+#[async_trait::async_trait]
+impl CollectionTransactions for Files {
+    async fn create_collection(&self, _collection: &Collection) -> anyhow::Result<String> {
+        Err(method_not_allowed())
+    }
+
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        match self.source(id).await? {
+            Some(source) => Ok(Some(collection(source).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn update_collection(
+        &self,
+        _collection: &Collection,
+        _if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Err(method_not_allowed())
+    }
+
+    async fn delete_collection(&self, _id: &str, _if_match: Option<&str>) -> anyhow::Result<()> {
+        Err(method_not_allowed())
+    }
+
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        match self.source(id).await? {
+            Some(source) => Ok(Some(blocking(move || source.etag()).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn list_collections(&self, _query: &Query) -> anyhow::Result<Collections> {
+        let mut collections = Vec::new();
+        for source in self.sources().await? {
+            collections.push(collection(source).await?);
+        }
+
+        let mut collections = Collections::new(collections);
+        collections.number_matched = collections.number_returned;
+
+        Ok(collections)
+    }
+}
+
+/// Collection of a file
+async fn collection(source: Source) -> anyhow::Result<Collection> {
+    let id = source.id.to_owned();
+    let info = blocking(move || Ok(source.open()?.info())).await?;
+
+    Ok(Collection {
+        title: Some(info.title.unwrap_or_else(|| id.to_owned())),
+        description: info.description,
+        item_type: "feature".to_string(),
+        crs: vec![info.crs.to_owned()],
+        extent: info.bounds.map(|bounds| Extent {
+            spatial: Some(SpatialExtent {
+                bbox: vec![Bbox::Bbox2D(bounds)],
+                crs: Some(info.crs.to_owned()),
+            }),
+            ..Default::default()
+        }),
+        storage_crs: Some(info.crs),
+        id,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use geo::Intersects;
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{Feature, FeatureCollection, NumberMatched, Query, Queryable, Queryables, Schema},
+};
+
+use crate::{
+    FeatureTransactions,
+    features::{
+        geometry, matches, matches_datetime, matches_value, oriented, primary_geometry, project,
+        rects, sort, untransformed, unversioned,
+    },
+};
+
+use super::{Files, Info, Reader, Source, blocking, method_not_allowed};
+
+#[async_trait::async_trait]
+impl FeatureTransactions for Files {
+    async fn create_feature(&self, _feature: &Feature) -> anyhow::Result<String> {
+        Err(method_not_allowed())
+    }
+
+    async fn read_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Feature>> {
+        let (info, mut reader) = open(self.existing(collection_id).await?).await?;
+        untransformed(Some(crs), info.crs.as_srid())?;
+
+        let Ok(position) = id.parse() else {
+            return Ok(None);
+        };
+        let feature = blocking(move || reader.feature(position)).await?;
+
+        Ok(feature.map(|mut feature| {
+            feature.collection = Some(collection_id.to_owned());
+            oriented(feature, Some(crs))
+        }))
+    }
+
+    async fn update_feature(
+        &self,
+        _feature: &Feature,
+        _if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Err(method_not_allowed())
+    }
+
+    async fn patch_feature(
+        &self,
+        _collection_id: &str,
+        _id: &str,
+        _patch: &Map<String, Value>,
+        _if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Err(method_not_allowed())
+    }
+
+    async fn delete_feature(
+        &self,
+        _collection_id: &str,
+        _id: &str,
+        _if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        Err(method_not_allowed())
+    }
+
+    async fn feature_etag(&self, collection_id: &str, id: &str) -> anyhow::Result<Option<String>> {
+        let source = self.existing(collection_id).await?;
+        let Ok(position) = id.parse() else {
+            return Ok(None);
+        };
+
+        // features change with their file
+        blocking(move || match source.open()?.feature(position)? {
+            Some(_) => Ok(Some(source.etag()?)),
+            None => Ok(None),
+        })
+        .await
+    }
+
+    async fn list_items(
+        &self,
+        collection_id: &str,
+        query: &Query,
+    ) -> anyhow::Result<FeatureCollection> {
+        let (info, mut reader) = open(self.existing(collection_id).await?).await?;
+        let srid = info.crs.as_srid();
+
+        untransformed(query.crs.as_ref(), srid)?;
+        unversioned(query.version_at.as_ref())?;
+        primary_geometry(query.bbox_geometry.as_deref())?;
+
+        let bbox = match query.bbox.as_ref() {
+            Some(bbox) => {
+                untransformed(query.bbox_crs.as_ref(), srid)?;
+                Some(rects(bbox, query.bbox_crs.as_ref()))
+            }
+            None => None,
+        };
+
+        let filter = query.parse_filter()?;
+        if filter.is_some() {
+            untransformed(query.filter_crs.as_ref(), srid)?;
+        }
+
+        // the `next` token is the offset of the next page
+        let offset = match query.next.as_deref() {
+            Some(token) => token.parse().map_err(|_| {
+                anyhow::Error::from(Exception::new_from_status(400).detail("Invalid `next` token"))
+            })?,
+            None => query.offset.unwrap_or(0),
+        };
+        let end = query
+            .limit
+            .map(|limit| offset.saturating_add(limit))
+            .unwrap_or(usize::MAX);
+        let count = query.number_matched.unwrap_or_default() != NumberMatched::Skip;
+
+        let exact = bbox.is_some()
+            || query.datetime.is_some()
+            || filter.is_some()
+            || !query.additional_parameters.is_empty()
+            || !query.sortby.is_empty();
+
+        let (features, number_matched, next) = if exact {
+            // candidates of the index or row group statistics, evaluated in process
+            let bounds = bbox.as_ref().and_then(|rects| {
+                rects
+                    .iter()
+                    .map(|rect| [rect.min().x, rect.min().y, rect.max().x, rect.max().y])
+                    .reduce(|a, b| {
+                        [
+                            a[0].min(b[0]),
+                            a[1].min(b[1]),
+                            a[2].max(b[2]),
+                            a[3].max(b[3]),
+                        ]
+                    })
+            });
+            let candidates = blocking(move || reader.read(bounds, None)).await?;
+
+            let mut matched = Vec::new();
+            for feature in &candidates {
+                if let Some(rects) = bbox.as_ref()
+                    && !geometry(feature)
+                        .is_some_and(|g| rects.iter().any(|rect| g.intersects(rect)))
+                {
+                    continue;
+                }
+                if let Some(datetime) = query.datetime.as_ref()
+                    && !matches_datetime(feature, datetime)
+                {
+                    continue;
+                }
+                if !query
+                    .additional_parameters
+                    .iter()
+                    .all(|(k, v)| matches_value(feature, k, v))
+                {
+                    continue;
+                }
+                if let Some(filter) = filter.as_ref()
+                    && !matches(filter, feature)?
+                {
+                    continue;
+                }
+                matched.push(feature);
+            }
+
+            sort(&mut matched, &query.sortby)?;
+
+            let number_matched = count.then_some(matched.len() as u64);
+            let next = (end < matched.len()).then(|| end.to_string());
+            let features: Vec<_> = matched
+                .into_iter()
+                .skip(offset)
+                .take(end - offset)
+                .cloned()
+                .collect();
+
+            (features, number_matched, next)
+        } else {
+            // only the features of the page
+            let window = offset as u64..end as u64;
+            let features = blocking(move || reader.read(None, Some(window))).await?;
+
+            let next = match info.count {
+                Some(total) => (end as u64) < total,
+                None => query.limit.is_some_and(|limit| features.len() == limit),
+            };
+
+            (
+                features,
+                info.count.filter(|_| count),
+                next.then(|| end.to_string()),
+            )
+        };
+
+        let features = features
+            .into_iter()
+            .map(|mut feature| {
+                feature.collection = Some(collection_id.to_owned());
+                let feature = project(
+                    feature,
+                    query.properties.as_deref(),
+                    query.skip_geometry.unwrap_or_default(),
+                );
+                oriented(feature, query.crs.as_ref())
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = number_matched;
+        fc.next = next;
+
+        Ok(fc)
+    }
+
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        true
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        let (info, _) = open(self.existing(collection_id).await?).await?;
+
+        Ok(Queryables {
+            title: Some(
+                info.title
+                    .to_owned()
+                    .unwrap_or_else(|| collection_id.to_owned()),
+            ),
+            queryables: properties(info),
+            additional_properties: false,
+            ..Default::default()
+        })
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        let (info, _) = open(self.existing(collection_id).await?).await?;
+
+        Ok(Schema {
+            title: Some(
+                info.title
+                    .to_owned()
+                    .unwrap_or_else(|| collection_id.to_owned()),
+            ),
+            description: info.description.to_owned(),
+            properties: properties(info),
+            ..Default::default()
+        })
+    }
+}
+
+/// Open a file, reading its description
+async fn open(source: Source) -> anyhow::Result<(Info, Reader)> {
+    blocking(move || {
+        let reader = source.open()?;
+        Ok((reader.info(), reader))
+    })
+    .await
+}
+
+/// Properties of a file with the feature id and geometry
+fn properties(info: Info) -> HashMap<String, Queryable> {
+    let mut properties = info.properties;
+
+    let mut id = Queryable::new("integer").title("Position of the feature in the file");
+    id.additional_properties
+        .insert("x-ogc-role".to_string(), "id".into());
+    properties.insert("id".to_string(), id);
+
+    let mut geometry = Queryable::default().format("geometry-any");
+    geometry
+        .additional_properties
+        .insert("x-ogc-role".to_string(), "primary-geometry".into());
+    properties.insert("geometry".to_string(), geometry);
+
+    properties
+}
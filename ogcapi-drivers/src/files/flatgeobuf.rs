@@ -0,0 +1,827 @@
+//! FlatGeobuf files
+//!
+//! A file starts with magic bytes and a header, followed by an optional packed
+//! Hilbert R-tree and the size-prefixed features (<https://flatgeobuf.org>).
+//! Header and features are flatbuffers, read with bounds checks on every
+//! access.
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+};
+
+use anyhow::{Context, bail};
+use geojson::{GeometryValue, Position};
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::Crs,
+    features::{Feature, Geometry, Queryable},
+};
+
+use super::{Info, feature};
+
+const INVALID: &str = "Invalid FlatGeobuf file";
+
+/// Size of a node of the packed R-tree, the envelope and an offset
+const NODE_SIZE: u64 = 40;
+
+/// Header of a file
+pub(super) struct Header {
+    name: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    envelope: Option<[f64; 4]>,
+    geometry_type: u8,
+    columns: Vec<Column>,
+    features_count: u64,
+    index_node_size: u16,
+    crs: Option<Crs>,
+}
+
+/// Attribute column
+struct Column {
+    name: String,
+    r#type: u8,
+    title: Option<String>,
+    description: Option<String>,
+}
+
+/// Reader of a file
+pub(super) struct Reader {
+    file: BufReader<File>,
+    header: Header,
+    /// Position of the packed R-tree
+    index: u64,
+    /// Position of the first feature
+    features: u64,
+}
+
+impl Reader {
+    pub(super) fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 12];
+        file.read_exact(&mut magic).context(INVALID)?;
+        if &magic[..3] != b"fgb" || &magic[4..7] != b"fgb" {
+            bail!(INVALID);
+        }
+        if magic[3] != 3 {
+            bail!("Unsupported FlatGeobuf version {}", magic[3]);
+        }
+
+        let size = u32::from_le_bytes(magic[8..].try_into().unwrap());
+        let mut buf = Vec::new();
+        file.by_ref().take(size as u64).read_to_end(&mut buf)?;
+        let header = Header::decode(&buf)?;
+
+        let index = 12 + size as u64;
+        let features = match header.index_nodes() {
+            Some(nodes) => index + nodes as u64 * NODE_SIZE,
+            None => index,
+        };
+
+        Ok(Reader {
+            file,
+            header,
+            index,
+            features,
+        })
+    }
+
+    /// Description of the file
+    pub(super) fn info(&self) -> Info {
+        let header = &self.header;
+
+        let properties = header
+            .columns
+            .iter()
+            .filter_map(|column| {
+                let mut queryable = match column.r#type {
+                    2 => Queryable::new("boolean"),
+                    0..=8 => Queryable::new("integer"),
+                    9 | 10 => Queryable::new("number"),
+                    11 => Queryable::new("string"),
+                    13 => Queryable::new("string").format("date-time"),
+                    12 => Queryable::default(),
+                    // binary attributes are not represented
+                    _ => return None,
+                };
+                queryable.title = column.title.to_owned();
+                queryable.description = column.description.to_owned();
+                Some((column.name.to_owned(), queryable))
+            })
+            .collect();
+
+        Info {
+            title: header.title.to_owned().or_else(|| header.name.to_owned()),
+            description: header.description.to_owned(),
+            bounds: header.envelope,
+            crs: header.crs.to_owned().unwrap_or_else(Crs::default2d),
+            count: (header.features_count > 0).then_some(header.features_count),
+            properties,
+        }
+    }
+
+    /// Positions and offsets of the features whose envelope intersects
+    /// `bounds`, `None` if the file has no index
+    pub(super) fn search(&mut self, bounds: [f64; 4]) -> anyhow::Result<Option<Vec<(u64, u64)>>> {
+        let Some(levels) = self.header.levels() else {
+            return Ok(None);
+        };
+        let node_size = self.header.index_node_size as usize;
+        let leaves = levels[0].start;
+
+        let mut results = Vec::new();
+        // nodes to visit as position and level, starting at the root
+        let mut queue = vec![(0, levels.len() - 1)];
+
+        while let Some((node, level)) = queue.pop() {
+            let end = (node + node_size).min(levels[level].end);
+            let nodes = self.nodes(node..end)?;
+
+            for (i, chunk) in nodes.chunks_exact(NODE_SIZE as usize).enumerate() {
+                let value =
+                    |i: usize| f64::from_le_bytes(chunk[i * 8..i * 8 + 8].try_into().unwrap());
+                let offset = u64::from_le_bytes(chunk[32..].try_into().unwrap());
+
+                if value(0) > bounds[2]
+                    || value(1) > bounds[3]
+                    || value(2) < bounds[0]
+                    || value(3) < bounds[1]
+                {
+                    continue;
+                }
+
+                if level == 0 {
+                    results.push(((node + i - leaves) as u64, offset));
+                } else {
+                    let child = usize::try_from(offset).context(INVALID)?;
+                    if !levels[level - 1].contains(&child) {
+                        bail!(INVALID);
+                    }
+                    queue.push((child, level - 1));
+                }
+            }
+        }
+
+        results.sort_unstable();
+
+        Ok(Some(results))
+    }
+
+    /// Features by position and offset
+    pub(super) fn read(&mut self, candidates: &[(u64, u64)]) -> anyhow::Result<Vec<Feature>> {
+        let mut features = Vec::with_capacity(candidates.len());
+        for &(position, offset) in candidates {
+            self.file.seek(SeekFrom::Start(self.features + offset))?;
+            let buf = self.next()?.context(INVALID)?;
+            features.push(self.header.feature(&buf, position)?);
+        }
+        Ok(features)
+    }
+
+    /// Features in file order within the `window` of positions
+    pub(super) fn scan(&mut self, window: Option<Range<u64>>) -> anyhow::Result<Vec<Feature>> {
+        let window = window.unwrap_or(0..u64::MAX);
+
+        // start at the first feature of the window if the index has its offset
+        let mut position = 0;
+        let mut offset = 0;
+        if window.start > 0
+            && let Some(levels) = self.header.levels()
+        {
+            if window.start >= levels[0].len() as u64 {
+                return Ok(Vec::new());
+            }
+            let leaf = levels[0].start + window.start as usize;
+            let node = self.nodes(leaf..leaf + 1)?;
+            position = window.start;
+            offset = u64::from_le_bytes(node[32..].try_into().unwrap());
+        }
+        self.file.seek(SeekFrom::Start(self.features + offset))?;
+
+        let mut features = Vec::new();
+        while position < window.end {
+            if window.contains(&position) {
+                let Some(buf) = self.next()? else {
+                    break;
+                };
+                features.push(self.header.feature(&buf, position)?);
+            } else if !self.skip()? {
+                break;
+            }
+            position += 1;
+        }
+
+        Ok(features)
+    }
+
+    /// Feature at a position
+    pub(super) fn feature(&mut self, position: u64) -> anyhow::Result<Option<Feature>> {
+        Ok(self.scan(Some(position..position + 1))?.pop())
+    }
+
+    /// Nodes of the packed R-tree
+    fn nodes(&mut self, nodes: Range<usize>) -> anyhow::Result<Vec<u8>> {
+        self.file
+            .seek(SeekFrom::Start(self.index + nodes.start as u64 * NODE_SIZE))?;
+        let mut buf = vec![0; nodes.len() * NODE_SIZE as usize];
+        self.file.read_exact(&mut buf).context(INVALID)?;
+        Ok(buf)
+    }
+
+    /// Size prefix of the next feature, `None` at the end of the file
+    fn size(&mut self) -> anyhow::Result<Option<u32>> {
+        let mut size = [0; 4];
+        match self.file.read_exact(&mut size) {
+            Ok(()) => Ok(Some(u32::from_le_bytes(size))),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Buffer of the next feature
+    fn next(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(size) = self.size()? else {
+            return Ok(None);
+        };
+        let mut buf = Vec::new();
+        self.file.by_ref().take(size as u64).read_to_end(&mut buf)?;
+        if buf.len() < size as usize {
+            bail!(INVALID);
+        }
+        Ok(Some(buf))
+    }
+
+    /// Skip the next feature, `false` at the end of the file
+    fn skip(&mut self) -> anyhow::Result<bool> {
+        match self.size()? {
+            Some(size) => {
+                self.file.seek_relative(size as i64)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl Header {
+    fn decode(buf: &[u8]) -> anyhow::Result<Self> {
+        let header = Table::root(buf)?;
+
+        let envelope = header
+            .vector(1, 8)?
+            .filter(|envelope| envelope.len() >= 32)
+            .map(|envelope| {
+                let value =
+                    |i: usize| f64::from_le_bytes(envelope[i * 8..i * 8 + 8].try_into().unwrap());
+                [value(0), value(1), value(2), value(3)]
+            });
+
+        let crs = match header.table(10)? {
+            Some(crs) => {
+                let org = crs.string(0)?;
+                let code = crs.scalar(1, 0i32)?;
+                // the organization defaults to EPSG
+                (code != 0 && org.is_none_or(|org| org.eq_ignore_ascii_case("EPSG")))
+                    .then(|| Crs::from_srid(code))
+            }
+            None => None,
+        };
+
+        Ok(Header {
+            name: header.string(0)?.map(ToOwned::to_owned),
+            title: header.string(11)?.map(ToOwned::to_owned),
+            description: header.string(12)?.map(ToOwned::to_owned),
+            envelope,
+            geometry_type: header.scalar(2, 0u8)?,
+            columns: columns(&header.tables(7)?)?,
+            features_count: header.scalar(8, 0u64)?,
+            index_node_size: header.scalar(9, 16u16)?,
+            crs,
+        })
+    }
+
+    /// Number of nodes of the packed R-tree, `None` if there is no index
+    fn index_nodes(&self) -> Option<usize> {
+        self.levels().map(|levels| levels[0].end)
+    }
+
+    /// Ranges of the nodes of each level of the packed R-tree, from the leaves
+    /// to the root, `None` if there is no index
+    fn levels(&self) -> Option<Vec<Range<usize>>> {
+        let node_size = self.index_node_size as usize;
+        let count = usize::try_from(self.features_count).ok()?;
+        if node_size < 2 || count == 0 {
+            return None;
+        }
+
+        let mut n = count;
+        let mut sizes = vec![n];
+        loop {
+            n = n.div_ceil(node_size);
+            sizes.push(n);
+            if n == 1 {
+                break;
+            }
+        }
+
+        // the root is the first node, the leaves are the last
+        let mut end: usize = sizes.iter().sum();
+        Some(
+            sizes
+                .into_iter()
+                .map(|size| {
+                    let level = end - size..end;
+                    end -= size;
+                    level
+                })
+                .collect(),
+        )
+    }
+
+    /// Decode the feature at a position
+    fn feature(&self, buf: &[u8], position: u64) -> anyhow::Result<Feature> {
+        let table = Table::root(buf)?;
+
+        let geometry = match table.table(0)? {
+            Some(geometry) => decode_geometry(&geometry, self.geometry_type)?,
+            None => None,
+        };
+
+        // features may have their own schema
+        let own = columns(&table.tables(2)?)?;
+        let columns = if own.is_empty() { &self.columns } else { &own };
+
+        let properties = properties(table.vector(1, 1)?.unwrap_or_default(), columns)?;
+
+        feature(position, geometry, properties)
+    }
+}
+
+fn columns(tables: &[Table]) -> anyhow::Result<Vec<Column>> {
+    tables
+        .iter()
+        .map(|column| {
+            Ok(Column {
+                name: column.string(0)?.context(INVALID)?.to_owned(),
+                r#type: column.scalar(1, 0u8)?,
+                title: column.string(2)?.map(ToOwned::to_owned),
+                description: column.string(3)?.map(ToOwned::to_owned),
+            })
+        })
+        .collect()
+}
+
+/// Decode a geometry of a type, `None` for empty points
+fn decode_geometry(table: &Table, r#type: u8) -> anyhow::Result<Option<Geometry>> {
+    // geometries of mixed types carry their own
+    let r#type = match r#type {
+        0 => table.scalar(6, 0u8)?,
+        r#type => r#type,
+    };
+
+    let doubles = |slot| -> anyhow::Result<Vec<f64>> {
+        Ok(table
+            .vector(slot, 8)?
+            .unwrap_or_default()
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect())
+    };
+    let xy = doubles(1)?;
+    let z = doubles(2)?;
+    let mut positions: Vec<Position> = xy
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, xy)| match z.get(i) {
+            Some(z) => Position::from([xy[0], xy[1], *z]),
+            None => Position::from([xy[0], xy[1]]),
+        })
+        .collect();
+
+    // parts of multi line strings and polygon rings end at these positions
+    let ends: Vec<usize> = table
+        .vector(0, 4)?
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        .collect();
+    let split = |positions: Vec<Position>| -> anyhow::Result<Vec<Vec<Position>>> {
+        if ends.is_empty() {
+            return Ok(vec![positions]);
+        }
+        let mut start = 0;
+        ends.iter()
+            .map(|&end| {
+                let part = positions.get(start..end).context(INVALID)?.to_vec();
+                start = end;
+                Ok(part)
+            })
+            .collect()
+    };
+
+    let parts = table.tables(7)?;
+
+    let value = match r#type {
+        1 if positions.is_empty() => return Ok(None),
+        1 => GeometryValue::Point {
+            coordinates: positions.swap_remove(0),
+        },
+        2 => GeometryValue::LineString {
+            coordinates: positions,
+        },
+        3 => GeometryValue::Polygon {
+            coordinates: split(positions)?,
+        },
+        4 => GeometryValue::MultiPoint {
+            coordinates: positions,
+        },
+        5 => GeometryValue::MultiLineString {
+            coordinates: split(positions)?,
+        },
+        6 => GeometryValue::MultiPolygon {
+            coordinates: parts
+                .iter()
+                .filter_map(|part| decode_geometry(part, 3).transpose())
+                .map(|polygon| match polygon?.value {
+                    GeometryValue::Polygon { coordinates } => Ok(coordinates),
+                    _ => bail!(INVALID),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        },
+        7 => GeometryValue::GeometryCollection {
+            geometries: parts
+                .iter()
+                .filter_map(|part| decode_geometry(part, 0).transpose())
+                .collect::<anyhow::Result<_>>()?,
+        },
+        r#type => bail!("Unsupported FlatGeobuf geometry type {type}"),
+    };
+
+    Ok(Some(Geometry::new(value)))
+}
+
+/// Decode the properties, a sequence of column indices and values
+fn properties(buf: &[u8], columns: &[Column]) -> anyhow::Result<Map<String, Value>> {
+    let mut properties = Map::new();
+    let mut pos = 0;
+
+    while pos < buf.len() {
+        let index = u16::from_le_bytes(take(buf, &mut pos)?);
+        let column = columns.get(index as usize).context(INVALID)?;
+
+        let value = match column.r#type {
+            0 => i8::from_le_bytes(take(buf, &mut pos)?).into(),
+            1 => u8::from_le_bytes(take(buf, &mut pos)?).into(),
+            2 => Value::Bool(u8::from_le_bytes(take(buf, &mut pos)?) != 0),
+            3 => i16::from_le_bytes(take(buf, &mut pos)?).into(),
+            4 => u16::from_le_bytes(take(buf, &mut pos)?).into(),
+            5 => i32::from_le_bytes(take(buf, &mut pos)?).into(),
+            6 => u32::from_le_bytes(take(buf, &mut pos)?).into(),
+            7 => i64::from_le_bytes(take(buf, &mut pos)?).into(),
+            8 => u64::from_le_bytes(take(buf, &mut pos)?).into(),
+            9 => (f32::from_le_bytes(take(buf, &mut pos)?) as f64).into(),
+            10 => f64::from_le_bytes(take(buf, &mut pos)?).into(),
+            r#type => {
+                let len = u32::from_le_bytes(take(buf, &mut pos)?) as usize;
+                let bytes = buf.get(pos..pos + len).context(INVALID)?;
+                pos += len;
+                match r#type {
+                    11 | 13 => Value::String(std::str::from_utf8(bytes)?.to_owned()),
+                    12 => serde_json::from_slice(bytes)?,
+                    // binary attributes are not represented
+                    _ => continue,
+                }
+            }
+        };
+        properties.insert(column.name.to_owned(), value);
+    }
+
+    Ok(properties)
+}
+
+/// Take the bytes of a scalar value
+fn take<const N: usize>(buf: &[u8], pos: &mut usize) -> anyhow::Result<[u8; N]> {
+    let bytes = read(buf, *pos)?;
+    *pos += N;
+    Ok(bytes)
+}
+
+fn read<const N: usize>(buf: &[u8], pos: usize) -> anyhow::Result<[u8; N]> {
+    Ok(buf
+        .get(pos..pos.checked_add(N).context(INVALID)?)
+        .context(INVALID)?
+        .try_into()
+        .unwrap())
+}
+
+/// Table of a flatbuffer
+struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+/// Scalar field of a table
+trait Scalar: Sized {
+    fn read(buf: &[u8], pos: usize) -> anyhow::Result<Self>;
+}
+
+macro_rules! scalar {
+    ($($t:ty),*) => {
+        $(impl Scalar for $t {
+            fn read(buf: &[u8], pos: usize) -> anyhow::Result<Self> {
+                Ok(<$t>::from_le_bytes(read(buf, pos)?))
+            }
+        })*
+    };
+}
+
+scalar!(u8, u16, i32, u32, u64);
+
+impl<'a> Table<'a> {
+    /// Root table of a buffer
+    fn root(buf: &'a [u8]) -> anyhow::Result<Self> {
+        Ok(Table {
+            buf,
+            pos: u32::read(buf, 0)? as usize,
+        })
+    }
+
+    /// Position of a field, `None` if absent
+    fn field(&self, slot: usize) -> anyhow::Result<Option<usize>> {
+        let vtable = (self.pos as i64 - i32::read(self.buf, self.pos)? as i64)
+            .try_into()
+            .context(INVALID)?;
+        let len = u16::read(self.buf, vtable)? as usize;
+        let entry = 4 + 2 * slot;
+        if entry + 2 > len {
+            return Ok(None);
+        }
+        let offset = u16::read(self.buf, vtable + entry)? as usize;
+        Ok((offset != 0).then_some(self.pos + offset))
+    }
+
+    fn scalar<T: Scalar>(&self, slot: usize, default: T) -> anyhow::Result<T> {
+        match self.field(slot)? {
+            Some(pos) => T::read(self.buf, pos),
+            None => Ok(default),
+        }
+    }
+
+    /// Position of the target of an offset field
+    fn target(&self, slot: usize) -> anyhow::Result<Option<usize>> {
+        match self.field(slot)? {
+            Some(pos) => Ok(Some(
+                pos.checked_add(u32::read(self.buf, pos)? as usize)
+                    .context(INVALID)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn table(&self, slot: usize) -> anyhow::Result<Option<Table<'a>>> {
+        Ok(self.target(slot)?.map(|pos| Table { buf: self.buf, pos }))
+    }
+
+    /// Bytes of a vector with elements of `size` bytes
+    fn vector(&self, slot: usize, size: usize) -> anyhow::Result<Option<&'a [u8]>> {
+        let Some(pos) = self.target(slot)? else {
+            return Ok(None);
+        };
+        let len = (u32::read(self.buf, pos)? as usize)
+            .checked_mul(size)
+            .context(INVALID)?;
+        Ok(Some(self.buf.get(pos + 4..pos + 4 + len).context(INVALID)?))
+    }
+
+    fn string(&self, slot: usize) -> anyhow::Result<Option<&'a str>> {
+        match self.vector(slot, 1)? {
+            Some(bytes) => Ok(Some(std::str::from_utf8(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Tables of a vector of tables
+    fn tables(&self, slot: usize) -> anyhow::Result<Vec<Table<'a>>> {
+        let Some(pos) = self.target(slot)? else {
+            return Ok(Vec::new());
+        };
+        (0..u32::read(self.buf, pos)? as usize)
+            .map(|i| {
+                let pos = pos + 4 + i * 4;
+                Ok(Table {
+                    buf: self.buf,
+                    pos: pos + u32::read(self.buf, pos)? as usize,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flatbuffers::{FlatBufferBuilder, WIPOffset};
+
+    use super::*;
+
+    /// Write a file of points with a `name` and a `population` column,
+    /// indexed with a node size of 2
+    fn write(path: &Path, points: &[(f64, f64, &str, i32)]) {
+        let mut fbb = FlatBufferBuilder::new();
+        let columns: Vec<_> = [("name", 11u8), ("population", 5)]
+            .into_iter()
+            .map(|(name, r#type)| {
+                let name = fbb.create_string(name);
+                let column = fbb.start_table();
+                fbb.push_slot_always(4, name);
+                fbb.push_slot(6, r#type, 0);
+                fbb.end_table(column)
+            })
+            .collect();
+        let columns = fbb.create_vector(&columns);
+        let name = fbb.create_string("Cities");
+        let crs = fbb.start_table();
+        fbb.push_slot(6, 4326, 0);
+        let crs = fbb.end_table(crs);
+        let header = fbb.start_table();
+        fbb.push_slot_always(4, name);
+        fbb.push_slot(8, 1u8, 0);
+        fbb.push_slot_always(18, columns);
+        fbb.push_slot(20, points.len() as u64, 0);
+        fbb.push_slot(22, 2u16, 16);
+        fbb.push_slot_always(24, crs);
+        let header = fbb.end_table(header);
+        fbb.finish_minimal(header);
+
+        let mut file = b"fgb\x03fgb\x00".to_vec();
+        file.extend((fbb.finished_data().len() as u32).to_le_bytes());
+        file.extend(fbb.finished_data());
+
+        // features and the leaves of the index
+        let mut features: Vec<u8> = Vec::new();
+        let mut leaves = Vec::new();
+        for (x, y, name, population) in points {
+            let mut properties = 0u16.to_le_bytes().to_vec();
+            properties.extend((name.len() as u32).to_le_bytes());
+            properties.extend(name.as_bytes());
+            properties.extend(1u16.to_le_bytes());
+            properties.extend(population.to_le_bytes());
+
+            let mut fbb = FlatBufferBuilder::new();
+            let xy = fbb.create_vector(&[*x, *y]);
+            let geometry = fbb.start_table();
+            fbb.push_slot_always(6, xy);
+            let geometry = fbb.end_table(geometry);
+            let properties = fbb.create_vector(&properties);
+            let feature = fbb.start_table();
+            fbb.push_slot_always::<WIPOffset<_>>(4, geometry);
+            fbb.push_slot_always(6, properties);
+            let feature = fbb.end_table(feature);
+            fbb.finish_size_prefixed(feature, None);
+
+            leaves.push(([*x, *y, *x, *y], features.len() as u64));
+            features.extend(fbb.finished_data());
+        }
+
+        // parents of consecutive pairs up to the root, with the position of
+        // their first child within its level
+        let mut levels = vec![leaves];
+        loop {
+            let parents: Vec<_> = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .enumerate()
+                .map(|(i, nodes)| {
+                    let bounds =
+                        nodes
+                            .iter()
+                            .fold([f64::MAX, f64::MAX, f64::MIN, f64::MIN], |b, (n, _)| {
+                                [
+                                    b[0].min(n[0]),
+                                    b[1].min(n[1]),
+                                    b[2].max(n[2]),
+                                    b[3].max(n[3]),
+                                ]
+                            });
+                    (bounds, i as u64 * 2)
+                })
+                .collect();
+            let root = parents.len() == 1;
+            levels.push(parents);
+            if root {
+                break;
+            }
+        }
+
+        // the root is written first, child offsets are positions in the index
+        let total: usize = levels.iter().map(Vec::len).sum();
+        let start = |level: usize| total - levels[..=level].iter().map(Vec::len).sum::<usize>();
+        for level in (0..levels.len()).rev() {
+            for (bounds, offset) in &levels[level] {
+                for value in bounds {
+                    file.extend(value.to_le_bytes());
+                }
+                let offset = match level {
+                    0 => *offset,
+                    level => start(level - 1) as u64 + offset,
+                };
+                file.extend(offset.to_le_bytes());
+            }
+        }
+
+        file.extend(features);
+        std::fs::write(path, file).unwrap();
+    }
+
+    const POINTS: [(f64, f64, &str, i32); 5] = [
+        (7.44, 46.95, "Bern", 134591),
+        (8.54, 47.37, "Zürich", 421878),
+        (8.04, 47.39, "Aarau", 21726),
+        (6.14, 46.20, "Genève", 203856),
+        (8.31, 47.05, "Luzern", 82620),
+    ];
+
+    fn reader(name: &str) -> Reader {
+        let path = std::env::temp_dir().join(format!("ogcapi-{name}-{}.fgb", std::process::id()));
+        write(&path, &POINTS);
+        Reader::open(&path).unwrap()
+    }
+
+    fn names(features: &[Feature]) -> Vec<&str> {
+        features
+            .iter()
+            .map(|f| f.properties.as_ref().unwrap()["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn levels() {
+        let mut reader = reader("levels");
+        assert_eq!(reader.header.levels().unwrap(), [6..11, 3..6, 1..3, 0..1]);
+
+        let info = reader.info();
+        assert_eq!(info.title.as_deref(), Some("Cities"));
+        assert_eq!(info.crs, Crs::default2d());
+        assert_eq!(info.count, Some(5));
+        assert_eq!(
+            info.properties["population"].r#type.as_deref(),
+            Some("integer")
+        );
+
+        // a single feature still has a root
+        reader.header.features_count = 1;
+        assert_eq!(reader.header.levels().unwrap(), [1..2, 0..1]);
+    }
+
+    #[test]
+    fn search() {
+        let mut reader = reader("search");
+
+        let candidates = reader.search([8.0, 47.0, 9.0, 48.0]).unwrap().unwrap();
+        assert_eq!(
+            candidates.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [1, 2, 4]
+        );
+        let features = reader.read(&candidates).unwrap();
+        assert_eq!(names(&features), ["Zürich", "Aarau", "Luzern"]);
+        assert_eq!(
+            features[0].geometry,
+            Some(Geometry::new_point([8.54, 47.37]))
+        );
+        assert_eq!(
+            features[0].properties.as_ref().unwrap()["population"],
+            421878
+        );
+
+        assert!(
+            reader
+                .search([0.0, 0.0, 1.0, 1.0])
+                .unwrap()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn scan() {
+        let mut reader = reader("scan");
+
+        assert_eq!(names(&reader.scan(None).unwrap()).len(), 5);
+        assert_eq!(
+            names(&reader.scan(Some(3..10)).unwrap()),
+            ["Genève", "Luzern"]
+        );
+        assert!(reader.scan(Some(5..6)).unwrap().is_empty());
+
+        let feature = reader.feature(2).unwrap().unwrap();
+        assert_eq!(
+            feature.id,
+            Some(ogcapi_types::features::FeatureId::Integer(2))
+        );
+        assert_eq!(names(&[feature]), ["Aarau"]);
+    }
+}
@@ -0,0 +1,329 @@
+//! GeoParquet files
+//!
+//! Geometries are WKB in the primary column of the `geo` file metadata
+//! (<https://geoparquet.org>). Row groups are pruned by the statistics of the
+//! bounding box covering columns if the file has them.
+use std::{collections::HashMap, fs::File, ops::Range, path::Path};
+
+use anyhow::{Context, bail};
+use arrow_array::{Array, RecordBatch, cast::AsArray};
+use arrow_json::{WriterBuilder, writer::JsonArray};
+use arrow_schema::DataType;
+use geozero::{CoordDimensions, GeozeroGeometry, geojson::GeoJsonWriter, wkb::Wkb};
+use parquet::{
+    arrow::arrow_reader::{ArrowReaderMetadata, ParquetRecordBatchReaderBuilder},
+    file::{metadata::RowGroupMetaData, statistics::Statistics},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::Crs,
+    features::{Feature, Geometry, Queryable},
+};
+
+use super::{Info, feature};
+
+/// The `geo` file metadata
+#[derive(Deserialize)]
+struct GeoMetadata {
+    primary_column: String,
+    columns: HashMap<String, GeoColumn>,
+}
+
+#[derive(Deserialize)]
+struct GeoColumn {
+    encoding: String,
+    /// PROJJSON, `None` for OGC:CRS84
+    #[serde(default)]
+    crs: Option<Value>,
+    #[serde(default)]
+    bbox: Option<Vec<f64>>,
+    #[serde(default)]
+    covering: Option<Covering>,
+}
+
+#[derive(Deserialize)]
+struct Covering {
+    bbox: BboxCovering,
+}
+
+/// Paths of the columns of a bounding box covering
+#[derive(Deserialize)]
+struct BboxCovering {
+    xmin: Vec<String>,
+    ymin: Vec<String>,
+    xmax: Vec<String>,
+    ymax: Vec<String>,
+}
+
+/// Reader of a file
+pub(super) struct Reader {
+    file: File,
+    metadata: ArrowReaderMetadata,
+    geometry: String,
+    column: GeoColumn,
+}
+
+impl Reader {
+    pub(super) fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let metadata = ArrowReaderMetadata::load(&file, Default::default())?;
+
+        let geo = metadata
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|kv| kv.iter().find(|kv| kv.key == "geo"))
+            .and_then(|kv| kv.value.as_deref())
+            .context("Parquet file without GeoParquet metadata")?;
+        let mut geo: GeoMetadata = serde_json::from_str(geo)?;
+
+        let column = geo
+            .columns
+            .remove(&geo.primary_column)
+            .context("Missing metadata of the primary geometry column")?;
+        if column.encoding != "WKB" {
+            bail!("Unsupported GeoParquet encoding `{}`", column.encoding);
+        }
+
+        Ok(Reader {
+            file,
+            metadata,
+            geometry: geo.primary_column,
+            column,
+        })
+    }
+
+    /// Description of the file
+    pub(super) fn info(&self) -> Info {
+        let crs = match self.column.crs.as_ref().and_then(|crs| crs.get("id")) {
+            Some(id) if id["authority"] == "EPSG" => id["code"]
+                .as_i64()
+                .map(|code| Crs::from_srid(code as i32))
+                .unwrap_or_else(Crs::default2d),
+            _ => Crs::default2d(),
+        };
+
+        let bounds = match self.column.bbox.as_deref() {
+            Some([x1, y1, x2, y2]) => Some([*x1, *y1, *x2, *y2]),
+            Some([x1, y1, _, x2, y2, _]) => Some([*x1, *y1, *x2, *y2]),
+            _ => {
+                // union of the row group statistics
+                let bounds: Option<Vec<_>> = self
+                    .metadata
+                    .metadata()
+                    .row_groups()
+                    .iter()
+                    .map(|row_group| self.bounds(row_group))
+                    .collect();
+                bounds.and_then(|bounds| {
+                    bounds.into_iter().reduce(|a, b| {
+                        [
+                            a[0].min(b[0]),
+                            a[1].min(b[1]),
+                            a[2].max(b[2]),
+                            a[3].max(b[3]),
+                        ]
+                    })
+                })
+            }
+        };
+
+        let properties = self
+            .metadata
+            .schema()
+            .fields()
+            .iter()
+            .filter(|field| !self.skipped(field.name()))
+            .filter_map(|field| {
+                let queryable = match field.data_type() {
+                    DataType::Boolean => Queryable::new("boolean"),
+                    t if t.is_integer() => Queryable::new("integer"),
+                    t if t.is_floating() || t.is_numeric() => Queryable::new("number"),
+                    DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+                        Queryable::new("string")
+                    }
+                    DataType::Date32 | DataType::Date64 => Queryable::new("string").format("date"),
+                    DataType::Timestamp(..) => Queryable::new("string").format("date-time"),
+                    DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(..) => {
+                        Queryable::new("array")
+                    }
+                    DataType::Struct(_) | DataType::Map(..) => Queryable::new("object"),
+                    // binary attributes are not represented
+                    t if binary(t) => return None,
+                    _ => Queryable::default(),
+                };
+                Some((field.name().to_owned(), queryable))
+            })
+            .collect();
+
+        Info {
+            title: None,
+            description: None,
+            bounds,
+            crs,
+            count: Some(self.metadata.metadata().file_metadata().num_rows() as u64),
+            properties,
+        }
+    }
+
+    /// Features of the row groups whose statistics intersect `bounds`, within
+    /// the `window` of row numbers
+    pub(super) fn read(
+        &self,
+        bounds: Option<[f64; 4]>,
+        window: Option<Range<u64>>,
+    ) -> anyhow::Result<Vec<Feature>> {
+        let window = window.unwrap_or(0..u64::MAX);
+
+        let mut features = Vec::new();
+        let mut start = 0;
+
+        for (i, row_group) in self.metadata.metadata().row_groups().iter().enumerate() {
+            let rows = start..start + row_group.num_rows() as u64;
+            start = rows.end;
+
+            if rows.end <= window.start || rows.start >= window.end {
+                continue;
+            }
+            if let Some(bounds) = bounds
+                && self.bounds(row_group).is_some_and(|[x1, y1, x2, y2]| {
+                    x1 > bounds[2] || y1 > bounds[3] || x2 < bounds[0] || y2 < bounds[1]
+                })
+            {
+                continue;
+            }
+
+            // rows of the window in this row group
+            let offset = window.start.saturating_sub(rows.start);
+            let limit = window.end.min(rows.end) - rows.start - offset;
+
+            let reader = ParquetRecordBatchReaderBuilder::new_with_metadata(
+                self.file.try_clone()?,
+                self.metadata.clone(),
+            )
+            .with_row_groups(vec![i])
+            .with_offset(offset as usize)
+            .with_limit(limit as usize)
+            .build()?;
+
+            let mut row = rows.start + offset;
+            for batch in reader {
+                let batch = batch?;
+                features.extend(self.features(&batch, row)?);
+                row += batch.num_rows() as u64;
+            }
+        }
+
+        Ok(features)
+    }
+
+    /// Feature at a row number
+    pub(super) fn feature(&self, row: u64) -> anyhow::Result<Option<Feature>> {
+        Ok(self.read(None, Some(row..row + 1))?.pop())
+    }
+
+    /// Features of a batch starting at a row number
+    fn features(&self, batch: &RecordBatch, row: u64) -> anyhow::Result<Vec<Feature>> {
+        if batch.num_rows() == 0 {
+            return Ok(Vec::new());
+        }
+
+        let geometries = batch
+            .column_by_name(&self.geometry)
+            .context("Missing geometry column")?;
+        let wkb = |i: usize| -> Option<&[u8]> {
+            if geometries.is_null(i) {
+                return None;
+            }
+            match geometries.data_type() {
+                DataType::Binary => Some(geometries.as_binary::<i32>().value(i)),
+                DataType::LargeBinary => Some(geometries.as_binary::<i64>().value(i)),
+                DataType::BinaryView => Some(geometries.as_binary_view().value(i)),
+                _ => None,
+            }
+        };
+
+        // properties of the other columns
+        let indices: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !self.skipped(field.name()) && !binary(field.data_type()))
+            .map(|(i, _)| i)
+            .collect();
+        let mut writer = WriterBuilder::new()
+            .with_explicit_nulls(true)
+            .build::<_, JsonArray>(Vec::new());
+        writer.write(&batch.project(&indices)?)?;
+        writer.finish()?;
+        let properties: Vec<Map<String, Value>> = serde_json::from_slice(&writer.into_inner())?;
+
+        properties
+            .into_iter()
+            .enumerate()
+            .map(|(i, properties)| {
+                let geometry = wkb(i).map(decode).transpose()?;
+                feature(row + i as u64, geometry, properties)
+            })
+            .collect()
+    }
+
+    /// Whether a column is not a property, the geometry or its covering
+    fn skipped(&self, name: &str) -> bool {
+        name == self.geometry
+            || self
+                .column
+                .covering
+                .as_ref()
+                .is_some_and(|covering| covering.bbox.xmin.first().is_some_and(|c| c == name))
+    }
+
+    /// Bounds of a row group from the statistics of the covering columns
+    fn bounds(&self, row_group: &RowGroupMetaData) -> Option<[f64; 4]> {
+        let covering = &self.column.covering.as_ref()?.bbox;
+
+        let statistics = |path: &[String], min: bool| {
+            let column = row_group
+                .columns()
+                .iter()
+                .find(|column| column.column_path().parts() == path)?;
+            match column.statistics()? {
+                Statistics::Double(s) if min => s.min_opt().copied(),
+                Statistics::Double(s) => s.max_opt().copied(),
+                Statistics::Float(s) if min => s.min_opt().map(|v| *v as f64),
+                Statistics::Float(s) => s.max_opt().map(|v| *v as f64),
+                _ => None,
+            }
+        };
+
+        Some([
+            statistics(&covering.xmin, true)?,
+            statistics(&covering.ymin, true)?,
+            statistics(&covering.xmax, false)?,
+            statistics(&covering.ymax, false)?,
+        ])
+    }
+}
+
+fn binary(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Binary
+            | DataType::LargeBinary
+            | DataType::BinaryView
+            | DataType::FixedSizeBinary(_)
+    )
+}
+
+/// Decode a WKB geometry
+fn decode(wkb: &[u8]) -> anyhow::Result<Geometry> {
+    let mut json = Vec::new();
+    Wkb(wkb).process_geom(&mut GeoJsonWriter::with_dims(
+        &mut json,
+        CoordDimensions::xyz(),
+    ))?;
+    Ok(serde_json::from_slice(&json)?)
+}
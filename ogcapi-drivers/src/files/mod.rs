@@ -0,0 +1,199 @@
+//! File driver
+//!
+//! Serves a directory of FlatGeobuf (`.fgb`) and GeoParquet (`.parquet`) files
+//! as read-only collections named by their file stems. Bounding box queries
+//! only read the features found in the packed R-tree of FlatGeobuf files and
+//! the row groups of GeoParquet files whose covering statistics intersect,
+//! further predicates are evaluated in process. Features are identified by
+//! their position in the file and coordinates are never transformed.
+mod collection;
+mod feature;
+mod flatgeobuf;
+mod geoparquet;
+
+use std::{
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{Feature, FeatureId, Geometry, Queryable},
+};
+
+/// Driver for a directory of FlatGeobuf and GeoParquet files
+#[derive(Debug, Clone)]
+pub struct Files {
+    root: PathBuf,
+}
+
+impl Files {
+    /// Serve the files of a directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Files { root: root.into() }
+    }
+
+    /// Files of the directory, sorted by collection id
+    async fn sources(&self) -> anyhow::Result<Vec<Source>> {
+        let root = self.root.to_owned();
+        blocking(move || {
+            let mut sources = Vec::new();
+            for entry in std::fs::read_dir(root)? {
+                let path = entry?.path();
+                if let Some(source) = Source::new(&path) {
+                    sources.push(source);
+                }
+            }
+            sources.sort_by(|a, b| a.id.cmp(&b.id));
+            Ok(sources)
+        })
+        .await
+    }
+
+    /// File of a collection
+    async fn source(&self, id: &str) -> anyhow::Result<Option<Source>> {
+        Ok(self
+            .sources()
+            .await?
+            .into_iter()
+            .find(|source| source.id == id))
+    }
+
+    /// File of an existing collection
+    async fn existing(&self, id: &str) -> anyhow::Result<Source> {
+        self.source(id).await?.ok_or_else(|| {
+            Exception::new_from_status(404)
+                .detail(format!("Collection `{id}` does not exist"))
+                .into()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    FlatGeobuf,
+    GeoParquet,
+}
+
+/// File of a collection
+#[derive(Debug, Clone)]
+struct Source {
+    id: String,
+    path: PathBuf,
+    format: Format,
+}
+
+/// Description of a file
+struct Info {
+    title: Option<String>,
+    description: Option<String>,
+    /// Bounds of the horizontal axes
+    bounds: Option<[f64; 4]>,
+    crs: Crs,
+    /// Number of features, if known
+    count: Option<u64>,
+    properties: HashMap<String, Queryable>,
+}
+
+/// Reader of a file
+enum Reader {
+    FlatGeobuf(flatgeobuf::Reader),
+    GeoParquet(geoparquet::Reader),
+}
+
+impl Source {
+    /// Source of a path with a supported extension
+    fn new(path: &Path) -> Option<Self> {
+        let format = match path.extension()?.to_str()? {
+            "fgb" => Format::FlatGeobuf,
+            "parquet" => Format::GeoParquet,
+            _ => return None,
+        };
+        Some(Source {
+            id: path.file_stem()?.to_str()?.to_owned(),
+            path: path.to_owned(),
+            format,
+        })
+    }
+
+    fn open(&self) -> anyhow::Result<Reader> {
+        Ok(match self.format {
+            Format::FlatGeobuf => Reader::FlatGeobuf(flatgeobuf::Reader::open(&self.path)?),
+            Format::GeoParquet => Reader::GeoParquet(geoparquet::Reader::open(&self.path)?),
+        })
+    }
+
+    /// Entity tag from the modification time and size of the file
+    fn etag(&self) -> anyhow::Result<String> {
+        let metadata = std::fs::metadata(&self.path)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(format!("{:x}-{:x}", modified.as_nanos(), metadata.len()))
+    }
+}
+
+impl Reader {
+    fn info(&self) -> Info {
+        match self {
+            Reader::FlatGeobuf(reader) => reader.info(),
+            Reader::GeoParquet(reader) => reader.info(),
+        }
+    }
+
+    /// Features in file order, the candidates intersecting `bounds` or those
+    /// within the `window` of positions
+    fn read(
+        &mut self,
+        bounds: Option<[f64; 4]>,
+        window: Option<Range<u64>>,
+    ) -> anyhow::Result<Vec<Feature>> {
+        match self {
+            Reader::FlatGeobuf(reader) => match bounds {
+                Some(bounds) => match reader.search(bounds)? {
+                    Some(candidates) => reader.read(&candidates),
+                    None => reader.scan(window),
+                },
+                None => reader.scan(window),
+            },
+            Reader::GeoParquet(reader) => reader.read(bounds, window),
+        }
+    }
+
+    /// Feature at a position
+    fn feature(&mut self, position: u64) -> anyhow::Result<Option<Feature>> {
+        match self {
+            Reader::FlatGeobuf(reader) => reader.feature(position),
+            Reader::GeoParquet(reader) => reader.feature(position),
+        }
+    }
+}
+
+/// Feature at a position
+fn feature(
+    position: u64,
+    geometry: Option<Geometry>,
+    properties: Map<String, Value>,
+) -> anyhow::Result<Feature> {
+    Ok(serde_json::from_value(serde_json::json!({
+        "type": "Feature",
+        "id": FeatureId::Integer(position),
+        "geometry": geometry,
+        "properties": properties,
+    }))?)
+}
+
+/// Run blocking file access on the blocking thread pool
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+    tokio::task::spawn_blocking(f).await?
+}
+
+fn method_not_allowed() -> anyhow::Error {
+    Exception::new_from_status(405)
+        .detail("Collections of files are read-only")
+        .into()
+}
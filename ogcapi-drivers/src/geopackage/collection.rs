@@ -0,0 +1,484 @@
+use sqlx::{Executor, FromRow, SqliteConnection};
+
+use ogcapi_types::common::{Bbox, Collection, Collections, Crs, Exception, Query, SpatialExtent};
+
+use crate::{CollectionTransactions, check_etag, precondition_failed};
+
+use super::{
+    GeoPackage, METADATA_MIME_TYPE, METADATA_STANDARD, crs, etag, not_found, quote, srs_id, touch,
+};
+
+/// Row of `gpkg_contents`
+#[derive(FromRow)]
+struct Contents {
+    table_name: String,
+    data_type: String,
+    identifier: Option<String>,
+    description: Option<String>,
+    min_x: Option<f64>,
+    min_y: Option<f64>,
+    max_x: Option<f64>,
+    max_y: Option<f64>,
+    srs_id: Option<i32>,
+}
+
+const CONTENTS: &str = r#"
+SELECT table_name, data_type, identifier, description, min_x, min_y, max_x, max_y, srs_id
+FROM gpkg_contents
+WHERE data_type IN ('features', 'tiles')
+"#;
+
+#[async_trait::async_trait]
+impl CollectionTransactions for GeoPackage {
+    async fn create_collection(&self, collection: &Collection) -> anyhow::Result<String> {
+        let mut tx = self.pool.begin().await?;
+
+        let exists: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE lower(name) = lower(?1))
+                OR EXISTS (SELECT 1 FROM gpkg_contents WHERE lower(table_name) = lower(?1))
+            "#,
+        )
+        .bind(&collection.id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if exists {
+            return Err(Exception::new_from_status(409)
+                .detail(format!("Collection `{}` already exists", collection.id))
+                .into());
+        }
+
+        let srs_id = srs_id(
+            &mut tx,
+            collection.storage_crs.as_ref().unwrap_or(&Crs::default2d()),
+        )
+        .await?;
+
+        let table = quote(&collection.id);
+        sqlx::query(&format!(
+            "CREATE TABLE {table} (fid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, geom GEOMETRY)"
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO gpkg_contents (table_name, data_type, identifier, description, srs_id)
+            VALUES (?, 'features', ?, ?, ?)
+            "#,
+        )
+        .bind(&collection.id)
+        .bind(collection.title.as_ref().unwrap_or(&collection.id))
+        .bind(collection.description.as_deref().unwrap_or_default())
+        .bind(srs_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("INSERT INTO gpkg_geometry_columns VALUES (?, 'geom', 'GEOMETRY', ?, 2, 2)")
+            .bind(&collection.id)
+            .bind(srs_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.execute(rtree(&collection.id, "geom", "fid").as_str())
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO gpkg_extensions VALUES (
+                ?, 'geom', 'gpkg_rtree_index',
+                'http://www.geopackage.org/spec120/#extension_rtree', 'write-only'
+            )
+            "#,
+        )
+        .bind(&collection.id)
+        .execute(&mut *tx)
+        .await?;
+
+        write_metadata(&mut tx, collection).await?;
+
+        tx.commit().await?;
+
+        Ok(collection.id.to_owned())
+    }
+
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        let mut conn = self.pool.acquire().await?;
+        read_collection(&mut conn, id).await
+    }
+
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        match read_collection(&mut tx, &collection.id).await? {
+            Some(current) => check_etag(&etag(&serde_json::to_vec(&current)?), if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => {
+                return Err(not_found(format!(
+                    "Collection `{}` does not exist",
+                    collection.id
+                )));
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE gpkg_contents
+            SET identifier = ?, description = ?
+            WHERE table_name = ?
+            "#,
+        )
+        .bind(collection.title.as_ref().unwrap_or(&collection.id))
+        .bind(collection.description.as_deref().unwrap_or_default())
+        .bind(&collection.id)
+        .execute(&mut *tx)
+        .await?;
+
+        touch(&mut tx, &collection.id).await?;
+
+        write_metadata(&mut tx, collection).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        match read_collection(&mut tx, id).await? {
+            Some(current) => check_etag(&etag(&serde_json::to_vec(&current)?), if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Ok(()),
+        }
+
+        let geometry: Option<String> = sqlx::query_scalar(
+            "SELECT column_name FROM gpkg_geometry_columns WHERE table_name = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(geometry) = geometry {
+            sqlx::query(&format!(
+                "DROP TABLE IF EXISTS {}",
+                quote(&format!("rtree_{id}_{geometry}"))
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(&format!("DROP TABLE {}", quote(id)))
+            .execute(&mut *tx)
+            .await?;
+
+        for table in [
+            "gpkg_geometry_columns",
+            "gpkg_tile_matrix",
+            "gpkg_tile_matrix_set",
+            "gpkg_extensions",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE table_name = ?"))
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        delete_metadata(&mut tx, id).await?;
+
+        sqlx::query("DELETE FROM gpkg_contents WHERE table_name = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        let collection = self.read_collection(id).await?;
+        Ok(match collection {
+            Some(collection) => Some(etag(&serde_json::to_vec(&collection)?)),
+            None => None,
+        })
+    }
+
+    async fn list_collections(&self, _query: &Query) -> anyhow::Result<Collections> {
+        let mut conn = self.pool.acquire().await?;
+
+        let contents: Vec<Contents> = sqlx::query_as(&format!("{CONTENTS} ORDER BY table_name"))
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut collections = Vec::with_capacity(contents.len());
+        for contents in contents {
+            collections.push(collection(&mut conn, contents).await?);
+        }
+        #[cfg(feature = "stac")]
+        collections.retain(|collection| collection.r#type == "Collection");
+
+        let mut collections = Collections::new(collections);
+        collections.number_matched = collections.number_returned;
+
+        Ok(collections)
+    }
+}
+
+async fn read_collection(
+    conn: &mut SqliteConnection,
+    id: &str,
+) -> anyhow::Result<Option<Collection>> {
+    let contents: Option<Contents> = sqlx::query_as(&format!("{CONTENTS} AND table_name = ?"))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(match contents {
+        Some(contents) => Some(collection(conn, contents).await?),
+        None => None,
+    })
+}
+
+/// Collection of a table, from its stored collection document and contents
+async fn collection(conn: &mut SqliteConnection, contents: Contents) -> anyhow::Result<Collection> {
+    let mut collection = match read_metadata(conn, &contents.table_name).await? {
+        Some(document) => serde_json::from_str(&document)?,
+        None => Collection::default(),
+    };
+
+    collection.id = contents.table_name.to_owned();
+    if contents.identifier.is_some() {
+        collection.title = contents.identifier;
+    }
+    if contents.description.as_ref().is_some_and(|d| !d.is_empty()) {
+        collection.description = contents.description;
+    }
+    if contents.data_type == "features" {
+        collection.item_type = "feature".to_string();
+    }
+
+    let Some(srs_id) = contents.srs_id else {
+        return Ok(collection);
+    };
+    let storage_crs = crs(conn, srs_id).await?;
+    if collection.crs.is_empty() {
+        collection.crs = vec![storage_crs.to_owned()];
+    }
+    collection.storage_crs = Some(storage_crs.to_owned());
+
+    if collection
+        .extent
+        .as_ref()
+        .is_none_or(|extent| extent.spatial.is_none())
+    {
+        let bounds = match indexed_bounds(conn, &contents.table_name).await? {
+            Some(bounds) => Some(bounds),
+            None => match (
+                contents.min_x,
+                contents.min_y,
+                contents.max_x,
+                contents.max_y,
+            ) {
+                (Some(x1), Some(y1), Some(x2), Some(y2)) => Some([x1, y1, x2, y2]),
+                _ => None,
+            },
+        };
+        if let Some(bounds) = bounds {
+            collection.extent.get_or_insert_default().spatial = Some(SpatialExtent {
+                bbox: vec![Bbox::Bbox2D(bounds)],
+                crs: Some(storage_crs),
+            });
+        }
+    }
+
+    Ok(collection)
+}
+
+/// Bounds of the R-tree spatial index of a feature table
+async fn indexed_bounds(
+    conn: &mut SqliteConnection,
+    table: &str,
+) -> anyhow::Result<Option<[f64; 4]>> {
+    let geometry: Option<String> =
+        sqlx::query_scalar("SELECT column_name FROM gpkg_geometry_columns WHERE table_name = ?")
+            .bind(table)
+            .fetch_optional(&mut *conn)
+            .await?;
+    let Some(geometry) = geometry else {
+        return Ok(None);
+    };
+
+    let rtree = format!("rtree_{table}_{geometry}");
+    if !table_exists(conn, &rtree).await? {
+        return Ok(None);
+    }
+
+    let bounds: (Option<f64>, Option<f64>, Option<f64>, Option<f64>) = sqlx::query_as(&format!(
+        "SELECT min(minx), min(miny), max(maxx), max(maxy) FROM {}",
+        quote(&rtree)
+    ))
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(match bounds {
+        (Some(x1), Some(y1), Some(x2), Some(y2)) => Some([x1, y1, x2, y2]),
+        _ => None,
+    })
+}
+
+async fn table_exists(conn: &mut SqliteConnection, name: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = ?)")
+        .bind(name)
+        .fetch_one(conn)
+        .await
+}
+
+/// Collection document of a table, stored with the metadata extension
+async fn read_metadata(conn: &mut SqliteConnection, table: &str) -> anyhow::Result<Option<String>> {
+    if !table_exists(conn, "gpkg_metadata_reference").await? {
+        return Ok(None);
+    }
+
+    Ok(sqlx::query_scalar(
+        r#"
+        SELECT m.metadata
+        FROM gpkg_metadata m JOIN gpkg_metadata_reference r ON r.md_file_id = m.id
+        WHERE r.reference_scope = 'table' AND r.table_name = ?
+            AND m.md_standard_uri = ? AND m.mime_type = ?
+        ORDER BY r.timestamp DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(table)
+    .bind(METADATA_STANDARD)
+    .bind(METADATA_MIME_TYPE)
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// Store the document of a collection with the metadata extension
+async fn write_metadata(
+    conn: &mut SqliteConnection,
+    collection: &Collection,
+) -> anyhow::Result<()> {
+    conn.execute(include_str!("metadata.sql")).await?;
+
+    delete_metadata(conn, &collection.id).await?;
+
+    let id = sqlx::query(
+        "INSERT INTO gpkg_metadata (md_scope, md_standard_uri, mime_type, metadata) VALUES ('dataset', ?, ?, ?)",
+    )
+    .bind(METADATA_STANDARD)
+    .bind(METADATA_MIME_TYPE)
+    .bind(serde_json::to_string(collection)?)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    sqlx::query(
+        "INSERT INTO gpkg_metadata_reference (reference_scope, table_name, md_file_id) VALUES ('table', ?, ?)",
+    )
+    .bind(&collection.id)
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_metadata(conn: &mut SqliteConnection, table: &str) -> anyhow::Result<()> {
+    if !table_exists(conn, "gpkg_metadata_reference").await? {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        DELETE FROM gpkg_metadata_reference
+        WHERE table_name = ? AND md_file_id IN (
+            SELECT id FROM gpkg_metadata WHERE md_standard_uri = ?
+        )
+        "#,
+    )
+    .bind(table)
+    .bind(METADATA_STANDARD)
+    .execute(&mut *conn)
+    .await?;
+
+    // remove documents which are no longer referenced
+    sqlx::query(
+        r#"
+        DELETE FROM gpkg_metadata
+        WHERE md_standard_uri = ? AND id NOT IN (
+            SELECT md_file_id FROM gpkg_metadata_reference
+        )
+        "#,
+    )
+    .bind(METADATA_STANDARD)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// R-tree spatial index of a geometry column with the triggers to maintain it
+fn rtree(table: &str, column: &str, pk: &str) -> String {
+    let rtree = quote(&format!("rtree_{table}_{column}"));
+    let trigger = |name: &str| quote(&format!("rtree_{table}_{column}_{name}"));
+    let (t, c, i) = (quote(table), quote(column), quote(pk));
+    let values = format!(
+        "(NEW.{i}, ST_MinX(NEW.{c}), ST_MaxX(NEW.{c}), ST_MinY(NEW.{c}), ST_MaxY(NEW.{c}))"
+    );
+
+    format!(
+        r#"
+        CREATE VIRTUAL TABLE {rtree} USING rtree(id, minx, maxx, miny, maxy);
+
+        CREATE TRIGGER {insert} AFTER INSERT ON {t}
+        WHEN (NEW.{c} NOT NULL AND NOT ST_IsEmpty(NEW.{c}))
+        BEGIN
+            INSERT OR REPLACE INTO {rtree} VALUES {values};
+        END;
+
+        CREATE TRIGGER {update1} AFTER UPDATE OF {c} ON {t}
+        WHEN OLD.{i} = NEW.{i} AND (NEW.{c} NOTNULL AND NOT ST_IsEmpty(NEW.{c}))
+        BEGIN
+            INSERT OR REPLACE INTO {rtree} VALUES {values};
+        END;
+
+        CREATE TRIGGER {update2} AFTER UPDATE OF {c} ON {t}
+        WHEN OLD.{i} = NEW.{i} AND (NEW.{c} ISNULL OR ST_IsEmpty(NEW.{c}))
+        BEGIN
+            DELETE FROM {rtree} WHERE id = OLD.{i};
+        END;
+
+        CREATE TRIGGER {update3} AFTER UPDATE ON {t}
+        WHEN OLD.{i} != NEW.{i} AND (NEW.{c} NOTNULL AND NOT ST_IsEmpty(NEW.{c}))
+        BEGIN
+            DELETE FROM {rtree} WHERE id = OLD.{i};
+            INSERT OR REPLACE INTO {rtree} VALUES {values};
+        END;
+
+        CREATE TRIGGER {update4} AFTER UPDATE ON {t}
+        WHEN OLD.{i} != NEW.{i} AND (NEW.{c} ISNULL OR ST_IsEmpty(NEW.{c}))
+        BEGIN
+            DELETE FROM {rtree} WHERE id IN (OLD.{i}, NEW.{i});
+        END;
+
+        CREATE TRIGGER {delete} AFTER DELETE ON {t}
+        WHEN OLD.{c} NOT NULL
+        BEGIN
+            DELETE FROM {rtree} WHERE id = OLD.{i};
+        END;
+        "#,
+        insert = trigger("insert"),
+        update1 = trigger("update1"),
+        update2 = trigger("update2"),
+        update3 = trigger("update3"),
+        update4 = trigger("update4"),
+        delete = trigger("delete"),
+    )
+}
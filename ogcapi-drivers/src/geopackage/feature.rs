@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+
+use geo::Intersects;
+use serde_json::{Map, Value};
+use sqlx::{
+    Row, Sqlite, SqliteConnection, TypeInfo, ValueRef,
+    query::Query as SqlQuery,
+    sqlite::{SqliteArguments, SqliteRow},
+};
+
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{
+        Direction, Feature, FeatureCollection, FeatureId, NumberMatched, Query, Queryable,
+        Queryables, Schema,
+    },
+};
+
+use crate::{
+    FeatureTransactions, check_etag,
+    features::{
+        matches, matches_datetime, oriented, primary_geometry, project, untransformed, unversioned,
+    },
+    precondition_failed,
+};
+
+use super::{GeoPackage, Layer, crs, etag, geometry, layer, not_found, quote, touch};
+
+/// Bound value of a statement
+enum Param {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+#[async_trait::async_trait]
+impl FeatureTransactions for GeoPackage {
+    async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String> {
+        let collection_id = feature.collection.as_ref().unwrap();
+        let ids = self
+            .create_features(collection_id, std::slice::from_ref(feature))
+            .await?;
+        Ok(ids[0].to_owned())
+    }
+
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        let mut tx = self.pool.begin().await?;
+        let mut layer = layer(&mut tx, collection_id).await?;
+
+        let mut ids = Vec::with_capacity(features.len());
+        for feature in features {
+            let id = match &feature.id {
+                Some(id) => Some(id.to_string().parse::<i64>().map_err(|_| {
+                    Exception::new_from_status(400)
+                        .detail(format!("Feature id `{id}` is not an integer"))
+                })?),
+                None => None,
+            };
+
+            let mut params = vec![Param::from(id)];
+            params.extend(write_params(&mut tx, &mut layer, feature).await?);
+
+            let sql = format!(
+                "INSERT INTO {table} ({columns}) VALUES ({values})",
+                table = quote(&layer.table),
+                columns = layer.column_list(),
+                values = vec!["?"; params.len()].join(", ")
+            );
+            let result = bind(sqlx::query(&sql), params).execute(&mut *tx).await;
+
+            match result {
+                Ok(result) => ids.push(result.last_insert_rowid().to_string()),
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                    return Err(Exception::new_from_status(400)
+                        .detail(format!(
+                            "No features were created: feature `{}` already exists",
+                            id.unwrap_or_default()
+                        ))
+                        .into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        touch(&mut tx, collection_id).await?;
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
+    async fn read_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Feature>> {
+        let mut conn = self.pool.acquire().await?;
+        let layer = layer(&mut conn, collection_id).await?;
+        untransformed(Some(crs), storage_srid(&mut conn, &layer).await?)?;
+
+        Ok(read_feature(&mut conn, &layer, id)
+            .await?
+            .map(|feature| oriented(feature, Some(crs))))
+    }
+
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let collection_id = feature.collection.as_ref().unwrap();
+        let id = feature
+            .id
+            .as_ref()
+            .map(ToString::to_string)
+            .ok_or_else(|| Exception::new_from_status(400).detail("Missing feature id"))?;
+
+        let mut tx = self.pool.begin().await?;
+        let mut layer = layer(&mut tx, collection_id).await?;
+
+        match read_feature(&mut tx, &layer, &id).await? {
+            Some(current) => check_etag(&etag(&serde_json::to_vec(&current)?), if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Err(not_found(format!("Feature `{id}` does not exist"))),
+        }
+
+        write_feature(&mut tx, &mut layer, &id, feature).await?;
+
+        touch(&mut tx, collection_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn patch_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let mut layer = layer(&mut tx, collection_id).await?;
+
+        let mut feature = match read_feature(&mut tx, &layer, id).await? {
+            Some(current) => {
+                check_etag(&etag(&serde_json::to_vec(&current)?), if_match)?;
+                current
+            }
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Err(not_found(format!("Feature `{id}` does not exist"))),
+        };
+
+        feature
+            .merge_patch(patch)
+            .map_err(|e| Exception::new_from_status(400).detail(e))?;
+
+        write_feature(&mut tx, &mut layer, id, &feature).await?;
+
+        touch(&mut tx, collection_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let layer = layer(&mut tx, collection_id).await?;
+
+        match read_feature(&mut tx, &layer, id).await? {
+            Some(current) => check_etag(&etag(&serde_json::to_vec(&current)?), if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Ok(()),
+        }
+
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE {} = ?",
+            quote(&layer.table),
+            quote(&layer.pk)
+        ))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        touch(&mut tx, collection_id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn feature_etag(&self, collection_id: &str, id: &str) -> anyhow::Result<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
+        let layer = layer(&mut conn, collection_id).await?;
+
+        Ok(match read_feature(&mut conn, &layer, id).await? {
+            Some(feature) => Some(etag(&serde_json::to_vec(&feature)?)),
+            None => None,
+        })
+    }
+
+    async fn list_items(
+        &self,
+        collection_id: &str,
+        query: &Query,
+    ) -> anyhow::Result<FeatureCollection> {
+        let mut conn = self.pool.acquire().await?;
+        let layer = layer(&mut conn, collection_id).await?;
+        let srid = storage_srid(&mut conn, &layer).await?;
+
+        untransformed(query.crs.as_ref(), srid)?;
+        unversioned(query.version_at.as_ref())?;
+        primary_geometry(query.bbox_geometry.as_deref())?;
+
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+
+        // candidates by bounding box, from the spatial index if available
+        let bbox = match query.bbox.as_ref() {
+            Some(bbox) => {
+                untransformed(query.bbox_crs.as_ref(), srid)?;
+                let rects = crate::features::rects(bbox, query.bbox_crs.as_ref());
+                let condition = match &layer.rtree {
+                    Some(rtree) => format!(
+                        "{pk} IN (SELECT id FROM {rtree} WHERE minx <= ? AND maxx >= ? AND miny <= ? AND maxy >= ?)",
+                        pk = quote(&layer.pk),
+                        rtree = quote(rtree)
+                    ),
+                    None => format!(
+                        "ST_MinX({g}) <= ? AND ST_MaxX({g}) >= ? AND ST_MinY({g}) <= ? AND ST_MaxY({g}) >= ?",
+                        g = quote(&layer.geometry)
+                    ),
+                };
+                // boxes spanning the antimeridian are split in two
+                conditions.push(format!("({})", vec![condition; rects.len()].join(" OR ")));
+                for rect in &rects {
+                    params.extend([
+                        Param::Real(rect.max().x),
+                        Param::Real(rect.min().x),
+                        Param::Real(rect.max().y),
+                        Param::Real(rect.min().y),
+                    ]);
+                }
+                Some(rects)
+            }
+            None => None,
+        };
+
+        let mut keys: Vec<_> = query.additional_parameters.iter().collect();
+        keys.sort();
+        for (key, value) in keys {
+            // features without the property match
+            let Some((column, r#type)) = layer.column(key) else {
+                continue;
+            };
+            conditions.push(format!("{} = ?", quote(column)));
+            params.push(match r#type {
+                "BOOLEAN" => Param::Integer(value.parse::<bool>().unwrap_or_default() as i64),
+                _ => Param::Text(value.to_owned()),
+            });
+        }
+
+        let filter = query.parse_filter()?;
+        if filter.is_some() {
+            untransformed(query.filter_crs.as_ref(), srid)?;
+        }
+
+        let mut order = Vec::new();
+        for sortby in &query.sortby {
+            let column = match sortby.property() {
+                "id" => &layer.pk,
+                "geometry" => {
+                    return Err(Exception::new_from_status(400)
+                        .detail("Property geometry is not sortable!")
+                        .into());
+                }
+                property => match layer.column(property) {
+                    Some((column, _)) => column,
+                    None => {
+                        return Err(Exception::new_from_status(400)
+                            .detail(format!("Property `{property}` is not sortable!"))
+                            .into());
+                    }
+                },
+            };
+            order.push(match sortby.direction {
+                Direction::Asc => format!("{} ASC", quote(column)),
+                Direction::Desc => format!("{} DESC", quote(column)),
+            });
+        }
+        order.push(quote(&layer.pk));
+
+        let sql = format!(
+            "SELECT {columns} FROM {table}{conditions} ORDER BY {order}",
+            columns = layer.column_list(),
+            table = quote(&layer.table),
+            conditions = if conditions.is_empty() {
+                String::new()
+            } else {
+                format!(" WHERE {}", conditions.join(" AND "))
+            },
+            order = order.join(", ")
+        );
+
+        // the `next` token is the offset of the next page
+        let offset = match query.next.as_deref() {
+            Some(token) => token.parse().map_err(|_| {
+                anyhow::Error::from(Exception::new_from_status(400).detail("Invalid `next` token"))
+            })?,
+            None => query.offset.unwrap_or(0),
+        };
+        let limit = query.limit.unwrap_or(usize::MAX);
+
+        let exact = bbox.is_some() || query.datetime.is_some() || filter.is_some();
+        let count = query.number_matched.unwrap_or_default() != NumberMatched::Skip;
+
+        let (features, number_matched, next) = if exact {
+            // predicates evaluated in process, matching the exact geometry
+            let mut matched = Vec::new();
+            for row in bind(sqlx::query(&sql), params)
+                .fetch_all(&mut *conn)
+                .await?
+            {
+                let feature = layer.feature(&row)?;
+                if let Some(rects) = bbox.as_ref()
+                    && !crate::features::geometry(&feature)
+                        .is_some_and(|g| rects.iter().any(|rect| g.intersects(rect)))
+                {
+                    continue;
+                }
+                if let Some(datetime) = query.datetime.as_ref()
+                    && !matches_datetime(&feature, datetime)
+                {
+                    continue;
+                }
+                if let Some(filter) = filter.as_ref()
+                    && !matches(filter, &feature)?
+                {
+                    continue;
+                }
+                matched.push(feature);
+            }
+
+            let number_matched = count.then_some(matched.len() as u64);
+            let end = offset.saturating_add(limit);
+            let next = (end < matched.len()).then(|| end.to_string());
+            let features: Vec<_> = matched.into_iter().skip(offset).take(limit).collect();
+
+            (features, number_matched, next)
+        } else {
+            let number_matched = if count {
+                let sql = format!("SELECT count(*) FROM ({sql})");
+                let count: i64 = bind(sqlx::query(&sql), clone(&params))
+                    .fetch_one(&mut *conn)
+                    .await?
+                    .get(0);
+                Some(count as u64)
+            } else {
+                None
+            };
+
+            // one more row tells if there is a next page
+            let sql = format!("{sql} LIMIT ? OFFSET ?");
+            let mut params = params;
+            params.push(Param::Integer(
+                limit.saturating_add(1).min(i64::MAX as usize) as i64,
+            ));
+            params.push(Param::Integer(offset.min(i64::MAX as usize) as i64));
+
+            let mut features = bind(sqlx::query(&sql), params)
+                .fetch_all(&mut *conn)
+                .await?
+                .iter()
+                .map(|row| layer.feature(row))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let next = (features.len() > limit).then(|| (offset + limit).to_string());
+            features.truncate(limit);
+
+            (features, number_matched, next)
+        };
+
+        let features = features
+            .into_iter()
+            .map(|feature| {
+                let feature = project(
+                    feature,
+                    query.properties.as_deref(),
+                    query.skip_geometry.unwrap_or_default(),
+                );
+                oriented(feature, query.crs.as_ref())
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = number_matched;
+        fc.next = next;
+
+        Ok(fc)
+    }
+
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        true
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        let layer = self.layer(collection_id).await?;
+
+        Ok(Queryables {
+            title: Some(collection_id.to_owned()),
+            queryables: properties(&layer),
+            additional_properties: false,
+            ..Default::default()
+        })
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        let layer = self.layer(collection_id).await?;
+
+        Ok(Schema {
+            title: Some(collection_id.to_owned()),
+            properties: properties(&layer),
+            ..Default::default()
+        })
+    }
+}
+
+impl Layer {
+    /// Selected or inserted columns, the primary key, the geometry and the
+    /// attributes
+    fn column_list(&self) -> String {
+        [&self.pk, &self.geometry]
+            .into_iter()
+            .chain(self.columns.iter().map(|(column, _)| column))
+            .map(|column| quote(column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Attribute column of a property, column names are case insensitive
+    fn column(&self, property: &str) -> Option<(&str, &str)> {
+        self.columns
+            .iter()
+            .find(|(column, _)| column.eq_ignore_ascii_case(property))
+            .map(|(column, r#type)| (column.as_str(), r#type.as_str()))
+    }
+
+    /// Feature of a row with the columns of `column_list`
+    fn feature(&self, row: &SqliteRow) -> anyhow::Result<Feature> {
+        let id: i64 = row.try_get(0)?;
+
+        let geometry = match row.try_get::<Option<Vec<u8>>, _>(1)? {
+            Some(blob) => geometry::decode(&blob)?,
+            None => None,
+        };
+
+        let mut properties = Map::new();
+        for (i, (column, r#type)) in self.columns.iter().enumerate() {
+            let raw = row.try_get_raw(i + 2)?;
+            // the type info of `NULL` values is the declared column type
+            if raw.is_null() {
+                properties.insert(column.to_owned(), Value::Null);
+                continue;
+            }
+            let value = match raw.type_info().name() {
+                "INTEGER" if r#type == "BOOLEAN" => {
+                    Value::Bool(row.try_get_unchecked::<i64, _>(i + 2)? != 0)
+                }
+                "INTEGER" => row.try_get_unchecked::<i64, _>(i + 2)?.into(),
+                "REAL" => row.try_get_unchecked::<f64, _>(i + 2)?.into(),
+                "TEXT" => row.try_get_unchecked::<String, _>(i + 2)?.into(),
+                // binary attributes are not represented
+                _ => continue,
+            };
+            properties.insert(column.to_owned(), value);
+        }
+
+        Ok(serde_json::from_value(serde_json::json!({
+            "type": "Feature",
+            "id": FeatureId::Integer(id as u64),
+            "collection": self.table,
+            "properties": properties,
+            "geometry": geometry,
+        }))?)
+    }
+}
+
+impl From<Option<i64>> for Param {
+    fn from(value: Option<i64>) -> Self {
+        value.map(Param::Integer).unwrap_or(Param::Null)
+    }
+}
+
+impl From<&Value> for Param {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => Param::Null,
+            Value::Bool(b) => Param::Integer(*b as i64),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Param::Integer(i),
+                None => n.as_f64().map(Param::Real).unwrap_or(Param::Null),
+            },
+            Value::String(s) => Param::Text(s.to_owned()),
+            // nested values are stored as json text
+            value => Param::Text(value.to_string()),
+        }
+    }
+}
+
+fn bind<'q>(
+    mut query: SqlQuery<'q, Sqlite, SqliteArguments<'q>>,
+    params: Vec<Param>,
+) -> SqlQuery<'q, Sqlite, SqliteArguments<'q>> {
+    for param in params {
+        query = match param {
+            Param::Null => query.bind(None::<i64>),
+            Param::Integer(i) => query.bind(i),
+            Param::Real(f) => query.bind(f),
+            Param::Text(s) => query.bind(s),
+            Param::Blob(b) => query.bind(b),
+        };
+    }
+    query
+}
+
+fn clone(params: &[Param]) -> Vec<Param> {
+    params
+        .iter()
+        .map(|param| match param {
+            Param::Null => Param::Null,
+            Param::Integer(i) => Param::Integer(*i),
+            Param::Real(f) => Param::Real(*f),
+            Param::Text(s) => Param::Text(s.to_owned()),
+            Param::Blob(b) => Param::Blob(b.to_owned()),
+        })
+        .collect()
+}
+
+/// Storage srid of a feature table
+async fn storage_srid(conn: &mut SqliteConnection, layer: &Layer) -> anyhow::Result<i32> {
+    Ok(crs(conn, layer.srs_id).await?.as_srid())
+}
+
+async fn read_feature(
+    conn: &mut SqliteConnection,
+    layer: &Layer,
+    id: &str,
+) -> anyhow::Result<Option<Feature>> {
+    let Ok(id) = id.parse::<i64>() else {
+        return Ok(None);
+    };
+
+    let row = sqlx::query(&format!(
+        "SELECT {columns} FROM {table} WHERE {pk} = ?",
+        columns = layer.column_list(),
+        table = quote(&layer.table),
+        pk = quote(&layer.pk)
+    ))
+    .bind(id)
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| layer.feature(&row)).transpose()
+}
+
+/// Replace the geometry and attributes of a feature
+async fn write_feature(
+    conn: &mut SqliteConnection,
+    layer: &mut Layer,
+    id: &str,
+    feature: &Feature,
+) -> anyhow::Result<()> {
+    let mut params = write_params(conn, layer, feature).await?;
+    params.push(Param::Text(id.to_owned()));
+
+    let sql = format!(
+        "UPDATE {table} SET {assignments} WHERE {pk} = ?",
+        table = quote(&layer.table),
+        assignments = std::iter::once(&layer.geometry)
+            .chain(layer.columns.iter().map(|(column, _)| column))
+            .map(|column| format!("{} = ?", quote(column)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        pk = quote(&layer.pk)
+    );
+    bind(sqlx::query(&sql), params).execute(conn).await?;
+
+    Ok(())
+}
+
+/// Values of the geometry and attribute columns of a feature, adding columns
+/// for new properties
+async fn write_params(
+    conn: &mut SqliteConnection,
+    layer: &mut Layer,
+    feature: &Feature,
+) -> anyhow::Result<Vec<Param>> {
+    let properties = feature.properties.as_ref();
+
+    for (key, value) in properties.into_iter().flatten() {
+        if key.eq_ignore_ascii_case(&layer.pk) || key.eq_ignore_ascii_case(&layer.geometry) {
+            return Err(Exception::new_from_status(400)
+                .detail(format!(
+                    "Property `{key}` conflicts with a column of the table"
+                ))
+                .into());
+        }
+        if layer.column(key).is_none() && !value.is_null() {
+            let r#type = match value {
+                Value::Bool(_) => "BOOLEAN",
+                Value::Number(n) if n.is_i64() => "INTEGER",
+                Value::Number(_) => "DOUBLE",
+                _ => "TEXT",
+            };
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {type}",
+                quote(&layer.table),
+                quote(key)
+            ))
+            .execute(&mut *conn)
+            .await?;
+            layer.columns.push((key.to_owned(), r#type.to_owned()));
+        }
+    }
+
+    let mut params = vec![match feature.geometry.as_ref() {
+        Some(geometry) => Param::Blob(geometry::encode(geometry, layer.srs_id)?),
+        None => Param::Null,
+    }];
+
+    for (column, _) in &layer.columns {
+        let value = properties.and_then(|properties| {
+            properties
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(column))
+                .map(|(_, value)| value)
+        });
+        params.push(value.map(Param::from).unwrap_or(Param::Null));
+    }
+
+    Ok(params)
+}
+
+/// Property schemas from the declared types of the columns
+fn properties(layer: &Layer) -> HashMap<String, Queryable> {
+    let mut properties = HashMap::new();
+
+    for (column, r#type) in &layer.columns {
+        let property = match r#type.as_str() {
+            "BOOLEAN" => Queryable::new("boolean"),
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "INTEGER" => Queryable::new("integer"),
+            "FLOAT" | "DOUBLE" | "REAL" => Queryable::new("number"),
+            "DATE" => Queryable::new("string").format("date"),
+            "DATETIME" => Queryable::new("string").format("date-time"),
+            t if t.starts_with("TEXT") => Queryable::new("string"),
+            // binary attributes are not represented
+            t if t.starts_with("BLOB") => continue,
+            _ => Queryable::default(),
+        };
+        properties.insert(column.to_owned(), property);
+    }
+
+    let mut id = Queryable::new("integer").title("Feature identifier");
+    id.additional_properties
+        .insert("x-ogc-role".to_string(), "id".into());
+    properties.insert("id".to_string(), id);
+
+    let mut geometry = Queryable::default().format("geometry-any");
+    geometry
+        .additional_properties
+        .insert("x-ogc-role".to_string(), "primary-geometry".into());
+    properties.insert("geometry".to_string(), geometry);
+
+    properties
+}
@@ -0,0 +1,140 @@
+//! SQL functions on geometry blobs
+//!
+//! The triggers of the R-tree spatial index extension call `ST_IsEmpty`,
+//! `ST_MinX`, `ST_MaxX`, `ST_MinY` and `ST_MaxY`, which SQLite does not
+//! provide (<http://www.geopackage.org/spec/#extension_rtree>).
+use std::ffi::{CStr, c_int};
+
+use libsqlite3_sys as ffi;
+use sqlx::SqliteConnection;
+
+use super::geometry::{envelope, is_empty};
+
+type Function =
+    unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value);
+
+const FUNCTIONS: [(&CStr, Function); 5] = [
+    (c"ST_IsEmpty", st_is_empty),
+    (c"ST_MinX", st_min_x),
+    (c"ST_MaxX", st_max_x),
+    (c"ST_MinY", st_min_y),
+    (c"ST_MaxY", st_max_y),
+];
+
+/// Register the functions with a connection
+pub(super) async fn register(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    let db = handle.as_raw_handle().as_ptr();
+
+    for (name, function) in FUNCTIONS {
+        // SAFETY: the handle is locked and the name is nul-terminated
+        let code = unsafe {
+            ffi::sqlite3_create_function_v2(
+                db,
+                name.as_ptr(),
+                1,
+                ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+                std::ptr::null_mut(),
+                Some(function),
+                None,
+                None,
+                None,
+            )
+        };
+        if code != ffi::SQLITE_OK {
+            return Err(sqlx::Error::Configuration(
+                format!("Failed to register function {name:?} ({code})").into(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Blob of the single argument, `None` for `NULL`
+///
+/// # Safety
+///
+/// `args` must point to one valid value.
+unsafe fn blob<'a>(args: *mut *mut ffi::sqlite3_value) -> Option<&'a [u8]> {
+    // SAFETY: sqlite passes one argument, which lives until the function returns
+    unsafe {
+        let value = *args;
+        if ffi::sqlite3_value_type(value) != ffi::SQLITE_BLOB {
+            return None;
+        }
+        let len = ffi::sqlite3_value_bytes(value);
+        let ptr = ffi::sqlite3_value_blob(value);
+        if ptr.is_null() || len <= 0 {
+            return Some(&[]);
+        }
+        Some(std::slice::from_raw_parts(ptr.cast(), len as usize))
+    }
+}
+
+unsafe extern "C" fn st_is_empty(
+    ctx: *mut ffi::sqlite3_context,
+    _n: c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: called by sqlite with a valid context and one argument
+    unsafe {
+        match blob(args).map(is_empty) {
+            None => ffi::sqlite3_result_null(ctx),
+            Some(Ok(empty)) => ffi::sqlite3_result_int(ctx, empty as c_int),
+            Some(Err(_)) => ffi::sqlite3_result_error(ctx, c"Invalid geometry blob".as_ptr(), -1),
+        }
+    }
+}
+
+/// Result a value of the envelope `[minx, maxx, miny, maxy]`
+///
+/// # Safety
+///
+/// Must be called with the arguments of a function.
+unsafe fn envelope_value(
+    ctx: *mut ffi::sqlite3_context,
+    args: *mut *mut ffi::sqlite3_value,
+    index: usize,
+) {
+    // SAFETY: called by sqlite with a valid context and one argument
+    unsafe {
+        match blob(args).map(envelope) {
+            Some(Ok(Some(envelope))) => ffi::sqlite3_result_double(ctx, envelope[index]),
+            None | Some(Ok(None)) => ffi::sqlite3_result_null(ctx),
+            Some(Err(_)) => ffi::sqlite3_result_error(ctx, c"Invalid geometry blob".as_ptr(), -1),
+        }
+    }
+}
+
+unsafe extern "C" fn st_min_x(
+    ctx: *mut ffi::sqlite3_context,
+    _n: c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) {
+    unsafe { envelope_value(ctx, args, 0) }
+}
+
+unsafe extern "C" fn st_max_x(
+    ctx: *mut ffi::sqlite3_context,
+    _n: c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) {
+    unsafe { envelope_value(ctx, args, 1) }
+}
+
+unsafe extern "C" fn st_min_y(
+    ctx: *mut ffi::sqlite3_context,
+    _n: c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) {
+    unsafe { envelope_value(ctx, args, 2) }
+}
+
+unsafe extern "C" fn st_max_y(
+    ctx: *mut ffi::sqlite3_context,
+    _n: c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) {
+    unsafe { envelope_value(ctx, args, 3) }
+}
@@ -0,0 +1,175 @@
+//! GeoPackage geometry blobs
+//!
+//! A blob is a header with the srs id and an optional envelope, followed by
+//! the geometry in WKB (<http://www.geopackage.org/spec/#gpb_format>).
+use anyhow::bail;
+use geo::BoundingRect;
+use geozero::{
+    CoordDimensions, GeozeroGeometry, ToGeo, ToWkb,
+    geojson::{GeoJson, GeoJsonWriter},
+    wkb::{GpkgWkb, Wkb},
+};
+
+use geojson::GeometryValue;
+
+use ogcapi_types::{common::Exception, features::Geometry};
+
+/// Header of a geometry blob
+struct Header {
+    empty: bool,
+    /// Envelope of the horizontal axes as `[minx, maxx, miny, maxy]`
+    envelope: Option<[f64; 4]>,
+    /// Offset of the WKB geometry
+    offset: usize,
+}
+
+fn header(blob: &[u8]) -> anyhow::Result<Header> {
+    let [b'G', b'P', _version, flags, ..] = blob else {
+        bail!("Invalid GeoPackage geometry blob");
+    };
+
+    let envelope_len = match (flags >> 1) & 0b111 {
+        0 => 0,
+        1 => 4,
+        2 | 3 => 6,
+        4 => 8,
+        indicator => bail!("Invalid envelope contents indicator {indicator}"),
+    };
+    let offset = 8 + envelope_len * 8;
+    if blob.len() < offset {
+        bail!("Invalid GeoPackage geometry blob");
+    }
+
+    let little_endian = flags & 1 == 1;
+    let value = |i: usize| {
+        let bytes: [u8; 8] = blob[8 + i * 8..16 + i * 8].try_into().unwrap();
+        if little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        }
+    };
+
+    Ok(Header {
+        empty: (flags >> 4) & 1 == 1,
+        envelope: (envelope_len > 0).then(|| [value(0), value(1), value(2), value(3)]),
+        offset,
+    })
+}
+
+/// Whether a blob holds an empty geometry
+pub(super) fn is_empty(blob: &[u8]) -> anyhow::Result<bool> {
+    Ok(header(blob)?.empty)
+}
+
+/// Envelope of the horizontal axes as `[minx, maxx, miny, maxy]`, `None` for
+/// empty geometries
+pub(super) fn envelope(blob: &[u8]) -> anyhow::Result<Option<[f64; 4]>> {
+    let header = header(blob)?;
+    if header.empty {
+        return Ok(None);
+    }
+    if let Some(envelope) = header.envelope {
+        return Ok(Some(envelope));
+    }
+
+    // compute the envelope if the header has none
+    Ok(Wkb(&blob[header.offset..])
+        .to_geo()?
+        .bounding_rect()
+        .map(|r| [r.min().x, r.max().x, r.min().y, r.max().y]))
+}
+
+/// Decode a blob, `None` for empty geometries
+pub(super) fn decode(blob: &[u8]) -> anyhow::Result<Option<Geometry>> {
+    if header(blob)?.empty {
+        return Ok(None);
+    }
+
+    let mut json = Vec::new();
+    GpkgWkb(blob).process_geom(&mut GeoJsonWriter::with_dims(
+        &mut json,
+        CoordDimensions::xyz(),
+    ))?;
+
+    Ok(Some(serde_json::from_slice(&json)?))
+}
+
+/// Encode a geometry with the srs id of its feature table
+pub(super) fn encode(geometry: &Geometry, srs_id: i32) -> anyhow::Result<Vec<u8>> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        anyhow::Error::from(
+            Exception::new_from_status(400).detail(format!("Invalid geometry: {e}")),
+        )
+    };
+
+    let envelope = geo::Geometry::try_from(geometry)
+        .map_err(|e| invalid(&e))?
+        .bounding_rect()
+        .map(|r| vec![r.min().x, r.max().x, r.min().y, r.max().y])
+        .ok_or_else(|| invalid(&"empty geometries are not supported"))?;
+
+    let dims = if has_z(&geometry.value) {
+        CoordDimensions::xyz()
+    } else {
+        CoordDimensions::xy()
+    };
+
+    let json = serde_json::to_string(geometry)?;
+    GeoJson(&json)
+        .to_gpkg_wkb(dims, Some(srs_id), envelope)
+        .map_err(|e| invalid(&e))
+}
+
+/// Whether a geometry has a third coordinate
+fn has_z(value: &GeometryValue) -> bool {
+    match value {
+        GeometryValue::Point { coordinates } => coordinates.len() > 2,
+        GeometryValue::MultiPoint { coordinates } | GeometryValue::LineString { coordinates } => {
+            coordinates.iter().any(|p| p.len() > 2)
+        }
+        GeometryValue::MultiLineString { coordinates } | GeometryValue::Polygon { coordinates } => {
+            coordinates.iter().flatten().any(|p| p.len() > 2)
+        }
+        GeometryValue::MultiPolygon { coordinates } => {
+            coordinates.iter().flatten().flatten().any(|p| p.len() > 2)
+        }
+        GeometryValue::GeometryCollection { geometries } => {
+            geometries.iter().any(|g| has_z(&g.value))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let geometry: Geometry = serde_json::from_str(
+            r#"{"type":"LineString","coordinates":[[7.4,46.9,540],[8.5,47.4,408]]}"#,
+        )
+        .unwrap();
+        let blob = encode(&geometry, 4326).unwrap();
+
+        assert_eq!(&blob[..2], b"GP");
+        assert_eq!(i32::from_le_bytes(blob[4..8].try_into().unwrap()), 4326);
+        assert!(!is_empty(&blob).unwrap());
+        assert_eq!(envelope(&blob).unwrap(), Some([7.4, 8.5, 46.9, 47.4]));
+        assert_eq!(decode(&blob).unwrap(), Some(geometry));
+    }
+
+    #[test]
+    fn without_envelope() {
+        // point (1 2) without envelope, big endian header
+        let mut blob = vec![b'G', b'P', 0, 0b0000_0000, 0, 0, 0x10, 0xe6];
+        let point = geo::Geometry::from(geo::point! { x: 1.0, y: 2.0 });
+        blob.extend(point.to_wkb(CoordDimensions::xy()).unwrap());
+        assert_eq!(envelope(&blob).unwrap(), Some([1.0, 1.0, 2.0, 2.0]));
+
+        // empty flag
+        blob[3] = 0b0001_0000;
+        assert!(is_empty(&blob).unwrap());
+        assert_eq!(decode(&blob).unwrap(), None);
+    }
+}
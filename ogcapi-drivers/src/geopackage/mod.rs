@@ -0,0 +1,264 @@
+//! GeoPackage driver
+//!
+//! Serves the feature and tile tables listed in `gpkg_contents` of a
+//! GeoPackage file as collections. Feature properties are stored in the
+//! columns of a feature table, and bounding box queries use the R-tree spatial
+//! index of its geometry column. Coordinates are never transformed, so requests
+//! must use the storage crs of a collection.
+mod collection;
+mod feature;
+mod functions;
+mod geometry;
+#[cfg(feature = "tiles")]
+mod tile;
+
+use std::{path::Path, str::FromStr};
+
+use sqlx::{
+    Row, SqliteConnection,
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+};
+
+use ogcapi_types::common::{Crs, Exception};
+
+/// Media type and standard of the collection documents stored with the
+/// metadata extension
+const METADATA_MIME_TYPE: &str = "application/json";
+const METADATA_STANDARD: &str = "http://www.opengis.net/spec/ogcapi-common-2/1.0";
+
+#[derive(Debug, Clone)]
+pub struct GeoPackage {
+    pub pool: SqlitePool,
+}
+
+/// Feature table of a GeoPackage
+struct Layer {
+    table: String,
+    /// Integer primary key, used as feature id
+    pk: String,
+    geometry: String,
+    srs_id: i32,
+    /// Attribute columns with their declared types
+    columns: Vec<(String, String)>,
+    /// Name of the R-tree spatial index, if any
+    rtree: Option<String>,
+}
+
+impl GeoPackage {
+    /// Open a GeoPackage file, creating an empty one if it does not exist
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        Self::connect_with(options).await
+    }
+
+    /// Connect to a GeoPackage from a url like `sqlite://data.gpkg`
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        Self::connect_with(options).await
+    }
+
+    async fn connect_with(options: SqliteConnectOptions) -> Result<Self, sqlx::Error> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(8)
+            .after_connect(|conn, _meta| Box::pin(functions::register(conn)))
+            .connect_with(options)
+            .await?;
+
+        // Setup the core tables of a new file
+        let initialized: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'gpkg_contents')",
+        )
+        .fetch_one(&pool)
+        .await?;
+        if !initialized {
+            sqlx::raw_sql(include_str!("schema.sql"))
+                .execute(&pool)
+                .await?;
+        }
+
+        Ok(GeoPackage { pool })
+    }
+
+    /// Feature table of a collection
+    async fn layer(&self, collection_id: &str) -> anyhow::Result<Layer> {
+        let mut conn = self.pool.acquire().await?;
+        layer(&mut conn, collection_id).await
+    }
+}
+
+/// Feature table of a collection
+async fn layer(conn: &mut SqliteConnection, collection_id: &str) -> anyhow::Result<Layer> {
+    let geometry_column = sqlx::query(
+        r#"
+        SELECT g.column_name, g.srs_id
+        FROM gpkg_geometry_columns g JOIN gpkg_contents c USING (table_name)
+        WHERE table_name = ? AND c.data_type = 'features'
+        "#,
+    )
+    .bind(collection_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(geometry_column) = geometry_column else {
+        return Err(Exception::new_from_status(404)
+            .detail(format!("Collection `{collection_id}` does not exist"))
+            .into());
+    };
+    let geometry: String = geometry_column.get(0);
+
+    let mut pk = None;
+    let mut columns = Vec::new();
+    for column in sqlx::query("SELECT name, type, pk FROM pragma_table_info(?) ORDER BY cid")
+        .bind(collection_id)
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        let name: String = column.get(0);
+        if column.get::<i64, _>(2) > 0 {
+            pk = Some(name);
+        } else if !name.eq_ignore_ascii_case(&geometry) {
+            columns.push((name, column.get::<String, _>(1).to_uppercase()));
+        }
+    }
+
+    let Some(pk) = pk else {
+        return Err(Exception::new_from_status(500)
+            .detail(format!(
+                "Feature table `{collection_id}` has no primary key"
+            ))
+            .into());
+    };
+
+    let rtree = format!("rtree_{collection_id}_{geometry}");
+    let rtree = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+    )
+    .bind(&rtree)
+    .fetch_one(&mut *conn)
+    .await?
+    .then_some(rtree);
+
+    Ok(Layer {
+        table: collection_id.to_owned(),
+        pk,
+        geometry,
+        srs_id: geometry_column.get(1),
+        columns,
+        rtree,
+    })
+}
+
+/// Storage crs of a GeoPackage spatial reference system
+async fn crs(conn: &mut SqliteConnection, srs_id: i32) -> anyhow::Result<Crs> {
+    let srs: Option<(String, i32)> = sqlx::query_as(
+        "SELECT organization, organization_coordsys_id FROM gpkg_spatial_ref_sys WHERE srs_id = ?",
+    )
+    .bind(srs_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(match srs {
+        Some((organization, code)) if organization.eq_ignore_ascii_case("EPSG") => {
+            Crs::from_srid(code)
+        }
+        _ => Crs::default2d(),
+    })
+}
+
+/// Spatial reference system of a crs, registering it if it is missing
+async fn srs_id(conn: &mut SqliteConnection, crs: &Crs) -> anyhow::Result<i32> {
+    let code = crs.as_srid();
+
+    let srs_id: Option<i32> = sqlx::query_scalar(
+        r#"
+        SELECT srs_id FROM gpkg_spatial_ref_sys
+        WHERE upper(organization) = 'EPSG' AND organization_coordsys_id = ?
+        "#,
+    )
+    .bind(code)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match srs_id {
+        Some(srs_id) => Ok(srs_id),
+        None => {
+            // the definition is unknown without a projection database
+            sqlx::query(
+                r#"
+                INSERT INTO gpkg_spatial_ref_sys
+                    (srs_name, srs_id, organization, organization_coordsys_id, definition)
+                VALUES (?, ?, 'EPSG', ?, 'undefined')
+                "#,
+            )
+            .bind(format!("EPSG:{code}"))
+            .bind(code)
+            .bind(code)
+            .execute(&mut *conn)
+            .await?;
+            Ok(code)
+        }
+    }
+}
+
+/// Record a change of a table in `gpkg_contents`
+async fn touch(conn: &mut SqliteConnection, table: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE gpkg_contents
+        SET last_change = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+        WHERE table_name = ?
+        "#,
+    )
+    .bind(table)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Quote an SQLite identifier
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Entity tag of a resource, a hash of its json representation
+fn etag(json: &[u8]) -> String {
+    // FNV-1a, which is stable across platforms and releases
+    let hash = json.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+fn not_found(detail: impl ToString) -> anyhow::Error {
+    Exception::new_from_status(404).detail(detail).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("places"), r#""places""#);
+        assert_eq!(
+            quote(r#"a"; DROP TABLE b; --"#),
+            r#""a""; DROP TABLE b; --""#
+        );
+    }
+
+    #[test]
+    fn etags() {
+        let a = etag(br#"{"name":"Bern"}"#);
+        let b = etag(br#"{"name":"Biel"}"#);
+        assert_eq!(a.len(), 16);
+        assert_ne!(a, b);
+        assert_eq!(a, etag(br#"{"name":"Bern"}"#));
+    }
+}
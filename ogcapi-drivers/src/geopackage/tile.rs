@@ -0,0 +1,77 @@
+use ogcapi_types::{common::Exception, tiles::TileMatrixSet};
+
+use crate::TileTransactions;
+
+use super::{GeoPackage, quote};
+
+#[async_trait::async_trait]
+impl TileTransactions for GeoPackage {
+    /// Tile of the tile tables among `collections`
+    ///
+    /// Tiles of several tables are concatenated, which is valid for vector
+    /// tiles (`application/vnd.mapbox-vector-tile`). Feature tables are not
+    /// rendered, so the geometry to render does not apply.
+    async fn tile(
+        &self,
+        collections: &[String],
+        tms: &TileMatrixSet,
+        matrix: &str,
+        row: u32,
+        col: u32,
+        _geometry: Option<&str>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let zoom_level: i64 = matrix.parse()?;
+        let tile_matrix = tms.tile_matrices.iter().find(|tm| tm.id == matrix);
+
+        let mut conn = self.pool.acquire().await?;
+        let mut tiles = Vec::new();
+
+        for collection_id in collections {
+            let dimensions: Option<(i64, i64)> = sqlx::query_as(
+                r#"
+                SELECT m.matrix_width, m.matrix_height
+                FROM gpkg_tile_matrix m JOIN gpkg_contents c USING (table_name)
+                WHERE table_name = ? AND c.data_type = 'tiles' AND m.zoom_level = ?
+                "#,
+            )
+            .bind(collection_id)
+            .bind(zoom_level)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            // no tiles at this zoom level or not a tile table
+            let Some((width, height)) = dimensions else {
+                continue;
+            };
+
+            if let Some(tm) = tile_matrix
+                && (tm.matrix_width.get() as i64 != width
+                    || tm.matrix_height.get() as i64 != height)
+            {
+                return Err(Exception::new_from_status(400)
+                    .detail(format!(
+                        "Tiles of collection `{collection_id}` do not match tile matrix `{matrix}` of `{}`",
+                        tms.id
+                    ))
+                    .into());
+            }
+
+            let tile: Option<Vec<u8>> = sqlx::query_scalar(&format!(
+                r#"
+                SELECT tile_data FROM {}
+                WHERE zoom_level = ? AND tile_column = ? AND tile_row = ?
+                "#,
+                quote(collection_id)
+            ))
+            .bind(zoom_level)
+            .bind(col)
+            .bind(row)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            tiles.extend(tile);
+        }
+
+        Ok(tiles.concat())
+    }
+}
@@ -1,8 +1,30 @@
+#[cfg(feature = "common")]
+pub mod cache;
+#[cfg(any(
+    feature = "files",
+    feature = "geopackage",
+    feature = "memory",
+    feature = "s3"
+))]
+mod features;
+#[cfg(feature = "files")]
+pub mod files;
+#[cfg(feature = "geopackage")]
+pub mod geopackage;
+#[cfg(feature = "memory")]
+pub mod memory;
 #[cfg(feature = "postgres")]
 pub mod postgres;
+#[cfg(feature = "common")]
+pub mod routing;
 #[cfg(feature = "s3")]
 pub mod s3;
 
+#[cfg(feature = "features")]
+use futures::{
+    StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
 #[cfg(feature = "common")]
 use ogcapi_types::common::{Collection, Collections, Query as CollectionQuery};
 #[cfg(feature = "edr")]
@@ -13,16 +35,20 @@ use ogcapi_types::stac::SearchParams;
 use ogcapi_types::styles::Styles;
 #[cfg(feature = "tiles")]
 use ogcapi_types::tiles::TileMatrixSet;
-#[cfg(feature = "features")]
-use ogcapi_types::{
-    common::Crs,
-    features::{Feature, Query as FeatureQuery, Queryables},
-};
 #[cfg(feature = "processes")]
 use ogcapi_types::{
     common::Link,
     processes::{Response, StatusCode, StatusInfo},
 };
+#[cfg(feature = "features")]
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{
+        Change, Feature, FeatureVersion, NumberMatched, Query as FeatureQuery, Queryables, Schema,
+    },
+};
+#[cfg(feature = "features")]
+use serde_json::{Map, Value};
 
 #[cfg(any(feature = "features", feature = "stac", feature = "edr"))]
 use ogcapi_types::features::FeatureCollection;
@@ -35,11 +61,23 @@ pub trait CollectionTransactions: Send + Sync {
 
     async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>>;
 
-    async fn update_collection(&self, collection: &Collection) -> anyhow::Result<()>;
+    /// Update a collection, provided its entity tag matches `if_match`
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()>;
 
-    async fn delete_collection(&self, id: &str) -> anyhow::Result<()>;
+    /// Delete a collection, provided its entity tag matches `if_match`
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()>;
 
     async fn list_collections(&self, query: &CollectionQuery) -> anyhow::Result<Collections>;
+
+    /// Entity tag of the current version of a collection
+    async fn collection_etag(&self, _id: &str) -> anyhow::Result<Option<String>> {
+        // Default to no versioning
+        Ok(None)
+    }
 }
 
 /// Trait for `Feature` transactions
@@ -48,15 +86,84 @@ pub trait CollectionTransactions: Send + Sync {
 pub trait FeatureTransactions: Send + Sync {
     async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String>;
 
+    /// Create multiple features, returning their ids in order
+    ///
+    /// Drivers should insert all features or none.
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        // Default to one feature after the other, which is not atomic
+        let mut ids = Vec::with_capacity(features.len());
+        for feature in features {
+            let mut feature = feature.to_owned();
+            feature.collection = Some(collection_id.to_owned());
+            ids.push(self.create_feature(&feature).await?);
+        }
+        Ok(ids)
+    }
+
     async fn read_feature(
         &self,
         collection_id: &str,
         id: &str,
         crs: &Crs,
     ) -> anyhow::Result<Option<Feature>>;
-    async fn update_feature(&self, feature: &Feature) -> anyhow::Result<()>;
 
-    async fn delete_feature(&self, collection_id: &str, id: &str) -> anyhow::Result<()>;
+    /// Update a feature, provided its entity tag matches `if_match`
+    async fn update_feature(&self, feature: &Feature, if_match: Option<&str>)
+    -> anyhow::Result<()>;
+
+    /// Partially update a feature with a JSON Merge Patch, provided its
+    /// entity tag matches `if_match`
+    async fn patch_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        // Default to read, patch and replace
+        let mut feature = self
+            .read_feature(collection_id, id, &Crs::default2d())
+            .await?
+            .ok_or_else(|| anyhow::Error::from(Exception::new_from_status(404)))?;
+        feature
+            .merge_patch(patch)
+            .map_err(|e| Exception::new_from_status(400).detail(e))?;
+        self.update_feature(&feature, if_match).await
+    }
+
+    /// Delete a feature, provided its entity tag matches `if_match`
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()>;
+
+    /// Entity tag of the current version of a feature
+    async fn feature_etag(
+        &self,
+        _collection_id: &str,
+        _id: &str,
+    ) -> anyhow::Result<Option<String>> {
+        // Default to no versioning
+        Ok(None)
+    }
+
+    /// Versions of a feature, oldest first, or `None` if the collection does
+    /// not record the history of its features
+    async fn feature_versions(
+        &self,
+        _collection_id: &str,
+        _id: &str,
+        _crs: &Crs,
+    ) -> anyhow::Result<Option<Vec<FeatureVersion>>> {
+        // Default to no history
+        Ok(None)
+    }
 
     async fn list_items(
         &self,
@@ -64,13 +171,60 @@ pub trait FeatureTransactions: Send + Sync {
         query: &FeatureQuery,
     ) -> anyhow::Result<FeatureCollection>;
 
+    /// Stream of the items of a query, all of them unless it has a limit
+    ///
+    /// Defaults to fetching pages of [`FeatureTransactions::list_items`] as
+    /// the stream is consumed.
+    fn stream_items<'a>(
+        &'a self,
+        collection_id: &'a str,
+        query: &'a FeatureQuery,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        paginate(
+            query.limit,
+            query.offset,
+            query.next.to_owned(),
+            move |limit, offset, next| {
+                let mut query = query.to_owned();
+                query.limit = Some(limit);
+                query.offset = offset;
+                query.next = next;
+                query.number_matched = Some(NumberMatched::Skip);
+                async move { self.list_items(collection_id, &query).await }
+            },
+        )
+    }
+
+    /// Whether [`FeatureTransactions::list_items`] returns a `next` token
+    /// while there are more features, otherwise pages follow by their offset
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        // Default to offset pagination
+        false
+    }
+
     async fn queryables(&self, _collection_id: &str) -> anyhow::Result<Queryables> {
         // Default to nothing is queryable
         Ok(Queryables {
-            queryables: Default::default(),
             additional_properties: false,
+            ..Default::default()
         })
     }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        // Default to the queryables, allowing further properties
+        Ok(self.queryables(collection_id).await?.into())
+    }
+}
+
+/// Trait for notifications of `Feature` changes
+#[cfg(feature = "features")]
+#[async_trait::async_trait]
+pub trait FeatureChanges: Send + Sync {
+    /// Stream of the changes of the features of a collection, from now on
+    async fn changes(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<Change>>>;
 }
 
 /// Trait for `STAC` search
@@ -78,6 +232,109 @@ pub trait FeatureTransactions: Send + Sync {
 #[async_trait::async_trait]
 pub trait StacSearch: Send + Sync {
     async fn search(&self, query: &SearchParams) -> anyhow::Result<FeatureCollection>;
+
+    /// Stream of the items of a search, all of them unless it has a limit
+    ///
+    /// Defaults to fetching pages of [`StacSearch::search`] as the stream is
+    /// consumed.
+    fn stream_search<'a>(
+        &'a self,
+        query: &'a SearchParams,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        paginate(
+            query.limit.map(|limit| limit as usize),
+            query.offset.map(|offset| offset as usize),
+            None,
+            move |limit, offset, _| {
+                let mut query = query.to_owned();
+                query.limit = Some(limit as u64);
+                query.offset = offset.map(|offset| offset as u64);
+                async move {
+                    // searches are paged by their offset
+                    let mut fc = self.search(&query).await?;
+                    fc.next = None;
+                    Ok(fc)
+                }
+            },
+        )
+    }
+}
+
+/// Number of features fetched at once by the default streams
+#[cfg(feature = "features")]
+const PAGE_SIZE: usize = 1000;
+
+/// Number of features sampled to derive the queryables and schema
+#[cfg(any(
+    feature = "memory",
+    feature = "s3",
+    all(feature = "postgres", feature = "features")
+))]
+const SAMPLE_SIZE: usize = 1000;
+
+/// Error of a conditional write whose entity tag does not match the stored version
+#[cfg(all(
+    feature = "common",
+    any(feature = "geopackage", feature = "memory", feature = "postgres")
+))]
+fn precondition_failed() -> anyhow::Error {
+    ogcapi_types::common::Exception::new_from_status(412)
+        .detail("The resource has been modified, the entity tag does not match")
+        .into()
+}
+
+/// Check the entity tag of a conditional write (`If-Match`)
+#[cfg(any(feature = "geopackage", feature = "memory"))]
+fn check_etag(etag: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+    match if_match {
+        Some(tag) if tag != etag => Err(precondition_failed()),
+        _ => Ok(()),
+    }
+}
+
+/// Stream of the features of consecutive pages, fetched by their limit,
+/// offset and `next` token as the stream is consumed
+#[cfg(feature = "features")]
+fn paginate<'a, F, Fut>(
+    limit: Option<usize>,
+    offset: Option<usize>,
+    next: Option<String>,
+    fetch: F,
+) -> BoxStream<'a, anyhow::Result<Feature>>
+where
+    F: FnMut(usize, Option<usize>, Option<String>) -> Fut + Send + 'a,
+    Fut: Future<Output = anyhow::Result<FeatureCollection>> + Send + 'a,
+{
+    // the remaining number of features, offset and token of the next page
+    let page = Some((limit, offset, next));
+
+    stream::try_unfold((fetch, page), |(mut fetch, page)| async move {
+        let Some((remaining, offset, next)) = page else {
+            return Ok(None);
+        };
+        let limit = remaining.map_or(PAGE_SIZE, |remaining| remaining.min(PAGE_SIZE));
+        if limit == 0 {
+            return Ok(None);
+        }
+
+        let mut fc = fetch(limit, offset, next.to_owned()).await?;
+        let returned = fc.features.len();
+        let remaining = remaining.map(|remaining| remaining.saturating_sub(returned));
+
+        // drivers with keyset pagination return a token while there are more
+        let page = match fc.next.take() {
+            Some(token) => Some((remaining, None, Some(token))),
+            None if next.is_none() && returned == limit => {
+                Some((remaining, Some(offset.unwrap_or(0) + returned), None))
+            }
+            None => None,
+        };
+
+        let features = stream::iter(fc.features.into_iter().map(Ok));
+        anyhow::Ok(Some((features, (fetch, page))))
+    })
+    .try_flatten()
+    .boxed()
 }
 
 /// Trait for `EDR` queries
@@ -142,6 +399,8 @@ pub trait StyleTransactions: Send + Sync {
 #[cfg(feature = "tiles")]
 #[async_trait::async_trait]
 pub trait TileTransactions: Send + Sync {
+    /// Tile of `collections`, rendering the named `geometry` of the features
+    /// of the collections that have it or else their primary geometry
     async fn tile(
         &self,
         collections: &[String],
@@ -149,5 +408,6 @@ pub trait TileTransactions: Send + Sync {
         matrix: &str,
         row: u32,
         col: u32,
+        geometry: Option<&str>,
     ) -> anyhow::Result<Vec<u8>>;
 }
@@ -0,0 +1,56 @@
+use futures::{StreamExt, channel::mpsc, stream::BoxStream};
+use geo::BoundingRect;
+
+use ogcapi_types::features::{Change, ChangeType, Feature};
+
+use crate::{FeatureChanges, features::geometry};
+
+use super::{Memory, Store};
+
+#[async_trait::async_trait]
+impl FeatureChanges for Memory {
+    async fn changes(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<Change>>> {
+        let mut store = self.write();
+        store.collection(collection_id)?;
+
+        let (sender, receiver) = mpsc::unbounded();
+        store.subscribers.push((collection_id.to_owned(), sender));
+
+        Ok(receiver.map(Ok).boxed())
+    }
+}
+
+impl Store {
+    /// Notify the subscribers of a collection of a change to a feature, the
+    /// previous feature if it has been deleted
+    pub(super) fn notify(
+        &mut self,
+        r#type: ChangeType,
+        collection_id: &str,
+        id: &str,
+        feature: &Feature,
+    ) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let change = Change {
+            r#type,
+            collection: collection_id.to_owned(),
+            id: id.to_owned(),
+            bbox: geometry(feature)
+                .and_then(|geometry| geometry.bounding_rect())
+                .map(|rect| [rect.min().x, rect.min().y, rect.max().x, rect.max().y]),
+            feature: (r#type != ChangeType::Deleted).then(|| feature.to_owned()),
+        };
+
+        // subscribers that went away are dropped
+        self.subscribers.retain(|(collection, sender)| {
+            !sender.is_closed()
+                && (collection != collection_id || sender.unbounded_send(change.clone()).is_ok())
+        });
+    }
+}
@@ -0,0 +1,138 @@
+use geo::BoundingRect;
+
+use ogcapi_types::common::{Bbox, Collection, Collections, Crs, Exception, Query, SpatialExtent};
+
+use crate::{CollectionTransactions, features::geometry, precondition_failed};
+
+use super::{Memory, Store, Versioned, not_found};
+
+#[async_trait::async_trait]
+impl CollectionTransactions for Memory {
+    async fn create_collection(&self, collection: &Collection) -> anyhow::Result<String> {
+        let mut store = self.write();
+
+        if store.collections.contains_key(&collection.id) {
+            return Err(Exception::new_from_status(409)
+                .detail(format!("Collection `{}` already exists", collection.id))
+                .into());
+        }
+
+        store.collections.insert(
+            collection.id.to_owned(),
+            Versioned::new(collection.to_owned()),
+        );
+        store
+            .items
+            .insert(collection.id.to_owned(), Default::default());
+
+        Ok(collection.id.to_owned())
+    }
+
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        let store = self.read();
+        Ok(store
+            .collections
+            .get(id)
+            .map(|collection| with_extent(&store, &collection.value)))
+    }
+
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut store = self.write();
+
+        match store.collections.get_mut(&collection.id) {
+            Some(current) => {
+                current.check(if_match)?;
+                current.replace(collection.to_owned());
+                Ok(())
+            }
+            None if if_match.is_some() => Err(precondition_failed()),
+            None => Err(not_found(format!(
+                "Collection `{}` does not exist",
+                collection.id
+            ))),
+        }
+    }
+
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        let mut store = self.write();
+
+        match store.collections.get(id) {
+            Some(current) => current.check(if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => {}
+        }
+
+        store.collections.remove(id);
+        store.items.remove(id);
+
+        Ok(())
+    }
+
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.read().collections.get(id).map(Versioned::etag))
+    }
+
+    async fn list_collections(&self, _query: &Query) -> anyhow::Result<Collections> {
+        let store = self.read();
+
+        let mut collections: Vec<Collection> = store
+            .collections
+            .values()
+            .map(|collection| with_extent(&store, &collection.value))
+            .collect();
+        #[cfg(feature = "stac")]
+        collections.retain(|collection| collection.r#type == "Collection");
+        collections.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut collections = Collections::new(collections);
+        collections.number_matched = collections.number_returned;
+
+        Ok(collections)
+    }
+}
+
+/// Collection with the spatial extent of its features, unless it is provided
+fn with_extent(store: &Store, collection: &Collection) -> Collection {
+    let mut collection = collection.to_owned();
+
+    if collection
+        .extent
+        .as_ref()
+        .is_some_and(|extent| extent.spatial.is_some())
+    {
+        return collection;
+    }
+
+    let bounds = store
+        .features(&collection.id)
+        .filter_map(|feature| geometry(feature)?.bounding_rect())
+        .reduce(|a, b| {
+            geo::Rect::new(
+                geo::coord! { x: a.min().x.min(b.min().x), y: a.min().y.min(b.min().y) },
+                geo::coord! { x: a.max().x.max(b.max().x), y: a.max().y.max(b.max().y) },
+            )
+        });
+
+    if let Some(bounds) = bounds {
+        collection.extent.get_or_insert_default().spatial = Some(SpatialExtent {
+            bbox: vec![Bbox::Bbox2D([
+                bounds.min().x,
+                bounds.min().y,
+                bounds.max().x,
+                bounds.max().y,
+            ])],
+            crs: Some(
+                collection
+                    .storage_crs
+                    .clone()
+                    .unwrap_or_else(Crs::default2d),
+            ),
+        });
+    }
+
+    collection
+}
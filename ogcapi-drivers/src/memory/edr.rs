@@ -0,0 +1,136 @@
+use geo::{Closest, Distance, Haversine, HaversineClosestPoint, Intersects, MapCoords};
+use wkt::TryFromWkt;
+
+use ogcapi_types::{
+    common::{AxisOrder, Bbox, Crs, Exception},
+    edr::{Query, QueryType},
+    features::FeatureCollection,
+};
+
+use crate::{
+    EdrQuerier,
+    features::{geometry, oriented, project, rects, untransformed},
+};
+
+use super::{Memory, storage_srid};
+
+#[async_trait::async_trait]
+impl EdrQuerier for Memory {
+    async fn query(
+        &self,
+        collection_id: &str,
+        query_type: &QueryType,
+        query: &Query,
+    ) -> anyhow::Result<(FeatureCollection, Crs)> {
+        let store = self.read();
+        let srid = storage_srid(&store.collection(collection_id)?.value);
+
+        // coordinates are compared in the horizontal axes only
+        let crs = query.crs.clone().unwrap_or_else(Crs::default2d);
+        untransformed(Some(&crs), srid)?;
+
+        let predicate: Box<dyn Fn(&geo::Geometry) -> bool> = match query_type {
+            QueryType::Position | QueryType::Area | QueryType::Trajectory => {
+                let coords = parse_wkt(&query.coords, &crs)?;
+                Box::new(move |geometry| geometry.intersects(&coords))
+            }
+            QueryType::Radius => {
+                let geo::Geometry::Point(center) = parse_wkt(&query.coords, &crs)? else {
+                    return Err(Exception::new_from_status(400)
+                        .detail("Radius queries require a point")
+                        .into());
+                };
+                let within = query.within.as_deref().unwrap_or("0");
+                let distance = within.trim().parse::<f64>().map_err(|e| {
+                    Exception::new_from_status(400).detail(format!("Invalid `within`: {e}"))
+                })? * meters(query.within_units.as_deref().unwrap_or("m"))?;
+
+                Box::new(
+                    move |geometry| match geometry.haversine_closest_point(&center) {
+                        Closest::Intersection(_) => true,
+                        Closest::SinglePoint(point) => {
+                            Haversine.distance(point, center) <= distance
+                        }
+                        Closest::Indeterminate => false,
+                    },
+                )
+            }
+            QueryType::Cube => {
+                let bbox: Vec<f64> = query
+                    .coords
+                    .split(',')
+                    .map(|c| c.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|e| {
+                        Exception::new_from_status(400)
+                            .detail(format!("Invalid cube coordinates `{}`: {e}", query.coords))
+                    })?;
+                let bbox = match bbox.as_slice() {
+                    [x1, y1, x2, y2] => Bbox::Bbox2D([*x1, *y1, *x2, *y2]),
+                    [x1, y1, z1, x2, y2, z2] => Bbox::Bbox3D([*x1, *y1, *z1, *x2, *y2, *z2]),
+                    _ => {
+                        return Err(Exception::new_from_status(400)
+                            .detail("Cube coordinates require 4 or 6 values")
+                            .into());
+                    }
+                };
+                let rects = rects(&bbox, Some(&crs));
+                Box::new(move |geometry| rects.iter().any(|rect| geometry.intersects(rect)))
+            }
+            qt => {
+                return Err(Exception::new_from_status(400)
+                    .detail(format!("Query type `{qt:?}` is not supported"))
+                    .into());
+            }
+        };
+
+        let parameters: Option<Vec<String>> = query
+            .parameter_name
+            .as_ref()
+            .map(|p| p.split(',').map(str::to_owned).collect());
+
+        let features: Vec<_> = store
+            .features(collection_id)
+            .filter(|feature| geometry(feature).is_some_and(|g| predicate(&g)))
+            .map(|feature| {
+                let feature = project(store.output(feature), parameters.as_deref(), false);
+                oriented(feature, Some(&crs))
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = fc.number_returned;
+
+        Ok((fc, crs))
+    }
+}
+
+/// Geometry of WKT coordinates in `crs`, in longitude/easting first order
+fn parse_wkt(coords: &str, crs: &Crs) -> anyhow::Result<geo::Geometry> {
+    let geometry = geo::Geometry::try_from_wkt_str(coords).map_err(|e| {
+        anyhow::Error::from(
+            Exception::new_from_status(400).detail(format!("Invalid coordinates `{coords}`: {e}")),
+        )
+    })?;
+
+    Ok(match crs.axis_order() {
+        AxisOrder::EastNorth => geometry,
+        AxisOrder::NorthEast => geometry.map_coords(|c| geo::coord! { x: c.y, y: c.x }),
+    })
+}
+
+/// Meters per distance unit
+fn meters(unit: &str) -> anyhow::Result<f64> {
+    Ok(match unit.trim().to_lowercase().as_str() {
+        "m" | "meter" | "meters" | "metre" | "metres" => 1.0,
+        "km" | "kilometer" | "kilometers" | "kilometre" | "kilometres" => 1000.0,
+        "mi" | "mile" | "miles" => 1609.344,
+        "nmi" | "nautical mile" | "nautical miles" => 1852.0,
+        "ft" | "foot" | "feet" => 0.3048,
+        unit => {
+            return Err(Exception::new_from_status(400)
+                .detail(format!("Unsupported distance unit `{unit}`"))
+                .into());
+        }
+    })
+}
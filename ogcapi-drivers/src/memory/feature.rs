@@ -0,0 +1,354 @@
+use std::collections::HashSet;
+
+use geo::Intersects;
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{
+        ChangeType, Feature, FeatureCollection, FeatureId, NumberMatched, Query, Queryables, Schema,
+    },
+};
+
+use crate::{
+    FeatureTransactions,
+    features::{
+        matches, matches_datetime, matches_value, named_geometry, oriented, project, rects,
+        sample_properties, sort, untransformed, unversioned,
+    },
+    precondition_failed,
+};
+
+use super::{Memory, Store, Versioned, not_found, storage_srid};
+
+#[async_trait::async_trait]
+impl FeatureTransactions for Memory {
+    async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String> {
+        let collection_id = feature.collection.as_ref().unwrap();
+        let ids = self
+            .create_features(collection_id, std::slice::from_ref(feature))
+            .await?;
+        Ok(ids[0].to_owned())
+    }
+
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        let mut store = self.write();
+        store.collection(collection_id)?;
+
+        // assign ids and check for duplicates before inserting anything
+        let mut items = Vec::with_capacity(features.len());
+        let mut ids = HashSet::new();
+        for feature in features {
+            let id = match &feature.id {
+                Some(id) => id.to_string(),
+                None => store.next_id(),
+            };
+            if !ids.insert(id.to_owned()) || store.items[collection_id].contains_key(&id) {
+                return Err(Exception::new_from_status(400)
+                    .detail(format!(
+                        "No features were created: feature `{id}` already exists"
+                    ))
+                    .into());
+            }
+
+            let mut feature = feature.to_owned();
+            feature.id = Some(FeatureId::String(id.to_owned()));
+            feature.collection = Some(collection_id.to_owned());
+            items.push((id, Versioned::new(feature)));
+        }
+
+        let ids = items.iter().map(|(id, _)| id.to_owned()).collect();
+        for (id, item) in &items {
+            store.notify(ChangeType::Created, collection_id, id, &item.value);
+        }
+        store.items.get_mut(collection_id).unwrap().extend(items);
+
+        Ok(ids)
+    }
+
+    async fn read_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Feature>> {
+        let store = self.read();
+        let collection = store.collection(collection_id)?;
+        untransformed(Some(crs), storage_srid(&collection.value))?;
+
+        Ok(store.items[collection_id]
+            .get(id)
+            .map(|item| oriented(store.output(&item.value), Some(crs))))
+    }
+
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let collection_id = feature.collection.as_ref().unwrap();
+        let id = feature
+            .id
+            .as_ref()
+            .map(ToString::to_string)
+            .ok_or_else(|| Exception::new_from_status(400).detail("Missing feature id"))?;
+
+        let mut store = self.write();
+        store.collection(collection_id)?;
+
+        let feature = match store.items.get_mut(collection_id).unwrap().get_mut(&id) {
+            Some(current) => {
+                current.check(if_match)?;
+                let mut feature = feature.to_owned();
+                feature.id = Some(FeatureId::String(id.to_owned()));
+                current.replace(feature.clone());
+                feature
+            }
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Err(not_found(format!("Feature `{id}` does not exist"))),
+        };
+        store.notify(ChangeType::Updated, collection_id, &id, &feature);
+
+        Ok(())
+    }
+
+    async fn patch_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if patch.get("geometry").is_some_and(Value::is_null) {
+            return Err(Exception::new_from_status(400)
+                .detail("The geometry of a feature can not be removed")
+                .into());
+        }
+
+        let mut store = self.write();
+        store.collection(collection_id)?;
+
+        let feature = match store.items.get_mut(collection_id).unwrap().get_mut(id) {
+            Some(current) => {
+                current.check(if_match)?;
+                let mut feature = current.value.to_owned();
+                feature
+                    .merge_patch(patch)
+                    .map_err(|e| Exception::new_from_status(400).detail(e))?;
+                current.replace(feature.clone());
+                feature
+            }
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => return Err(not_found(format!("Feature `{id}` does not exist"))),
+        };
+        store.notify(ChangeType::Updated, collection_id, id, &feature);
+
+        Ok(())
+    }
+
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let mut store = self.write();
+        store.collection(collection_id)?;
+
+        let items = store.items.get_mut(collection_id).unwrap();
+        match items.get(id) {
+            Some(current) => current.check(if_match)?,
+            None if if_match.is_some() => return Err(precondition_failed()),
+            None => {}
+        }
+        if let Some(removed) = items.remove(id) {
+            store.notify(ChangeType::Deleted, collection_id, id, &removed.value);
+        }
+
+        Ok(())
+    }
+
+    async fn feature_etag(&self, collection_id: &str, id: &str) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .read()
+            .items
+            .get(collection_id)
+            .and_then(|items| items.get(id))
+            .map(Versioned::etag))
+    }
+
+    async fn list_items(
+        &self,
+        collection_id: &str,
+        query: &Query,
+    ) -> anyhow::Result<FeatureCollection> {
+        let store = self.read();
+        let collection = &store.collection(collection_id)?.value;
+        let srid = storage_srid(collection);
+
+        untransformed(query.crs.as_ref(), srid)?;
+        unversioned(query.version_at.as_ref())?;
+        if let Some(name) = query.bbox_geometry.as_deref() {
+            collection.geometry_column(name)?;
+        }
+
+        let bbox = match query.bbox.as_ref() {
+            Some(bbox) => {
+                untransformed(query.bbox_crs.as_ref(), srid)?;
+                Some(rects(bbox, query.bbox_crs.as_ref()))
+            }
+            None => None,
+        };
+
+        let filter = query.parse_filter()?;
+        if filter.is_some() {
+            untransformed(query.filter_crs.as_ref(), srid)?;
+        }
+
+        let mut features = Vec::new();
+        for feature in store.features(collection_id) {
+            if let Some(rects) = bbox.as_ref()
+                && !named_geometry(feature, query.bbox_geometry.as_deref())
+                    .is_some_and(|geometry| rects.iter().any(|rect| geometry.intersects(rect)))
+            {
+                continue;
+            }
+            if let Some(datetime) = query.datetime.as_ref()
+                && !matches_datetime(feature, datetime)
+            {
+                continue;
+            }
+            if !query
+                .additional_parameters
+                .iter()
+                .all(|(k, v)| matches_value(feature, k, v))
+            {
+                continue;
+            }
+            if let Some(filter) = filter.as_ref()
+                && !matches(filter, feature)?
+            {
+                continue;
+            }
+            features.push(feature);
+        }
+
+        let number_matched = match query.number_matched.unwrap_or_default() {
+            NumberMatched::Exact | NumberMatched::Estimate => Some(features.len() as u64),
+            NumberMatched::Skip => None,
+        };
+
+        sort(&mut features, &query.sortby)?;
+
+        // the `next` token is the offset of the next page
+        let offset = match query.next.as_deref() {
+            Some(token) => token.parse().map_err(|_| {
+                anyhow::Error::from(Exception::new_from_status(400).detail("Invalid `next` token"))
+            })?,
+            None => query.offset.unwrap_or(0),
+        };
+        let end = query
+            .limit
+            .map(|limit| offset.saturating_add(limit))
+            .unwrap_or(usize::MAX);
+        let next = (end < features.len()).then(|| end.to_string());
+
+        let features = features
+            .into_iter()
+            .skip(offset)
+            .take(end - offset)
+            .map(|feature| {
+                let feature = project(
+                    store.output(feature),
+                    query.properties.as_deref(),
+                    query.skip_geometry.unwrap_or_default(),
+                );
+                oriented(feature, query.crs.as_ref())
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = number_matched;
+        fc.next = next;
+
+        Ok(fc)
+    }
+
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        true
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        let store = self.read();
+        let collection = &store.collection(collection_id)?.value;
+
+        // stored in the collection document
+        if let Some(queryables) = collection.additional_properties.get("queryables") {
+            return Ok(serde_json::from_value(queryables.to_owned())?);
+        }
+
+        Ok(Queryables {
+            title: collection.title.to_owned(),
+            queryables: sample_properties(store.features(collection_id)),
+            additional_properties: false,
+            ..Default::default()
+        })
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        let store = self.read();
+        let collection = &store.collection(collection_id)?.value;
+
+        // stored in the collection document
+        if let Some(schema) = collection.additional_properties.get("schema") {
+            return Ok(serde_json::from_value(schema.to_owned())?);
+        }
+
+        Ok(Schema {
+            title: collection.title.to_owned(),
+            description: collection.description.to_owned(),
+            properties: sample_properties(store.features(collection_id)),
+            ..Default::default()
+        })
+    }
+}
+
+impl Store {
+    /// Feature as returned to clients
+    #[cfg(not(feature = "stac"))]
+    pub(super) fn output(&self, feature: &Feature) -> Feature {
+        feature.to_owned()
+    }
+
+    /// Feature as returned to clients, with the STAC members of its collection
+    /// and a bounding box
+    #[cfg(feature = "stac")]
+    pub(super) fn output(&self, feature: &Feature) -> Feature {
+        use geo::BoundingRect;
+
+        use crate::features::geometry;
+
+        let mut feature = feature.to_owned();
+
+        if let Some(collection) = feature
+            .collection
+            .as_ref()
+            .and_then(|id| self.collections.get(id))
+        {
+            feature.stac_version = collection.value.stac_version.to_owned();
+            feature.stac_extensions = collection.value.stac_extensions.to_owned();
+        }
+
+        if feature.bbox.is_none() {
+            feature.bbox = geometry(&feature)
+                .and_then(|geometry| geometry.bounding_rect())
+                .map(|r| [r.min().x, r.min().y, r.max().x, r.max().y].into());
+        }
+
+        feature
+    }
+}
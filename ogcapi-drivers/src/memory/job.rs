@@ -0,0 +1,128 @@
+use chrono::Utc;
+
+use ogcapi_types::{
+    common::Link,
+    processes::{ExecuteResults, Response, StatusCode, StatusInfo},
+};
+
+use crate::{JobHandler, ProcessResult};
+
+use super::{Job, Memory};
+
+#[async_trait::async_trait]
+impl JobHandler for Memory {
+    async fn register(&self, job: &StatusInfo, response_mode: Response) -> anyhow::Result<String> {
+        let mut store = self.write();
+
+        let mut status = job.to_owned();
+        if status.job_id.is_empty() {
+            status.job_id = store.next_id();
+        }
+        let now = Utc::now();
+        status.created = Some(now);
+        status.updated = Some(now);
+        status.progress.get_or_insert(0);
+
+        let id = status.job_id.to_owned();
+        store.jobs.insert(
+            id.to_owned(),
+            Job {
+                status,
+                response: response_mode,
+                results: None,
+            },
+        );
+
+        Ok(id)
+    }
+
+    async fn update(&self, job: &StatusInfo) -> anyhow::Result<()> {
+        if let Some(current) = self.write().jobs.get_mut(&job.job_id) {
+            let now = Utc::now();
+            let status = &mut current.status;
+            status.status = job.status.to_owned();
+            status.message = job.message.to_owned();
+            status.progress = job.progress;
+            status.links = job.links.to_owned();
+            status.updated = Some(now);
+            if matches!(
+                job.status,
+                StatusCode::Successful | StatusCode::Failed | StatusCode::Dismissed
+            ) {
+                status.finished = Some(now);
+            }
+        }
+        Ok(())
+    }
+
+    async fn status_list(&self, offset: usize, limit: usize) -> anyhow::Result<Vec<StatusInfo>> {
+        let store = self.read();
+
+        let mut status_list: Vec<StatusInfo> = store
+            .jobs
+            .values()
+            .map(|job| job.status.to_owned())
+            .collect();
+        status_list.sort_by_key(|status| std::cmp::Reverse(status.created));
+
+        Ok(status_list.into_iter().skip(offset).take(limit).collect())
+    }
+
+    async fn status(&self, id: &str) -> anyhow::Result<Option<StatusInfo>> {
+        Ok(self.read().jobs.get(id).map(|job| job.status.to_owned()))
+    }
+
+    async fn finish(
+        &self,
+        job_id: &str,
+        status: &StatusCode,
+        message: Option<String>,
+        links: Vec<Link>,
+        results: Option<ExecuteResults>,
+    ) -> anyhow::Result<()> {
+        if let Some(job) = self.write().jobs.get_mut(job_id) {
+            let now = Utc::now();
+            job.status.status = status.to_owned();
+            job.status.message = message;
+            job.status.links = links;
+            job.status.progress = Some(100);
+            job.status.finished = Some(now);
+            job.status.updated = Some(now);
+            job.results = results;
+        }
+        Ok(())
+    }
+
+    async fn dismiss(&self, id: &str) -> anyhow::Result<Option<StatusInfo>> {
+        let mut store = self.write();
+
+        let Some(job) = store.jobs.get_mut(id).filter(|job| {
+            matches!(
+                job.status.status,
+                StatusCode::Accepted | StatusCode::Running
+            )
+        }) else {
+            return Ok(None);
+        };
+
+        job.status.status = StatusCode::Dismissed;
+        job.status.message = Some("Job dismissed".to_string());
+
+        Ok(Some(job.status.to_owned()))
+    }
+
+    async fn results(&self, id: &str) -> anyhow::Result<ProcessResult> {
+        Ok(match self.read().jobs.get(id) {
+            None => ProcessResult::NoSuchJob,
+            Some(Job { results: None, .. }) => ProcessResult::NotReady,
+            Some(Job {
+                results: Some(results),
+                response,
+                ..
+            }) => ProcessResult::Results {
+                results: results.to_owned(),
+                response_mode: response.to_owned(),
+            },
+        })
+    }
+}
@@ -0,0 +1,166 @@
+//! In-memory driver
+//!
+//! Keeps all data in process, which is useful for tests, examples and small
+//! ephemeral deployments. Coordinates are never transformed, so requests must
+//! use the storage crs of a collection.
+mod changes;
+mod collection;
+#[cfg(feature = "edr")]
+mod edr;
+mod feature;
+#[cfg(feature = "processes")]
+mod job;
+#[cfg(feature = "stac")]
+mod stac;
+#[cfg(feature = "styles")]
+mod style;
+#[cfg(feature = "tiles")]
+mod tile;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use futures::channel::mpsc::UnboundedSender;
+#[cfg(feature = "styles")]
+use serde_json::Value;
+
+#[cfg(feature = "processes")]
+use ogcapi_types::processes::{ExecuteResults, Response, StatusInfo};
+#[cfg(feature = "styles")]
+use ogcapi_types::styles::Style;
+use ogcapi_types::{
+    common::{Collection, Crs, Exception},
+    features::{Change, Feature},
+};
+
+use crate::check_etag;
+
+/// In-memory driver
+///
+/// Clones share the same data.
+#[derive(Clone, Default)]
+pub struct Memory {
+    store: Arc<RwLock<Store>>,
+}
+
+#[derive(Default)]
+struct Store {
+    collections: HashMap<String, Versioned<Collection>>,
+    /// Features by collection id, ordered by feature id
+    items: HashMap<String, BTreeMap<String, Versioned<Feature>>>,
+    #[cfg(feature = "processes")]
+    jobs: HashMap<String, Job>,
+    #[cfg(feature = "styles")]
+    styles: HashMap<String, (Style, Value)>,
+    /// Subscribers to the feature changes of a collection
+    subscribers: Vec<(String, UnboundedSender<Change>)>,
+    /// Last generated identifier
+    sequence: u64,
+}
+
+/// Stored value with the version used as entity tag
+struct Versioned<T> {
+    value: T,
+    version: u64,
+}
+
+impl<T> Versioned<T> {
+    fn new(value: T) -> Self {
+        Versioned { value, version: 1 }
+    }
+
+    fn etag(&self) -> String {
+        self.version.to_string()
+    }
+
+    /// Check `If-Match` against the current version
+    fn check(&self, if_match: Option<&str>) -> anyhow::Result<()> {
+        check_etag(&self.etag(), if_match)
+    }
+
+    fn replace(&mut self, value: T) {
+        self.value = value;
+        self.version += 1;
+    }
+}
+
+#[cfg(feature = "processes")]
+struct Job {
+    status: StatusInfo,
+    response: Response,
+    results: Option<ExecuteResults>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a style, replacing any style with the same id
+    #[cfg(feature = "styles")]
+    pub fn insert_style(&self, style: Style, value: Value) {
+        self.write()
+            .styles
+            .insert(style.id.to_owned(), (style, value));
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Store> {
+        self.store.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Store> {
+        self.store.write().unwrap()
+    }
+}
+
+impl Store {
+    /// Generate a new identifier
+    fn next_id(&mut self) -> String {
+        self.sequence += 1;
+        self.sequence.to_string()
+    }
+
+    fn collection(&self, id: &str) -> anyhow::Result<&Versioned<Collection>> {
+        self.collections
+            .get(id)
+            .ok_or_else(|| not_found(format!("Collection `{id}` does not exist")))
+    }
+
+    /// Features of a collection, ordered by id
+    fn features(&self, collection_id: &str) -> impl Iterator<Item = &Feature> {
+        self.items
+            .get(collection_id)
+            .into_iter()
+            .flat_map(|items| items.values().map(|item| &item.value))
+    }
+}
+
+/// Storage srid of a collection
+fn storage_srid(collection: &Collection) -> i32 {
+    collection
+        .storage_crs
+        .as_ref()
+        .unwrap_or(&Crs::default2d())
+        .as_srid()
+}
+
+fn not_found(detail: impl ToString) -> anyhow::Error {
+    Exception::new_from_status(404).detail(detail).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        let mut versioned = Versioned::new("a");
+        assert!(versioned.check(Some("1")).is_ok());
+        versioned.replace("b");
+        assert!(versioned.check(None).is_ok());
+        assert!(versioned.check(Some("1")).is_err());
+        assert_eq!(versioned.etag(), "2");
+    }
+}
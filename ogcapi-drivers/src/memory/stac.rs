@@ -0,0 +1,91 @@
+use geo::Intersects;
+
+use ogcapi_types::{features::FeatureCollection, stac::SearchParams};
+
+use crate::{
+    StacSearch,
+    features::{geometry, matches_datetime, project, rects, sort},
+};
+
+use super::Memory;
+
+#[async_trait::async_trait]
+impl StacSearch for Memory {
+    async fn search(&self, query: &SearchParams) -> anyhow::Result<FeatureCollection> {
+        let store = self.read();
+
+        let bbox = query
+            .bbox
+            .as_ref()
+            .map(|bbox| rects(&bbox.normalize(), None));
+        let intersects = query
+            .intersects
+            .as_ref()
+            .map(geo::Geometry::try_from)
+            .transpose()?;
+
+        let mut collection_ids: Vec<&String> = store
+            .collections
+            .values()
+            .filter(|collection| collection.value.r#type == "Collection")
+            .map(|collection| &collection.value.id)
+            .filter(|id| query.collections.is_empty() || query.collections.contains(id))
+            .collect();
+        collection_ids.sort();
+
+        let mut features = Vec::new();
+        for collection_id in collection_ids {
+            for feature in store.features(collection_id) {
+                let geometry = geometry(feature);
+                if let Some(rects) = bbox.as_ref()
+                    && !geometry
+                        .as_ref()
+                        .is_some_and(|g| rects.iter().any(|rect| g.intersects(rect)))
+                {
+                    continue;
+                }
+                if let Some(intersects) = intersects.as_ref()
+                    && !geometry.as_ref().is_some_and(|g| g.intersects(intersects))
+                {
+                    continue;
+                }
+                if let Some(datetime) = query.datetime.as_ref()
+                    && !matches_datetime(feature, datetime)
+                {
+                    continue;
+                }
+                if !query.ids.is_empty()
+                    && !feature
+                        .id
+                        .as_ref()
+                        .is_some_and(|id| query.ids.contains(&id.to_string()))
+                {
+                    continue;
+                }
+                features.push(feature);
+            }
+        }
+
+        let number_matched = features.len() as u64;
+
+        sort(&mut features, &query.sortby)?;
+
+        let features = features
+            .into_iter()
+            .skip(query.offset.unwrap_or(0) as usize)
+            .take(query.limit.map(|l| l as usize).unwrap_or(usize::MAX))
+            .map(|feature| {
+                project(
+                    store.output(feature),
+                    query.properties.as_deref(),
+                    query.skip_geometry.unwrap_or_default(),
+                )
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = Some(number_matched);
+
+        Ok(fc)
+    }
+}
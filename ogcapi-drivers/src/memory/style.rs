@@ -0,0 +1,28 @@
+use ogcapi_types::styles::Styles;
+
+use crate::StyleTransactions;
+
+use super::Memory;
+
+#[async_trait::async_trait]
+impl StyleTransactions for Memory {
+    async fn list_styles(&self) -> anyhow::Result<Styles> {
+        let mut styles: Vec<_> = self
+            .read()
+            .styles
+            .values()
+            .map(|(style, _)| style.to_owned())
+            .collect();
+        styles.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(Styles { styles })
+    }
+
+    async fn read_style(&self, id: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        Ok(self
+            .read()
+            .styles
+            .get(id)
+            .map(|(_, value)| value.to_owned()))
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+use geo::{
+    BooleanOps, Coord, Intersects, LineString, MapCoords, MultiLineString, MultiPolygon, Point,
+    Polygon, Rect,
+    orient::{Direction, Orient},
+};
+use serde_json::{Map, Value};
+
+use ogcapi_types::{common::Exception, tiles::TileMatrixSet};
+
+use crate::{TileTransactions, features::named_geometry};
+
+use super::{Memory, storage_srid};
+
+/// Extent of a tile in tile coordinates
+const EXTENT: u32 = 4096;
+
+/// Buffer around a tile in tile coordinates
+const BUFFER: f64 = 64.0;
+
+/// Half the circumference of the earth in web mercator (EPSG:3857)
+const HALF_CIRCUMFERENCE: f64 = 20037508.342789244;
+
+#[async_trait::async_trait]
+impl TileTransactions for Memory {
+    /// Encode a Mapbox Vector Tile of the `WebMercatorQuad` tile matrix set
+    async fn tile(
+        &self,
+        collections: &[String],
+        _tms: &TileMatrixSet,
+        matrix: &str,
+        row: u32,
+        col: u32,
+        geometry: Option<&str>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let envelope = tile_envelope(matrix.parse()?, row, col);
+        let shifts = shifts(&envelope);
+
+        let store = self.read();
+        let mut tile = Vec::new();
+
+        for collection_id in collections {
+            let Some(collection) = store.collections.get(collection_id) else {
+                continue;
+            };
+
+            let to_web_mercator = match storage_srid(&collection.value) {
+                4326 => true,
+                3857 => false,
+                srid => {
+                    return Err(Exception::new_from_status(400)
+                        .detail(format!("Tiles of EPSG:{srid} are not supported"))
+                        .into());
+                }
+            };
+
+            // the named geometry, if the collection has it
+            let name = geometry.filter(|name| collection.value.geometry_column(name).is_ok());
+
+            let mut layer = Layer::default();
+            for feature in store.features(collection_id) {
+                let Some(mut geometry) = named_geometry(feature, name) else {
+                    continue;
+                };
+                if to_web_mercator {
+                    geometry = geometry.map_coords(web_mercator);
+                }
+
+                let mut attributes = Map::new();
+                attributes.insert("collection".to_string(), collection_id.as_str().into());
+                attributes.extend(feature.properties.to_owned().unwrap_or_default());
+
+                for shift in &shifts {
+                    let geometry = geometry.map_coords(|c| c + geo::coord! { x: *shift, y: 0.0 });
+                    layer.add_feature(&geometry, &envelope, &attributes);
+                }
+            }
+
+            if !layer.features.is_empty() {
+                message(&mut tile, 3, &layer.encode(collection_id));
+            }
+        }
+
+        Ok(tile)
+    }
+}
+
+/// Envelope of a tile in web mercator
+fn tile_envelope(zoom: i32, row: u32, col: u32) -> Rect {
+    let size = 2.0 * HALF_CIRCUMFERENCE / 2f64.powi(zoom);
+    let min_x = -HALF_CIRCUMFERENCE + col as f64 * size;
+    let max_y = HALF_CIRCUMFERENCE - row as f64 * size;
+    Rect::new(
+        geo::coord! { x: min_x, y: max_y - size },
+        geo::coord! { x: min_x + size, y: max_y },
+    )
+}
+
+/// Shifts along the x axis of the features of a tile
+///
+/// The buffer of tiles at the edges of the tile matrix spans the antimeridian,
+/// features on the other side are moved next to the tile.
+fn shifts(envelope: &Rect) -> Vec<f64> {
+    let buffer = envelope.width() * BUFFER / EXTENT as f64;
+    let mut shifts = vec![0.0];
+    if envelope.min().x - buffer < -HALF_CIRCUMFERENCE {
+        shifts.push(-2.0 * HALF_CIRCUMFERENCE);
+    }
+    if envelope.max().x + buffer > HALF_CIRCUMFERENCE {
+        shifts.push(2.0 * HALF_CIRCUMFERENCE);
+    }
+    shifts
+}
+
+/// Project longitude and latitude to web mercator
+fn web_mercator(coord: Coord) -> Coord {
+    let lat = coord.y.clamp(-85.06, 85.06).to_radians();
+    geo::coord! {
+        x: coord.x * HALF_CIRCUMFERENCE / 180.0,
+        y: (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln() * HALF_CIRCUMFERENCE
+            / std::f64::consts::PI,
+    }
+}
+
+/// Layer of a vector tile with deduplicated attribute keys and values
+#[derive(Default)]
+struct Layer {
+    features: Vec<Vec<u8>>,
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    values: Vec<Vec<u8>>,
+    value_index: HashMap<Vec<u8>, u32>,
+}
+
+impl Layer {
+    /// Clip a geometry to the buffered tile envelope and add it as feature
+    fn add_feature(
+        &mut self,
+        geometry: &geo::Geometry,
+        envelope: &Rect,
+        attributes: &Map<String, Value>,
+    ) {
+        let buffer = envelope.width() * BUFFER / EXTENT as f64;
+        let clip = Rect::new(
+            envelope.min() - geo::coord! { x: buffer, y: buffer },
+            envelope.max() + geo::coord! { x: buffer, y: buffer },
+        );
+        if !geometry.intersects(&clip) {
+            return;
+        }
+
+        // tile coordinates, with the y axis pointing down
+        let scale = EXTENT as f64 / envelope.width();
+        let to_tile = |c: Coord| {
+            geo::coord! {
+                x: (c.x - envelope.min().x) * scale,
+                y: (envelope.max().y - c.y) * scale,
+            }
+        };
+
+        let (r#type, commands) = match geometry {
+            geo::Geometry::Point(p) => (1, points(&[*p], &clip, to_tile)),
+            geo::Geometry::MultiPoint(mp) => (1, points(&mp.0, &clip, to_tile)),
+            geo::Geometry::Line(l) => (
+                2,
+                lines(&MultiLineString::from(LineString::from(*l)), &clip, to_tile),
+            ),
+            geo::Geometry::LineString(ls) => (
+                2,
+                lines(&MultiLineString::from(ls.to_owned()), &clip, to_tile),
+            ),
+            geo::Geometry::MultiLineString(mls) => (2, lines(mls, &clip, to_tile)),
+            geo::Geometry::Polygon(p) => (
+                3,
+                polygons(&MultiPolygon::from(p.to_owned()), &clip, to_tile),
+            ),
+            geo::Geometry::MultiPolygon(mp) => (3, polygons(mp, &clip, to_tile)),
+            geo::Geometry::Rect(r) => (
+                3,
+                polygons(&MultiPolygon::from(r.to_polygon()), &clip, to_tile),
+            ),
+            geo::Geometry::Triangle(t) => (
+                3,
+                polygons(&MultiPolygon::from(t.to_polygon()), &clip, to_tile),
+            ),
+            geo::Geometry::GeometryCollection(_) => return,
+        };
+        if commands.is_empty() {
+            return;
+        }
+
+        let mut tags = Vec::new();
+        for (key, value) in attributes {
+            if value.is_null() {
+                continue;
+            }
+            tags.push(self.key(key));
+            tags.push(self.value(value));
+        }
+
+        let mut feature = Vec::new();
+        packed(&mut feature, 2, &tags);
+        varint(&mut feature, 3 << 3);
+        varint(&mut feature, r#type);
+        packed(&mut feature, 4, &commands);
+        self.features.push(feature);
+    }
+
+    fn key(&mut self, key: &str) -> u32 {
+        if let Some(index) = self.key_index.get(key) {
+            return *index;
+        }
+        let index = self.keys.len() as u32;
+        self.keys.push(key.to_owned());
+        self.key_index.insert(key.to_owned(), index);
+        index
+    }
+
+    fn value(&mut self, value: &Value) -> u32 {
+        let mut encoded = Vec::new();
+        match value {
+            Value::String(s) => bytes(&mut encoded, 1, s.as_bytes()),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => {
+                    varint(&mut encoded, 6 << 3);
+                    varint(&mut encoded, ((i << 1) ^ (i >> 63)) as u64);
+                }
+                None => {
+                    varint(&mut encoded, (3 << 3) | 1);
+                    encoded.extend(n.as_f64().unwrap_or_default().to_le_bytes());
+                }
+            },
+            Value::Bool(b) => {
+                varint(&mut encoded, 7 << 3);
+                varint(&mut encoded, *b as u64);
+            }
+            value => bytes(&mut encoded, 1, value.to_string().as_bytes()),
+        }
+
+        if let Some(index) = self.value_index.get(&encoded) {
+            return *index;
+        }
+        let index = self.values.len() as u32;
+        self.values.push(encoded.to_owned());
+        self.value_index.insert(encoded, index);
+        index
+    }
+
+    fn encode(&self, name: &str) -> Vec<u8> {
+        let mut layer = Vec::new();
+        varint(&mut layer, 15 << 3);
+        varint(&mut layer, 2);
+        bytes(&mut layer, 1, name.as_bytes());
+        for feature in &self.features {
+            message(&mut layer, 2, feature);
+        }
+        for key in &self.keys {
+            bytes(&mut layer, 3, key.as_bytes());
+        }
+        for value in &self.values {
+            message(&mut layer, 4, value);
+        }
+        varint(&mut layer, 5 << 3);
+        varint(&mut layer, EXTENT as u64);
+        layer
+    }
+}
+
+/// Geometry commands of points within the clip rectangle
+fn points(points: &[Point], clip: &Rect, to_tile: impl Fn(Coord) -> Coord) -> Vec<u32> {
+    let coords: Vec<Coord> = points
+        .iter()
+        .filter(|p| clip.intersects(*p))
+        .map(|p| to_tile(p.0))
+        .collect();
+
+    let mut commands = Commands::default();
+    if !coords.is_empty() {
+        commands.move_to(&coords);
+    }
+    commands.0
+}
+
+/// Geometry commands of line strings clipped to the rectangle
+fn lines(lines: &MultiLineString, clip: &Rect, to_tile: impl Fn(Coord) -> Coord) -> Vec<u32> {
+    let mut commands = Commands::default();
+    for line in clip.to_polygon().clip(lines, false) {
+        let coords = quantize(line.0.into_iter().map(&to_tile));
+        if let [first, rest @ ..] = coords.as_slice()
+            && !rest.is_empty()
+        {
+            commands.move_to(&[*first]);
+            commands.line_to(rest);
+        }
+    }
+    commands.0
+}
+
+/// Geometry commands of polygons clipped to the rectangle
+fn polygons(polygons: &MultiPolygon, clip: &Rect, to_tile: impl Fn(Coord) -> Coord) -> Vec<u32> {
+    let clipped = clip.to_polygon().intersection(polygons);
+
+    let mut commands = Commands::default();
+    for polygon in clipped {
+        // exterior rings have a positive area in tile coordinates
+        let polygon: Polygon = polygon.map_coords(&to_tile).orient(Direction::Default);
+        if !commands.ring(polygon.exterior()) {
+            continue;
+        }
+        for interior in polygon.interiors() {
+            commands.ring(interior);
+        }
+    }
+    commands.0
+}
+
+/// Round coordinates to integers, dropping repeated points
+fn quantize(coords: impl Iterator<Item = Coord>) -> Vec<Coord> {
+    let mut quantized: Vec<Coord> = Vec::new();
+    for c in coords {
+        let c = geo::coord! { x: c.x.round(), y: c.y.round() };
+        if quantized.last() != Some(&c) {
+            quantized.push(c);
+        }
+    }
+    quantized
+}
+
+/// Encoded geometry commands, with coordinates relative to the cursor
+#[derive(Default)]
+struct Commands(Vec<u32>, (i32, i32));
+
+impl Commands {
+    fn command(&mut self, id: u32, count: usize) {
+        self.0.push((id & 0x7) | ((count as u32) << 3));
+    }
+
+    fn coords(&mut self, coords: &[Coord]) {
+        for c in coords {
+            let (x, y) = (c.x.round() as i32, c.y.round() as i32);
+            let (dx, dy) = (x - self.1.0, y - self.1.1);
+            self.0.push(((dx << 1) ^ (dx >> 31)) as u32);
+            self.0.push(((dy << 1) ^ (dy >> 31)) as u32);
+            self.1 = (x, y);
+        }
+    }
+
+    fn move_to(&mut self, coords: &[Coord]) {
+        self.command(1, coords.len());
+        self.coords(coords);
+    }
+
+    fn line_to(&mut self, coords: &[Coord]) {
+        self.command(2, coords.len());
+        self.coords(coords);
+    }
+
+    /// Encode a closed ring, returns false for degenerate rings
+    fn ring(&mut self, ring: &LineString) -> bool {
+        let mut coords = quantize(ring.0.iter().copied());
+        if coords.len() > 1 && coords.first() == coords.last() {
+            coords.pop();
+        }
+        if coords.len() < 3 {
+            return false;
+        }
+        self.move_to(&coords[..1]);
+        self.line_to(&coords[1..]);
+        self.command(7, 1);
+        true
+    }
+}
+
+fn varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Length delimited field
+fn bytes(buf: &mut Vec<u8>, field: u64, data: &[u8]) {
+    varint(buf, (field << 3) | 2);
+    varint(buf, data.len() as u64);
+    buf.extend_from_slice(data);
+}
+
+fn message(buf: &mut Vec<u8>, field: u64, message: &[u8]) {
+    bytes(buf, field, message);
+}
+
+/// Packed repeated `uint32` field
+fn packed(buf: &mut Vec<u8>, field: u64, values: &[u32]) {
+    let mut data = Vec::new();
+    for value in values {
+        varint(&mut data, *value as u64);
+    }
+    bytes(buf, field, &data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point() {
+        let mut layer = Layer::default();
+        let geometry = geo::Geometry::from(geo::point! { x: 0.0, y: 0.0 });
+        layer.add_feature(&geometry, &tile_envelope(0, 0, 0), &Map::new());
+
+        // MoveTo(1) to the center of the tile, zigzag encoded
+        let mut expected = Vec::new();
+        packed(&mut expected, 4, &[9, 4096, 4096]);
+        assert!(layer.features[0].ends_with(&expected));
+
+        // outside of the tile and its buffer
+        let geometry = geo::Geometry::from(geo::point! { x: 0.0, y: 0.0 });
+        layer.add_feature(&geometry, &tile_envelope(2, 0, 0), &Map::new());
+        assert_eq!(layer.features.len(), 1);
+    }
+
+    #[test]
+    fn polygon() {
+        let mut commands = Commands::default();
+        let square = Polygon::new(
+            LineString::from(vec![(0., 0.), (10., 0.), (10., 10.), (0., 10.)]),
+            vec![],
+        );
+        assert!(commands.ring(square.exterior()));
+        assert_eq!(commands.0, [9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15]);
+
+        let line = LineString::from(vec![(0., 0.), (0.2, 0.2), (0., 0.)]);
+        assert!(!Commands::default().ring(&line));
+    }
+
+    #[test]
+    fn antimeridian() {
+        assert_eq!(shifts(&tile_envelope(2, 1, 1)), [0.0]);
+        assert_eq!(
+            shifts(&tile_envelope(2, 1, 0)),
+            [0.0, -2.0 * HALF_CIRCUMFERENCE]
+        );
+        assert_eq!(shifts(&tile_envelope(0, 0, 0)).len(), 3);
+    }
+
+    #[test]
+    fn projection() {
+        let c = web_mercator(geo::coord! { x: 180.0, y: 0.0 });
+        assert!((c.x - HALF_CIRCUMFERENCE).abs() < 1e-6);
+        assert!(c.y.abs() < 1e-6);
+    }
+}
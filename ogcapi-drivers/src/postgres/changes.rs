@@ -0,0 +1,89 @@
+use futures::{
+    StreamExt, TryStreamExt,
+    stream::{self, BoxStream},
+};
+use serde::Deserialize;
+use sqlx::postgres::PgListener;
+
+use ogcapi_types::{
+    common::{Crs, Exception},
+    features::{Change, ChangeType},
+};
+
+use crate::{CollectionTransactions, FeatureChanges, FeatureTransactions};
+
+use super::Db;
+
+/// Channel notified by the triggers of the item tables
+const CHANNEL: &str = "ogcapi_items";
+
+/// Notification of the features changed by a statement, in batches
+#[derive(Deserialize)]
+struct Notification {
+    r#type: ChangeType,
+    collection: String,
+    changes: Vec<Changed>,
+}
+
+#[derive(Deserialize)]
+struct Changed {
+    id: String,
+    bbox: Option<[f64; 4]>,
+}
+
+#[async_trait::async_trait]
+impl FeatureChanges for Db {
+    async fn changes(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<Change>>> {
+        if self.read_collection(collection_id).await?.is_none() {
+            return Err(Exception::new_from_status(404)
+                .detail(format!("Collection `{collection_id}` does not exist"))
+                .into());
+        }
+
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(CHANNEL).await?;
+
+        let db = self.clone();
+        let collection_id = collection_id.to_owned();
+        let changes = listener
+            .into_stream()
+            .map_err(anyhow::Error::from)
+            .map_ok(move |notification| {
+                let changes = match serde_json::from_str::<Notification>(notification.payload()) {
+                    Ok(n) if n.collection == collection_id => n
+                        .changes
+                        .into_iter()
+                        .map(|changed| {
+                            Ok(Change {
+                                r#type: n.r#type,
+                                collection: n.collection.to_owned(),
+                                id: changed.id,
+                                bbox: changed.bbox,
+                                feature: None,
+                            })
+                        })
+                        .collect(),
+                    Ok(_) => Vec::new(),
+                    Err(e) => vec![Err(e.into())],
+                };
+                stream::iter(changes)
+            })
+            .try_flatten()
+            .and_then(move |mut change| {
+                let db = db.clone();
+                async move {
+                    if change.r#type != ChangeType::Deleted {
+                        change.feature = db
+                            .read_feature(&change.collection, &change.id, &Crs::default2d())
+                            .await?;
+                    }
+                    Ok(change)
+                }
+            });
+
+        Ok(changes.boxed())
+    }
+}
@@ -1,10 +1,18 @@
-use sqlx::types::Json;
+use sqlx::{PgConnection, types::Json};
 
-use ogcapi_types::common::{Collection, Collections, Crs, Query};
+#[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+use ogcapi_types::common::Exception;
+use ogcapi_types::common::{Collection, Collections, Crs, GeometryColumn, Query};
 
-use crate::CollectionTransactions;
+use crate::{CollectionTransactions, precondition_failed};
 
-use super::Db;
+use super::{
+    Db,
+    sql::{
+        geometry_column, geometry_column_name, history_table, items_table, quote_literal,
+        row_version,
+    },
+};
 
 const COLLECTION: &str = r#"
 CASE
@@ -41,45 +49,50 @@ impl CollectionTransactions for Db {
             .map(|crs| crs.as_srid())
             .unwrap_or_else(|| Crs::default2d().as_srid());
 
+        let table = items_table(&collection.id)?;
+
         let mut tx = self.pool.begin().await?;
 
         sqlx::query(&format!(
             r#"
-            CREATE TABLE items."{0}" (
+            CREATE TABLE {table} (
                 id text PRIMARY KEY DEFAULT gen_random_uuid()::text,
-                collection text REFERENCES meta.collections(id) DEFAULT '{0}',
+                collection text REFERENCES meta.collections(id) DEFAULT {default},
                 properties jsonb,
                 geom geometry(GEOMETRY, {srid}) NOT NULL,
                 links jsonb NOT NULL DEFAULT '[]'::jsonb,
                 assets jsonb NOT NULL DEFAULT '{{}}'::jsonb,
-                bbox jsonb
+                bbox jsonb,
+                version bigint NOT NULL DEFAULT 1
             )
             "#,
-            collection.id
+            default = quote_literal(&collection.id)?
         ))
         .execute(&mut *tx)
         .await?;
 
-        sqlx::query(&format!(
-            r#"CREATE INDEX ON items."{}" USING btree (collection)"#,
-            collection.id
-        ))
-        .execute(&mut *tx)
-        .await?;
+        sqlx::query(&format!("CREATE INDEX ON {table} USING btree (collection)"))
+            .execute(&mut *tx)
+            .await?;
 
-        sqlx::query(&format!(
-            r#"CREATE INDEX ON items."{}" USING gin (properties)"#,
-            collection.id
-        ))
-        .execute(&mut *tx)
-        .await?;
+        sqlx::query(&format!("CREATE INDEX ON {table} USING gin (properties)"))
+            .execute(&mut *tx)
+            .await?;
 
-        sqlx::query(&format!(
-            r#"CREATE INDEX ON items."{}" USING gist (geom)"#,
-            collection.id
-        ))
-        .execute(&mut *tx)
-        .await?;
+        sqlx::query(&format!("CREATE INDEX ON {table} USING gist (geom)"))
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("SELECT meta.notify_item_changes_on($1::regclass)")
+            .bind(&table)
+            .execute(&mut *tx)
+            .await?;
+
+        add_geometry_columns(&mut tx, &table, &collection.geometry_columns).await?;
+
+        if versioned(collection) {
+            record_history(&mut tx, &collection.id).await?;
+        }
 
         sqlx::query("INSERT INTO meta.collections ( id, collection ) VALUES ( $1, $2 )")
             .bind(&collection.id)
@@ -89,39 +102,101 @@ impl CollectionTransactions for Db {
 
         tx.commit().await?;
 
+        self.cache.invalidate(&collection.id);
+
         Ok(collection.id.to_owned())
     }
 
     async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
-        // TODO: cache
-        let collection: Option<Json<Collection>> = sqlx::query_scalar(&format!(
+        self.cache
+            .collection(id, async {
+                let collection: Option<Json<Collection>> = sqlx::query_scalar(&format!(
+                    r#"
+                    SELECT {COLLECTION} AS "collection!"
+                    FROM meta.collections
+                    WHERE id = $1
+                    "#,
+                ))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+                Ok(collection.map(|c| c.0))
+            })
+            .await
+    }
+
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let version = row_version(if_match)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
             r#"
-            SELECT {COLLECTION} AS "collection!"
-            FROM meta.collections
-            WHERE id = $1
+            UPDATE meta.collections
+            SET collection = $2, version = version + 1
+            WHERE id = $1 AND ($3::bigint IS NULL OR version = $3)
             "#,
-        ))
-        .bind(id)
-        .fetch_optional(&self.pool)
+        )
+        .bind(&collection.id)
+        .bind(Json(collection))
+        .bind(version)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(collection.map(|c| c.0))
-    }
+        if version.is_some() && result.rows_affected() == 0 {
+            return Err(precondition_failed());
+        }
 
-    async fn update_collection(&self, collection: &Collection) -> anyhow::Result<()> {
-        sqlx::query("UPDATE meta.collections SET collection = $2 WHERE id = $1")
-            .bind(&collection.id)
-            .bind(Json(collection))
-            .execute(&self.pool)
-            .await?;
+        // versioning can be enabled later on, but not disabled
+        if result.rows_affected() > 0
+            && versioned(collection)
+            && !has_history(&mut tx, &collection.id).await?
+        {
+            record_history(&mut tx, &collection.id).await?;
+        }
+
+        // columns of geometries that are removed from the collection are kept
+        if result.rows_affected() > 0 {
+            let geometries = &collection.geometry_columns;
+            add_geometry_columns(&mut tx, &items_table(&collection.id)?, geometries).await?;
+            if has_history(&mut tx, &collection.id).await? {
+                add_geometry_columns(&mut tx, &history_table(&collection.id)?, geometries).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        self.cache.invalidate(&collection.id);
 
         Ok(())
     }
 
-    async fn delete_collection(&self, id: &str) -> anyhow::Result<()> {
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        let version = row_version(if_match)?;
+
         let mut tx = self.pool.begin().await?;
 
-        sqlx::query(&format!(r#"DROP TABLE IF EXISTS items."{id}""#))
+        if version.is_some() {
+            let current: Option<i64> =
+                sqlx::query_scalar("SELECT version FROM meta.collections WHERE id = $1 FOR UPDATE")
+                    .bind(id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            if current != version {
+                return Err(precondition_failed());
+            }
+        }
+
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", items_table(id)?))
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", history_table(id)?))
             .execute(&mut *tx)
             .await?;
 
@@ -132,9 +207,21 @@ impl CollectionTransactions for Db {
 
         tx.commit().await?;
 
+        self.cache.invalidate(id);
+
         Ok(())
     }
 
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        let version: Option<i64> =
+            sqlx::query_scalar("SELECT version FROM meta.collections WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(version.map(|v| v.to_string()))
+    }
+
     async fn list_collections(&self, _query: &Query) -> anyhow::Result<Collections> {
         let where_clause = match cfg!(feature = "stac") {
             true => Some(r#"WHERE collection ->> 'type' = 'Collection'"#),
@@ -144,7 +231,7 @@ impl CollectionTransactions for Db {
             "SELECT array_to_json(array_agg({COLLECTION})) FROM meta.collections {}",
             where_clause.unwrap_or_default()
         ))
-        .fetch_one(&self.pool)
+        .fetch_one(self.reader())
         .await?;
 
         let collections = collections.map(|c| c.0).unwrap_or_default();
@@ -154,3 +241,142 @@ impl CollectionTransactions for Db {
         Ok(collections)
     }
 }
+
+impl Db {
+    /// Storage srid of a collection, from its `storageCrs` or else the `geom` column
+    #[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+    pub(super) async fn storage_srid(&self, collection_id: &str) -> anyhow::Result<i32> {
+        self.cache
+            .srid(collection_id, async {
+                let collection = self.read_collection(collection_id).await?;
+                match collection {
+                    Some(collection) => match collection.storage_crs.map(|crs| crs.as_srid()) {
+                        Some(srid) => Ok(srid),
+                        None => Ok(sqlx::query_scalar("SELECT Find_SRID('items', $1, 'geom')")
+                            .bind(collection_id)
+                            .fetch_one(&self.pool)
+                            .await?),
+                    },
+                    None => Err(Exception::new_from_status(404).into()),
+                }
+            })
+            .await
+    }
+}
+
+/// Whether a collection records the history of its features, as requested by
+/// the `versioning` member of its document
+fn versioned(collection: &Collection) -> bool {
+    collection
+        .additional_properties
+        .get("versioning")
+        .is_some_and(|versioning| versioning == true)
+}
+
+/// Whether the history of the features of a collection is recorded
+pub(super) async fn has_history(
+    conn: &mut PgConnection,
+    collection_id: &str,
+) -> anyhow::Result<bool> {
+    Ok(sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+        .bind(history_table(collection_id)?)
+        .fetch_one(conn)
+        .await?)
+}
+
+/// Record the history of the features of a collection, starting with the
+/// current features
+async fn record_history(conn: &mut PgConnection, collection_id: &str) -> anyhow::Result<()> {
+    let items = items_table(collection_id)?;
+    let history = history_table(collection_id)?;
+
+    sqlx::query(&format!(
+        "CREATE TABLE {history} (LIKE {items} INCLUDING GENERATED, valid_from timestamptz NOT NULL, valid_to timestamptz)"
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        "CREATE INDEX ON {history} USING btree (id, valid_from)"
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!("CREATE INDEX ON {history} USING gist (geom)"))
+        .execute(&mut *conn)
+        .await?;
+
+    // generated columns are computed by the history table
+    let columns: String = sqlx::query_scalar(
+        r#"
+        SELECT string_agg(quote_ident(attname), ', ' ORDER BY attnum)
+        FROM pg_attribute
+        WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped AND attgenerated = ''
+        "#,
+    )
+    .bind(&items)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        "INSERT INTO {history} ({columns}, valid_from) SELECT {columns}, now() FROM {items}"
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        r#"
+        CREATE TRIGGER record_history AFTER INSERT OR UPDATE OR DELETE ON {items}
+        FOR EACH ROW EXECUTE FUNCTION meta.record_item_history()
+        "#
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Add the missing columns of named geometries to a table of features, which
+/// are generated from the GeoJSON geometries of the feature properties
+async fn add_geometry_columns(
+    conn: &mut PgConnection,
+    table: &str,
+    geometries: &[GeometryColumn],
+) -> anyhow::Result<()> {
+    let existing: Vec<String> = sqlx::query_scalar(
+        "SELECT attname::text FROM pg_attribute WHERE attrelid = $1::regclass AND NOT attisdropped",
+    )
+    .bind(table)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for geometry in geometries {
+        if existing.contains(&geometry_column_name(&geometry.name)) {
+            continue;
+        }
+        let column = geometry_column(&geometry.name)?;
+
+        // values that are not GeoJSON geometries are null
+        sqlx::query(&format!(
+            r#"
+            ALTER TABLE {table} ADD COLUMN {column} geometry GENERATED ALWAYS AS (
+                CASE WHEN properties -> {name} ->> 'type' IN (
+                    'Point', 'MultiPoint', 'LineString', 'MultiLineString',
+                    'Polygon', 'MultiPolygon', 'GeometryCollection'
+                ) THEN
+                    ST_SetSRID(ST_GeomFromGeoJSON(properties -> {name}), ST_SRID(geom))
+                END
+            ) STORED
+            "#,
+            name = quote_literal(&geometry.name)?
+        ))
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(&format!("CREATE INDEX ON {table} USING gist ({column})"))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
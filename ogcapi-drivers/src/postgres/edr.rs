@@ -1,14 +1,17 @@
 use sqlx::types::Json;
 
 use ogcapi_types::{
-    common::{Crs, Exception},
+    common::{AxisOrder, Crs, Exception},
     edr::{Query, QueryType},
     features::{Feature, FeatureCollection},
 };
 
-use crate::{CollectionTransactions, EdrQuerier};
+use crate::EdrQuerier;
 
-use super::Db;
+use super::{
+    Db,
+    sql::{Params, items_table},
+};
 
 #[async_trait::async_trait]
 impl EdrQuerier for Db {
@@ -18,48 +21,32 @@ impl EdrQuerier for Db {
         query_type: &QueryType,
         query: &Query,
     ) -> anyhow::Result<(FeatureCollection, Crs)> {
-        let collection = self.read_collection(collection_id).await?;
-        let storage_srid = match collection {
-            Some(collection) => match collection.storage_crs.map(|crs| crs.as_srid()) {
-                Some(srid) => srid,
-                None => {
-                    sqlx::query_scalar(&format!(
-                        "SELECT Find_SRID('items', '{collection_id}', 'geom')"
-                    ))
-                    .fetch_one(&self.pool)
-                    .await?
-                }
-            },
-            None => return Err(Exception::new_from_status(404).into()),
-        };
+        let table = items_table(collection_id)?;
+        let storage_srid = self.storage_srid(collection_id).await?;
+
+        let mut params = Params::default();
 
         let mut geometry_type = query.coords.split('(').next().unwrap().to_uppercase();
         geometry_type.retain(|c| !c.is_whitespace());
 
-        let (spatial_predicate, srid) = match &query_type {
+        let (spatial_predicate, crs) = match &query_type {
             QueryType::Position | QueryType::Area | QueryType::Trajectory => {
                 if geometry_type.ends_with('Z') || geometry_type.ends_with('M') {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default3d().as_srid());
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default3d);
                     let predicate = format!(
-                        "ST_3DIntersects(geom, ST_Transform(ST_GeomFromEWKT('SRID={};{}'), {}))",
-                        srid, query.coords, storage_srid
+                        "ST_3DIntersects(geom, ST_Transform({}, {}))",
+                        geometry_from_text(&query.coords, &crs, &mut params)?,
+                        params.bind(storage_srid)?
                     );
-                    (predicate, srid)
+                    (predicate, crs)
                 } else {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default2d().as_srid());
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default2d);
                     let predicate = format!(
-                        "ST_Intersects(geom, ST_Transform(ST_GeomFromEWKT('SRID={};{}'), {}))",
-                        srid, query.coords, storage_srid
+                        "ST_Intersects(geom, ST_Transform({}, {}))",
+                        geometry_from_text(&query.coords, &crs, &mut params)?,
+                        params.bind(storage_srid)?
                     );
-                    (predicate, srid)
+                    (predicate, crs)
                 }
             }
             QueryType::Radius => {
@@ -79,48 +66,59 @@ impl EdrQuerier for Db {
                     .expect("Failed to parse & convert distance");
 
                 if geometry_type.ends_with('Z') || geometry_type.ends_with('M') {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default3d().as_srid());
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default3d);
                     let predicate = format!(
-                        "ST_3DDWithin(geom, ST_Transform(ST_GeomFromEWKT('SRID={};{}'), {}))",
-                        srid, query.coords, storage_srid
+                        "ST_3DDWithin(geom, ST_Transform({}, {}), {})",
+                        geometry_from_text(&query.coords, &crs, &mut params)?,
+                        params.bind(storage_srid)?,
+                        params.bind(distance)?
                     );
-                    (predicate, srid)
+                    (predicate, crs)
                 } else {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default2d().as_srid());
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default2d);
                     let predicate = format!(
-                        "ST_DWithin(ST_Transform(geom, 4326)::geography, ST_Transform(ST_GeomFromEWKT('SRID={};{}'), 4326)::geography, {}, false)",
-                        srid, query.coords, distance
+                        "ST_DWithin(ST_Transform(geom, 4326)::geography, ST_Transform({}, 4326)::geography, {}, false)",
+                        geometry_from_text(&query.coords, &crs, &mut params)?,
+                        params.bind(distance)?
                     );
-                    (predicate, srid)
+                    (predicate, crs)
                 }
             }
             QueryType::Cube => {
-                let bbox: Vec<&str> = query.coords.split(',').collect();
+                let mut bbox = query
+                    .coords
+                    .split(',')
+                    .map(|c| c.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|e| {
+                        Exception::new_from_status(400)
+                            .detail(format!("Invalid cube coordinates `{}`: {e}", query.coords))
+                    })?;
+
+                // coordinate system axis order (PostGIS is lng, lat | the crs definition may be lat, lng)
+                let north_east = query
+                    .crs
+                    .as_ref()
+                    .is_some_and(|crs| crs.axis_order() == AxisOrder::NorthEast);
+                if north_east && matches!(bbox.len(), 4 | 6) {
+                    let upper = bbox.len() / 2;
+                    bbox.swap(0, 1);
+                    bbox.swap(upper, upper + 1);
+                }
                 if bbox.len() == 4 {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default2d().as_srid());
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default2d);
                     let predicate = format!(
-                        "ST_Intersects(geom, ST_Transform(ST_MakeEnvelope({}, {}), {})",
-                        query.coords, srid, storage_srid
+                        "ST_Intersects(geom, ST_Transform(ST_MakeEnvelope({}, {}, {}, {}, {}), {}))",
+                        params.bind(bbox[0])?,
+                        params.bind(bbox[1])?,
+                        params.bind(bbox[2])?,
+                        params.bind(bbox[3])?,
+                        params.bind(crs.as_srid())?,
+                        params.bind(storage_srid)?
                     );
-                    (predicate, srid)
-                } else {
-                    let srid: i32 = query
-                        .crs
-                        .as_ref()
-                        .map(|crs| crs.as_srid())
-                        .unwrap_or_else(|| Crs::default3d().as_srid());
+                    (predicate, crs)
+                } else if bbox.len() == 6 {
+                    let crs = query.crs.clone().unwrap_or_else(Crs::default3d);
                     let predicate = format!(
                         "ST_3DIntersects(
                             geom,
@@ -132,63 +130,100 @@ impl EdrQuerier for Db {
                                 {}
                             )
                         )",
-                        bbox[0], bbox[1], bbox[2], bbox[3], bbox[4], bbox[5], srid, storage_srid
+                        params.bind(bbox[0])?,
+                        params.bind(bbox[1])?,
+                        params.bind(bbox[2])?,
+                        params.bind(bbox[3])?,
+                        params.bind(bbox[4])?,
+                        params.bind(bbox[5])?,
+                        params.bind(crs.as_srid())?,
+                        params.bind(storage_srid)?
                     );
-                    (predicate, srid)
+                    (predicate, crs)
+                } else {
+                    return Err(Exception::new_from_status(400)
+                        .detail("Cube coordinates require 4 or 6 values")
+                        .into());
                 }
             }
             qt => unimplemented!("{qt:?}"),
         };
 
         let properties = if let Some(parameters) = &query.parameter_name {
+            let parameters: Vec<String> = parameters.split(',').map(str::to_owned).collect();
             format!(
-                "{0} as properties",
-                parameters
-                    .split(',')
-                    .map(|s| format!(
-                        "('{{\"{s}\":' || (properties -> '{s}')::text || '}}')::jsonb"
-                    ))
-                    .collect::<Vec<String>>()
-                    .join("||")
+                r#"
+                COALESCE(
+                    (
+                        SELECT jsonb_object_agg(key, value) FROM jsonb_each(properties)
+                        WHERE key = ANY({})
+                    ),
+                    '{{}}'::jsonb
+                ) as properties
+                "#,
+                params.bind(parameters)?
             )
         } else {
             "properties".to_string()
         };
 
+        let collection = params.bind(collection_id)?;
+        let response_srid = params.bind(crs.as_srid())?;
+        let geometry = match crs.axis_order() {
+            AxisOrder::EastNorth => format!("ST_Transform(geom, {response_srid})"),
+            AxisOrder::NorthEast => {
+                format!("ST_FlipCoordinates(ST_Transform(geom, {response_srid}))")
+            }
+        };
+
         let sql = format!(
             r#"
             SELECT
                 id,
                 {properties},
-                ST_AsGeoJSON(ST_Transform(geom, $1))::jsonb as geometry,
+                ST_AsGeoJSON({geometry})::jsonb as geometry,
                 links,
-                '{collection_id}' as collection,
+                {collection} as collection,
                 assets
-            FROM items."{collection_id}"
+            FROM {table}
             WHERE {spatial_predicate}
             "#
         );
 
-        let number_matched = sqlx::query(&sql)
-            .bind(srid)
-            .execute(&self.pool)
+        let number_matched = sqlx::query_with(&sql, params.clone().into_arguments())
+            .execute(self.reader())
             .await?
             .rows_affected();
 
-        let features: Option<Json<Vec<Feature>>> = sqlx::query_scalar(&format!(
-            r#"
-            SELECT array_to_json(array_agg(row_to_json(t)))
-            FROM ( {sql} ) t
-            "#
-        ))
-        .bind(srid)
-        .fetch_one(&self.pool)
+        let features: Option<Json<Vec<Feature>>> = sqlx::query_scalar_with(
+            &format!(
+                r#"
+                SELECT array_to_json(array_agg(row_to_json(t)))
+                FROM ( {sql} ) t
+                "#
+            ),
+            params.into_arguments(),
+        )
+        .fetch_one(self.reader())
         .await?;
 
         let features = features.map(|f| f.0).unwrap_or_default();
         let mut fc = FeatureCollection::new(features);
         fc.number_matched = Some(number_matched);
 
-        Ok((fc, Crs::from_srid(srid)))
+        Ok((fc, crs))
     }
 }
+
+/// Geometry of WKT coordinates in `crs`, with the axes in PostGIS order
+fn geometry_from_text(coords: &str, crs: &Crs, params: &mut Params) -> anyhow::Result<String> {
+    let geometry = format!(
+        "ST_GeomFromText({}, {})",
+        params.bind(coords)?,
+        params.bind(crs.as_srid())?
+    );
+    Ok(match crs.axis_order() {
+        AxisOrder::EastNorth => geometry,
+        AxisOrder::NorthEast => format!("ST_FlipCoordinates({geometry})"),
+    })
+}
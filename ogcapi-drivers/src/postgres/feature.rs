@@ -1,46 +1,130 @@
+use std::collections::HashMap;
+
+use futures::stream::BoxStream;
+use serde_json::{Map, Value};
+
 use ogcapi_types::{
-    common::{Authority, Bbox, Crs, Datetime, Exception, IntervalDatetime},
-    features::{Feature, FeatureCollection, Query},
+    common::{AxisOrder, Bbox, Crs, Exception, GeometryColumn},
+    features::{
+        Feature, FeatureCollection, FeatureVersion, NumberMatched, Query, Queryable, Queryables,
+        Schema,
+    },
 };
 
-use crate::{CollectionTransactions, FeatureTransactions};
+use crate::{CollectionTransactions, FeatureTransactions, SAMPLE_SIZE, precondition_failed};
+
+use super::{
+    Db,
+    collection::has_history,
+    filter::FilterTranslator,
+    sql::{
+        Cursor, Params, datetime_predicate, geometry_column, history_table, items_table, order_by,
+        properties_projection, quote_literal, row_version, sort_key_values, sort_keys,
+        stream_features,
+    },
+};
 
-use super::Db;
+/// Default expression for the `properties` column
+const PROPERTIES: &str = "properties";
+
+/// Expression for a geometry `column` in `crs`, whose srid is bound as `$1`
+///
+/// Geometries are stored in "traditional GIS order" and flipped for CRSs with
+/// latitude or northing first.
+fn geometry(crs: &Crs, column: &str) -> String {
+    match crs.axis_order() {
+        AxisOrder::EastNorth => format!("ST_AsGeoJSON(ST_Transform({column}, $1))::jsonb"),
+        AxisOrder::NorthEast => {
+            format!("ST_AsGeoJSON(ST_FlipCoordinates(ST_Transform({column}, $1)))::jsonb")
+        }
+    }
+}
+
+/// Expression for `properties` with the named geometries in `crs`, whose
+/// srid is bound as `$1`
+///
+/// Geometries that are not among the selected properties are omitted.
+fn with_geometries(
+    properties: &str,
+    geometries: &[GeometryColumn],
+    selection: Option<&[String]>,
+    crs: &Crs,
+) -> anyhow::Result<String> {
+    let mut members = Vec::new();
+    for column in geometries {
+        if selection.is_some_and(|selection| !selection.contains(&column.name)) {
+            continue;
+        }
+        members.push(format!(
+            "{}, {}",
+            quote_literal(&column.name)?,
+            geometry(crs, &format!("items.{}", geometry_column(&column.name)?))
+        ));
+    }
+
+    if members.is_empty() {
+        return Ok(properties.to_owned());
+    }
+    Ok(format!(
+        "({properties} || jsonb_strip_nulls(jsonb_build_object({})))",
+        members.join(", ")
+    ))
+}
 
+/// Selected columns with the given `properties` and `geometry` expressions
 #[cfg(not(feature = "stac"))]
-static ROWS: &str = "
-items.id,
-items.collection,
-properties,
-ST_AsGeoJSON(ST_Transform(geom, $1))::jsonb AS geometry,
-links
-";
+fn rows(properties: &str, geometry: &str) -> String {
+    format!(
+        "
+        items.id,
+        items.collection,
+        {properties} AS properties,
+        {geometry} AS geometry,
+        links
+        "
+    )
+}
 
+/// Selected columns with the given `properties` and `geometry` expressions
 #[cfg(feature = "stac")]
-static ROWS: &str = "
-items.id,
-items.collection,
-properties,
-ST_AsGeoJSON(ST_Transform(geom, $1))::jsonb AS geometry,
-links,
-meta.collection ->> 'stac_version' AS stac_version,
-COALESCE(
-    (meta.collection -> 'stac_extensions'),
-    '[]'::jsonb
-) AS stac_extensions,
-assets,
-COALESCE(
-    bbox,
-    array_to_json(
-        ARRAY[
-            st_xmin(st_transform(geom, 4326)::box2d),
-            st_ymin(st_transform(geom, 4326)::box2d),
-            st_xmax(st_transform(geom, 4326)::box2d),
-            st_ymax(st_transform(geom, 4326)::box2d)
-        ]
-    )::jsonb
-) as bbox
-";
+fn rows(properties: &str, geometry: &str) -> String {
+    format!(
+        "
+        items.id,
+        items.collection,
+        {properties} AS properties,
+        {geometry} AS geometry,
+        links,
+        meta.collection ->> 'stac_version' AS stac_version,
+        COALESCE(
+            (meta.collection -> 'stac_extensions'),
+            '[]'::jsonb
+        ) AS stac_extensions,
+        assets,
+        COALESCE(
+            bbox,
+            array_to_json(
+                ARRAY[
+                    st_xmin(st_transform(geom, 4326)::box2d),
+                    st_ymin(st_transform(geom, 4326)::box2d),
+                    st_xmax(st_transform(geom, 4326)::box2d),
+                    st_ymax(st_transform(geom, 4326)::box2d)
+                ]
+            )::jsonb
+        ) as bbox
+        "
+    )
+}
+
+/// Report a rejected batch of features as bad request, nothing was inserted
+fn rollback(error: sqlx::Error) -> anyhow::Error {
+    match error {
+        sqlx::Error::Database(e) => Exception::new_from_status(400)
+            .detail(format!("No features were created: {}", e.message()))
+            .into(),
+        e => e.into(),
+    }
+}
 
 #[async_trait::async_trait]
 impl FeatureTransactions for Db {
@@ -49,7 +133,7 @@ impl FeatureTransactions for Db {
 
         let id: (String,) = sqlx::query_as(&format!(
             r#"
-            INSERT INTO items."{0}" (
+            INSERT INTO {0} (
                 id,
                 properties,
                 geom,
@@ -59,38 +143,127 @@ impl FeatureTransactions for Db {
             ) VALUES (
                 COALESCE($1 ->> 'id', gen_random_uuid()::text),
                 $1 -> 'properties',
-                ST_SetSRID(ST_GeomFromGeoJSON($1 -> 'geometry'), (SELECT Find_SRID('items', '{0}', 'geom'))),
+                ST_SetSRID(ST_GeomFromGeoJSON($1 -> 'geometry'), (SELECT Find_SRID('items', $2, 'geom'))),
                 $1 -> 'links',
                 COALESCE($1 -> 'assets', '{{}}'::jsonb),
                 $1 -> 'bbox'
             )
             RETURNING id
             "#,
-            &collection_id
+            items_table(collection_id)?
         ))
         .bind(serde_json::to_value(feature)?)
+        .bind(collection_id)
         .fetch_one(&self.pool)
         .await?;
 
         Ok(id.0)
     }
 
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        let table = items_table(collection_id)?;
+        let srid = self.storage_srid(collection_id).await?;
+
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            CREATE TEMPORARY TABLE features_import (
+                n bigserial,
+                id text,
+                feature jsonb NOT NULL
+            ) ON COMMIT DROP
+            "#,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // one feature per line in the text format of COPY, where backslash is
+        // the escape character (JSON has no unescaped line breaks or tabs)
+        let mut data = Vec::new();
+        for feature in features {
+            data.extend(
+                serde_json::to_string(feature)?
+                    .replace('\\', "\\\\")
+                    .as_bytes(),
+            );
+            data.push(b'\n');
+        }
+
+        let mut copy = tx
+            .copy_in_raw("COPY features_import (feature) FROM STDIN")
+            .await?;
+        copy.send(data).await?;
+        copy.finish().await?;
+
+        sqlx::query(
+            "UPDATE features_import SET id = COALESCE(feature ->> 'id', gen_random_uuid()::text)",
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO {table} (
+                id,
+                properties,
+                geom,
+                links,
+                assets,
+                bbox
+            )
+            SELECT
+                id,
+                feature -> 'properties',
+                ST_SetSRID(ST_GeomFromGeoJSON(feature -> 'geometry'), $1),
+                COALESCE(feature -> 'links', '[]'::jsonb),
+                COALESCE(feature -> 'assets', '{{}}'::jsonb),
+                feature -> 'bbox'
+            FROM features_import
+            ORDER BY n
+            "#
+        ))
+        .bind(srid)
+        .execute(&mut *tx)
+        .await
+        .map_err(rollback)?;
+
+        let ids = sqlx::query_scalar("SELECT id FROM features_import ORDER BY n")
+            .fetch_all(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(ids)
+    }
+
     async fn read_feature(
         &self,
         collection: &str,
         id: &str,
         crs: &Crs,
     ) -> anyhow::Result<Option<Feature>> {
+        let geometries = self.geometry_columns(collection).await?;
+
         let feature: Option<sqlx::types::Json<Feature>> = sqlx::query_scalar(&format!(
             r#"
             SELECT row_to_json(t)
             FROM (
-                SELECT {ROWS}
-                FROM items."{collection}" items JOIN meta.collections meta
+                SELECT {rows}
+                FROM {table} items JOIN meta.collections meta
                     ON items.collection = meta.id
                 WHERE items.id = $2
             ) t
-            "#
+            "#,
+            rows = rows(
+                &with_geometries(PROPERTIES, &geometries, None, crs)?,
+                &geometry(crs, "geom")
+            ),
+            table = items_table(collection)?
         ))
         .bind(crs.as_srid())
         .bind(id)
@@ -100,44 +273,310 @@ impl FeatureTransactions for Db {
         Ok(feature.map(|f| f.0))
     }
 
-    async fn update_feature(&self, feature: &Feature) -> anyhow::Result<()> {
-        sqlx::query(&format!(
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let version = row_version(if_match)?;
+        let collection = feature.collection.as_ref().unwrap();
+
+        let result = sqlx::query(&format!(
             r#"
-            UPDATE items."{0}"
+            UPDATE {0}
             SET
                 properties = $1 -> 'properties',
-                geom = ST_GeomFromGeoJSON($1 -> 'geometry'),
+                geom = ST_SetSRID(ST_GeomFromGeoJSON($1 -> 'geometry'), $3),
                 links = $1 -> 'links',
-                assets = COALESCE($1 -> 'assets', '{{}}'::jsonb)
-            WHERE id = $1 ->> 'id'
+                assets = COALESCE($1 -> 'assets', '{{}}'::jsonb),
+                version = version + 1
+            WHERE id = $1 ->> 'id' AND ($2::bigint IS NULL OR version = $2)
             "#,
-            &feature.collection.as_ref().unwrap()
+            items_table(collection)?
         ))
         .bind(serde_json::to_value(feature)?)
+        .bind(version)
+        .bind(self.storage_srid(collection).await?)
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Err(match version {
+                Some(_) => precondition_failed(),
+                None => Exception::new_from_status(404).into(),
+            });
+        }
+
         Ok(())
     }
 
-    async fn delete_feature(&self, collection: &str, id: &str) -> anyhow::Result<()> {
-        sqlx::query(&format!(
-            r#"DELETE FROM items."{collection}" WHERE id = $1"#
+    async fn patch_feature(
+        &self,
+        collection: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if patch.get("geometry").is_some_and(Value::is_null) {
+            return Err(Exception::new_from_status(400)
+                .detail("The geometry of a feature can not be removed")
+                .into());
+        }
+
+        let version = row_version(if_match)?;
+
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE {0}
+            SET
+                properties = CASE
+                    WHEN $3 ? 'properties' THEN jsonb_merge_patch(properties, $3 -> 'properties')
+                    ELSE properties
+                END,
+                geom = CASE
+                    WHEN $3 ? 'geometry' THEN ST_SetSRID(ST_GeomFromGeoJSON($3 -> 'geometry'), $4)
+                    ELSE geom
+                END,
+                links = CASE
+                    WHEN $3 ? 'links' THEN COALESCE(NULLIF($3 -> 'links', 'null'), '[]'::jsonb)
+                    ELSE links
+                END,
+                assets = CASE
+                    WHEN $3 ? 'assets' THEN COALESCE(NULLIF(jsonb_merge_patch(assets, $3 -> 'assets'), 'null'), '{{}}'::jsonb)
+                    ELSE assets
+                END,
+                bbox = CASE
+                    WHEN $3 ? 'bbox' THEN NULLIF($3 -> 'bbox', 'null')
+                    ELSE bbox
+                END,
+                version = version + 1
+            WHERE id = $1 AND ($2::bigint IS NULL OR version = $2)
+            "#,
+            items_table(collection)?
+        ))
+        .bind(id)
+        .bind(version)
+        .bind(Value::Object(patch.to_owned()))
+        .bind(self.storage_srid(collection).await?)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(match version {
+                Some(_) => precondition_failed(),
+                None => Exception::new_from_status(404).into(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn delete_feature(
+        &self,
+        collection: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let version = row_version(if_match)?;
+
+        let result = sqlx::query(&format!(
+            "DELETE FROM {} WHERE id = $1 AND ($2::bigint IS NULL OR version = $2)",
+            items_table(collection)?
         ))
         .bind(id)
+        .bind(version)
         .execute(&self.pool)
         .await?;
 
+        if version.is_some() && result.rows_affected() == 0 {
+            return Err(precondition_failed());
+        }
+
         Ok(())
     }
 
+    async fn feature_etag(&self, collection: &str, id: &str) -> anyhow::Result<Option<String>> {
+        let version: Option<i64> = sqlx::query_scalar(&format!(
+            "SELECT version FROM {} WHERE id = $1",
+            items_table(collection)?
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(version.map(|v| v.to_string()))
+    }
+
+    async fn feature_versions(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Vec<FeatureVersion>>> {
+        if !has_history(&mut *self.pool.acquire().await?, collection_id).await? {
+            return Ok(None);
+        }
+        let geometries = self.geometry_columns(collection_id).await?;
+
+        let versions: Vec<sqlx::types::Json<FeatureVersion>> = sqlx::query_scalar(&format!(
+            r#"
+            SELECT jsonb_build_object(
+                'version', t.version,
+                'validFrom', t.valid_from,
+                'validTo', t.valid_to,
+                'feature', to_jsonb(t) - 'version' - 'valid_from' - 'valid_to'
+            )
+            FROM (
+                SELECT {rows}, items.version, items.valid_from, items.valid_to
+                FROM {table} items JOIN meta.collections meta
+                    ON items.collection = meta.id
+                WHERE items.id = $2
+            ) t
+            ORDER BY t.valid_from, t.version
+            "#,
+            rows = rows(
+                &with_geometries(PROPERTIES, &geometries, None, crs)?,
+                &geometry(crs, "geom")
+            ),
+            table = history_table(collection_id)?
+        ))
+        .bind(crs.as_srid())
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Some(versions.into_iter().map(|v| v.0).collect()))
+    }
+
     async fn list_items(
         &self,
         collection_id: &str,
         query: &Query,
     ) -> anyhow::Result<FeatureCollection> {
+        // fetch one more feature than requested to know whether there is a next page
+        let (sql, params, number_matched) = self
+            .select_items(collection_id, query, query.limit.map(|l| l + 1))
+            .await?;
+
+        let mut rows: Vec<(sqlx::types::Json<Feature>, sqlx::types::Json<Vec<Value>>)> =
+            sqlx::query_as_with(&sql, params.into_arguments())
+                .fetch_all(self.reader())
+                .await?;
+
+        let next = match query.limit {
+            Some(limit) if rows.len() > limit => {
+                rows.truncate(limit);
+                rows.last()
+                    .map(|(_, key)| Cursor(key.0.to_owned()).encode())
+            }
+            _ => None,
+        };
+
+        let features = rows.into_iter().map(|(feature, _)| feature.0).collect();
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = number_matched;
+        fc.next = next;
+
+        Ok(fc)
+    }
+
+    fn stream_items<'a>(
+        &'a self,
+        collection_id: &'a str,
+        query: &'a Query,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        stream_features(self.reader(), async move {
+            let mut query = query.to_owned();
+            query.number_matched = Some(NumberMatched::Skip);
+            let (sql, params, _) = self
+                .select_items(collection_id, &query, query.limit)
+                .await?;
+            Ok((sql, params))
+        })
+    }
+
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        true
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        let collection = self
+            .read_collection(collection_id)
+            .await?
+            .ok_or_else(|| Exception::new_from_status(404))?;
+
+        // stored in the collection document
+        if let Some(queryables) = collection.additional_properties.get("queryables") {
+            return Ok(serde_json::from_value(queryables.to_owned())?);
+        }
+
+        Ok(Queryables {
+            title: collection.title,
+            queryables: self.sample_properties(collection_id).await?,
+            additional_properties: false,
+            ..Default::default()
+        })
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        let collection = self
+            .read_collection(collection_id)
+            .await?
+            .ok_or_else(|| Exception::new_from_status(404))?;
+
+        // stored in the collection document
+        if let Some(schema) = collection.additional_properties.get("schema") {
+            return Ok(serde_json::from_value(schema.to_owned())?);
+        }
+
+        Ok(Schema {
+            title: collection.title,
+            description: collection.description,
+            properties: self.sample_properties(collection_id).await?,
+            ..Default::default()
+        })
+    }
+}
+
+impl Db {
+    /// Statement selecting up to `limit` features of a collection with their
+    /// sort keys, and the number of matching features
+    async fn select_items(
+        &self,
+        collection_id: &str,
+        query: &Query,
+        limit: Option<usize>,
+    ) -> anyhow::Result<(String, Params, Option<u64>)> {
+        let mut table = items_table(collection_id)?;
+        let collection = self
+            .read_collection(collection_id)
+            .await?
+            .ok_or_else(|| anyhow::Error::from(Exception::new_from_status(404)))?;
+
+        let mut params = Params::default();
         let mut where_conditions = vec!["TRUE".to_owned()];
 
+        // srid, bound first as `$1` is used in `geometry`
+        let crs = query.crs.clone().unwrap_or_else(Crs::default2d);
+        params.bind(crs.as_srid())?;
+
+        // versions of the features at a point in time
+        if let Some(version_at) = query.version_at.as_ref() {
+            if !has_history(&mut *self.reader().acquire().await?, collection_id).await? {
+                return Err(Exception::new_from_status(400)
+                    .detail(format!(
+                        "Collection `{collection_id}` does not record the history of its features"
+                    ))
+                    .into());
+            }
+            table = history_table(collection_id)?;
+            let at = params.bind(version_at.to_rfc3339())?;
+            where_conditions.push(format!(
+                "items.valid_from <= {at}::timestamptz \
+                AND (items.valid_to IS NULL OR items.valid_to > {at}::timestamptz)"
+            ));
+        }
+
         // bbox
         if let Some(bbox) = query.bbox.as_ref() {
             // crs
@@ -146,174 +585,297 @@ impl FeatureTransactions for Db {
                 Bbox::Bbox3D(_) => Crs::default3d(),
             });
 
-            // coordinate system axis order (OGC and Postgis is lng, lat | EPSG is lat, lng)
-            let order = match bbox_crs.authority {
-                Authority::OGC => [0, 1, 2, 3],
-                Authority::EPSG => [0, 1, 2, 3], // Use "traditional GIS order". TODO: should follow the crs definition [1, 0, 3, 2]
+            // coordinate system axis order (PostGIS is lng, lat | the crs definition may be lat, lng)
+            let bbox = match bbox_crs.axis_order() {
+                AxisOrder::EastNorth => bbox.to_owned(),
+                AxisOrder::NorthEast => bbox.swap_axes(),
             };
 
-            let collection = self.read_collection(collection_id).await?;
-            let storage_srid = match collection {
-                Some(collection) => match collection.storage_crs.map(|crs| crs.as_srid()) {
-                    Some(srid) => srid,
-                    None => {
-                        sqlx::query_scalar(&format!(
-                            "SELECT Find_SRID('items', '{collection_id}', 'geom')"
-                        ))
-                        .fetch_one(&self.pool)
-                        .await?
-                    }
-                },
-                None => return Err(Exception::new_from_status(404).into()),
+            let storage_srid = params.bind(self.storage_srid(collection_id).await?)?;
+            let bbox_srid = params.bind(bbox_crs.as_srid())?;
+
+            // the primary or a named geometry
+            let column = match query.bbox_geometry.as_deref() {
+                Some(name) => {
+                    collection.geometry_column(name)?;
+                    format!("items.{}", geometry_column(name)?)
+                }
+                None => "geom".to_owned(),
             };
 
-            // TODO: handle antimeridian (lower > upper on axis 1)
-            let intersection = match bbox {
-                Bbox::Bbox2D(bbox) => format!(
-                    "ST_Intersects(geom, ST_Transform(ST_MakeEnvelope({}, {}, {}, {}, {}), {storage_srid}))",
-                    bbox[order[0]],
-                    bbox[order[1]],
-                    bbox[order[2]],
-                    bbox[order[3]],
-                    bbox_crs.as_srid()
-                ),
-                Bbox::Bbox3D(bbox) => format!(
-                    // FIXME: ensure proper height/box transformation handling
-                    r#"ST_3DIntersects(geom, ST_Envelope(ST_Transform(ST_SetSRID(ST_MakeLine(ARRAY[
-                        ST_MakePoint({x1}, {y1}, {z1}), 
-                        ST_MakePoint({x2}, {y1}, {z1}),
-                        ST_MakePoint({x1}, {y2}, {z1}), 
-                        ST_MakePoint({x2}, {y2}, {z1}),
-                        ST_MakePoint({x1}, {y1}, {z2}), 
-                        ST_MakePoint({x2}, {y1}, {z2}),
-                        ST_MakePoint({x1}, {y2}, {z2}), 
-                        ST_MakePoint({x2}, {y2}, {z2})
-                    ]), {srid}), {storage_srid})))"#,
-                    x1 = bbox[order[0]],
-                    y1 = bbox[order[1]],
-                    z1 = bbox[2],
-                    x2 = bbox[order[2] + 1],
-                    y2 = bbox[order[3] + 1],
-                    z2 = bbox[5],
-                    srid = bbox_crs.as_srid()
-                ),
+            // boxes spanning the antimeridian (lower > upper on axis 1) are split in
+            // two, in projected coordinate systems they are invalid
+            let bboxes = if !bbox.crosses_antimeridian() {
+                vec![bbox]
+            } else if self.is_geographic(bbox_crs.as_srid()).await? {
+                bbox.split_antimeridian()
+            } else {
+                return Err(Exception::new_from_status(400)
+                    .detail(format!(
+                        "The lower corner of the bbox exceeds the upper corner in `{bbox_crs}`"
+                    ))
+                    .into());
             };
 
-            where_conditions.push(intersection);
+            let mut intersections = Vec::new();
+            for bbox in bboxes {
+                intersections.push(match bbox {
+                    Bbox::Bbox2D(bbox) => format!(
+                        "ST_Intersects({column}, ST_Transform(ST_MakeEnvelope({}, {}, {}, {}, {bbox_srid}), {storage_srid}))",
+                        params.bind(bbox[0])?,
+                        params.bind(bbox[1])?,
+                        params.bind(bbox[2])?,
+                        params.bind(bbox[3])?,
+                    ),
+                    Bbox::Bbox3D(bbox) => format!(
+                        // FIXME: ensure proper height/box transformation handling
+                        r#"ST_3DIntersects({column}, ST_Envelope(ST_Transform(ST_SetSRID(ST_MakeLine(ARRAY[
+                            ST_MakePoint({x1}, {y1}, {z1}),
+                            ST_MakePoint({x2}, {y1}, {z1}),
+                            ST_MakePoint({x1}, {y2}, {z1}),
+                            ST_MakePoint({x2}, {y2}, {z1}),
+                            ST_MakePoint({x1}, {y1}, {z2}),
+                            ST_MakePoint({x2}, {y1}, {z2}),
+                            ST_MakePoint({x1}, {y2}, {z2}),
+                            ST_MakePoint({x2}, {y2}, {z2})
+                        ]), {bbox_srid}), {storage_srid})))"#,
+                        x1 = params.bind(bbox[0])?,
+                        y1 = params.bind(bbox[1])?,
+                        z1 = params.bind(bbox[2])?,
+                        x2 = params.bind(bbox[3])?,
+                        y2 = params.bind(bbox[4])?,
+                        z2 = params.bind(bbox[5])?,
+                    ),
+                });
+            }
+
+            where_conditions.push(format!("({})", intersections.join(" OR ")));
         }
 
         // datetime
         if let Some(datetime) = query.datetime.as_ref() {
-            let (from, to) = match datetime {
-                Datetime::Datetime(_) => (
-                    format!("CAST('{datetime}' AS timestamptz)"),
-                    format!("CAST('{datetime}' AS timestamptz)"),
-                ),
-                Datetime::Interval { from, to } => {
-                    let from = match from {
-                        IntervalDatetime::Datetime(_) => {
-                            format!("CAST('{from}' AS timestamptz)")
-                        }
-                        IntervalDatetime::Open => "to_timestamp('-infinity')".to_owned(),
-                    };
-                    let to = match to {
-                        IntervalDatetime::Datetime(_) => {
-                            format!("CAST('{to}' AS timestamptz)")
-                        }
-                        IntervalDatetime::Open => "NOW()".to_owned(),
-                    };
-                    (from, to)
-                }
-            };
-
-            where_conditions.push(format!(
-                r#"
-                (
-                    CASE
-                        WHEN (properties->'datetime') IS NOT NULL THEN (
-                            CAST(properties->>'datetime' AS timestamptz)
-                            BETWEEN {from} AND {to}
-                        )
-                        WHEN (
-                            (properties->'datetime') IS NULL
-                            AND (properties->'start_datetime') IS NOT NULL
-                            AND (properties->'end_datetime') IS NOT NULL
-                        ) THEN (
-                            ({from}, {to}) OVERLAPS (
-                                CAST(properties->>'start_datetime' AS timestamptz),
-                                CAST(properties->>'end_datetime' AS timestamptz)
-                            )
-                        )
-                        ELSE TRUE
-                    END
-                )
-                "#
-            ));
+            where_conditions.push(datetime_predicate(datetime, &mut params)?);
         }
 
         // kv
         for (k, v) in query.additional_parameters.iter() {
+            // numbers are compared by value, a value that is no number matches none
+            let number = v
+                .parse::<f64>()
+                .is_ok_and(f64::is_finite)
+                .then_some(v.as_str());
+            let k = params.bind(k)?;
+            let number = match number {
+                Some(number) => format!(
+                    "(properties -> {k})::numeric = {}::numeric",
+                    params.bind(number)?
+                ),
+                None => "FALSE".to_owned(),
+            };
+            let v = params.bind(v)?;
             where_conditions.push(format!(
                 r#"
                 CASE
-                    WHEN properties ? '{k}' THEN (
+                    WHEN properties ? {k} THEN (
                         CASE
-                            WHEN jsonb_typeof(properties -> '{k}') = 'number'
-                            THEN RTRIM(properties ->> '{k}', '.0') = RTRIM('{v}', '.0')
-                            ELSE properties ->> '{k}' = '{v}'
+                            WHEN jsonb_typeof(properties -> {k}) = 'number'
+                            THEN {number}
+                            ELSE properties ->> {k} = {v}
                         END
-                    ) 
+                    )
                     ELSE TRUE
                 END
                 "#
             ));
         }
 
+        // filter
+        if let Some(filter) = query.parse_filter()? {
+            let filter_srid = query
+                .filter_crs
+                .as_ref()
+                .map(|crs| crs.as_srid())
+                .unwrap_or_else(|| Crs::default2d().as_srid());
+            let storage_srid = self.storage_srid(collection_id).await?;
+            where_conditions.push(
+                FilterTranslator::new(
+                    &mut params,
+                    filter_srid,
+                    storage_srid,
+                    &collection.geometry_columns,
+                )
+                .predicate(&filter)?,
+            );
+        }
+
         let conditions = where_conditions.join(" AND ");
 
         // count
-        let number_matched: (i64,) = sqlx::query_as(&format!(
-            r#"
-            SELECT count(*) FROM items."{collection_id}"
-            WHERE {conditions}
-            "#,
-        ))
-        .fetch_one(&self.pool)
-        .await?;
+        let number_matched = self
+            .number_matched(
+                &format!("SELECT 1 FROM {table} items WHERE {conditions}"),
+                &table,
+                where_conditions.len() == 1,
+                params.clone(),
+                query.number_matched.unwrap_or_default(),
+            )
+            .await?;
+
+        // keyset pagination
+        let sort_keys = sort_keys(&query.sortby, &mut params)?;
+        let keyset = match query.next.as_deref() {
+            Some(token) => Cursor::decode(token)?.predicate(&sort_keys, &mut params)?,
+            None => "TRUE".to_owned(),
+        };
+        let order_by = order_by(&sort_keys);
+        let sort_key = sort_key_values(&sort_keys);
 
-        // srid
-        let srid = query
-            .crs
-            .as_ref()
-            .map(|crs| crs.as_srid())
-            .unwrap_or_else(|| Crs::default2d().as_srid());
+        // projection
+        let rows = rows(
+            &with_geometries(
+                &properties_projection(query.properties.as_deref(), &mut params)?,
+                &collection.geometry_columns,
+                query.properties.as_deref(),
+                &crs,
+            )?,
+            &match query.skip_geometry {
+                Some(true) => "NULL::jsonb".to_owned(),
+                _ => geometry(&crs, "geom"),
+            },
+        );
 
-        // fetch
-        let features: Option<sqlx::types::Json<Vec<Feature>>> = sqlx::query_scalar(&format!(
+        let limit = params.bind(limit.map(|l| l as i64))?;
+        let offset = match query.next {
+            Some(_) => params.bind(0_i64)?,
+            None => params.bind(query.offset.unwrap_or(0) as i64)?,
+        };
+
+        let sql = format!(
             r#"
-            SELECT array_to_json(array_agg(row_to_json(t)))
+            SELECT to_jsonb(t) - 'sort_key', t.sort_key
             FROM (
-                SELECT {ROWS}
-                FROM items."{collection_id}" items JOIN meta.collections meta
+                SELECT {rows}, {sort_key} AS sort_key
+                FROM {table} items JOIN meta.collections meta
                     ON items.collection = meta.id
-                WHERE {conditions}
-                LIMIT {}
-                OFFSET {}
+                WHERE {conditions} AND {keyset}
+                ORDER BY {order_by}
+                LIMIT {limit}
+                OFFSET {offset}
             ) t
+            "#
+        );
+
+        Ok((sql, params, number_matched))
+    }
+
+    /// Whether the coordinate system of `srid` is geographic, in longitude and latitude
+    async fn is_geographic(&self, srid: i32) -> anyhow::Result<bool> {
+        Ok(sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM spatial_ref_sys WHERE srid = $1 AND proj4text LIKE '%+proj=longlat%')",
+        )
+        .bind(srid)
+        .fetch_one(self.reader())
+        .await?)
+    }
+
+    /// Named geometries of a collection
+    async fn geometry_columns(&self, collection_id: &str) -> anyhow::Result<Vec<GeometryColumn>> {
+        Ok(self
+            .read_collection(collection_id)
+            .await?
+            .map(|collection| collection.geometry_columns)
+            .unwrap_or_default())
+    }
+
+    /// Number of rows returned by `sql`, counted exactly or estimated from
+    /// the table statistics (`unfiltered` rows of `table`) or the query plan
+    pub(super) async fn number_matched(
+        &self,
+        sql: &str,
+        table: &str,
+        unfiltered: bool,
+        params: Params,
+        mode: NumberMatched,
+    ) -> anyhow::Result<Option<u64>> {
+        match mode {
+            NumberMatched::Exact => {
+                let count: i64 = sqlx::query_scalar_with(
+                    &format!("SELECT count(*) FROM ({sql}) t"),
+                    params.into_arguments(),
+                )
+                .fetch_one(self.reader())
+                .await?;
+                Ok(Some(count as u64))
+            }
+            NumberMatched::Estimate if unfiltered => {
+                let estimate: Option<f32> = sqlx::query_scalar(
+                    "SELECT reltuples FROM pg_class WHERE oid = to_regclass($1)",
+                )
+                .bind(table)
+                .fetch_optional(self.reader())
+                .await?;
+                // `reltuples` is negative for tables that were never analyzed
+                Ok(estimate.filter(|e| *e >= 0.0).map(|e| e as u64))
+            }
+            NumberMatched::Estimate => {
+                let plan: sqlx::types::Json<Value> = sqlx::query_scalar_with(
+                    &format!("EXPLAIN (FORMAT JSON) {sql}"),
+                    params.into_arguments(),
+                )
+                .fetch_one(self.reader())
+                .await?;
+                Ok(plan.0[0]["Plan"]["Plan Rows"].as_f64().map(|e| e as u64))
+            }
+            NumberMatched::Skip => Ok(None),
+        }
+    }
+
+    /// Derive property schemas from the json types of sampled features
+    async fn sample_properties(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<HashMap<String, Queryable>> {
+        let types: Vec<(String, Vec<String>)> = sqlx::query_as(&format!(
+            r#"
+            SELECT key, array_agg(DISTINCT jsonb_typeof(value))
+            FROM (
+                SELECT properties FROM {}
+                WHERE jsonb_typeof(properties) = 'object'
+                LIMIT $1
+            ) t, jsonb_each(t.properties)
+            WHERE jsonb_typeof(value) <> 'null'
+            GROUP BY key
             "#,
-            query
-                .limit
-                .map_or_else(|| String::from("NULL"), |l| l.to_string()),
-            query.offset.unwrap_or(0)
+            items_table(collection_id)?
         ))
-        .bind(srid)
-        .fetch_one(&self.pool)
+        .bind(SAMPLE_SIZE as i64)
+        .fetch_all(&self.pool)
         .await?;
 
-        let features = features.map(|f| f.0).unwrap_or_default();
-        let mut fc = FeatureCollection::new(features);
-        fc.number_matched = Some(number_matched.0 as u64);
+        let mut properties = HashMap::new();
 
-        Ok(fc)
+        for (key, types) in types {
+            let property = match types.as_slice() {
+                [t] if t == "string" && key.ends_with("datetime") => {
+                    Queryable::new(t).format("date-time")
+                }
+                [t] => Queryable::new(t),
+                _ => Queryable::default(),
+            };
+            properties.insert(key, property);
+        }
+
+        let mut id = Queryable::new("string").title("Feature identifier");
+        id.additional_properties
+            .insert("x-ogc-role".to_string(), "id".into());
+        properties.insert("id".to_string(), id);
+
+        let mut geometry = Queryable::default().format("geometry-any");
+        geometry
+            .additional_properties
+            .insert("x-ogc-role".to_string(), "primary-geometry".into());
+        properties.insert("geometry".to_string(), geometry);
+
+        Ok(properties)
     }
 }
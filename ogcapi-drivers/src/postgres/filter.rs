@@ -0,0 +1,420 @@
+use ogcapi_types::{
+    common::GeometryColumn,
+    features::cql2::{Expr, Operator, invalid},
+};
+
+use super::sql::{Params, geometry_column};
+
+/// Translates CQL2 expressions into parameterized SQL predicates on an
+/// `items` table.
+///
+/// Every literal and property name is bound as a parameter, starting after
+/// the parameters already present in `params`.
+pub(crate) struct FilterTranslator<'a> {
+    params: &'a mut Params,
+    /// SRID of geometry literals in the filter (`filter-crs`)
+    filter_srid: i32,
+    /// SRID of the geometry column
+    storage_srid: i32,
+    /// Named geometry columns of the collection
+    geometries: &'a [GeometryColumn],
+}
+
+impl<'a> FilterTranslator<'a> {
+    pub(crate) fn new(
+        params: &'a mut Params,
+        filter_srid: i32,
+        storage_srid: i32,
+        geometries: &'a [GeometryColumn],
+    ) -> Self {
+        FilterTranslator {
+            params,
+            filter_srid,
+            storage_srid,
+            geometries,
+        }
+    }
+
+    /// Translate a boolean expression into a SQL predicate
+    pub(crate) fn predicate(&mut self, expr: &Expr) -> anyhow::Result<String> {
+        match expr {
+            Expr::Bool(b) => Ok(b.to_string().to_uppercase()),
+            Expr::Op { op, args } => self.operation(*op, args),
+            expr => Err(invalid(format!("Expected a boolean expression, found `{expr:?}`")).into()),
+        }
+    }
+
+    fn operation(&mut self, op: Operator, args: &[Expr]) -> anyhow::Result<String> {
+        match (op, args) {
+            (Operator::And | Operator::Or, args) => {
+                let predicates = args
+                    .iter()
+                    .map(|arg| self.predicate(arg))
+                    .collect::<anyhow::Result<Vec<String>>>()?;
+                Ok(format!(
+                    "({})",
+                    predicates.join(&format!(" {} ", op_sql(op)))
+                ))
+            }
+            (Operator::Not, [arg]) => Ok(format!("NOT ({})", self.predicate(arg)?)),
+            (
+                Operator::Eq
+                | Operator::Neq
+                | Operator::Lt
+                | Operator::Lte
+                | Operator::Gt
+                | Operator::Gte,
+                [a, b],
+            ) => {
+                let domain = Domain::of(a, b);
+                Ok(format!(
+                    "{} {} {}",
+                    self.scalar(a, domain)?,
+                    op_sql(op),
+                    self.scalar(b, domain)?
+                ))
+            }
+            (Operator::Like, [a, b]) => Ok(format!(
+                "{} LIKE {}",
+                self.scalar(a, Domain::Text)?,
+                self.scalar(b, Domain::Text)?
+            )),
+            (Operator::Between, [a, low, high]) => {
+                let domain = Domain::of(low, high);
+                Ok(format!(
+                    "{} BETWEEN {} AND {}",
+                    self.scalar(a, domain)?,
+                    self.scalar(low, domain)?,
+                    self.scalar(high, domain)?
+                ))
+            }
+            (Operator::In, [a, Expr::Array(list)]) => {
+                let domain = list.first().map(|b| Domain::of(a, b)).unwrap_or_default();
+                let list = list
+                    .iter()
+                    .map(|item| self.scalar(item, domain))
+                    .collect::<anyhow::Result<Vec<String>>>()?;
+                Ok(format!(
+                    "{} IN ({})",
+                    self.scalar(a, domain)?,
+                    list.join(", ")
+                ))
+            }
+            (Operator::IsNull, [a]) => Ok(format!(
+                "COALESCE({}, 'null'::jsonb) = 'null'::jsonb",
+                self.scalar(a, Domain::Json)?
+            )),
+            (op, [a, b]) if op.is_spatial() => Ok(format!(
+                "{}({}, {})",
+                op_sql(op),
+                self.geometry(a)?,
+                self.geometry(b)?
+            )),
+            (op, [a, b]) if op.is_temporal() => {
+                let (a_start, a_end) = self.interval(a)?;
+                let (b_start, b_end) = self.interval(b)?;
+                let predicate = match op {
+                    Operator::TAfter => format!("{a_start} > {b_end}"),
+                    Operator::TBefore => format!("{a_end} < {b_start}"),
+                    Operator::TContains => format!("{a_start} < {b_start} AND {a_end} > {b_end}"),
+                    Operator::TDisjoint => {
+                        format!("NOT ({a_start} <= {b_end} AND {a_end} >= {b_start})")
+                    }
+                    Operator::TDuring => format!("{a_start} > {b_start} AND {a_end} < {b_end}"),
+                    Operator::TEquals => format!("{a_start} = {b_start} AND {a_end} = {b_end}"),
+                    Operator::TFinishedBy => {
+                        format!("{a_start} < {b_start} AND {a_end} = {b_end}")
+                    }
+                    Operator::TFinishes => format!("{a_start} > {b_start} AND {a_end} = {b_end}"),
+                    Operator::TIntersects => {
+                        format!("{a_start} <= {b_end} AND {a_end} >= {b_start}")
+                    }
+                    Operator::TMeets => format!("{a_end} = {b_start}"),
+                    Operator::TMetBy => format!("{a_start} = {b_end}"),
+                    Operator::TOverlappedBy => format!(
+                        "{a_start} > {b_start} AND {a_start} < {b_end} AND {a_end} > {b_end}"
+                    ),
+                    Operator::TOverlaps => format!(
+                        "{a_start} < {b_start} AND {a_end} > {b_start} AND {a_end} < {b_end}"
+                    ),
+                    Operator::TStartedBy => format!("{a_start} = {b_start} AND {a_end} > {b_end}"),
+                    Operator::TStarts => format!("{a_start} = {b_start} AND {a_end} < {b_end}"),
+                    _ => unreachable!(),
+                };
+                Ok(format!("({predicate})"))
+            }
+            (op, [a, b]) if op.is_array() => {
+                let a = self.scalar(a, Domain::Json)?;
+                let b = self.scalar(b, Domain::Json)?;
+                Ok(match op {
+                    Operator::AEquals => format!("{a} = {b}"),
+                    Operator::AContains => format!("{a} @> {b}"),
+                    Operator::AContainedBy => format!("{a} <@ {b}"),
+                    Operator::AOverlaps => format!(
+                        "EXISTS (SELECT 1 FROM jsonb_array_elements({a}) e WHERE {b} @> jsonb_build_array(e))"
+                    ),
+                    _ => unreachable!(),
+                })
+            }
+            (op, args) => {
+                Err(invalid(format!("Invalid arguments for operator `{op}`: {args:?}")).into())
+            }
+        }
+    }
+
+    /// Translate a scalar expression in the given domain
+    fn scalar(&mut self, expr: &Expr, domain: Domain) -> anyhow::Result<String> {
+        Ok(match (expr, domain) {
+            (Expr::Property(name), _) => self.property(name, domain)?,
+            (Expr::Function { name, args }, _) => match (name.as_str(), args.as_slice()) {
+                ("casei", [arg]) => format!("lower({})", self.scalar(arg, Domain::Text)?),
+                (name, _) => return Err(invalid(format!("Unsupported function `{name}`")).into()),
+            },
+            (Expr::Null, _) => "'null'::jsonb".to_string(),
+            (Expr::Bool(b), Domain::Json) => {
+                format!("to_jsonb({}::boolean)", self.params.bind(*b)?)
+            }
+            (Expr::Number(n), Domain::Json) => {
+                format!("to_jsonb({}::float8)", self.params.bind(*n)?)
+            }
+            (Expr::String(s), Domain::Json) => format!("to_jsonb({}::text)", self.params.bind(s)?),
+            (Expr::String(s), Domain::Text) => format!("{}::text", self.params.bind(s)?),
+            (Expr::Array(_), Domain::Json) => {
+                let value = self.json_literal(expr)?;
+                format!("{}::jsonb", self.params.bind(value.to_string())?)
+            }
+            (Expr::Date(_) | Expr::Timestamp(_), _) => self.instant(expr)?,
+            (expr, domain) => {
+                return Err(invalid(format!("Unable to use `{expr:?}` as {domain:?}")).into());
+            }
+        })
+    }
+
+    fn property(&mut self, name: &str, domain: Domain) -> anyhow::Result<String> {
+        Ok(match (name, domain) {
+            ("id", Domain::Json) => "to_jsonb(items.id)".to_string(),
+            ("id", _) => "items.id".to_string(),
+            (_, Domain::Json) => format!("(properties -> {}::text)", self.params.bind(name)?),
+            (_, Domain::Text) => format!("(properties ->> {}::text)", self.params.bind(name)?),
+            (_, Domain::Instant) => format!(
+                "CAST(properties ->> {}::text AS timestamptz)",
+                self.params.bind(name)?
+            ),
+        })
+    }
+
+    fn instant(&mut self, expr: &Expr) -> anyhow::Result<String> {
+        match expr {
+            Expr::Date(d) => Ok(format!(
+                "{}::timestamptz",
+                self.params.bind(d.format("%Y-%m-%d").to_string())?
+            )),
+            Expr::Timestamp(t) => Ok(format!(
+                "{}::timestamptz",
+                self.params.bind(t.to_rfc3339())?
+            )),
+            Expr::Property(name) => self.property(name, Domain::Instant),
+            expr => Err(invalid(format!("Expected an instant, found `{expr:?}`")).into()),
+        }
+    }
+
+    /// Translate a temporal expression into its start and end
+    fn interval(&mut self, expr: &Expr) -> anyhow::Result<(String, String)> {
+        match expr {
+            Expr::Interval(start, end) => {
+                let start = match start.as_ref() {
+                    Expr::String(s) if s == ".." => "'-infinity'::timestamptz".to_string(),
+                    start => self.instant(start)?,
+                };
+                let end = match end.as_ref() {
+                    Expr::String(s) if s == ".." => "'infinity'::timestamptz".to_string(),
+                    end => self.instant(end)?,
+                };
+                Ok((start, end))
+            }
+            expr => {
+                let instant = self.instant(expr)?;
+                Ok((instant.clone(), instant))
+            }
+        }
+    }
+
+    fn geometry(&mut self, expr: &Expr) -> anyhow::Result<String> {
+        let (filter_srid, storage_srid) = (self.filter_srid, self.storage_srid);
+        match expr {
+            // the primary geometry or a named geometry in its own column
+            Expr::Property(name) if name == "geometry" => Ok("geom".to_string()),
+            Expr::Property(name) if self.geometries.iter().any(|column| &column.name == name) => {
+                Ok(format!("items.{}", geometry_column(name)?))
+            }
+            Expr::Property(name) => {
+                Err(invalid(format!("Property `{name}` is not a geometry")).into())
+            }
+            Expr::Geometry(geometry) => Ok(format!(
+                "ST_Transform(ST_SetSRID(ST_GeomFromGeoJSON({}::text), {filter_srid}), {storage_srid})",
+                self.params.bind(serde_json::to_string(geometry)?)?
+            )),
+            Expr::Bbox(bbox) => {
+                let (x1, y1, x2, y2) = match bbox.as_slice() {
+                    [x1, y1, x2, y2] | [x1, y1, _, x2, y2, _] => (*x1, *y1, *x2, *y2),
+                    _ => return Err(invalid(format!("Invalid bbox `{bbox:?}`")).into()),
+                };
+                Ok(format!(
+                    "ST_Transform(ST_MakeEnvelope({}, {}, {}, {}, {filter_srid}), {storage_srid})",
+                    self.params.bind(x1)?,
+                    self.params.bind(y1)?,
+                    self.params.bind(x2)?,
+                    self.params.bind(y2)?
+                ))
+            }
+            expr => Err(invalid(format!("Expected a geometry, found `{expr:?}`")).into()),
+        }
+    }
+
+    fn json_literal(&self, expr: &Expr) -> anyhow::Result<serde_json::Value> {
+        Ok(match expr {
+            Expr::Null => serde_json::Value::Null,
+            Expr::Bool(b) => (*b).into(),
+            Expr::Number(n) => (*n).into(),
+            Expr::String(s) => s.as_str().into(),
+            Expr::Array(items) => items
+                .iter()
+                .map(|item| self.json_literal(item))
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into(),
+            expr => return Err(invalid(format!("Unsupported array item `{expr:?}`")).into()),
+        })
+    }
+}
+
+/// Value domain in which operands are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Domain {
+    /// Compare as `jsonb`, which orders numbers numerically and strings lexically
+    #[default]
+    Json,
+    Text,
+    Instant,
+}
+
+impl Domain {
+    fn of(a: &Expr, b: &Expr) -> Self {
+        match (a, b) {
+            (Expr::Date(_) | Expr::Timestamp(_), _) | (_, Expr::Date(_) | Expr::Timestamp(_)) => {
+                Domain::Instant
+            }
+            (Expr::Function { .. }, _) | (_, Expr::Function { .. }) => Domain::Text,
+            _ => Domain::Json,
+        }
+    }
+}
+
+fn op_sql(op: Operator) -> &'static str {
+    match op {
+        Operator::And => "AND",
+        Operator::Or => "OR",
+        Operator::Eq => "=",
+        Operator::Neq => "<>",
+        Operator::Lt => "<",
+        Operator::Lte => "<=",
+        Operator::Gt => ">",
+        Operator::Gte => ">=",
+        Operator::SContains => "ST_Contains",
+        Operator::SCrosses => "ST_Crosses",
+        Operator::SDisjoint => "ST_Disjoint",
+        Operator::SEquals => "ST_Equals",
+        Operator::SIntersects => "ST_Intersects",
+        Operator::SOverlaps => "ST_Overlaps",
+        Operator::STouches => "ST_Touches",
+        Operator::SWithin => "ST_Within",
+        op => unreachable!("no direct SQL equivalent for `{op}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Arguments;
+
+    use ogcapi_types::common::Exception;
+
+    use super::*;
+
+    fn try_translate(filter: &str) -> anyhow::Result<(String, usize)> {
+        let expr: Expr = filter.parse().unwrap();
+        let mut params = Params::default();
+        let geometries = [GeometryColumn {
+            name: "entrance".to_string(),
+            title: None,
+            place: false,
+        }];
+        let sql = FilterTranslator::new(&mut params, 4326, 2056, &geometries).predicate(&expr)?;
+        Ok((sql, params.into_arguments().len()))
+    }
+
+    fn translate(filter: &str) -> (String, usize) {
+        try_translate(filter).unwrap()
+    }
+
+    #[test]
+    fn comparison() {
+        let (sql, n) = translate("name = 'Bern' AND pop >= 100000");
+        assert_eq!(
+            sql,
+            "((properties -> $1::text) = to_jsonb($2::text) AND (properties -> $3::text) >= to_jsonb($4::float8))"
+        );
+        assert_eq!(n, 4);
+    }
+
+    #[test]
+    fn literals_are_parameterized() {
+        let (sql, _) = translate("name = 'x''); DROP TABLE meta.collections; --'");
+        assert!(!sql.contains("DROP"));
+
+        let (sql, _) = translate("\"x'); DROP TABLE meta.collections; --\" = 1");
+        assert!(!sql.contains("DROP"));
+    }
+
+    #[test]
+    fn spatial() {
+        let (sql, n) = translate("S_INTERSECTS(geometry, BBOX(6, 45, 9, 49))");
+        assert_eq!(
+            sql,
+            "ST_Intersects(geom, ST_Transform(ST_MakeEnvelope($1, $2, $3, $4, 4326), 2056))"
+        );
+        assert_eq!(n, 4);
+
+        let (sql, _) = translate("S_INTERSECTS(entrance, BBOX(6, 45, 9, 49))");
+        assert!(sql.starts_with(r#"ST_Intersects(items."geom_entrance", "#));
+    }
+
+    #[test]
+    fn invalid_filters() {
+        for filter in [
+            "upper(name) = 'BERN'",
+            "S_INTERSECTS(name, BBOX(6, 45, 9, 49))",
+            "S_INTERSECTS(geometry, 'Bern')",
+            "T_AFTER(datetime, 3)",
+        ] {
+            let err = try_translate(filter).unwrap_err();
+            let exception = err.downcast::<Exception>().unwrap();
+            assert_eq!(exception.status, Some(400), "{filter}");
+        }
+    }
+
+    #[test]
+    fn temporal() {
+        let (sql, n) = translate("T_INTERSECTS(datetime, INTERVAL('2020-01-01', '..'))");
+        assert_eq!(
+            sql,
+            "(CAST(properties ->> $1::text AS timestamptz) <= 'infinity'::timestamptz \
+            AND CAST(properties ->> $1::text AS timestamptz) >= $2::timestamptz)"
+        );
+        assert_eq!(n, 2);
+    }
+
+    #[test]
+    fn array() {
+        let (sql, _) = translate("A_CONTAINS(tags, ('a', 'b'))");
+        assert_eq!(sql, "(properties -> $1::text) @> $2::jsonb");
+    }
+}
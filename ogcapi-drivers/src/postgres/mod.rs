@@ -1,11 +1,17 @@
+#[cfg(feature = "features")]
+mod changes;
 #[cfg(feature = "common")]
 mod collection;
 #[cfg(feature = "edr")]
 mod edr;
 #[cfg(feature = "features")]
 mod feature;
+#[cfg(feature = "features")]
+mod filter;
 #[cfg(feature = "processes")]
 mod job;
+#[cfg(feature = "common")]
+mod sql;
 #[cfg(feature = "stac")]
 mod stac;
 #[cfg(feature = "styles")]
@@ -13,6 +19,8 @@ mod style;
 #[cfg(feature = "tiles")]
 mod tile;
 
+use std::time::Duration;
+
 use sqlx::{
     Postgres,
     migrate::MigrateDatabase,
@@ -20,45 +28,138 @@ use sqlx::{
 };
 use url::Url;
 
+#[cfg(feature = "common")]
+use crate::cache::CollectionCache;
+
+/// Connection pool configuration
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum number of connections
+    pub max_connections: u32,
+    /// Number of connections kept open
+    pub min_connections: u32,
+    /// Time to wait for a connection
+    pub acquire_timeout: Duration,
+    /// Time after which idle connections are closed
+    pub idle_timeout: Option<Duration>,
+    /// Time after which statements are aborted (`statement_timeout`)
+    pub statement_timeout: Option<Duration>,
+    /// Name of the application reported to the server (`application_name`)
+    pub application_name: Option<String>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_connections: 8,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            statement_timeout: None,
+            application_name: None,
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Connect a pool to a database
+    pub async fn connect(&self, options: PgConnectOptions) -> Result<PgPool, sqlx::Error> {
+        let mut options = options;
+        if let Some(name) = self.application_name.as_deref() {
+            options = options.application_name(name);
+        }
+        if let Some(timeout) = self.statement_timeout {
+            options = options.options([("statement_timeout", timeout.as_millis().to_string())]);
+        }
+
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .connect_with(options)
+            .await
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Db {
     pub pool: PgPool,
+    /// Pool of a read replica for listing, search, tile and EDR queries
+    pub replica: Option<PgPool>,
+    /// Cache of collection documents and storage srids
+    #[cfg(feature = "common")]
+    pub(crate) cache: CollectionCache,
+}
+
+impl From<PgPool> for Db {
+    fn from(pool: PgPool) -> Self {
+        Db {
+            pool,
+            replica: None,
+            #[cfg(feature = "common")]
+            cache: Default::default(),
+        }
+    }
 }
 
 impl Db {
     /// Create driver from env `DATABASE_URL` or else `PGUSER` and friends
     pub async fn new() -> Result<Self, sqlx::Error> {
-        let pool = if let Ok(url) = std::env::var("DATABASE_URL") {
-            PgPoolOptions::new()
-                .max_connections(8)
-                .connect(&url)
-                .await?
-        } else {
-            PgPoolOptions::new()
-                .max_connections(8)
-                .connect_with(PgConnectOptions::new())
-                .await?
+        Db::new_with(&PoolConfig::default()).await
+    }
+
+    /// Create driver from env `DATABASE_URL` or else `PGUSER` and friends,
+    /// with a pool configuration
+    pub async fn new_with(config: &PoolConfig) -> Result<Self, sqlx::Error> {
+        let options = match std::env::var("DATABASE_URL") {
+            Ok(url) => url.parse()?,
+            Err(_) => PgConnectOptions::new(),
         };
 
-        Ok(Db { pool })
+        Ok(Db::from(config.connect(options).await?))
     }
 
     /// Setup database driver from url
     pub async fn setup(url: &Url) -> Result<Self, sqlx::Error> {
+        Db::setup_with(url, &PoolConfig::default()).await
+    }
+
+    /// Setup database driver from url with a pool configuration
+    pub async fn setup_with(url: &Url, config: &PoolConfig) -> Result<Self, sqlx::Error> {
         // Create database if not exists
         if !Postgres::database_exists(url.as_str()).await? {
             Postgres::create_database(url.as_str()).await?
         }
 
         // Create pool
-        let pool = PgPoolOptions::new()
-            .max_connections(8)
-            .connect(url.as_str())
-            .await?;
+        let pool = config.connect(url.as_str().parse()?).await?;
 
         // Run embedded migrations
         sqlx::migrate!().run(&pool).await?;
 
-        Ok(Db { pool })
+        Ok(Db::from(pool))
+    }
+
+    /// Send listing, search, tile and EDR queries to a read replica
+    ///
+    /// Transactions, single reads and job updates stay on the primary.
+    pub async fn replica(mut self, url: &Url, config: &PoolConfig) -> Result<Self, sqlx::Error> {
+        self.replica = Some(config.connect(url.as_str().parse()?).await?);
+        Ok(self)
+    }
+
+    /// Pool for read-only queries, the replica if there is one
+    #[cfg(feature = "common")]
+    pub(crate) fn reader(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+
+    /// Cache collection metadata for a time to live, a zero duration disables
+    /// caching
+    #[cfg(feature = "common")]
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = CollectionCache::new(ttl);
+        self
     }
 }
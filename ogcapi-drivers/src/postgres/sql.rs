@@ -0,0 +1,461 @@
+//! Building blocks for parameterized SQL statements
+//!
+//! User supplied values are never interpolated into statements. Values are
+//! bound as parameters with [`Params`], identifiers such as collection ids
+//! are quoted with [`quote_ident`].
+#[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+use anyhow::anyhow;
+use anyhow::bail;
+#[cfg(feature = "features")]
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+#[cfg(feature = "features")]
+use futures::{
+    SinkExt, StreamExt,
+    channel::mpsc,
+    future,
+    stream::{self, BoxStream},
+};
+#[cfg(feature = "features")]
+use serde_json::Value;
+#[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+use sqlx::{Arguments, Encode, Postgres, Type, postgres::PgArguments};
+#[cfg(feature = "features")]
+use sqlx::{PgPool, types::Json};
+
+#[cfg(feature = "features")]
+use ogcapi_types::common::Exception;
+
+use crate::precondition_failed;
+
+#[cfg(feature = "features")]
+use ogcapi_types::{
+    common::{Datetime, IntervalDatetime},
+    features::{Direction, Feature, SortBy},
+};
+
+/// Bind parameters of a SQL statement
+#[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+#[derive(Default, Clone)]
+pub(crate) struct Params {
+    args: PgArguments,
+}
+
+#[cfg(any(feature = "edr", feature = "features", feature = "tiles"))]
+impl Params {
+    /// Bind a value and return its placeholder, e.g. `$3`
+    pub(crate) fn bind<'q, T>(&mut self, value: T) -> anyhow::Result<String>
+    where
+        T: 'q + Encode<'q, Postgres> + Type<Postgres>,
+    {
+        self.args.add(value).map_err(|e| anyhow!(e))?;
+        Ok(format!("${}", self.args.len()))
+    }
+
+    pub(crate) fn into_arguments(self) -> PgArguments {
+        self.args
+    }
+}
+
+/// Quote an identifier, doubling embedded double quotes
+pub(crate) fn quote_ident(ident: &str) -> anyhow::Result<String> {
+    if ident.is_empty() || ident.contains('\0') {
+        bail!("Invalid identifier `{}`", ident.escape_debug());
+    }
+    Ok(format!("\"{}\"", ident.replace('"', "\"\"")))
+}
+
+/// Quote a string literal for statements that do not accept parameters (DDL)
+pub(crate) fn quote_literal(value: &str) -> anyhow::Result<String> {
+    if value.contains('\0') {
+        bail!("Invalid literal `{}`", value.escape_debug());
+    }
+    Ok(format!("'{}'", value.replace('\'', "''")))
+}
+
+/// Qualified name of the items table of a collection
+pub(crate) fn items_table(collection_id: &str) -> anyhow::Result<String> {
+    Ok(format!("items.{}", quote_ident(collection_id)?))
+}
+
+/// Qualified name of the history table of a collection
+pub(crate) fn history_table(collection_id: &str) -> anyhow::Result<String> {
+    Ok(format!("history.{}", quote_ident(collection_id)?))
+}
+
+/// Column of a named geometry, generated from the feature property `name`
+pub(crate) fn geometry_column(name: &str) -> anyhow::Result<String> {
+    quote_ident(&geometry_column_name(name))
+}
+
+/// Unquoted name of the column of a named geometry, truncated to the maximum
+/// identifier length as Postgres does
+pub(crate) fn geometry_column_name(name: &str) -> String {
+    // NAMEDATALEN - 1 bytes
+    const MAX_LEN: usize = 63;
+
+    let mut column = format!("geom_{name}");
+    if column.len() > MAX_LEN {
+        let end = (0..=MAX_LEN)
+            .rev()
+            .find(|i| column.is_char_boundary(*i))
+            .unwrap_or_default();
+        column.truncate(end);
+    }
+    column
+}
+
+/// Row version required by the entity tag of a conditional write (`If-Match`)
+///
+/// Entity tags are the decimal `version` of the row.
+pub(crate) fn row_version(if_match: Option<&str>) -> anyhow::Result<Option<i64>> {
+    if_match
+        .map(|etag| etag.parse().map_err(|_| precondition_failed()))
+        .transpose()
+}
+
+/// Sort key of a statement
+#[cfg(feature = "features")]
+pub(crate) struct SortKey {
+    /// SQL expression of type `text` or `jsonb`
+    pub(crate) expr: String,
+    pub(crate) jsonb: bool,
+    pub(crate) descending: bool,
+}
+
+#[cfg(feature = "features")]
+impl SortKey {
+    /// Sort by feature id, which is unique and therefore the final sort key
+    pub(crate) fn id() -> Self {
+        SortKey {
+            expr: "items.id".to_string(),
+            jsonb: false,
+            descending: false,
+        }
+    }
+}
+
+/// Expression selecting the given members of the `properties` column, or all
+/// properties if no selection is given
+#[cfg(feature = "features")]
+pub(crate) fn properties_projection(
+    properties: Option<&[String]>,
+    params: &mut Params,
+) -> anyhow::Result<String> {
+    Ok(match properties {
+        Some(properties) => format!(
+            r#"
+            (
+                SELECT COALESCE(jsonb_object_agg(key, value), '{{}}'::jsonb)
+                FROM jsonb_each(properties)
+                WHERE key = ANY({})
+            )
+            "#,
+            params.bind(properties.to_vec())?
+        ),
+        None => "properties".to_owned(),
+    })
+}
+
+/// Sort keys for the `sortby` parameter, followed by the feature id to
+/// obtain a total order
+#[cfg(feature = "features")]
+pub(crate) fn sort_keys(sortby: &[SortBy], params: &mut Params) -> anyhow::Result<Vec<SortKey>> {
+    let mut keys = Vec::new();
+    for sortby in sortby {
+        let (expr, jsonb) = match sortby.property() {
+            "id" => ("items.id".to_string(), false),
+            "collection" => ("items.collection".to_string(), false),
+            "geometry" => {
+                return Err(Exception::new_from_status(400)
+                    .detail("Property geometry is not sortable!")
+                    .into());
+            }
+            // missing properties sort like JSON `null`
+            property => (
+                format!(
+                    "COALESCE(items.properties -> {}, 'null'::jsonb)",
+                    params.bind(property.to_owned())?
+                ),
+                true,
+            ),
+        };
+        keys.push(SortKey {
+            expr,
+            jsonb,
+            descending: sortby.direction == Direction::Desc,
+        });
+    }
+
+    if !keys.iter().any(|key| key.expr == "items.id") {
+        keys.push(SortKey::id());
+    }
+
+    Ok(keys)
+}
+
+/// `ORDER BY` expressions for the sort keys
+#[cfg(feature = "features")]
+pub(crate) fn order_by(keys: &[SortKey]) -> String {
+    keys.iter()
+        .map(|key| match key.descending {
+            true => format!("{} DESC", key.expr),
+            false => format!("{} ASC", key.expr),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Expression collecting the sort key values of a row into a `jsonb` array
+#[cfg(feature = "features")]
+pub(crate) fn sort_key_values(keys: &[SortKey]) -> String {
+    let exprs: Vec<&str> = keys.iter().map(|key| key.expr.as_str()).collect();
+    format!("jsonb_build_array({})", exprs.join(", "))
+}
+
+/// Opaque pagination token holding the sort key values of the last row of a page
+#[cfg(feature = "features")]
+#[derive(Debug, PartialEq)]
+pub(crate) struct Cursor(pub(crate) Vec<Value>);
+
+#[cfg(feature = "features")]
+impl Cursor {
+    pub(crate) fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Value::from(self.0.to_owned()).to_string())
+    }
+
+    pub(crate) fn decode(token: &str) -> anyhow::Result<Self> {
+        URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .map(Cursor)
+            .ok_or_else(|| {
+                Exception::new_from_status(400)
+                    .detail("Invalid `next` token")
+                    .into()
+            })
+    }
+
+    /// Predicate selecting the rows following the cursor for the given sort keys
+    pub(crate) fn predicate(
+        &self,
+        keys: &[SortKey],
+        params: &mut Params,
+    ) -> anyhow::Result<String> {
+        if keys.len() != self.0.len() {
+            return Err(Exception::new_from_status(400)
+                .detail("The `next` token does not match the sort order")
+                .into());
+        }
+
+        let mut values = Vec::new();
+        for (key, value) in keys.iter().zip(self.0.iter()) {
+            values.push(match (key.jsonb, value) {
+                (true, value) => format!("{}::jsonb", params.bind(value.to_string())?),
+                (false, Value::String(s)) => params.bind(s)?,
+                (false, value) => params.bind(value.to_string())?,
+            });
+        }
+
+        // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ...
+        let alternatives: Vec<String> = (0..keys.len())
+            .map(|i| {
+                let mut conjunction: Vec<String> = (0..i)
+                    .map(|j| format!("{} = {}", keys[j].expr, values[j]))
+                    .collect();
+                let op = if keys[i].descending { "<" } else { ">" };
+                conjunction.push(format!("{} {op} {}", keys[i].expr, values[i]));
+                format!("({})", conjunction.join(" AND "))
+            })
+            .collect();
+
+        Ok(format!("({})", alternatives.join(" OR ")))
+    }
+}
+
+/// Predicate matching features by their `datetime` or their
+/// `start_datetime` and `end_datetime` properties
+#[cfg(feature = "features")]
+pub(crate) fn datetime_predicate(
+    datetime: &Datetime,
+    params: &mut Params,
+) -> anyhow::Result<String> {
+    let (from, to) = match datetime {
+        Datetime::Datetime(_) => {
+            let datetime = format!("{}::timestamptz", params.bind(datetime.to_string())?);
+            (datetime.clone(), datetime)
+        }
+        Datetime::Interval { from, to } => {
+            let from = match from {
+                IntervalDatetime::Datetime(_) => {
+                    format!("{}::timestamptz", params.bind(from.to_string())?)
+                }
+                IntervalDatetime::Open => "to_timestamp('-infinity')".to_owned(),
+            };
+            let to = match to {
+                IntervalDatetime::Datetime(_) => {
+                    format!("{}::timestamptz", params.bind(to.to_string())?)
+                }
+                IntervalDatetime::Open => "NOW()".to_owned(),
+            };
+            (from, to)
+        }
+    };
+
+    Ok(format!(
+        r#"
+        (
+            CASE
+                WHEN (properties->'datetime') IS NOT NULL THEN (
+                    CAST(properties->>'datetime' AS timestamptz)
+                    BETWEEN {from} AND {to}
+                )
+                WHEN (
+                    (properties->'datetime') IS NULL
+                    AND (properties->'start_datetime') IS NOT NULL
+                    AND (properties->'end_datetime') IS NOT NULL
+                ) THEN (
+                    ({from}, {to}) OVERLAPS (
+                        CAST(properties->>'start_datetime' AS timestamptz),
+                        CAST(properties->>'end_datetime' AS timestamptz)
+                    )
+                )
+                ELSE TRUE
+            END
+        )
+        "#
+    ))
+}
+
+/// Number of features buffered ahead of the consumer of a feature stream
+#[cfg(feature = "features")]
+const CAPACITY: usize = 64;
+
+/// Stream of the features in the first column of the rows of a statement,
+/// fetched from `pool` as the stream is consumed
+///
+/// The statement is built by the future feeding the stream, which owns it
+/// while the rows are fetched.
+#[cfg(feature = "features")]
+pub(crate) fn stream_features<'a, F>(
+    pool: &'a PgPool,
+    statement: F,
+) -> BoxStream<'a, anyhow::Result<Feature>>
+where
+    F: Future<Output = anyhow::Result<(String, Params)>> + Send + 'a,
+{
+    let (mut tx, rx) = mpsc::channel(CAPACITY);
+
+    let feed = async move {
+        let (sql, params) = match statement.await {
+            Ok(statement) => statement,
+            Err(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        let mut rows =
+            sqlx::query_scalar_with::<_, Json<Feature>, _>(&sql, params.into_arguments())
+                .fetch(pool);
+        while let Some(row) = rows.next().await {
+            let feature = row.map(|feature| feature.0).map_err(anyhow::Error::from);
+            let failed = feature.is_err();
+            // stop once the stream is dropped or the statement failed
+            if tx.send(feature).await.is_err() || failed {
+                break;
+            }
+        }
+    };
+
+    // the feed is polled along with the receiver, it never yields itself
+    stream::select(rx, stream::once(feed).filter_map(|()| future::ready(None))).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote_ident("lakes").unwrap(), r#""lakes""#);
+        assert_eq!(
+            items_table(r#"x"; DROP TABLE meta.collections; --"#).unwrap(),
+            r#"items."x""; DROP TABLE meta.collections; --""#
+        );
+        assert_eq!(
+            quote_literal("x'); DROP TABLE meta.collections; --").unwrap(),
+            "'x''); DROP TABLE meta.collections; --'"
+        );
+        assert!(quote_ident("").is_err());
+        assert!(quote_ident("a\0b").is_err());
+    }
+
+    #[test]
+    fn geometry_column_names() {
+        assert_eq!(geometry_column("entrance").unwrap(), r#""geom_entrance""#);
+
+        let name = "ä".repeat(40);
+        let column = geometry_column_name(&name);
+        assert_eq!(column.len(), 63);
+        assert_eq!(column, format!("geom_{}", "ä".repeat(29)));
+    }
+
+    #[test]
+    fn cursor() {
+        let cursor = Cursor(vec!["a".into(), 1.into()]);
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("not a token").is_err());
+
+        let keys = [
+            SortKey {
+                expr: "(properties -> 'name')".to_string(),
+                jsonb: true,
+                descending: true,
+            },
+            SortKey::id(),
+        ];
+        let mut params = Params::default();
+        let predicate = cursor.predicate(&keys, &mut params).unwrap();
+        assert_eq!(
+            predicate,
+            "(((properties -> 'name') < $1::jsonb) OR ((properties -> 'name') = $1::jsonb AND items.id > $2))"
+        );
+        assert!(cursor.predicate(&keys[1..], &mut params).is_err());
+    }
+
+    #[cfg(feature = "features")]
+    #[test]
+    fn sorting() {
+        let mut params = Params::default();
+        let keys = sort_keys(
+            &[SortBy::desc("properties.datetime"), SortBy::asc("name")],
+            &mut params,
+        )
+        .unwrap();
+        assert_eq!(
+            order_by(&keys),
+            "COALESCE(items.properties -> $1, 'null'::jsonb) DESC, \
+            COALESCE(items.properties -> $2, 'null'::jsonb) ASC, items.id ASC"
+        );
+
+        let keys = sort_keys(&[SortBy::desc("id")], &mut params).unwrap();
+        assert_eq!(order_by(&keys), "items.id DESC");
+
+        assert!(sort_keys(&[SortBy::asc("geometry")], &mut params).is_err());
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(row_version(None).unwrap(), None);
+        assert_eq!(row_version(Some("3")).unwrap(), Some(3));
+        assert!(row_version(Some("abc")).is_err());
+    }
+
+    #[test]
+    fn placeholders() {
+        let mut params = Params::default();
+        assert_eq!(params.bind(4326).unwrap(), "$1");
+        assert_eq!(params.bind("name").unwrap(), "$2");
+        assert_eq!(params.into_arguments().len(), 2);
+    }
+}
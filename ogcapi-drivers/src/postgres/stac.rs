@@ -1,168 +1,172 @@
+use futures::stream::BoxStream;
+use sqlx::PgConnection;
+
 use ogcapi_types::{
-    common::{Bbox, Datetime, IntervalDatetime},
     features::{Feature, FeatureCollection},
     stac::SearchParams,
 };
 
 use crate::StacSearch;
 
-use super::Db;
+use super::{
+    Db,
+    sql::{
+        Params, datetime_predicate, items_table, order_by, properties_projection, sort_keys,
+        stream_features,
+    },
+};
 
 #[async_trait::async_trait]
 impl StacSearch for Db {
     async fn search(&self, query: &SearchParams) -> anyhow::Result<FeatureCollection> {
-        let mut tx = self.pool.begin().await?;
-
-        // WITH
-        let mut collection_ids: Vec<String> = sqlx::query_scalar(
-            r#"
-            SELECT id FROM meta.collections 
-            WHERE collection ->> 'type' = 'Collection'
-            "#,
-        )
-        .fetch_all(&mut *tx)
-        .await?;
-
-        if !query.collections.is_empty() {
-            collection_ids.retain(|id| query.collections.contains(id));
-        }
-
-        let union_all_items = collection_ids
-            .iter()
-            .map(|collection_id| {
-                format!(
-                    r#"
-                    SELECT * FROM items."{collection_id}"
-                    "#
-                )
-            })
-            .collect::<Vec<String>>()
-            .join(" UNION ALL ");
-
-        // WHERE
-        let mut where_conditions = vec!["TRUE".to_string()];
-
-        // bbox
-        if let Some(bbox) = query.bbox.as_ref() {
-            let envelope = match bbox {
-                Bbox::Bbox2D(bbox) => format!(
-                    "ST_MakeEnvelope({}, {}, {}, {}, 4326)",
-                    bbox[0], bbox[1], bbox[2], bbox[3]
-                ),
-                Bbox::Bbox3D(bbox) => format!(
-                    "ST_MakeEnvelope({}, {}, {}, {}, 4326)",
-                    bbox[0], bbox[1], bbox[3], bbox[4]
-                ),
-            };
-            where_conditions.push(format!("geom && {envelope}"));
-        }
+        let mut tx = self.reader().begin().await?;
 
-        // datetime
-        if let Some(datetime) = query.datetime.as_ref() {
-            let (from, to) = match datetime {
-                Datetime::Datetime(_) => (
-                    format!("CAST('{datetime}' AS timestamptz)"),
-                    format!("CAST('{datetime}' AS timestamptz)"),
-                ),
-                Datetime::Interval { from, to } => {
-                    let from = match from {
-                        IntervalDatetime::Datetime(_) => {
-                            format!("CAST('{from}' AS timestamptz)")
-                        }
-                        IntervalDatetime::Open => "to_timestamp('-infinity')".to_owned(),
-                    };
-                    let to = match to {
-                        IntervalDatetime::Datetime(_) => {
-                            format!("CAST('{to}' AS timestamptz)")
-                        }
-                        IntervalDatetime::Open => "NOW()".to_owned(),
-                    };
-                    (from, to)
-                }
-            };
-
-            where_conditions.push(format!(
-                r#"
-                (
-                    CASE
-                        WHEN (properties->'datetime') IS NOT NULL THEN (
-                            CAST(properties->>'datetime' AS timestamptz)
-                            BETWEEN {from} AND {to}
-                        )
-                        WHEN (
-                            (properties->'datetime') IS NULL
-                            AND (properties->'start_datetime') IS NOT NULL
-                            AND (properties->'end_datetime') IS NOT NULL
-                        ) THEN (
-                            ({from}, {to}) OVERLAPS (
-                                CAST(properties->>'start_datetime' AS timestamptz),
-                                CAST(properties->>'end_datetime' AS timestamptz)
-                            )
-                        )
-                        ELSE TRUE
-                    END
-                )
-                "#
-            ));
-        }
-
-        // ids
-        if !query.ids.is_empty() {
-            where_conditions.push(format!("id IN ('{}')", query.ids.join("','")))
-        }
-
-        // intersects
-        if let Some(intersects) = query.intersects.as_ref() {
-            where_conditions.push(format!("geom && ST_GeomFromGeoJSON('{intersects}')"));
-        }
-
-        let conditions = where_conditions.join(" AND ");
+        let ((sql, params), (count, count_params)) = search_statement(&mut tx, query).await?;
 
         // COUNT
-        let number_matched: (i64,) = sqlx::query_as(&format!(
-            r#"
-            WITH items AS ({union_all_items})
-            SELECT count(*) FROM items
-            WHERE {conditions}
-            "#,
-        ))
-        .fetch_one(&mut *tx)
-        .await?;
+        let number_matched: i64 = sqlx::query_scalar_with(&count, count_params.into_arguments())
+            .fetch_one(&mut *tx)
+            .await?;
 
         // FETCH
-        let features: Option<sqlx::types::Json<Vec<Feature>>> = sqlx::query_scalar(&format!(
-            r#"
-            WITH items AS ({union_all_items})
-            SELECT array_to_json(array_agg(row_to_json(t)))
-            FROM (
-                SELECT
-                    id,
-                    collection,
-                    properties,
-                    ST_AsGeoJSON(ST_Transform(geom, 4326))::jsonb as geometry,
-                    links,
-                    assets,
-                    bbox
-                FROM items
-                WHERE {conditions}
-                LIMIT {}
-                OFFSET {}
-            ) t
-            "#,
-            query
-                .limit
-                .map_or_else(|| String::from("NULL"), |l| l.to_string()),
-            query.offset.unwrap_or(0)
-        ))
-        .fetch_one(&mut *tx)
-        .await?;
+        let features: Vec<sqlx::types::Json<Feature>> =
+            sqlx::query_scalar_with(&sql, params.into_arguments())
+                .fetch_all(&mut *tx)
+                .await?;
 
         tx.commit().await?;
 
-        let features = features.map(|f| f.0).unwrap_or_default();
+        let features = features.into_iter().map(|f| f.0).collect();
         let mut fc = FeatureCollection::new(features);
-        fc.number_matched = Some(number_matched.0 as u64);
+        fc.number_matched = Some(number_matched as u64);
 
         Ok(fc)
     }
+
+    fn stream_search<'a>(
+        &'a self,
+        query: &'a SearchParams,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        stream_features(self.reader(), async move {
+            let mut conn = self.reader().acquire().await?;
+            let (rows, _) = search_statement(&mut conn, query).await?;
+            Ok(rows)
+        })
+    }
+}
+
+/// Statement selecting the features of a search as JSON rows, and the
+/// statement counting them, with their parameters
+async fn search_statement(
+    conn: &mut PgConnection,
+    query: &SearchParams,
+) -> anyhow::Result<((String, Params), (String, Params))> {
+    // WITH
+    let mut collection_ids: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM meta.collections 
+        WHERE collection ->> 'type' = 'Collection'
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if !query.collections.is_empty() {
+        collection_ids.retain(|id| query.collections.contains(id));
+    }
+
+    let union_all_items = collection_ids
+        .iter()
+        .map(|collection_id| Ok(format!("SELECT * FROM {}", items_table(collection_id)?)))
+        .collect::<anyhow::Result<Vec<String>>>()?
+        .join(" UNION ALL ");
+
+    // WHERE
+    let mut params = Params::default();
+    let mut where_conditions = vec!["TRUE".to_string()];
+
+    // bbox
+    if let Some(bbox) = query.bbox.as_ref() {
+        // boxes spanning the antimeridian are split in two
+        let mut envelopes = Vec::new();
+        for bbox in bbox.normalize().split_antimeridian() {
+            let [x1, y1, x2, y2] = bbox.horizontal();
+            envelopes.push(format!(
+                "geom && ST_MakeEnvelope({}, {}, {}, {}, 4326)",
+                params.bind(x1)?,
+                params.bind(y1)?,
+                params.bind(x2)?,
+                params.bind(y2)?
+            ));
+        }
+        where_conditions.push(format!("({})", envelopes.join(" OR ")));
+    }
+
+    // datetime
+    if let Some(datetime) = query.datetime.as_ref() {
+        where_conditions.push(datetime_predicate(datetime, &mut params)?);
+    }
+
+    // ids
+    if !query.ids.is_empty() {
+        where_conditions.push(format!("id = ANY({})", params.bind(&query.ids)?))
+    }
+
+    // intersects
+    if let Some(intersects) = query.intersects.as_ref() {
+        where_conditions.push(format!(
+            "geom && ST_GeomFromGeoJSON({})",
+            params.bind(intersects.to_string())?
+        ));
+    }
+
+    let conditions = where_conditions.join(" AND ");
+
+    // COUNT
+    let count = format!(
+        r#"
+        WITH items AS ({union_all_items})
+        SELECT count(*) FROM items
+        WHERE {conditions}
+        "#,
+    );
+    let count_params = params.clone();
+
+    // ORDER BY
+    let order_by = order_by(&sort_keys(&query.sortby, &mut params)?);
+
+    // projection
+    let properties = properties_projection(query.properties.as_deref(), &mut params)?;
+    let geometry = match query.skip_geometry {
+        Some(true) => "NULL::jsonb",
+        _ => "ST_AsGeoJSON(ST_Transform(geom, 4326))::jsonb",
+    };
+
+    // FETCH
+    let limit = params.bind(query.limit.map(|l| l as i64))?;
+    let offset = params.bind(query.offset.unwrap_or(0) as i64)?;
+
+    let sql = format!(
+        r#"
+        WITH items AS ({union_all_items})
+        SELECT row_to_json(t)
+        FROM (
+            SELECT
+                id,
+                collection,
+                {properties} AS properties,
+                {geometry} AS geometry,
+                links,
+                assets,
+                bbox
+            FROM items
+            WHERE {conditions}
+            ORDER BY {order_by}
+            LIMIT {limit}
+            OFFSET {offset}
+        ) t
+        "#
+    );
+
+    Ok(((sql, params), (count, count_params)))
 }
@@ -2,7 +2,10 @@ use ogcapi_types::tiles::TileMatrixSet;
 
 use crate::{CollectionTransactions, TileTransactions};
 
-use super::Db;
+use super::{
+    Db,
+    sql::{Params, geometry_column, items_table},
+};
 
 #[async_trait::async_trait]
 impl TileTransactions for Db {
@@ -13,45 +16,125 @@ impl TileTransactions for Db {
         matrix: &str,
         row: u32,
         col: u32,
+        geometry: Option<&str>,
     ) -> anyhow::Result<Vec<u8>> {
+        let zoom = matrix.parse::<i32>()?;
+
+        let mut params = Params::default();
+        params.bind(zoom)?;
+        params.bind(row as i32)?;
+        params.bind(col as i32)?;
+
         let mut sql: Vec<String> = Vec::new();
 
         for collection_id in collections {
             if let Some(collection) = self.read_collection(collection_id).await? {
-                let storage_srid = match collection.storage_crs.map(|crs| crs.as_srid()) {
-                    Some(srid) => srid,
-                    None => {
-                        sqlx::query_scalar(&format!(
-                            "SELECT Find_SRID('items', '{collection_id}', 'geom')"
-                        ))
-                        .fetch_one(&self.pool)
-                        .await?
+                // the named geometry, if the collection has it
+                let column = match geometry {
+                    Some(name) if collection.geometry_column(name).is_ok() => {
+                        geometry_column(name)?
                     }
+                    _ => "geom".to_owned(),
                 };
+                let storage_srid = params.bind(self.storage_srid(collection_id).await?)?;
+                let layer = params.bind(collection_id)?;
 
-                sql.push(format!(
-                    r#"
-                    SELECT ST_AsMVT(mvtgeom, '{collection_id}', 4096, 'geom')
-                    FROM (
+                // features of the buffer beyond the antimeridian are moved next to the tile
+                let mut parts = Vec::new();
+                for ([x1, y1, x2, y2], shift) in envelopes(zoom, row, col) {
+                    parts.push(format!(
+                        r#"
                         SELECT
-                            ST_AsMVTGeom(ST_Transform(ST_Force2D(geom), 3857), ST_TileEnvelope($1, $3, $2), 4096, 64, TRUE) AS geom,
-                            '{collection_id}' as collection,
+                            ST_AsMVTGeom(ST_Translate(ST_Transform(ST_Force2D({column}), 3857), {shift}, 0), ST_TileEnvelope($1, $3, $2), 4096, 64, TRUE) AS geom,
+                            {layer} as collection,
                             properties
-                        FROM items.{collection_id}
-                        WHERE geom && ST_Transform(ST_TileEnvelope($1, $3, $2, margin => (64.0 / 4096)), {storage_srid})
-                    ) AS mvtgeom
-                    "#
+                        FROM {table}
+                        WHERE {column} && ST_Transform(ST_MakeEnvelope({x1}, {y1}, {x2}, {y2}, 3857), {storage_srid})
+                        "#,
+                        shift = params.bind(shift)?,
+                        x1 = params.bind(x1)?,
+                        y1 = params.bind(y1)?,
+                        x2 = params.bind(x2)?,
+                        y2 = params.bind(y2)?,
+                        table = items_table(collection_id)?
+                    ));
+                }
+
+                sql.push(format!(
+                    r#"
+                    SELECT ST_AsMVT(mvtgeom, {layer}, 4096, 'geom')
+                    FROM ({}) AS mvtgeom
+                    "#,
+                    parts.join(" UNION ALL ")
                 ));
             };
         }
 
-        let tiles: Vec<Vec<u8>> = sqlx::query_scalar(&sql.join(" UNION ALL "))
-            .bind(matrix.parse::<i32>().unwrap())
-            .bind(row as i32)
-            .bind(col as i32)
-            .fetch_all(&self.pool)
-            .await?;
+        let tiles: Vec<Vec<u8>> =
+            sqlx::query_scalar_with(&sql.join(" UNION ALL "), params.into_arguments())
+                .fetch_all(self.reader())
+                .await?;
 
         Ok(tiles.concat())
     }
 }
+
+/// Half the circumference of the earth in web mercator (EPSG:3857)
+const HALF_CIRCUMFERENCE: f64 = 20037508.342789244;
+
+/// Envelopes in web mercator of a `WebMercatorQuad` tile buffered by 64 of
+/// 4096 units, with the shift of their features along the x axis
+///
+/// The buffer of tiles at the edges of the tile matrix spans the antimeridian
+/// and is split into the part within the matrix and the part wrapped around
+/// to its other side.
+fn envelopes(zoom: i32, row: u32, col: u32) -> Vec<([f64; 4], f64)> {
+    let size = 2.0 * HALF_CIRCUMFERENCE / 2f64.powi(zoom);
+    let buffer = size * 64.0 / 4096.0;
+
+    let x1 = -HALF_CIRCUMFERENCE + col as f64 * size - buffer;
+    let x2 = x1 + size + 2.0 * buffer;
+    let y2 = HALF_CIRCUMFERENCE - row as f64 * size + buffer;
+    let y1 = y2 - size - 2.0 * buffer;
+
+    let mut envelopes = vec![(
+        [
+            x1.max(-HALF_CIRCUMFERENCE),
+            y1,
+            x2.min(HALF_CIRCUMFERENCE),
+            y2,
+        ],
+        0.0,
+    )];
+    if x1 < -HALF_CIRCUMFERENCE {
+        envelopes.push((
+            [x1 + 2.0 * HALF_CIRCUMFERENCE, y1, HALF_CIRCUMFERENCE, y2],
+            -2.0 * HALF_CIRCUMFERENCE,
+        ));
+    }
+    if x2 > HALF_CIRCUMFERENCE {
+        envelopes.push((
+            [-HALF_CIRCUMFERENCE, y1, x2 - 2.0 * HALF_CIRCUMFERENCE, y2],
+            2.0 * HALF_CIRCUMFERENCE,
+        ));
+    }
+    envelopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn antimeridian() {
+        // tiles at the edges of the matrix wrap around
+        let parts = envelopes(1, 0, 0);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].0[0], -HALF_CIRCUMFERENCE);
+        assert_eq!(parts[1].1, -2.0 * HALF_CIRCUMFERENCE);
+        assert!(parts[1].0[0] < HALF_CIRCUMFERENCE);
+
+        assert_eq!(envelopes(0, 0, 0).len(), 3);
+        assert_eq!(envelopes(2, 1, 1).len(), 1);
+    }
+}
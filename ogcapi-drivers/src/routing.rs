@@ -0,0 +1,404 @@
+//! Routing driver
+//!
+//! Federates several backends in one service by dispatching on the
+//! collection id. A `Routing` holds one backend per route and a default
+//! backend for the collections no route matches, for any of the driver
+//! traits that are scoped by collections. Routes are matched in reverse
+//! order of registration, so later routes take precedence.
+use std::sync::Arc;
+
+#[cfg(feature = "features")]
+use futures::stream::BoxStream;
+#[cfg(feature = "stac")]
+use futures::{StreamExt, stream};
+#[cfg(any(feature = "features", feature = "edr"))]
+use ogcapi_types::common::Crs;
+#[cfg(any(feature = "features", feature = "stac"))]
+use ogcapi_types::common::Exception;
+use ogcapi_types::common::{Collection, Collections, Query as CollectionQuery};
+#[cfg(feature = "edr")]
+use ogcapi_types::edr::{Query as EdrQuery, QueryType};
+#[cfg(any(feature = "features", feature = "stac", feature = "edr"))]
+use ogcapi_types::features::FeatureCollection;
+#[cfg(feature = "features")]
+use ogcapi_types::features::{
+    Change, Feature, FeatureVersion, Query as FeatureQuery, Queryables, Schema,
+};
+#[cfg(feature = "stac")]
+use ogcapi_types::stac::SearchParams;
+#[cfg(feature = "tiles")]
+use ogcapi_types::tiles::TileMatrixSet;
+#[cfg(feature = "features")]
+use serde_json::{Map, Value};
+
+use crate::CollectionTransactions;
+#[cfg(feature = "edr")]
+use crate::EdrQuerier;
+#[cfg(feature = "stac")]
+use crate::StacSearch;
+#[cfg(feature = "tiles")]
+use crate::TileTransactions;
+#[cfg(feature = "features")]
+use crate::{FeatureChanges, FeatureTransactions};
+
+/// Collections served by a backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// The collection with an id
+    Collection(String),
+    /// The collections whose id starts with a prefix
+    Prefix(String),
+}
+
+impl Route {
+    pub fn matches(&self, collection_id: &str) -> bool {
+        match self {
+            Route::Collection(id) => id == collection_id,
+            Route::Prefix(prefix) => collection_id.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// Driver dispatching on the collection id to the backend of a route
+pub struct Routing<T: ?Sized> {
+    default: Arc<T>,
+    routes: Vec<(Route, Arc<T>)>,
+}
+
+impl<T: ?Sized> Routing<T> {
+    /// Route all collections to a default backend
+    pub fn new(default: Arc<T>) -> Self {
+        Routing {
+            default,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Route collections to a backend, taking precedence over earlier routes
+    pub fn route(mut self, route: Route, backend: Arc<T>) -> Self {
+        self.routes.push((route, backend));
+        self
+    }
+
+    /// Route a collection to a backend
+    pub fn collection(self, id: impl ToString, backend: Arc<T>) -> Self {
+        self.route(Route::Collection(id.to_string()), backend)
+    }
+
+    /// Route the collections with an id prefix to a backend
+    pub fn prefix(self, prefix: impl ToString, backend: Arc<T>) -> Self {
+        self.route(Route::Prefix(prefix.to_string()), backend)
+    }
+
+    /// Backend of a collection
+    fn backend(&self, collection_id: &str) -> &Arc<T> {
+        self.routes
+            .iter()
+            .rev()
+            .find(|(route, _)| route.matches(collection_id))
+            .map(|(_, backend)| backend)
+            .unwrap_or(&self.default)
+    }
+
+    /// Distinct backends, the default one first
+    fn backends(&self) -> impl Iterator<Item = &Arc<T>> {
+        let mut backends: Vec<&Arc<T>> = vec![&self.default];
+        for (_, backend) in &self.routes {
+            if !backends.iter().any(|b| Arc::ptr_eq(b, backend)) {
+                backends.push(backend);
+            }
+        }
+        backends.into_iter()
+    }
+
+    /// Collections grouped by their backends, in order of appearance
+    #[cfg(feature = "tiles")]
+    fn groups(&self, collections: &[String]) -> Vec<(&Arc<T>, Vec<String>)> {
+        let mut groups: Vec<(&Arc<T>, Vec<String>)> = Vec::new();
+        for collection_id in collections {
+            let backend = self.backend(collection_id);
+            match groups.iter_mut().find(|(b, _)| Arc::ptr_eq(b, backend)) {
+                Some((_, ids)) => ids.push(collection_id.to_owned()),
+                None => groups.push((backend, vec![collection_id.to_owned()])),
+            }
+        }
+        groups
+    }
+}
+
+#[async_trait::async_trait]
+impl CollectionTransactions for Routing<dyn CollectionTransactions> {
+    async fn create_collection(&self, collection: &Collection) -> anyhow::Result<String> {
+        self.backend(&collection.id)
+            .create_collection(collection)
+            .await
+    }
+
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        self.backend(id).read_collection(id).await
+    }
+
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.backend(&collection.id)
+            .update_collection(collection, if_match)
+            .await
+    }
+
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        self.backend(id).delete_collection(id, if_match).await
+    }
+
+    async fn list_collections(&self, query: &CollectionQuery) -> anyhow::Result<Collections> {
+        // page the merged collections instead of those of each backend
+        let unpaged = CollectionQuery {
+            pagination: Default::default(),
+            ..query.clone()
+        };
+
+        let mut collections = Vec::new();
+        for backend in self.backends() {
+            // only the collections routed to the backend
+            let listed = backend.list_collections(&unpaged).await?;
+            collections.extend(
+                listed
+                    .collections
+                    .into_iter()
+                    .filter(|collection| Arc::ptr_eq(self.backend(&collection.id), backend)),
+            );
+        }
+        collections.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let number_matched = collections.len() as u64;
+        let pagination = &query.pagination;
+        let collections = collections
+            .into_iter()
+            .skip(pagination.offset.unwrap_or_default())
+            .take(pagination.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        let mut collections = Collections::new(collections);
+        collections.number_matched = Some(number_matched);
+
+        Ok(collections)
+    }
+
+    async fn collection_etag(&self, id: &str) -> anyhow::Result<Option<String>> {
+        self.backend(id).collection_etag(id).await
+    }
+}
+
+#[cfg(feature = "features")]
+#[async_trait::async_trait]
+impl FeatureTransactions for Routing<dyn FeatureTransactions> {
+    async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String> {
+        self.backend(collection(feature)?)
+            .create_feature(feature)
+            .await
+    }
+
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        self.backend(collection_id)
+            .create_features(collection_id, features)
+            .await
+    }
+
+    async fn read_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Feature>> {
+        self.backend(collection_id)
+            .read_feature(collection_id, id, crs)
+            .await
+    }
+
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.backend(collection(feature)?)
+            .update_feature(feature, if_match)
+            .await
+    }
+
+    async fn patch_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        patch: &Map<String, Value>,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.backend(collection_id)
+            .patch_feature(collection_id, id, patch, if_match)
+            .await
+    }
+
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.backend(collection_id)
+            .delete_feature(collection_id, id, if_match)
+            .await
+    }
+
+    async fn feature_etag(&self, collection_id: &str, id: &str) -> anyhow::Result<Option<String>> {
+        self.backend(collection_id)
+            .feature_etag(collection_id, id)
+            .await
+    }
+
+    async fn feature_versions(
+        &self,
+        collection_id: &str,
+        id: &str,
+        crs: &Crs,
+    ) -> anyhow::Result<Option<Vec<FeatureVersion>>> {
+        self.backend(collection_id)
+            .feature_versions(collection_id, id, crs)
+            .await
+    }
+
+    async fn list_items(
+        &self,
+        collection_id: &str,
+        query: &FeatureQuery,
+    ) -> anyhow::Result<FeatureCollection> {
+        self.backend(collection_id)
+            .list_items(collection_id, query)
+            .await
+    }
+
+    fn stream_items<'a>(
+        &'a self,
+        collection_id: &'a str,
+        query: &'a FeatureQuery,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        self.backend(collection_id)
+            .stream_items(collection_id, query)
+    }
+
+    fn keyset_pagination(&self, collection_id: &str) -> bool {
+        self.backend(collection_id).keyset_pagination(collection_id)
+    }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        self.backend(collection_id).queryables(collection_id).await
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        self.backend(collection_id).schema(collection_id).await
+    }
+}
+
+#[cfg(feature = "features")]
+#[async_trait::async_trait]
+impl FeatureChanges for Routing<dyn FeatureChanges> {
+    async fn changes(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<BoxStream<'static, anyhow::Result<Change>>> {
+        self.backend(collection_id).changes(collection_id).await
+    }
+}
+
+#[cfg(feature = "edr")]
+#[async_trait::async_trait]
+impl EdrQuerier for Routing<dyn EdrQuerier> {
+    async fn query(
+        &self,
+        collection_id: &str,
+        query_type: &QueryType,
+        query: &EdrQuery,
+    ) -> anyhow::Result<(FeatureCollection, Crs)> {
+        self.backend(collection_id)
+            .query(collection_id, query_type, query)
+            .await
+    }
+}
+
+#[cfg(feature = "tiles")]
+#[async_trait::async_trait]
+impl TileTransactions for Routing<dyn TileTransactions> {
+    async fn tile(
+        &self,
+        collections: &[String],
+        tms: &TileMatrixSet,
+        matrix: &str,
+        row: u32,
+        col: u32,
+        geometry: Option<&str>,
+    ) -> anyhow::Result<Vec<u8>> {
+        // the layers of vector tiles concatenate
+        let mut tile = Vec::new();
+        for (backend, collections) in self.groups(collections) {
+            tile.extend(
+                backend
+                    .tile(&collections, tms, matrix, row, col, geometry)
+                    .await?,
+            );
+        }
+        Ok(tile)
+    }
+}
+
+#[cfg(feature = "stac")]
+#[async_trait::async_trait]
+impl StacSearch for Routing<dyn StacSearch> {
+    async fn search(&self, query: &SearchParams) -> anyhow::Result<FeatureCollection> {
+        self.searched(query)?.search(query).await
+    }
+
+    fn stream_search<'a>(
+        &'a self,
+        query: &'a SearchParams,
+    ) -> BoxStream<'a, anyhow::Result<Feature>> {
+        match self.searched(query) {
+            Ok(backend) => backend.stream_search(query),
+            Err(e) => stream::once(async { Err(e) }).boxed(),
+        }
+    }
+}
+
+#[cfg(feature = "stac")]
+impl Routing<dyn StacSearch> {
+    /// Backend of the collections of a search
+    fn searched(&self, query: &SearchParams) -> anyhow::Result<&Arc<dyn StacSearch>> {
+        // searches without collections are answered by the default backend
+        let mut backends = query
+            .collections
+            .iter()
+            .map(|collection_id| self.backend(collection_id));
+        let backend = backends.next().unwrap_or(&self.default);
+
+        if backends.any(|other| !Arc::ptr_eq(other, backend)) {
+            return Err(Exception::new_from_status(400)
+                .detail("Collections of different backends can not be searched together")
+                .into());
+        }
+
+        Ok(backend)
+    }
+}
+
+/// Collection of a feature
+#[cfg(feature = "features")]
+fn collection(feature: &Feature) -> anyhow::Result<&str> {
+    feature.collection.as_deref().ok_or_else(|| {
+        Exception::new_from_status(400)
+            .detail("Missing collection of the feature")
+            .into()
+    })
+}
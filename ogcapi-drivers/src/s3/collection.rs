@@ -1,102 +1,145 @@
-use aws_sdk_s3::{error::SdkError, operation::get_object::GetObjectError};
-
-use ogcapi_types::common::{Collection, Collections, Query, media_type::JSON};
+use ogcapi_types::common::{
+    Bbox, Collection, Collections, Crs, Exception, Query, SpatialExtent, media_type::JSON,
+};
 
 use crate::CollectionTransactions;
 
-use super::S3;
+use super::{Precondition, S3, collection_key, index::Index, index_key, unconditional};
 
 #[async_trait::async_trait]
 impl CollectionTransactions for S3 {
-    async fn create_collection(&self, collection: &Collection) -> Result<String, anyhow::Error> {
-        let key = format!("collections/{}/collection.json", collection.id);
-        let data = serde_json::to_vec(&collection)?;
-
-        self.put_object(
-            self.bucket.clone().unwrap_or_default(),
-            &key,
-            data,
-            Some(JSON.to_string()),
+    async fn create_collection(&self, collection: &Collection) -> anyhow::Result<String> {
+        if !self
+            .write_json(
+                &collection_key(&collection.id),
+                collection,
+                JSON,
+                Precondition::Absent,
+            )
+            .await?
+        {
+            return Err(Exception::new_from_status(409)
+                .detail(format!("Collection `{}` already exists", collection.id))
+                .into());
+        }
+
+        self.write_json(
+            &index_key(&collection.id),
+            &Index::default(),
+            JSON,
+            Precondition::None,
         )
         .await?;
 
+        self.cache.invalidate(&collection.id);
+
         Ok(collection.id.to_owned())
     }
 
-    async fn read_collection(&self, id: &str) -> Result<Option<Collection>, anyhow::Error> {
-        // TODO: cache
-        let key = format!("collections/{id}/collection.json");
-
-        match self
-            .get_object(self.bucket.clone().unwrap_or_default(), &key)
-            .await
-        {
-            Ok(r) => Ok(Some(serde_json::from_slice(
-                &r.body.collect().await?.into_bytes(),
-            )?)),
-            Err(e) => match e {
-                SdkError::ServiceError(err) => match err.err() {
-                    GetObjectError::NoSuchKey(_) => Ok(None),
-                    _ => Err(anyhow::Error::new(err.into_err())),
-                },
-                _ => Err(anyhow::Error::new(e)),
-            },
+    async fn read_collection(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        match self.document(id).await? {
+            Some(collection) => Ok(Some(self.with_extent(collection).await?)),
+            None => Ok(None),
         }
     }
 
-    async fn update_collection(&self, collection: &Collection) -> Result<(), anyhow::Error> {
-        let key = format!("collections/{}/collection.json", collection.id);
-        let data = serde_json::to_vec(&collection)?;
+    async fn update_collection(
+        &self,
+        collection: &Collection,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        unconditional(if_match)?;
+
+        let key = collection_key(&collection.id);
+        if !self.exists(&key).await? {
+            return Err(Exception::new_from_status(404)
+                .detail(format!("Collection `{}` does not exist", collection.id))
+                .into());
+        }
 
-        self.put_object(
-            self.bucket.clone().unwrap_or_default(),
-            &key,
-            data,
-            Some(JSON.to_string()),
-        )
-        .await?;
+        self.write_json(&key, collection, JSON, Precondition::None)
+            .await?;
+
+        self.cache.invalidate(&collection.id);
 
         Ok(())
     }
 
-    async fn delete_collection(&self, id: &str) -> Result<(), anyhow::Error> {
-        let key = format!("collections/{id}");
+    async fn delete_collection(&self, id: &str, if_match: Option<&str>) -> anyhow::Result<()> {
+        unconditional(if_match)?;
 
-        self.delete_object(self.bucket.clone().unwrap_or_default(), &key)
-            .await?;
+        // the collection document, index and items
+        let (keys, _) = self.list(&format!("collections/{id}/"), false).await?;
+        self.delete_keys(&keys).await?;
+
+        self.cache.invalidate(id);
 
         Ok(())
     }
 
-    async fn list_collections(&self, _query: &Query) -> Result<Collections, anyhow::Error> {
-        let mut collections = Vec::new();
+    async fn list_collections(&self, _query: &Query) -> anyhow::Result<Collections> {
+        let (_, prefixes) = self.list("collections/", true).await?;
 
-        let resp = self
-            .client
-            .list_objects()
-            .bucket(self.bucket.clone().unwrap_or_default())
-            .send()
+        let documents = self
+            .concurrently(prefixes, |s3, prefix| async move {
+                s3.read_json::<Collection>(&format!("{prefix}collection.json"))
+                    .await
+            })
             .await?;
 
-        for object in resp.contents.unwrap() {
-            if let Some(key) = object.key()
-                && key.ends_with("collection.json")
-            {
-                let r = self
-                    .get_object(self.bucket.clone().unwrap_or_default(), key)
-                    .await?;
-
-                let c = serde_json::from_slice(&r.body.collect().await?.into_bytes()[..])?;
-
-                collections.push(c);
-            }
+        let mut collections = Vec::with_capacity(documents.len());
+        for (collection, _) in documents.into_iter().flatten() {
+            collections.push(self.with_extent(collection).await?);
         }
+        #[cfg(feature = "stac")]
+        collections.retain(|collection| collection.r#type == "Collection");
+        collections.sort_by(|a, b| a.id.cmp(&b.id));
 
         let mut collections = Collections::new(collections);
-        collections
-            .number_returned
-            .clone_into(&mut collections.number_matched);
+        collections.number_matched = collections.number_returned;
 
         Ok(collections)
     }
 }
+
+impl S3 {
+    /// Collection with the spatial extent of its index, unless it is provided
+    async fn with_extent(&self, mut collection: Collection) -> anyhow::Result<Collection> {
+        if collection
+            .extent
+            .as_ref()
+            .is_some_and(|extent| extent.spatial.is_some())
+        {
+            return Ok(collection);
+        }
+
+        let bounds = self
+            .index(&collection.id)
+            .await?
+            .features
+            .into_values()
+            .filter_map(|entry| entry.bbox)
+            .reduce(|a, b| {
+                [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ]
+            });
+
+        if let Some(bounds) = bounds {
+            collection.extent.get_or_insert_default().spatial = Some(SpatialExtent {
+                bbox: vec![Bbox::Bbox2D(bounds)],
+                crs: Some(
+                    collection
+                        .storage_crs
+                        .clone()
+                        .unwrap_or_else(Crs::default2d),
+                ),
+            });
+        }
+
+        Ok(collection)
+    }
+}
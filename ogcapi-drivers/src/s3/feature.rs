@@ -1,92 +1,411 @@
-use aws_sdk_s3::{error::SdkError, operation::get_object::GetObjectError};
+use std::collections::{HashMap, HashSet};
+
+use geo::Intersects;
 
 use ogcapi_types::{
-    common::{Crs, media_type::GEO_JSON},
-    features::{Feature, FeatureCollection, Query},
+    common::{Collection, Crs, Exception, media_type::GEO_JSON},
+    features::{
+        Feature, FeatureCollection, FeatureId, NumberMatched, Query, Queryable, Queryables, Schema,
+    },
 };
 
-use crate::FeatureTransactions;
+use crate::{
+    FeatureTransactions, SAMPLE_SIZE,
+    features::{
+        matches, matches_temporal, matches_value, named_geometry, oriented, project, rects,
+        sample_properties, sort, untransformed, unversioned,
+    },
+};
 
-use super::S3;
+use super::{Precondition, S3, index::Entry, item_key, unconditional};
 
 #[async_trait::async_trait]
 impl FeatureTransactions for S3 {
     async fn create_feature(&self, feature: &Feature) -> anyhow::Result<String> {
-        let key = format!(
-            "collections/{}/items/{}.json",
-            feature.collection.as_ref().unwrap(),
-            feature.id.as_ref().unwrap()
-        );
-        let data = serde_json::to_vec(&feature)?;
-
-        self.put_object(
-            self.bucket.clone().unwrap_or_default(),
-            &key,
-            data,
-            Some(GEO_JSON.to_string()),
-        )
-        .await?;
-
-        Ok(key)
+        let collection_id = feature.collection.as_ref().unwrap();
+        let ids = self
+            .create_features(collection_id, std::slice::from_ref(feature))
+            .await?;
+        Ok(ids[0].to_owned())
+    }
+
+    async fn create_features(
+        &self,
+        collection_id: &str,
+        features: &[Feature],
+    ) -> anyhow::Result<Vec<String>> {
+        self.existing(collection_id).await?;
+
+        // assign ids and check for duplicates before writing anything
+        let mut items = Vec::with_capacity(features.len());
+        let mut ids = HashSet::new();
+        for feature in features {
+            let id = match &feature.id {
+                Some(id) => id.to_string(),
+                None => uuid::Uuid::new_v4().to_string(),
+            };
+            if !ids.insert(id.to_owned()) {
+                return Err(already_exists(&id));
+            }
+
+            let mut feature = feature.to_owned();
+            feature.id = Some(FeatureId::String(id.to_owned()));
+            feature.collection = Some(collection_id.to_owned());
+            items.push((id, feature));
+        }
+
+        // objects are created unless they exist
+        let collection = collection_id.to_owned();
+        let created = self
+            .concurrently(items, move |s3, (id, feature)| {
+                let key = item_key(&collection, &id);
+                async move {
+                    let created = s3
+                        .write_json(&key, &feature, GEO_JSON, Precondition::Absent)
+                        .await;
+                    Ok((id, Entry::new(&feature), created))
+                }
+            })
+            .await?;
+
+        let mut ids = Vec::with_capacity(created.len());
+        let mut entries = Vec::with_capacity(created.len());
+        let mut result = Ok(());
+        for (id, entry, outcome) in created {
+            match outcome {
+                Ok(true) => {
+                    ids.push(id.to_owned());
+                    entries.push((id, entry));
+                }
+                Ok(false) => result = result.and(Err(already_exists(&id))),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+
+        if result.is_ok() {
+            let entries = &entries;
+            result = self
+                .modify_index(collection_id, |index| {
+                    for (id, entry) in entries {
+                        index.features.insert(id.to_owned(), entry.to_owned());
+                    }
+                })
+                .await;
+        }
+
+        // roll back the objects that have been created
+        if let Err(e) = result {
+            let keys: Vec<_> = ids.iter().map(|id| item_key(collection_id, id)).collect();
+            self.delete_keys(&keys).await?;
+            return Err(e);
+        }
+
+        Ok(ids)
     }
 
     async fn read_feature(
         &self,
-        collection: &str,
+        collection_id: &str,
         id: &str,
-        _crs: &Crs,
+        crs: &Crs,
     ) -> anyhow::Result<Option<Feature>> {
-        let key = format!("collections/{collection}/items/{id}.json");
-
-        match self
-            .get_object(self.bucket.clone().unwrap_or_default(), &key)
-            .await
-        {
-            Ok(r) => Ok(Some(serde_json::from_slice(
-                &r.body.collect().await?.into_bytes(),
-            )?)),
-            Err(e) => match e {
-                SdkError::ServiceError(err) => match err.err() {
-                    GetObjectError::NoSuchKey(_) => Ok(None),
-                    _ => Err(anyhow::Error::new(err.into_err())),
-                },
-                _ => Err(anyhow::Error::new(e)),
-            },
-        }
+        let collection = self.existing(collection_id).await?;
+        untransformed(Some(crs), storage_srid(&collection))?;
+
+        Ok(self
+            .read_json(&item_key(collection_id, id))
+            .await?
+            .map(|(feature, _)| oriented(feature, Some(crs))))
     }
-    async fn update_feature(&self, feature: &Feature) -> anyhow::Result<()> {
-        let key = format!(
-            "collections/{}/items/{}.json",
-            feature.collection.as_ref().unwrap(),
-            feature.id.as_ref().unwrap()
-        );
-        let data = serde_json::to_vec(&feature)?;
-
-        self.put_object(
-            self.bucket.clone().unwrap_or_default(),
-            &key,
-            data,
-            Some(GEO_JSON.to_string()),
-        )
-        .await?;
-
-        Ok(())
+
+    async fn update_feature(
+        &self,
+        feature: &Feature,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        unconditional(if_match)?;
+
+        let collection_id = feature.collection.as_ref().unwrap();
+        let id = feature
+            .id
+            .as_ref()
+            .map(ToString::to_string)
+            .ok_or_else(|| Exception::new_from_status(400).detail("Missing feature id"))?;
+
+        self.existing(collection_id).await?;
+
+        let key = item_key(collection_id, &id);
+        if !self.exists(&key).await? {
+            return Err(Exception::new_from_status(404)
+                .detail(format!("Feature `{id}` does not exist"))
+                .into());
+        }
+
+        let mut feature = feature.to_owned();
+        feature.id = Some(FeatureId::String(id.to_owned()));
+        self.write_json(&key, &feature, GEO_JSON, Precondition::None)
+            .await?;
+
+        let entry = Entry::new(&feature);
+        self.modify_index(collection_id, |index| {
+            index.features.insert(id.to_owned(), entry.to_owned());
+        })
+        .await
     }
 
-    async fn delete_feature(&self, collection: &str, id: &str) -> anyhow::Result<()> {
-        let key = format!("collections/{collection}/items/{id}.json");
+    async fn delete_feature(
+        &self,
+        collection_id: &str,
+        id: &str,
+        if_match: Option<&str>,
+    ) -> anyhow::Result<()> {
+        unconditional(if_match)?;
+
+        self.existing(collection_id).await?;
 
-        self.delete_object(self.bucket.clone().unwrap_or_default(), &key)
+        self.delete_object(self.default_bucket(), item_key(collection_id, id))
             .await?;
 
-        Ok(())
+        self.modify_index(collection_id, |index| {
+            index.features.remove(id);
+        })
+        .await
     }
 
     async fn list_items(
         &self,
-        _collection: &str,
-        _query: &Query,
+        collection_id: &str,
+        query: &Query,
     ) -> anyhow::Result<FeatureCollection> {
-        unimplemented!()
+        let collection = self.existing(collection_id).await?;
+        let srid = storage_srid(&collection);
+
+        untransformed(query.crs.as_ref(), srid)?;
+        unversioned(query.version_at.as_ref())?;
+        if let Some(name) = query.bbox_geometry.as_deref() {
+            collection.geometry_column(name)?;
+        }
+
+        let bbox = match query.bbox.as_ref() {
+            Some(bbox) => {
+                untransformed(query.bbox_crs.as_ref(), srid)?;
+                Some(rects(bbox, query.bbox_crs.as_ref()))
+            }
+            None => None,
+        };
+
+        let filter = query.parse_filter()?;
+        if filter.is_some() {
+            untransformed(query.filter_crs.as_ref(), srid)?;
+        }
+
+        // the `next` token is the offset of the next page
+        let offset = match query.next.as_deref() {
+            Some(token) => token.parse().map_err(|_| {
+                anyhow::Error::from(Exception::new_from_status(400).detail("Invalid `next` token"))
+            })?,
+            None => query.offset.unwrap_or(0),
+        };
+        let end = query
+            .limit
+            .map(|limit| offset.saturating_add(limit))
+            .unwrap_or(usize::MAX);
+        let count = query.number_matched.unwrap_or_default() != NumberMatched::Skip;
+
+        // candidates of the index, which holds the bounds of the primary geometry
+        let index = self.index(collection_id).await?;
+        let candidates: Vec<String> = index
+            .features
+            .into_iter()
+            .filter(|(_, entry)| {
+                query.bbox_geometry.is_some()
+                    || bbox
+                        .as_ref()
+                        .is_none_or(|rects| rects.iter().any(|rect| entry.intersects(rect)))
+            })
+            .filter(|(_, entry)| {
+                query
+                    .datetime
+                    .as_ref()
+                    .is_none_or(|datetime| matches_temporal(Some(&entry.temporal), datetime))
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        let exact = bbox.is_some()
+            || filter.is_some()
+            || !query.additional_parameters.is_empty()
+            || !query.sortby.is_empty();
+
+        let (features, number_matched, next) = if exact {
+            // candidates are evaluated in process
+            let candidates = self.features(collection_id, candidates).await?;
+
+            let mut matched = Vec::new();
+            for feature in &candidates {
+                if let Some(rects) = bbox.as_ref()
+                    && !named_geometry(feature, query.bbox_geometry.as_deref())
+                        .is_some_and(|g| rects.iter().any(|rect| g.intersects(rect)))
+                {
+                    continue;
+                }
+                if !query
+                    .additional_parameters
+                    .iter()
+                    .all(|(k, v)| matches_value(feature, k, v))
+                {
+                    continue;
+                }
+                if let Some(filter) = filter.as_ref()
+                    && !matches(filter, feature)?
+                {
+                    continue;
+                }
+                matched.push(feature);
+            }
+
+            sort(&mut matched, &query.sortby)?;
+
+            let number_matched = count.then_some(matched.len() as u64);
+            let next = (end < matched.len()).then(|| end.to_string());
+            let features: Vec<_> = matched
+                .into_iter()
+                .skip(offset)
+                .take(end - offset)
+                .cloned()
+                .collect();
+
+            (features, number_matched, next)
+        } else {
+            // only the objects of the page
+            let number_matched = count.then_some(candidates.len() as u64);
+            let next = (end < candidates.len()).then(|| end.to_string());
+            let page = candidates
+                .into_iter()
+                .skip(offset)
+                .take(end - offset)
+                .collect();
+
+            (
+                self.features(collection_id, page).await?,
+                number_matched,
+                next,
+            )
+        };
+
+        let features = features
+            .into_iter()
+            .map(|feature| {
+                let feature = project(
+                    feature,
+                    query.properties.as_deref(),
+                    query.skip_geometry.unwrap_or_default(),
+                );
+                oriented(feature, query.crs.as_ref())
+            })
+            .collect();
+
+        let mut fc = FeatureCollection::new(features);
+        fc.number_matched = number_matched;
+        fc.next = next;
+
+        Ok(fc)
+    }
+
+    fn keyset_pagination(&self, _collection_id: &str) -> bool {
+        true
     }
+
+    async fn queryables(&self, collection_id: &str) -> anyhow::Result<Queryables> {
+        let collection = self.existing(collection_id).await?;
+
+        // stored in the collection document
+        if let Some(queryables) = collection.additional_properties.get("queryables") {
+            return Ok(serde_json::from_value(queryables.to_owned())?);
+        }
+
+        Ok(Queryables {
+            title: collection.title.to_owned(),
+            queryables: self.sample_properties(collection_id).await?,
+            additional_properties: false,
+            ..Default::default()
+        })
+    }
+
+    async fn schema(&self, collection_id: &str) -> anyhow::Result<Schema> {
+        let collection = self.existing(collection_id).await?;
+
+        // stored in the collection document
+        if let Some(schema) = collection.additional_properties.get("schema") {
+            return Ok(serde_json::from_value(schema.to_owned())?);
+        }
+
+        Ok(Schema {
+            title: collection.title.to_owned(),
+            description: collection.description.to_owned(),
+            properties: self.sample_properties(collection_id).await?,
+            ..Default::default()
+        })
+    }
+}
+
+impl S3 {
+    /// Features of a collection in the order of their ids, skipping those
+    /// that no longer exist
+    async fn features(
+        &self,
+        collection_id: &str,
+        ids: Vec<String>,
+    ) -> anyhow::Result<Vec<Feature>> {
+        let collection = collection_id.to_owned();
+        let features = self
+            .concurrently(ids, move |s3, id| {
+                let key = item_key(&collection, &id);
+                async move { s3.read_json::<Feature>(&key).await }
+            })
+            .await?;
+
+        Ok(features
+            .into_iter()
+            .flatten()
+            .map(|(mut feature, _)| {
+                feature.collection = Some(collection_id.to_owned());
+                feature
+            })
+            .collect())
+    }
+
+    /// Property schemas derived from the first features of a collection
+    async fn sample_properties(
+        &self,
+        collection_id: &str,
+    ) -> anyhow::Result<HashMap<String, Queryable>> {
+        let ids = self
+            .index(collection_id)
+            .await?
+            .features
+            .into_keys()
+            .take(SAMPLE_SIZE)
+            .collect();
+        let features = self.features(collection_id, ids).await?;
+
+        Ok(sample_properties(features.iter()))
+    }
+}
+
+/// Storage srid of a collection
+fn storage_srid(collection: &Collection) -> i32 {
+    collection
+        .storage_crs
+        .as_ref()
+        .unwrap_or(&Crs::default2d())
+        .as_srid()
+}
+
+fn already_exists(id: &str) -> anyhow::Error {
+    Exception::new_from_status(400)
+        .detail(format!(
+            "No features were created: feature `{id}` already exists"
+        ))
+        .into()
 }
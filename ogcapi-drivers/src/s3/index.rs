@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use geo::{BoundingRect, Intersects};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use ogcapi_types::{
+    common::{Exception, media_type::JSON},
+    features::Feature,
+};
+
+use crate::features::geometry;
+
+use super::{Precondition, S3, index_key};
+
+/// Attempts to apply a change to an index that is modified concurrently
+const RETRIES: usize = 10;
+
+/// Properties that are evaluated by the `datetime` parameter
+const TEMPORAL: [&str; 3] = ["datetime", "start_datetime", "end_datetime"];
+
+/// Object index of a collection
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct Index {
+    /// Entries by feature id
+    pub(super) features: BTreeMap<String, Entry>,
+}
+
+/// Index entry of a feature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Entry {
+    /// Bounds of the horizontal axes of the geometry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) bbox: Option<[f64; 4]>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub(super) temporal: Map<String, Value>,
+}
+
+impl Entry {
+    pub(super) fn new(feature: &Feature) -> Self {
+        let bbox = geometry(feature)
+            .and_then(|geometry| geometry.bounding_rect())
+            .map(|rect| [rect.min().x, rect.min().y, rect.max().x, rect.max().y]);
+
+        let temporal = feature
+            .properties
+            .iter()
+            .flatten()
+            .filter(|(key, _)| TEMPORAL.contains(&key.as_str()))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+
+        Entry { bbox, temporal }
+    }
+
+    /// Whether the bounds of the geometry intersect a rectangle
+    pub(super) fn intersects(&self, rect: &geo::Rect) -> bool {
+        self.bbox.is_some_and(|[x1, y1, x2, y2]| {
+            geo::Rect::new(geo::coord! { x: x1, y: y1 }, geo::coord! { x: x2, y: y2 })
+                .intersects(rect)
+        })
+    }
+}
+
+impl S3 {
+    /// Index of a collection
+    pub(super) async fn index(&self, collection_id: &str) -> anyhow::Result<Index> {
+        Ok(self.load_index(collection_id).await?.0)
+    }
+
+    /// Apply a change to the index of a collection, retrying if it has been
+    /// modified concurrently
+    pub(super) async fn modify_index(
+        &self,
+        collection_id: &str,
+        f: impl Fn(&mut Index) + Send + Sync,
+    ) -> anyhow::Result<()> {
+        for _ in 0..RETRIES {
+            let (mut index, etag) = self.load_index(collection_id).await?;
+            f(&mut index);
+
+            let precondition = match etag.as_deref() {
+                Some(etag) => Precondition::Match(etag),
+                None => Precondition::None,
+            };
+            if self
+                .write_json(&index_key(collection_id), &index, JSON, precondition)
+                .await?
+            {
+                return Ok(());
+            }
+        }
+
+        Err(Exception::new_from_status(409)
+            .detail(format!(
+                "The index of collection `{collection_id}` is modified concurrently"
+            ))
+            .into())
+    }
+
+    /// Index of a collection with its entity tag, rebuilt from the items if
+    /// it is missing
+    async fn load_index(&self, collection_id: &str) -> anyhow::Result<(Index, Option<String>)> {
+        let key = index_key(collection_id);
+
+        if let Some(index) = self.read_json(&key).await? {
+            return Ok(index);
+        }
+
+        let index = self.rebuild_index(collection_id).await?;
+        // unless another writer has stored an index in the meantime
+        self.write_json(&key, &index, JSON, Precondition::Absent)
+            .await?;
+
+        self.read_json(&key)
+            .await?
+            .with_context(|| format!("Missing index of collection `{collection_id}`"))
+    }
+
+    /// Index of the items of a collection
+    async fn rebuild_index(&self, collection_id: &str) -> anyhow::Result<Index> {
+        let (keys, _) = self
+            .list(&format!("collections/{collection_id}/items/"), false)
+            .await?;
+
+        let features = self
+            .concurrently(keys, |s3, key| async move {
+                s3.read_json::<Feature>(&key).await
+            })
+            .await?;
+
+        let features = features
+            .into_iter()
+            .flatten()
+            .filter_map(|(feature, _)| {
+                let id = feature.id.as_ref()?.to_string();
+                Some((id, Entry::new(&feature)))
+            })
+            .collect();
+
+        Ok(Index { features })
+    }
+}
@@ -1,5 +1,22 @@
+//! S3 driver
+//!
+//! Stores collections and features as JSON objects in a bucket of any
+//! S3-compatible store, such as MinIO:
+//!
+//! - `collections/{collectionId}/collection.json`
+//! - `collections/{collectionId}/items/{featureId}.json`
+//! - `collections/{collectionId}/index.json`
+//!
+//! The index maps the feature ids of a collection to their bounding boxes
+//! and temporal properties, so that listing items only fetches the objects
+//! of a page or the candidates of a bbox query. It is updated with
+//! conditional writes and rebuilt from the items if it is missing.
+//! Coordinates are never transformed.
 mod collection;
 mod feature;
+mod index;
+
+use std::{future::Future, time::Duration};
 
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::{
@@ -8,12 +25,19 @@ use aws_sdk_s3::{
     operation::{
         delete_object::{DeleteObjectError, DeleteObjectOutput},
         get_object::{GetObjectError, GetObjectOutput},
+        head_object::HeadObjectError,
         put_object::{PutObjectError, PutObjectOutput},
     },
+    types::{Delete, ObjectIdentifier},
 };
+use serde::{Serialize, de::DeserializeOwned};
 
 pub use aws_sdk_s3::primitives::ByteStream;
 
+use ogcapi_types::common::{Collection, Exception};
+
+use crate::cache::CollectionCache;
+
 /// S3 driver
 #[derive(Clone)]
 pub struct S3 {
@@ -21,6 +45,8 @@ pub struct S3 {
     pub client: Client,
     /// Default bucket
     pub bucket: Option<String>,
+    /// Cache of collection documents
+    cache: CollectionCache,
 }
 
 impl S3 {
@@ -46,6 +72,7 @@ impl S3 {
         S3 {
             client,
             bucket: None,
+            cache: Default::default(),
         }
     }
 
@@ -53,6 +80,13 @@ impl S3 {
         self.bucket = Some(bucket.to_string())
     }
 
+    /// Cache collection documents for a time to live, a zero duration
+    /// disables caching
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = CollectionCache::new(ttl);
+        self
+    }
+
     pub async fn put_object(
         &self,
         bucket: impl Into<String>,
@@ -96,3 +130,209 @@ impl S3 {
             .await
     }
 }
+
+/// Objects carry no entity tags, so conditional writes never match
+fn unconditional(if_match: Option<&str>) -> anyhow::Result<()> {
+    match if_match {
+        Some(_) => Err(Exception::new_from_status(412)
+            .detail("Conditional writes are not supported")
+            .into()),
+        None => Ok(()),
+    }
+}
+
+/// Number of concurrent requests when fetching or writing many objects
+const CONCURRENCY: usize = 32;
+
+/// Condition of a write
+enum Precondition<'a> {
+    None,
+    /// The object must not exist
+    Absent,
+    /// The entity tag of the object must match
+    Match(&'a str),
+}
+
+impl S3 {
+    fn default_bucket(&self) -> String {
+        self.bucket.clone().unwrap_or_default()
+    }
+
+    /// Read a JSON object with its entity tag, `None` if it does not exist
+    async fn read_json<T: DeserializeOwned>(
+        &self,
+        key: &str,
+    ) -> anyhow::Result<Option<(T, Option<String>)>> {
+        match self.get_object(self.default_bucket(), key).await {
+            Ok(r) => {
+                let etag = r.e_tag.to_owned();
+                let value = serde_json::from_slice(&r.body.collect().await?.into_bytes())?;
+                Ok(Some((value, etag)))
+            }
+            Err(SdkError::ServiceError(err)) if err.err().is_no_such_key() => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write a JSON object, `false` if the precondition failed
+    async fn write_json(
+        &self,
+        key: &str,
+        value: &impl Serialize,
+        content_type: &str,
+        precondition: Precondition<'_>,
+    ) -> anyhow::Result<bool> {
+        let mut request = self
+            .client
+            .put_object()
+            .bucket(self.default_bucket())
+            .key(key)
+            .body(ByteStream::from(serde_json::to_vec(value)?))
+            .content_type(content_type);
+        request = match precondition {
+            Precondition::None => request,
+            Precondition::Absent => request.if_none_match("*"),
+            Precondition::Match(etag) => request.if_match(etag),
+        };
+
+        match request.send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.raw_response().is_some_and(|r| r.status().as_u16() == 412) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether an object exists
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        match self
+            .client
+            .head_object()
+            .bucket(self.default_bucket())
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(SdkError::ServiceError(err))
+                if matches!(err.err(), HeadObjectError::NotFound(_)) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Keys of the objects and common prefixes below a prefix, up to the
+    /// next `/` if `delimited`
+    async fn list(
+        &self,
+        prefix: &str,
+        delimited: bool,
+    ) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+        let mut keys = Vec::new();
+        let mut prefixes = Vec::new();
+
+        let mut pages = self
+            .client
+            .list_objects_v2()
+            .bucket(self.default_bucket())
+            .prefix(prefix)
+            .set_delimiter(delimited.then(|| "/".to_string()))
+            .into_paginator()
+            .send();
+
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            keys.extend(page.contents().iter().filter_map(|o| o.key.to_owned()));
+            prefixes.extend(
+                page.common_prefixes()
+                    .iter()
+                    .filter_map(|p| p.prefix.to_owned()),
+            );
+        }
+
+        Ok((keys, prefixes))
+    }
+
+    /// Delete objects, a thousand per request
+    async fn delete_keys(&self, keys: &[String]) -> anyhow::Result<()> {
+        for chunk in keys.chunks(1000) {
+            let objects = chunk
+                .iter()
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .collect::<Result<_, _>>()?;
+
+            self.client
+                .delete_objects()
+                .bucket(self.default_bucket())
+                .delete(
+                    Delete::builder()
+                        .set_objects(Some(objects))
+                        .quiet(true)
+                        .build()?,
+                )
+                .send()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stored document of a collection
+    async fn document(&self, id: &str) -> anyhow::Result<Option<Collection>> {
+        self.cache
+            .collection(id, async {
+                Ok(self
+                    .read_json(&collection_key(id))
+                    .await?
+                    .map(|(collection, _)| collection))
+            })
+            .await
+    }
+
+    /// Document of an existing collection
+    async fn existing(&self, id: &str) -> anyhow::Result<Collection> {
+        match self.document(id).await? {
+            Some(collection) => Ok(collection),
+            None => Err(Exception::new_from_status(404)
+                .detail(format!("Collection `{id}` does not exist"))
+                .into()),
+        }
+    }
+
+    /// Run a request for each input, a few at a time, keeping their order
+    async fn concurrently<I, T, F, Fut>(&self, inputs: Vec<I>, f: F) -> anyhow::Result<Vec<T>>
+    where
+        F: Fn(S3, I) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut inputs = inputs.into_iter().peekable();
+
+        while inputs.peek().is_some() {
+            let handles: Vec<_> = inputs
+                .by_ref()
+                .take(CONCURRENCY)
+                .map(|input| tokio::spawn(f(self.clone(), input)))
+                .collect();
+            for handle in handles {
+                outputs.push(handle.await??);
+            }
+        }
+
+        Ok(outputs)
+    }
+}
+
+fn collection_key(collection_id: &str) -> String {
+    format!("collections/{collection_id}/collection.json")
+}
+
+fn item_key(collection_id: &str, id: &str) -> String {
+    format!("collections/{collection_id}/items/{id}.json")
+}
+
+fn index_key(collection_id: &str) -> String {
+    format!("collections/{collection_id}/index.json")
+}
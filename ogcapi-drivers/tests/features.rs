@@ -0,0 +1,1798 @@
+/// Fixtures shared by the tests of the in-process drivers
+#[cfg(any(
+    feature = "memory",
+    feature = "geopackage",
+    feature = "files",
+    feature = "s3"
+))]
+mod common {
+    #[cfg(any(feature = "memory", feature = "geopackage", feature = "s3"))]
+    use ogcapi_drivers::{CollectionTransactions, FeatureTransactions};
+    use ogcapi_types::features::Feature;
+    #[cfg(any(feature = "memory", feature = "geopackage", feature = "s3"))]
+    use ogcapi_types::{
+        common::{Collection, Crs},
+        features::Geometry,
+    };
+    #[cfg(any(feature = "memory", feature = "geopackage", feature = "s3"))]
+    use serde_json::Value;
+
+    /// Names of the features, in order
+    pub fn names(features: &[Feature]) -> Vec<String> {
+        features
+            .iter()
+            .map(|f| {
+                f.properties.as_ref().unwrap()["name"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    /// Point feature of a place with its name and further properties
+    #[cfg(any(feature = "memory", feature = "geopackage", feature = "s3"))]
+    pub fn place<const N: usize>(
+        name: &str,
+        x: f64,
+        y: f64,
+        properties: [(&str, Value); N],
+    ) -> Feature {
+        let mut feature = Feature::new(Geometry::new_point([x, y]));
+        feature.properties = Some(
+            std::iter::once(("name".to_string(), name.into()))
+                .chain(properties.map(|(key, value)| (key.to_string(), value)))
+                .collect(),
+        );
+        feature
+    }
+
+    /// Create the `places` collection with the `features`
+    #[cfg(any(feature = "memory", feature = "geopackage", feature = "s3"))]
+    pub async fn setup_places(
+        driver: &(impl CollectionTransactions + FeatureTransactions),
+        features: &[Feature],
+    ) {
+        let collection = Collection {
+            id: "places".to_string(),
+            title: Some("Places".to_string()),
+            crs: vec![Crs::default2d()],
+            ..Default::default()
+        };
+        driver.create_collection(&collection).await.unwrap();
+        driver.create_features("places", features).await.unwrap();
+    }
+}
+
+#[cfg(all(feature = "postgres", feature = "features"))]
+mod postgres {
+    use futures::StreamExt;
+    use ogcapi_drivers::{
+        CollectionTransactions, FeatureChanges, FeatureTransactions, postgres::Db,
+    };
+    use ogcapi_types::{
+        common::{Bbox, Collection, Crs, Exception, GeometryColumn},
+        features::{ChangeType, Feature, FeatureId, Geometry, NumberMatched, Query, SortBy},
+    };
+
+    async fn setup(db: &Db, collection_id: &str) {
+        let collection = Collection {
+            id: collection_id.to_string(),
+            crs: vec![Crs::default2d()],
+            ..Default::default()
+        };
+        db.create_collection(&collection).await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([7.428959, 1.513394]));
+        feature.collection = Some(collection_id.to_string());
+        feature.properties = Some(serde_json::Map::from_iter([(
+            "name".to_string(),
+            "Bern".into(),
+        )]));
+        db.create_feature(&feature).await.unwrap();
+    }
+
+    async fn meta_collections_exist(db: &Db) -> bool {
+        sqlx::query_scalar("SELECT to_regclass('meta.collections') IS NOT NULL")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn malicious_collection_id(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        let collection_id = r#"x"; DROP TABLE meta.collections; --"#;
+        setup(&db, collection_id).await;
+
+        let fc = db
+            .list_items(collection_id, &Query::default())
+            .await
+            .unwrap();
+        assert_eq!(fc.number_matched, Some(1));
+
+        db.delete_collection(collection_id, None).await.unwrap();
+        assert!(meta_collections_exist(&db).await);
+    }
+
+    #[sqlx::test]
+    async fn malicious_key_value_pairs(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+
+        // values are compared literally
+        for v in ["x' OR '1'='1", "'; DROP TABLE meta.collections; --"] {
+            let mut query = Query::default();
+            query
+                .additional_parameters
+                .insert("name".to_string(), v.to_string());
+
+            let fc = db.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.number_matched, Some(0), "name={v}");
+        }
+
+        // keys are looked up literally, unknown properties do not restrict the result
+        let mut query = Query::default();
+        query.additional_parameters.insert(
+            "name' = 'x' OR properties ->> 'name".to_string(),
+            "Bern".to_string(),
+        );
+        let fc = db.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.number_matched, Some(1));
+
+        let mut query = Query::default();
+        query
+            .additional_parameters
+            .insert("name".to_string(), "Bern".to_string());
+        let fc = db.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.number_matched, Some(1));
+
+        assert!(meta_collections_exist(&db).await);
+    }
+
+    #[sqlx::test]
+    async fn numeric_key_value_pairs(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        let collection = Collection {
+            id: "places".to_string(),
+            crs: vec![Crs::default2d()],
+            ..Default::default()
+        };
+        db.create_collection(&collection).await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([8.5417, 47.3769]));
+        feature.collection = Some("places".to_string());
+        feature.properties = Some(serde_json::Map::from_iter([(
+            "population".to_string(),
+            10.into(),
+        )]));
+        db.create_feature(&feature).await.unwrap();
+
+        // numbers are compared by value
+        for (v, matched) in [
+            ("10", 1),
+            ("10.0", 1),
+            ("1e1", 1),
+            ("1", 0),
+            ("100", 0),
+            ("ten", 0),
+        ] {
+            let mut query = Query::default();
+            query
+                .additional_parameters
+                .insert("population".to_string(), v.to_string());
+            let fc = db.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.features.len(), matched, "population={v}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn keyset_pagination(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        let mut feature = Feature::new(Geometry::new_point([8.5417, 47.3769]));
+        feature.collection = Some("places".to_string());
+        db.create_feature(&feature).await.unwrap();
+
+        let mut query = Query {
+            limit: Some(1),
+            number_matched: Some(NumberMatched::Skip),
+            ..Default::default()
+        };
+        let first = db.list_items("places", &query).await.unwrap();
+        assert_eq!(first.number_matched, None);
+        assert_eq!(first.features.len(), 1);
+
+        query.next = first.next;
+        let second = db.list_items("places", &query).await.unwrap();
+        assert_eq!(second.features.len(), 1);
+        assert_ne!(first.features[0].id, second.features[0].id);
+        assert!(second.next.is_none());
+
+        query.next = Some("not a token".to_string());
+        assert!(db.list_items("places", &query).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn stream_items(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        let features: Vec<Feature> = (0..1500)
+            .map(|i| Feature::new(Geometry::new_point([i as f64 / 1000.0, 0.0])))
+            .collect();
+        db.create_features("places", &features).await.unwrap();
+
+        // rows are streamed, not paged
+        let streamed: Vec<Feature> = db
+            .stream_items("places", &Query::default())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 1501);
+
+        let query = Query {
+            limit: Some(1200),
+            offset: Some(2),
+            sortby: vec![SortBy::desc("name")],
+            ..Default::default()
+        };
+        let streamed: Vec<Feature> = db
+            .stream_items("places", &query)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 1200);
+
+        // errors of the statement are the first item
+        let mut streamed = db.stream_items("missing", &query);
+        assert!(streamed.next().await.unwrap().is_err());
+        assert!(streamed.next().await.is_none());
+    }
+
+    #[sqlx::test]
+    async fn sortby(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        for name in ["Zürich", "Aarau"] {
+            let mut feature = Feature::new(Geometry::new_point([8.0, 47.0]));
+            feature.collection = Some("places".to_string());
+            feature.properties = Some(serde_json::Map::from_iter([(
+                "name".to_string(),
+                name.into(),
+            )]));
+            db.create_feature(&feature).await.unwrap();
+        }
+
+        let mut query = Query {
+            limit: Some(2),
+            sortby: vec![SortBy::desc("name")],
+            ..Default::default()
+        };
+        let mut names = Vec::new();
+        loop {
+            let fc = db.list_items("places", &query).await.unwrap();
+            names.extend(
+                fc.features
+                    .iter()
+                    .map(|f| f.properties.as_ref().unwrap()["name"].to_string()),
+            );
+            match fc.next {
+                Some(next) => query.next = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(names, [r#""Zürich""#, r#""Bern""#, r#""Aarau""#]);
+    }
+
+    #[sqlx::test]
+    async fn property_selection(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+
+        let query = Query {
+            properties: Some(vec!["population".to_string()]),
+            skip_geometry: Some(true),
+            ..Default::default()
+        };
+        let fc = db.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.features[0].properties, Some(Default::default()));
+        assert_eq!(fc.features[0].geometry, None);
+
+        let fc = db.list_items("places", &Query::default()).await.unwrap();
+        assert!(fc.features[0].properties.as_ref().unwrap()["name"] == "Bern");
+        assert!(fc.features[0].geometry.is_some());
+    }
+
+    #[sqlx::test]
+    async fn conditional_writes(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        let fc = db.list_items("places", &Query::default()).await.unwrap();
+        let mut feature = fc.features[0].clone();
+        let id = feature.id.as_ref().unwrap().to_string();
+        feature.collection = Some("places".to_string());
+
+        let etag = db.feature_etag("places", &id).await.unwrap().unwrap();
+        db.update_feature(&feature, Some(&etag)).await.unwrap();
+
+        // replaced geometries are in the storage crs
+        let srid: i32 = sqlx::query_scalar(r#"SELECT ST_SRID(geom) FROM items."places""#)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(srid, 4326);
+
+        // missing features are not found
+        let mut missing = feature.clone();
+        missing.id = Some(FeatureId::String("missing".to_string()));
+        let err = db.update_feature(&missing, None).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(404));
+
+        // the version has changed
+        let err = db.update_feature(&feature, Some(&etag)).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(412));
+        assert!(db.delete_feature("places", &id, Some(&etag)).await.is_err());
+
+        let etag = db.feature_etag("places", &id).await.unwrap().unwrap();
+        db.delete_feature("places", &id, Some(&etag)).await.unwrap();
+        assert_eq!(db.feature_etag("places", &id).await.unwrap(), None);
+
+        let etag = db.collection_etag("places").await.unwrap().unwrap();
+        assert!(db.delete_collection("places", Some("0")).await.is_err());
+        db.delete_collection("places", Some(&etag)).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn merge_patch(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        let fc = db.list_items("places", &Query::default()).await.unwrap();
+        let id = fc.features[0].id.as_ref().unwrap().to_string();
+
+        let patch = serde_json::json!({
+            "properties": { "population": 134591 },
+            "geometry": { "type": "Point", "coordinates": [7.4474, 46.948] }
+        });
+        db.patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+
+        let feature = db
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        let properties = feature.properties.unwrap();
+        assert_eq!(properties["name"], "Bern");
+        assert_eq!(properties["population"], 134591);
+        assert_eq!(
+            feature.geometry,
+            Some(Geometry::new_point([7.4474, 46.948]))
+        );
+
+        let patch = serde_json::json!({ "properties": { "name": null } });
+        db.patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        let feature = db
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!feature.properties.unwrap().contains_key("name"));
+
+        assert!(
+            db.patch_feature("places", "missing", patch.as_object().unwrap(), None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[sqlx::test]
+    async fn bulk_ingestion(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+
+        let features: Vec<Feature> = ["Thun", r#"Biel\Bienne"#, "Köniz"]
+            .into_iter()
+            .map(|name| {
+                let mut feature = Feature::new(Geometry::new_point([7.5, 46.8]));
+                feature.properties = Some(serde_json::Map::from_iter([(
+                    "name".to_string(),
+                    name.into(),
+                )]));
+                feature
+            })
+            .collect();
+
+        let ids = db.create_features("places", &features).await.unwrap();
+        assert_eq!(ids.len(), 3);
+
+        let feature = db
+            .read_feature("places", &ids[1], &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.properties.unwrap()["name"], r#"Biel\Bienne"#);
+
+        // duplicate ids roll back the whole batch
+        let mut duplicate = features[0].clone();
+        duplicate.id = Some(FeatureId::String(ids[0].to_owned()));
+        let err = db
+            .create_features("places", &[features[2].clone(), duplicate])
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+
+        let fc = db.list_items("places", &Query::default()).await.unwrap();
+        assert_eq!(fc.number_matched, Some(4));
+    }
+
+    #[sqlx::test]
+    async fn changes(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+        let mut changes = db.changes("places").await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([7.63, 46.76]));
+        feature.collection = Some("places".to_string());
+        let id = db.create_feature(&feature).await.unwrap();
+        db.delete_feature("places", &id, None).await.unwrap();
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.r#type, ChangeType::Created);
+        assert_eq!(change.id, id);
+        assert!(change.feature.is_some());
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.r#type, ChangeType::Deleted);
+        assert_eq!(change.bbox, Some([7.63, 46.76, 7.63, 46.76]));
+
+        // bulk ingest is notified in batches, one change per feature
+        let features: Vec<_> = (0..200)
+            .map(|i| Feature::new(Geometry::new_point([7.0 + i as f64 / 100.0, 46.0])))
+            .collect();
+        db.create_features("places", &features).await.unwrap();
+        for _ in 0..200 {
+            let change = changes.next().await.unwrap().unwrap();
+            assert_eq!(change.r#type, ChangeType::Created);
+            assert!(change.bbox.is_some());
+        }
+    }
+
+    #[sqlx::test]
+    async fn versions(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        let mut collection = Collection {
+            id: "places".to_string(),
+            crs: vec![Crs::default2d()],
+            ..Default::default()
+        };
+        collection
+            .additional_properties
+            .insert("versioning".to_string(), true.into());
+        db.create_collection(&collection).await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([7.43, 46.95]));
+        feature.collection = Some("places".to_string());
+        let id = db.create_feature(&feature).await.unwrap();
+
+        let patch = serde_json::json!({ "properties": { "name": "Bern" } });
+        db.patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        db.delete_feature("places", &id, None).await.unwrap();
+
+        let versions = db
+            .feature_versions("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(versions.iter().all(|version| version.valid_to.is_some()));
+        assert_eq!(
+            versions[1].feature.properties.as_ref().unwrap()["name"],
+            "Bern"
+        );
+
+        // the feature as it was before it has been deleted
+        let query = Query {
+            version_at: versions[1].valid_from.into(),
+            ..Default::default()
+        };
+        let fc = db.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.features.len(), 1);
+
+        let fc = db.list_items("places", &Query::default()).await.unwrap();
+        assert!(fc.features.is_empty());
+
+        // collections without versioning
+        setup(&db, "other").await;
+        assert!(
+            db.feature_versions("other", &id, &Crs::default2d())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test]
+    async fn geometry_columns(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        let mut collection = Collection {
+            id: "lakes".to_string(),
+            crs: vec![Crs::default2d()],
+            ..Default::default()
+        };
+        collection
+            .additional_properties
+            .insert("versioning".to_string(), true.into());
+        db.create_collection(&collection).await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([7.72, 46.69]));
+        feature.collection = Some("lakes".to_string());
+        feature.properties = Some(serde_json::Map::from_iter([(
+            "outflow".to_string(),
+            serde_json::json!({"type": "Point", "coordinates": [7.63, 46.76]}),
+        )]));
+        let id = db.create_feature(&feature).await.unwrap();
+
+        // geometry columns added later on, also to the history
+        collection.geometry_columns = vec![GeometryColumn {
+            name: "outflow".to_string(),
+            title: None,
+            place: false,
+        }];
+        db.update_collection(&collection, None).await.unwrap();
+
+        let mut query = Query {
+            bbox: Some(Bbox::Bbox2D([7.6, 46.75, 7.65, 46.8])),
+            bbox_geometry: Some("outflow".to_string()),
+            ..Default::default()
+        };
+        let fc = db.list_items("lakes", &query).await.unwrap();
+        assert_eq!(fc.features.len(), 1);
+
+        query.bbox_geometry = None;
+        let fc = db.list_items("lakes", &query).await.unwrap();
+        assert!(fc.features.is_empty());
+
+        let patch = serde_json::json!({ "properties": { "name": "Thunersee" } });
+        db.patch_feature("lakes", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        let versions = db
+            .feature_versions("lakes", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(
+            versions[1].feature.properties.as_ref().unwrap()["outflow"]["type"],
+            "Point"
+        );
+
+        // values that are not geometries are ingested without one
+        let features: Vec<_> = [
+            serde_json::json!("Aare"),
+            serde_json::json!(42),
+            serde_json::json!({"name": "Aare"}),
+        ]
+        .into_iter()
+        .map(|outflow| {
+            let mut feature = Feature::new(Geometry::new_point([7.72, 46.69]));
+            feature.properties = Some(serde_json::Map::from_iter([(
+                "outflow".to_string(),
+                outflow,
+            )]));
+            feature
+        })
+        .collect();
+        db.create_features("lakes", &features).await.unwrap();
+        query.bbox_geometry = Some("outflow".to_string());
+        let fc = db.list_items("lakes", &query).await.unwrap();
+        assert_eq!(fc.features.len(), 1);
+
+        // names longer than identifiers are truncated, also when comparing
+        collection.geometry_columns.push(GeometryColumn {
+            name: "outflow".repeat(10),
+            title: None,
+            place: false,
+        });
+        db.update_collection(&collection, None).await.unwrap();
+        db.update_collection(&collection, None).await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn antimeridian(pool: sqlx::PgPool) -> () {
+        let db = Db::from(pool);
+
+        setup(&db, "places").await;
+
+        // geographic boxes spanning the antimeridian are split
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([170.0, -10.0, 10.0, 10.0])),
+            ..Default::default()
+        };
+        let fc = db.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.features.len(), 1);
+
+        // projected boxes must not
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([
+                2_700_000.0,
+                1_100_000.0,
+                2_500_000.0,
+                1_300_000.0,
+            ])),
+            bbox_crs: Some(Crs::from_epsg(2056)),
+            ..Default::default()
+        };
+        let err = db.list_items("places", &query).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+    }
+}
+
+#[cfg(feature = "memory")]
+mod memory {
+    use futures::StreamExt;
+    use ogcapi_drivers::{
+        CollectionTransactions, FeatureChanges, FeatureTransactions, memory::Memory,
+    };
+    use ogcapi_types::{
+        common::{Bbox, Collection, Crs, Exception, GeometryColumn},
+        features::{ChangeType, Feature, FeatureId, Geometry, Query, SortBy},
+    };
+
+    use crate::common::{names, place, setup_places};
+
+    async fn setup() -> Memory {
+        let memory = Memory::new();
+        let features = [
+            place("Bern", 7.44, 46.95, []),
+            place("Zürich", 8.54, 47.37, []),
+            place("Aarau", 8.04, 47.39, []),
+        ];
+        setup_places(&memory, &features).await;
+
+        memory
+    }
+
+    #[tokio::test]
+    async fn sortby() {
+        let memory = setup().await;
+
+        let mut query = Query {
+            limit: Some(2),
+            sortby: vec![SortBy::desc("name")],
+            ..Default::default()
+        };
+        let mut names_sorted = Vec::new();
+        loop {
+            let fc = memory.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.number_matched, Some(3));
+            names_sorted.extend(names(&fc.features));
+            match fc.next {
+                Some(next) => query.next = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(names_sorted, ["Zürich", "Bern", "Aarau"]);
+    }
+
+    #[tokio::test]
+    async fn spatial_and_filter() {
+        let memory = setup().await;
+
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([8.0, 47.0, 9.0, 48.0])),
+            filter: Some("name LIKE '%rich'".to_string()),
+            ..Default::default()
+        };
+        let fc = memory.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich"]);
+
+        // coordinates are not transformed
+        let query = Query {
+            crs: Some(Crs::from_epsg(2056)),
+            ..Default::default()
+        };
+        let err = memory.list_items("places", &query).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+    }
+
+    #[tokio::test]
+    async fn axis_order() {
+        let memory = setup().await;
+
+        // EPSG:4326 is latitude first
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([47.0, 8.0, 48.0, 9.0])),
+            bbox_crs: Some(Crs::from_epsg(4326)),
+            crs: Some(Crs::from_epsg(4326)),
+            ..Default::default()
+        };
+        let fc = memory.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich", "Aarau"]);
+        assert_eq!(
+            fc.features[0].geometry,
+            Some(Geometry::new_point([47.37, 8.54]))
+        );
+
+        let id = fc.features[0].id.as_ref().unwrap().to_string();
+        let feature = memory
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.geometry, Some(Geometry::new_point([8.54, 47.37])));
+    }
+
+    #[tokio::test]
+    async fn antimeridian() {
+        let memory = setup().await;
+
+        let features: Vec<Feature> = [("Suva", 178.44, -18.14), ("Apia", -171.76, -13.83)]
+            .into_iter()
+            .map(|(name, x, y)| {
+                let mut feature = Feature::new(Geometry::new_point([x, y]));
+                feature.properties = Some(serde_json::Map::from_iter([(
+                    "name".to_string(),
+                    name.into(),
+                )]));
+                feature
+            })
+            .collect();
+        memory.create_features("places", &features).await.unwrap();
+
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([170.0, -20.0, -170.0, -10.0])),
+            ..Default::default()
+        };
+        let fc = memory.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Suva", "Apia"]);
+    }
+
+    #[tokio::test]
+    async fn geometry_columns() {
+        let memory = Memory::new();
+
+        let collection = Collection {
+            id: "lakes".to_string(),
+            geometry_columns: vec![GeometryColumn {
+                name: "outflow".to_string(),
+                title: None,
+                place: false,
+            }],
+            ..Default::default()
+        };
+        memory.create_collection(&collection).await.unwrap();
+
+        // Lake Thun flows out at Thun into the Aare
+        let mut feature = Feature::new(Geometry::new_point([7.72, 46.69]));
+        feature.properties = Some(serde_json::Map::from_iter([(
+            "outflow".to_string(),
+            serde_json::json!({"type": "Point", "coordinates": [7.63, 46.76]}),
+        )]));
+        memory.create_features("lakes", &[feature]).await.unwrap();
+
+        let mut query = Query {
+            bbox: Some(Bbox::Bbox2D([7.6, 46.75, 7.65, 46.8])),
+            ..Default::default()
+        };
+        let fc = memory.list_items("lakes", &query).await.unwrap();
+        assert!(fc.features.is_empty());
+
+        query.bbox_geometry = Some("outflow".to_string());
+        let fc = memory.list_items("lakes", &query).await.unwrap();
+        assert_eq!(fc.features.len(), 1);
+
+        query.bbox_geometry = Some("inflow".to_string());
+        let err = memory.list_items("lakes", &query).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+    }
+
+    #[tokio::test]
+    async fn conditional_writes() {
+        let memory = setup().await;
+
+        let fc = memory
+            .list_items("places", &Query::default())
+            .await
+            .unwrap();
+        let feature = fc.features[0].clone();
+        let id = feature.id.as_ref().unwrap().to_string();
+
+        let etag = memory.feature_etag("places", &id).await.unwrap().unwrap();
+        memory.update_feature(&feature, Some(&etag)).await.unwrap();
+        let err = memory
+            .update_feature(&feature, Some(&etag))
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(412));
+
+        let patch = serde_json::json!({ "properties": { "population": 134591 } });
+        memory
+            .patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        let feature = memory
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.properties.unwrap()["population"], 134591);
+
+        let etag = memory.feature_etag("places", &id).await.unwrap().unwrap();
+        memory
+            .delete_feature("places", &id, Some(&etag))
+            .await
+            .unwrap();
+        assert_eq!(memory.feature_etag("places", &id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn bulk_ingestion() {
+        let memory = setup().await;
+
+        // duplicate ids roll back the whole batch
+        let mut feature = Feature::new(Geometry::new_point([7.5, 46.8]));
+        feature.id = Some(FeatureId::String("thun".to_string()));
+        let err = memory
+            .create_features("places", &[feature.clone(), feature])
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+
+        let fc = memory
+            .list_items("places", &Query::default())
+            .await
+            .unwrap();
+        assert_eq!(fc.number_matched, Some(3));
+
+        let collection = memory.read_collection("places").await.unwrap().unwrap();
+        let spatial = collection.extent.unwrap().spatial.unwrap();
+        assert_eq!(spatial.bbox, [Bbox::Bbox2D([7.44, 46.95, 8.54, 47.39])]);
+    }
+
+    #[tokio::test]
+    async fn changes() {
+        let memory = setup().await;
+
+        let mut changes = memory.changes("places").await.unwrap();
+
+        let mut feature = Feature::new(Geometry::new_point([7.63, 46.76]));
+        feature.collection = Some("places".to_string());
+        let id = memory.create_feature(&feature).await.unwrap();
+        let patch = serde_json::json!({ "properties": { "name": "Thun" } });
+        memory
+            .patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        memory.delete_feature("places", &id, None).await.unwrap();
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.r#type, ChangeType::Created);
+        assert_eq!(change.bbox, Some([7.63, 46.76, 7.63, 46.76]));
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.r#type, ChangeType::Updated);
+        assert_eq!(names(&[change.feature.unwrap()]), ["Thun"]);
+
+        let change = changes.next().await.unwrap().unwrap();
+        assert_eq!(change.r#type, ChangeType::Deleted);
+        assert_eq!(change.id, id);
+        assert!(change.feature.is_none());
+
+        // subscriptions are scoped by collection
+        let err = memory.changes("missing").await.err().unwrap();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn stream_items() {
+        let memory = setup().await;
+
+        // more features than fit into one page
+        let features: Vec<Feature> = (0..1500)
+            .map(|i| Feature::new(Geometry::new_point([i as f64 / 1000.0, 0.0])))
+            .collect();
+        memory.create_features("places", &features).await.unwrap();
+
+        let query = Query::default();
+        let streamed: Vec<Feature> = memory
+            .stream_items("places", &query)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 1503);
+
+        // limit and offset apply to the whole stream
+        let query = Query {
+            limit: Some(1200),
+            offset: Some(2),
+            sortby: vec![SortBy::desc("name")],
+            ..Default::default()
+        };
+        let streamed: Vec<Feature> = memory
+            .stream_items("places", &query)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 1200);
+        assert_eq!(names(&streamed[..1]), ["Aarau"]);
+    }
+}
+
+#[cfg(feature = "memory")]
+mod routing {
+    use std::sync::Arc;
+
+    use ogcapi_drivers::{
+        CollectionTransactions, FeatureTransactions, memory::Memory, routing::Routing,
+    };
+    use ogcapi_types::{
+        common::{Collection, Query as CollectionQuery, query::LimitOffsetPagination},
+        features::{Feature, Geometry, Query},
+    };
+
+    #[tokio::test]
+    async fn federation() {
+        let default = Memory::new();
+        let lakes = Memory::new();
+        let swiss = Memory::new();
+
+        let collections = Routing::<dyn CollectionTransactions>::new(Arc::new(default.clone()))
+            .prefix("ch-", Arc::new(swiss.clone()))
+            .collection("lakes", Arc::new(lakes.clone()));
+        let features = Routing::<dyn FeatureTransactions>::new(Arc::new(default.clone()))
+            .prefix("ch-", Arc::new(swiss.clone()))
+            .collection("lakes", Arc::new(lakes.clone()));
+
+        for id in ["places", "lakes", "ch-lakes", "ch-places"] {
+            let collection = Collection {
+                id: id.to_string(),
+                ..Default::default()
+            };
+            collections.create_collection(&collection).await.unwrap();
+        }
+
+        // collections of other backends are hidden
+        let collection = Collection {
+            id: "rivers".to_string(),
+            ..Default::default()
+        };
+        lakes.create_collection(&collection).await.unwrap();
+
+        assert!(default.read_collection("places").await.unwrap().is_some());
+        assert!(lakes.read_collection("lakes").await.unwrap().is_some());
+        assert!(swiss.read_collection("ch-lakes").await.unwrap().is_some());
+        assert!(
+            default
+                .read_collection("ch-places")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let listed = collections
+            .list_collections(&Default::default())
+            .await
+            .unwrap();
+        let ids: Vec<_> = listed.collections.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["ch-lakes", "ch-places", "lakes", "places"]);
+        assert_eq!(listed.number_matched, Some(4));
+
+        // paging applies to the merged collections
+        let query = CollectionQuery {
+            pagination: LimitOffsetPagination {
+                limit: Some(2),
+                offset: Some(1),
+            },
+            ..Default::default()
+        };
+        let listed = collections.list_collections(&query).await.unwrap();
+        let ids: Vec<_> = listed.collections.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["ch-places", "lakes"]);
+        assert_eq!(listed.number_matched, Some(4));
+        assert_eq!(listed.number_returned, Some(2));
+
+        let mut feature = Feature::new(Geometry::new_point([8.3, 47.05]));
+        feature.collection = Some("ch-lakes".to_string());
+        features.create_feature(&feature).await.unwrap();
+
+        let fc = swiss
+            .list_items("ch-lakes", &Query::default())
+            .await
+            .unwrap();
+        assert_eq!(fc.features.len(), 1);
+        let fc = features
+            .list_items("ch-lakes", &Query::default())
+            .await
+            .unwrap();
+        assert_eq!(fc.features.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shared_backend() {
+        let swiss: Arc<dyn CollectionTransactions> = Arc::new(Memory::new());
+        let collections = Routing::<dyn CollectionTransactions>::new(Arc::new(Memory::new()))
+            .prefix("ch-", swiss.clone())
+            .collection("lakes", swiss.clone());
+
+        for id in ["places", "lakes", "ch-lakes"] {
+            let collection = Collection {
+                id: id.to_string(),
+                ..Default::default()
+            };
+            collections.create_collection(&collection).await.unwrap();
+        }
+
+        let listed = collections
+            .list_collections(&Default::default())
+            .await
+            .unwrap();
+        let ids: Vec<_> = listed.collections.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["ch-lakes", "lakes", "places"]);
+        assert_eq!(listed.number_matched, Some(3));
+    }
+}
+
+#[cfg(feature = "geopackage")]
+mod geopackage {
+    use ogcapi_drivers::{CollectionTransactions, FeatureTransactions, geopackage::GeoPackage};
+    use ogcapi_types::{
+        common::{Bbox, Collection, Crs, Exception},
+        features::{Feature, FeatureId, Geometry, Query, SortBy},
+    };
+
+    use crate::common::{names, place, setup_places};
+
+    async fn setup(name: &str) -> GeoPackage {
+        let path = std::env::temp_dir().join(format!("ogcapi-{name}-{}.gpkg", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let gpkg = GeoPackage::open(&path).await.unwrap();
+
+        let features = [
+            place("Bern", 7.44, 46.95, [("population", 134591.into())]),
+            place("Zürich", 8.54, 47.37, [("population", 421878.into())]),
+            place("Aarau", 8.04, 47.39, [("population", 21726.into())]),
+        ];
+        setup_places(&gpkg, &features).await;
+
+        gpkg
+    }
+
+    #[tokio::test]
+    async fn collections() {
+        let gpkg = setup("collections").await;
+
+        let collection = gpkg.read_collection("places").await.unwrap().unwrap();
+        assert_eq!(collection.title.as_deref(), Some("Places"));
+        assert_eq!(collection.storage_crs, Some(Crs::default2d()));
+        // from the spatial index, which rounds outwards to single precision
+        let spatial = collection.extent.unwrap().spatial.unwrap();
+        let Bbox::Bbox2D(bbox) = spatial.bbox[0] else {
+            panic!("expected a 2D bbox")
+        };
+        for (value, expected) in bbox.into_iter().zip([7.44, 46.95, 8.54, 47.39]) {
+            assert!((value - expected).abs() < 1e-5);
+        }
+
+        let err = gpkg
+            .create_collection(&Collection {
+                id: "places".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(409));
+
+        let etag = gpkg.collection_etag("places").await.unwrap().unwrap();
+        let mut collection = gpkg.read_collection("places").await.unwrap().unwrap();
+        collection.keywords = vec!["cities".to_string()];
+        gpkg.update_collection(&collection, Some(&etag))
+            .await
+            .unwrap();
+        let err = gpkg
+            .update_collection(&collection, Some(&etag))
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(412));
+
+        // round trip of the collection document
+        let collection = gpkg.read_collection("places").await.unwrap().unwrap();
+        assert_eq!(collection.keywords, ["cities"]);
+
+        gpkg.delete_collection("places", None).await.unwrap();
+        assert!(gpkg.read_collection("places").await.unwrap().is_none());
+        let collections = gpkg.list_collections(&Default::default()).await.unwrap();
+        assert!(collections.collections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn items() {
+        let gpkg = setup("items").await;
+
+        let mut query = Query {
+            limit: Some(2),
+            sortby: vec![SortBy::desc("population")],
+            ..Default::default()
+        };
+        let mut sorted = Vec::new();
+        loop {
+            let fc = gpkg.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.number_matched, Some(3));
+            sorted.extend(names(&fc.features));
+            match fc.next {
+                Some(next) => query.next = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(sorted, ["Zürich", "Bern", "Aarau"]);
+
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([8.0, 47.0, 9.0, 48.0])),
+            filter: Some("population > 100000".to_string()),
+            ..Default::default()
+        };
+        let fc = gpkg.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich"]);
+        assert_eq!(fc.number_matched, Some(1));
+
+        let query = Query {
+            additional_parameters: [("name".to_string(), "Aarau".to_string())].into(),
+            ..Default::default()
+        };
+        let fc = gpkg.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Aarau"]);
+
+        let queryables = gpkg.queryables("places").await.unwrap();
+        assert_eq!(
+            queryables.queryables["population"].r#type.as_deref(),
+            Some("integer")
+        );
+    }
+
+    #[tokio::test]
+    async fn transactions() {
+        let gpkg = setup("transactions").await;
+
+        let feature = gpkg
+            .read_feature("places", "1", &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.id, Some(FeatureId::Integer(1)));
+        assert_eq!(feature.geometry, Some(Geometry::new_point([7.44, 46.95])));
+
+        let etag = gpkg.feature_etag("places", "1").await.unwrap().unwrap();
+        let patch = serde_json::json!({ "properties": { "canton": "BE", "population": null } });
+        gpkg.patch_feature("places", "1", patch.as_object().unwrap(), Some(&etag))
+            .await
+            .unwrap();
+        let err = gpkg
+            .patch_feature("places", "1", patch.as_object().unwrap(), Some(&etag))
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(412));
+
+        let feature = gpkg
+            .read_feature("places", "1", &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        let properties = feature.properties.unwrap();
+        assert_eq!(properties["canton"], "BE");
+        assert_eq!(properties["population"], serde_json::Value::Null);
+
+        // duplicate ids roll back the whole batch
+        let mut feature = Feature::new(Geometry::new_point([7.63, 46.75]));
+        feature.id = Some(FeatureId::Integer(4));
+        let err = gpkg
+            .create_features("places", &[feature.clone(), feature])
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+        let fc = gpkg.list_items("places", &Query::default()).await.unwrap();
+        assert_eq!(fc.number_matched, Some(3));
+
+        gpkg.delete_feature("places", "2", None).await.unwrap();
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([8.0, 47.0, 9.0, 48.0])),
+            ..Default::default()
+        };
+        let fc = gpkg.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Aarau"]);
+    }
+}
+
+#[cfg(feature = "files")]
+mod files {
+    use std::sync::Arc;
+
+    use arrow_array::{
+        ArrayRef, BinaryArray, Float64Array, Int64Array, RecordBatch, StringArray, StructArray,
+    };
+    use arrow_schema::{DataType, Field};
+    use parquet::{arrow::ArrowWriter, file::properties::WriterProperties, format::KeyValue};
+
+    use ogcapi_drivers::{CollectionTransactions, FeatureTransactions, files::Files};
+    use ogcapi_types::{
+        common::{Bbox, Collection, Crs, Exception},
+        features::{FeatureId, Geometry, Query, SortBy},
+    };
+
+    use crate::common::names;
+
+    const PLACES: [(&str, f64, f64, i64); 5] = [
+        ("Bern", 7.44, 46.95, 134591),
+        ("Zürich", 8.54, 47.37, 421878),
+        ("Aarau", 8.04, 47.39, 21726),
+        ("Genève", 6.14, 46.20, 203856),
+        ("Luzern", 8.31, 47.05, 82620),
+    ];
+
+    /// Directory with a GeoParquet file of places in row groups of two, with a
+    /// bounding box covering
+    fn setup(name: &str) -> Files {
+        let dir = std::env::temp_dir().join(format!("ogcapi-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let wkb: Vec<Vec<u8>> = PLACES
+            .iter()
+            .map(|(_, x, y, _)| {
+                let mut wkb = vec![1, 1, 0, 0, 0];
+                wkb.extend(x.to_le_bytes());
+                wkb.extend(y.to_le_bytes());
+                wkb
+            })
+            .collect();
+        let coordinate = |f: fn(&(&str, f64, f64, i64)) -> f64| -> ArrayRef {
+            Arc::new(Float64Array::from_iter_values(PLACES.iter().map(f)))
+        };
+        let bbox = StructArray::from(vec![
+            (
+                Arc::new(Field::new("xmin", DataType::Float64, false)),
+                coordinate(|p| p.1),
+            ),
+            (
+                Arc::new(Field::new("ymin", DataType::Float64, false)),
+                coordinate(|p| p.2),
+            ),
+            (
+                Arc::new(Field::new("xmax", DataType::Float64, false)),
+                coordinate(|p| p.1),
+            ),
+            (
+                Arc::new(Field::new("ymax", DataType::Float64, false)),
+                coordinate(|p| p.2),
+            ),
+        ]);
+        let batch = RecordBatch::try_from_iter([
+            (
+                "name",
+                Arc::new(StringArray::from_iter_values(PLACES.iter().map(|p| p.0))) as ArrayRef,
+            ),
+            (
+                "population",
+                Arc::new(Int64Array::from_iter_values(PLACES.iter().map(|p| p.3))),
+            ),
+            (
+                "geometry",
+                Arc::new(BinaryArray::from_iter_values(wkb.iter())),
+            ),
+            ("bbox", Arc::new(bbox)),
+        ])
+        .unwrap();
+
+        let geo = serde_json::json!({
+            "version": "1.1.0",
+            "primary_column": "geometry",
+            "columns": {
+                "geometry": {
+                    "encoding": "WKB",
+                    "geometry_types": ["Point"],
+                    "covering": {
+                        "bbox": {
+                            "xmin": ["bbox", "xmin"],
+                            "ymin": ["bbox", "ymin"],
+                            "xmax": ["bbox", "xmax"],
+                            "ymax": ["bbox", "ymax"]
+                        }
+                    }
+                }
+            }
+        });
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .set_key_value_metadata(Some(vec![KeyValue::new(
+                "geo".to_string(),
+                geo.to_string(),
+            )]))
+            .build();
+
+        let file = std::fs::File::create(dir.join("places.parquet")).unwrap();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        // not a collection
+        std::fs::write(dir.join("README.md"), "Places").unwrap();
+
+        Files::new(dir)
+    }
+
+    #[tokio::test]
+    async fn collections() {
+        let files = setup("files-collections");
+
+        let collections = files.list_collections(&Default::default()).await.unwrap();
+        assert_eq!(collections.collections.len(), 1);
+
+        let collection = files.read_collection("places").await.unwrap().unwrap();
+        assert_eq!(collection.storage_crs, Some(Crs::default2d()));
+        // from the row group statistics
+        let spatial = collection.extent.unwrap().spatial.unwrap();
+        assert_eq!(spatial.bbox, [Bbox::Bbox2D([6.14, 46.20, 8.54, 47.39])]);
+
+        assert!(files.read_collection("README").await.unwrap().is_none());
+        assert!(files.collection_etag("places").await.unwrap().is_some());
+
+        // read-only
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        let err = files
+            .update_collection(&collection, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(405));
+        let err = files.delete_collection("places", None).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(405));
+    }
+
+    #[tokio::test]
+    async fn items() {
+        let files = setup("files-items");
+
+        let mut query = Query {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut all = Vec::new();
+        loop {
+            let fc = files.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.number_matched, Some(5));
+            all.extend(names(&fc.features));
+            match fc.next {
+                Some(next) => query.next = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(all, PLACES.map(|p| p.0));
+
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([8.0, 47.0, 9.0, 48.0])),
+            filter: Some("population > 50000".to_string()),
+            sortby: vec![SortBy::desc("population")],
+            ..Default::default()
+        };
+        let fc = files.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich", "Luzern"]);
+        assert_eq!(fc.features[0].id, Some(FeatureId::Integer(1)));
+        assert_eq!(
+            fc.features[0].geometry,
+            Some(Geometry::new_point([8.54, 47.37]))
+        );
+
+        let feature = files
+            .read_feature("places", "3", &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&[feature]), ["Genève"]);
+        assert!(
+            files
+                .read_feature("places", "5", &Crs::default2d())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let queryables = files.queryables("places").await.unwrap();
+        assert_eq!(
+            queryables.queryables["population"].r#type.as_deref(),
+            Some("integer")
+        );
+        assert!(!queryables.queryables.contains_key("bbox"));
+
+        // read-only
+        let err = files.delete_feature("places", "1", None).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(405));
+    }
+}
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region, RequestChecksumCalculation};
+    use axum::{
+        Router,
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, Method, StatusCode, Uri, header},
+        response::{IntoResponse, Response},
+    };
+
+    use ogcapi_drivers::{CollectionTransactions, FeatureTransactions, s3::S3};
+    use ogcapi_types::{
+        common::{Bbox, Collection, Crs, Exception},
+        features::{Feature, FeatureId, Geometry, Query, SortBy},
+    };
+
+    use crate::common::{names, place, setup_places};
+
+    /// Objects by key with their entity tag
+    type Bucket = Arc<Mutex<BTreeMap<String, (Bytes, String)>>>;
+
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+    /// Minimal stand-in for MinIO, serving a single bucket with path style
+    /// addressing
+    async fn serve(
+        State(bucket): State<Bucket>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        let path = uri.path().trim_start_matches('/');
+        let key = path
+            .split_once('/')
+            .map(|(_, key)| decode(key))
+            .unwrap_or_default();
+        let params: BTreeMap<String, String> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (k, v) = p.split_once('=').unwrap_or((p, ""));
+                (decode(k), decode(v))
+            })
+            .collect();
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let mut objects = bucket.lock().unwrap();
+
+        match method {
+            Method::GET if key.is_empty() => {
+                let prefix = params.get("prefix").cloned().unwrap_or_default();
+                let delimiter = params.get("delimiter");
+
+                let mut contents = String::new();
+                let mut prefixes = Vec::new();
+                for k in objects.keys().filter(|k| k.starts_with(&prefix)) {
+                    match delimiter.and_then(|d| k[prefix.len()..].find(d.as_str())) {
+                        Some(i) => {
+                            let p = &k[..prefix.len() + i + 1];
+                            if !prefixes.contains(&p) {
+                                prefixes.push(p);
+                            }
+                        }
+                        None => contents.push_str(&format!("<Contents><Key>{k}</Key></Contents>")),
+                    }
+                }
+                let prefixes: String = prefixes
+                    .iter()
+                    .map(|p| format!("<CommonPrefixes><Prefix>{p}</Prefix></CommonPrefixes>"))
+                    .collect();
+
+                xml(
+                    StatusCode::OK,
+                    format!(
+                        "<ListBucketResult><Prefix>{prefix}</Prefix><IsTruncated>false</IsTruncated>{contents}{prefixes}</ListBucketResult>"
+                    ),
+                )
+            }
+            Method::POST if params.contains_key("delete") => {
+                let body = String::from_utf8_lossy(&body);
+                for k in body.split("<Key>").skip(1) {
+                    objects.remove(k.split("</Key>").next().unwrap());
+                }
+                xml(StatusCode::OK, "<DeleteResult></DeleteResult>".to_string())
+            }
+            Method::PUT => {
+                let current = objects.get(&key).map(|(_, etag)| etag.as_str());
+                let precondition = match (header("if-none-match"), header("if-match")) {
+                    (Some("*"), _) => current.is_none(),
+                    (_, Some(etag)) => current == Some(etag),
+                    _ => true,
+                };
+                if !precondition {
+                    return xml(
+                        StatusCode::PRECONDITION_FAILED,
+                        "<Error><Code>PreconditionFailed</Code></Error>".to_string(),
+                    );
+                }
+
+                let etag = format!("\"{}\"", SEQUENCE.fetch_add(1, Ordering::Relaxed));
+                objects.insert(key, (body, etag.to_owned()));
+                ([(header::ETAG, etag)], StatusCode::OK).into_response()
+            }
+            Method::GET | Method::HEAD => match objects.get(&key) {
+                Some((body, etag)) => (
+                    [(header::ETAG, etag.to_owned())],
+                    if method == Method::GET {
+                        body.to_owned()
+                    } else {
+                        Bytes::new()
+                    },
+                )
+                    .into_response(),
+                None if method == Method::HEAD => StatusCode::NOT_FOUND.into_response(),
+                None => xml(
+                    StatusCode::NOT_FOUND,
+                    "<Error><Code>NoSuchKey</Code></Error>".to_string(),
+                ),
+            },
+            Method::DELETE => {
+                objects.remove(&key);
+                StatusCode::NO_CONTENT.into_response()
+            }
+            _ => StatusCode::NOT_IMPLEMENTED.into_response(),
+        }
+    }
+
+    fn xml(status: StatusCode, body: String) -> Response {
+        (status, [(header::CONTENT_TYPE, "application/xml")], body).into_response()
+    }
+
+    /// Percent-decode a path or query component
+    fn decode(s: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut chars = s.bytes();
+        while let Some(b) = chars.next() {
+            match b {
+                b'%' => {
+                    let hex = [chars.next().unwrap(), chars.next().unwrap()];
+                    let hex = std::str::from_utf8(&hex).unwrap();
+                    bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                }
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// Driver of a bucket served by the stand-in, with its objects
+    async fn setup() -> (S3, Bucket) {
+        let bucket = Bucket::default();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new().fallback(serve).with_state(bucket.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("minio", "minio123", None, None, "test"))
+            .endpoint_url(endpoint)
+            .force_path_style(true)
+            .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
+            .build();
+        let mut s3 = S3::new_with(aws_sdk_s3::Client::from_conf(config)).await;
+        s3.set_default_bucket("ogcapi");
+
+        let features = [
+            ("Bern", 7.44, 46.95, "2024-01-01T00:00:00Z"),
+            ("Zürich", 8.54, 47.37, "2024-06-01T00:00:00Z"),
+            ("Aarau", 8.04, 47.39, "2025-01-01T00:00:00Z"),
+        ]
+        .map(|(name, x, y, datetime)| {
+            let mut feature = place(name, x, y, [("datetime", datetime.into())]);
+            feature.id = Some(FeatureId::String(name.to_lowercase()));
+            feature
+        });
+        setup_places(&s3, &features).await;
+
+        (s3, bucket)
+    }
+
+    #[tokio::test]
+    async fn collections() {
+        let (s3, bucket) = setup().await;
+
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        let err = s3.create_collection(&collection).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(409));
+
+        let collection = Collection {
+            id: "empty".to_string(),
+            ..Default::default()
+        };
+        s3.create_collection(&collection).await.unwrap();
+
+        let collections = s3.list_collections(&Default::default()).await.unwrap();
+        let ids: Vec<_> = collections.collections.iter().map(|c| &c.id).collect();
+        assert_eq!(ids, ["empty", "places"]);
+
+        let collection = s3.read_collection("places").await.unwrap().unwrap();
+        let spatial = collection.extent.unwrap().spatial.unwrap();
+        assert_eq!(spatial.bbox, [Bbox::Bbox2D([7.44, 46.95, 8.54, 47.39])]);
+
+        // with its items and index
+        s3.delete_collection("places", None).await.unwrap();
+        assert!(s3.read_collection("places").await.unwrap().is_none());
+        assert!(
+            bucket
+                .lock()
+                .unwrap()
+                .keys()
+                .all(|key| key.starts_with("collections/empty/"))
+        );
+
+        let err = s3
+            .list_items("places", &Query::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn items() {
+        let (s3, bucket) = setup().await;
+
+        let mut query = Query {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut all = Vec::new();
+        loop {
+            let fc = s3.list_items("places", &query).await.unwrap();
+            assert_eq!(fc.number_matched, Some(3));
+            all.extend(names(&fc.features));
+            match fc.next {
+                Some(next) => query.next = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(all, ["Aarau", "Bern", "Zürich"]);
+
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([8.0, 47.0, 9.0, 48.0])),
+            sortby: vec![SortBy::desc("name")],
+            ..Default::default()
+        };
+        let fc = s3.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich", "Aarau"]);
+
+        let query = Query {
+            datetime: Some("2024-03-01T00:00:00Z/..".parse().unwrap()),
+            filter: Some("name LIKE '%rich'".to_string()),
+            ..Default::default()
+        };
+        let fc = s3.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Zürich"]);
+
+        // the index is rebuilt from the items
+        bucket
+            .lock()
+            .unwrap()
+            .remove("collections/places/index.json");
+        let query = Query {
+            datetime: Some("../2024-12-31T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let fc = s3.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Bern", "Zürich"]);
+
+        let queryables = s3.queryables("places").await.unwrap();
+        assert_eq!(
+            queryables.queryables["name"].r#type.as_deref(),
+            Some("string")
+        );
+
+        // coordinates are not transformed
+        let err = s3
+            .read_feature("places", "bern", &Crs::from_epsg(2056))
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+    }
+
+    #[tokio::test]
+    async fn transactions() {
+        let (s3, _) = setup().await;
+
+        // duplicate ids roll back the whole batch
+        let mut thun = Feature::new(Geometry::new_point([7.63, 46.76]));
+        thun.properties = Some(serde_json::Map::from_iter([(
+            "name".to_string(),
+            "Thun".into(),
+        )]));
+        let mut bern = thun.clone();
+        bern.id = Some(FeatureId::String("bern".to_string()));
+        let err = s3
+            .create_features("places", &[thun.clone(), bern])
+            .await
+            .unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(400));
+        let fc = s3.list_items("places", &Query::default()).await.unwrap();
+        assert_eq!(fc.number_matched, Some(3));
+
+        // ids are assigned
+        thun.collection = Some("places".to_string());
+        let id = s3.create_feature(&thun).await.unwrap();
+        let mut feature = s3
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(names(&[feature.clone()]), ["Thun"]);
+
+        // the index follows updates
+        feature.geometry = Some(Geometry::new_point([8.0, 47.5]));
+        s3.update_feature(&feature, None).await.unwrap();
+        let query = Query {
+            bbox: Some(Bbox::Bbox2D([7.9, 47.4, 8.1, 47.6])),
+            ..Default::default()
+        };
+        let fc = s3.list_items("places", &query).await.unwrap();
+        assert_eq!(names(&fc.features), ["Thun"]);
+
+        let patch = serde_json::json!({ "properties": { "population": 43568 } });
+        s3.patch_feature("places", &id, patch.as_object().unwrap(), None)
+            .await
+            .unwrap();
+        let feature = s3
+            .read_feature("places", &id, &Crs::default2d())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(feature.properties.unwrap()["population"], 43568);
+
+        s3.delete_feature("places", &id, None).await.unwrap();
+        assert!(
+            s3.read_feature("places", &id, &Crs::default2d())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let fc = s3.list_items("places", &query).await.unwrap();
+        assert_eq!(fc.number_matched, Some(0));
+
+        let mut missing = Feature::new(Geometry::new_point([7.0, 46.0]));
+        missing.id = Some(FeatureId::String("missing".to_string()));
+        missing.collection = Some("places".to_string());
+        let err = s3.update_feature(&missing, None).await.unwrap_err();
+        assert_eq!(err.downcast::<Exception>().unwrap().status, Some(404));
+    }
+}
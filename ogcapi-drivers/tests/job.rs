@@ -10,7 +10,7 @@ mod postgres {
 
     #[sqlx::test]
     async fn job_handling(pool: sqlx::PgPool) -> () {
-        let db = Db { pool };
+        let db = Db::from(pool);
 
         let job = StatusInfo {
             job_id: "test-job".to_string(),
@@ -33,7 +33,7 @@ mod postgres {
 
     #[sqlx::test]
     async fn job_result(pool: sqlx::PgPool) -> () {
-        let db = Db { pool };
+        let db = Db::from(pool);
 
         let job = StatusInfo {
             job_id: "test-job".to_string(),
@@ -89,7 +89,7 @@ mod postgres {
     async fn job_status_list(pool: sqlx::PgPool) -> () {
         use ogcapi_types::common::Link;
 
-        let db = Db { pool };
+        let db = Db::from(pool);
 
         let job = StatusInfo {
             job_id: "test-job-status-list".to_string(),
@@ -120,7 +120,7 @@ mod postgres {
 
     #[sqlx::test]
     async fn job_result_failed(pool: sqlx::PgPool) -> () {
-        let db = Db { pool };
+        let db = Db::from(pool);
 
         let job = StatusInfo {
             job_id: "test-job".to_string(),
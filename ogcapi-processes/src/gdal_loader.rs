@@ -211,7 +211,7 @@ impl Processor for GdalLoader {
         // Setup driver
         let db = Db::setup(&Url::parse(&inputs.database_url)?).await?;
 
-        db.delete_collection(&collection.id).await.unwrap();
+        db.delete_collection(&collection.id, None).await.unwrap();
         db.create_collection(&collection).await.unwrap();
 
         // Set concrete geometry type if possible https://github.com/georust/gdal/blob/00adecc94361228a2197224205fc9260d14d7549/gdal-sys/prebuilt-bindings/gdal_3.4.rs#L3454
@@ -156,7 +156,7 @@ impl Processor for GeoJsonLoader {
             ..Default::default()
         };
 
-        db.delete_collection(&collection.id).await?;
+        db.delete_collection(&collection.id, None).await?;
         db.create_collection(&collection).await?;
 
         // Load features
@@ -0,0 +1,219 @@
+//! FlatGeobuf encoding
+//!
+//! Files start with magic bytes and a header, followed by the size-prefixed
+//! features (<https://flatgeobuf.org>). As the number and extent of streamed
+//! features are unknown up front, files are written without a spatial index
+//! and the geometry types are those of the features.
+use flatbuffers::{FlatBufferBuilder, TableFinishedWIPOffset, WIPOffset};
+use geojson::{GeometryValue, Position};
+
+use ogcapi_types::{common::Crs, features::Feature};
+
+use super::{Cell, Column, ColumnType};
+
+const MAGIC: &[u8] = b"fgb\x03fgb\x00";
+
+/// Writer of a file
+pub(super) struct Writer {
+    columns: Vec<Column>,
+    fbb: FlatBufferBuilder<'static>,
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub(super) fn new(columns: Vec<Column>, crs: &Crs) -> Self {
+        let mut fbb = FlatBufferBuilder::new();
+
+        let offsets: Vec<_> = columns
+            .iter()
+            .map(|column| {
+                let name = fbb.create_string(&column.name);
+                let title = column.title.as_deref().map(|t| fbb.create_string(t));
+                let description = column.description.as_deref().map(|d| fbb.create_string(d));
+                let table = fbb.start_table();
+                fbb.push_slot_always(4, name);
+                fbb.push_slot_always(6, column_type(column.r#type));
+                if let Some(title) = title {
+                    fbb.push_slot_always(8, title);
+                }
+                if let Some(description) = description {
+                    fbb.push_slot_always(10, description);
+                }
+                fbb.end_table(table)
+            })
+            .collect();
+        let offsets = fbb.create_vector(&offsets);
+
+        // the organization defaults to EPSG
+        let table = fbb.start_table();
+        fbb.push_slot(6, crs.as_srid(), 0);
+        let crs = fbb.end_table(table);
+
+        // geometries of unknown types carry their own, there is no index
+        let header = fbb.start_table();
+        fbb.push_slot_always(18, offsets);
+        fbb.push_slot_always(22, 0u16);
+        fbb.push_slot_always(24, crs);
+        let header = fbb.end_table(header);
+        fbb.finish_size_prefixed(header, None);
+
+        let mut buf = MAGIC.to_vec();
+        buf.extend(fbb.finished_data());
+        fbb.reset();
+
+        Writer { columns, fbb, buf }
+    }
+}
+
+impl super::Writer for Writer {
+    fn write(&mut self, feature: &Feature) -> anyhow::Result<()> {
+        let mut properties = Vec::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            let Some(cell) = column.cell(feature) else {
+                continue;
+            };
+            properties.extend((i as u16).to_le_bytes());
+            match cell {
+                Cell::Boolean(value) => properties.push(value as u8),
+                Cell::Integer(value) => properties.extend(value.to_le_bytes()),
+                Cell::Number(value) => properties.extend(value.to_le_bytes()),
+                Cell::String(value) => {
+                    properties.extend((value.len() as u32).to_le_bytes());
+                    properties.extend(value.as_bytes());
+                }
+            }
+        }
+
+        let fbb = &mut self.fbb;
+        let geometry = feature
+            .geometry
+            .as_ref()
+            .map(|geometry| encode_geometry(fbb, &geometry.value));
+        let properties = fbb.create_vector(&properties);
+        let table = fbb.start_table();
+        if let Some(geometry) = geometry {
+            fbb.push_slot_always(4, geometry);
+        }
+        fbb.push_slot_always(6, properties);
+        let table = fbb.end_table(table);
+        fbb.finish_size_prefixed(table, None);
+
+        self.buf.extend(fbb.finished_data());
+        fbb.reset();
+
+        Ok(())
+    }
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<Vec<u8>> {
+        Ok(self.buf)
+    }
+}
+
+/// Column type of the FlatGeobuf schema
+fn column_type(r#type: ColumnType) -> u8 {
+    match r#type {
+        ColumnType::Boolean => 2,
+        ColumnType::Integer => 7,
+        ColumnType::Number => 10,
+        ColumnType::String => 11,
+        ColumnType::Json => 12,
+        ColumnType::DateTime => 13,
+    }
+}
+
+/// Encode a geometry with its type
+fn encode_geometry<'a>(
+    fbb: &mut FlatBufferBuilder<'a>,
+    value: &GeometryValue,
+) -> WIPOffset<TableFinishedWIPOffset> {
+    match value {
+        GeometryValue::Point { coordinates } => encode(fbb, 1, &[coordinates], &[], &[]),
+        GeometryValue::LineString { coordinates } => {
+            encode(fbb, 2, &Vec::from_iter(coordinates), &[], &[])
+        }
+        GeometryValue::Polygon { coordinates } => {
+            let (positions, ends) = flatten(coordinates);
+            encode(fbb, 3, &positions, &ends, &[])
+        }
+        GeometryValue::MultiPoint { coordinates } => {
+            encode(fbb, 4, &Vec::from_iter(coordinates), &[], &[])
+        }
+        GeometryValue::MultiLineString { coordinates } => {
+            let (positions, ends) = flatten(coordinates);
+            encode(fbb, 5, &positions, &ends, &[])
+        }
+        GeometryValue::MultiPolygon { coordinates } => {
+            let parts: Vec<_> = coordinates
+                .iter()
+                .map(|polygon| {
+                    let (positions, ends) = flatten(polygon);
+                    encode(fbb, 3, &positions, &ends, &[])
+                })
+                .collect();
+            encode(fbb, 6, &[], &[], &parts)
+        }
+        GeometryValue::GeometryCollection { geometries } => {
+            let parts: Vec<_> = geometries
+                .iter()
+                .map(|geometry| encode_geometry(fbb, &geometry.value))
+                .collect();
+            encode(fbb, 7, &[], &[], &parts)
+        }
+    }
+}
+
+/// Positions of parts and the positions at which the parts end
+fn flatten(parts: &[Vec<Position>]) -> (Vec<&Position>, Vec<u32>) {
+    let mut ends = Vec::with_capacity(parts.len());
+    let mut positions = Vec::new();
+    for part in parts {
+        positions.extend(part);
+        ends.push(positions.len() as u32);
+    }
+    (positions, ends)
+}
+
+/// Geometry of a type with positions, the ends of its parts or its parts
+fn encode<'a>(
+    fbb: &mut FlatBufferBuilder<'a>,
+    r#type: u8,
+    positions: &[&Position],
+    ends: &[u32],
+    parts: &[WIPOffset<TableFinishedWIPOffset>],
+) -> WIPOffset<TableFinishedWIPOffset> {
+    let xy: Vec<f64> = positions
+        .iter()
+        .flat_map(|position| position.as_slice().iter().take(2).copied())
+        .collect();
+    // heights only if all positions have them
+    let z: Option<Vec<f64>> = positions
+        .iter()
+        .map(|position| position.as_slice().get(2).copied())
+        .collect();
+
+    // single parts need no ends
+    let ends = (ends.len() > 1).then(|| fbb.create_vector(ends));
+    let xy = (!xy.is_empty()).then(|| fbb.create_vector(&xy));
+    let z = z.filter(|z| !z.is_empty()).map(|z| fbb.create_vector(&z));
+    let parts = (!parts.is_empty()).then(|| fbb.create_vector(parts));
+
+    let table = fbb.start_table();
+    if let Some(ends) = ends {
+        fbb.push_slot_always(4, ends);
+    }
+    if let Some(xy) = xy {
+        fbb.push_slot_always(6, xy);
+    }
+    if let Some(z) = z {
+        fbb.push_slot_always(8, z);
+    }
+    fbb.push_slot_always(16, r#type);
+    if let Some(parts) = parts {
+        fbb.push_slot_always(18, parts);
+    }
+    fbb.end_table(table)
+}
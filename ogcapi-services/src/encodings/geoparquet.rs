@@ -0,0 +1,306 @@
+//! GeoParquet encoding
+//!
+//! Geometries are WKB in the `geometry` column, described by the `geo` file
+//! metadata (<https://geoparquet.org>). Features are written in row groups
+//! as they arrive, the bounding box is added to the metadata in the footer.
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{BinaryBuilder, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder},
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use geojson::{GeometryValue, Position};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression, file::metadata::KeyValue,
+    file::properties::WriterProperties,
+};
+use serde_json::{Value, json};
+
+use ogcapi_types::{common::Crs, features::Feature};
+
+use super::{Cell, Column, ColumnType};
+
+/// Name of the geometry column
+const GEOMETRY: &str = "geometry";
+
+/// Number of rows of a row group
+const ROW_GROUP_SIZE: usize = 8192;
+
+/// Writer of a file
+pub(super) struct Writer {
+    columns: Vec<Column>,
+    schema: SchemaRef,
+    builders: Vec<Builder>,
+    geometries: BinaryBuilder,
+    rows: usize,
+    writer: ArrowWriter<Vec<u8>>,
+    crs: Option<Value>,
+    bbox: Option<[f64; 4]>,
+}
+
+/// Builder of the array of a column
+enum Builder {
+    Boolean(BooleanBuilder),
+    Integer(Int64Builder),
+    Number(Float64Builder),
+    String(StringBuilder),
+}
+
+impl Writer {
+    pub(super) fn new(columns: Vec<Column>, crs: &Crs) -> anyhow::Result<Self> {
+        let mut fields: Vec<Field> = columns
+            .iter()
+            .map(|column| {
+                let data_type = match column.r#type {
+                    ColumnType::Boolean => DataType::Boolean,
+                    ColumnType::Integer => DataType::Int64,
+                    ColumnType::Number => DataType::Float64,
+                    ColumnType::String | ColumnType::DateTime | ColumnType::Json => DataType::Utf8,
+                };
+                Field::new(&column.name, data_type, true)
+            })
+            .collect();
+        fields.push(Field::new(GEOMETRY, DataType::Binary, true));
+        let schema = Arc::new(Schema::new(fields));
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_SIZE)
+            .build();
+        let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))?;
+
+        // `None` is OGC:CRS84, other systems are referenced by their EPSG code
+        let crs = (*crs != Crs::default2d()).then(|| {
+            json!({
+                "id": { "authority": "EPSG", "code": crs.as_srid() }
+            })
+        });
+
+        Ok(Writer {
+            builders: columns
+                .iter()
+                .map(|column| Builder::new(column.r#type))
+                .collect(),
+            columns,
+            schema,
+            geometries: BinaryBuilder::new(),
+            rows: 0,
+            writer,
+            crs,
+            bbox: None,
+        })
+    }
+
+    /// Write the buffered rows as a row group
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        let mut arrays: Vec<ArrayRef> = self.builders.iter_mut().map(Builder::finish).collect();
+        arrays.push(Arc::new(self.geometries.finish()));
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+
+        self.writer.write(&batch)?;
+        self.writer.flush()?;
+        self.rows = 0;
+
+        Ok(())
+    }
+}
+
+impl super::Writer for Writer {
+    fn write(&mut self, feature: &Feature) -> anyhow::Result<()> {
+        for (column, builder) in self.columns.iter().zip(self.builders.iter_mut()) {
+            builder.append(column.cell(feature));
+        }
+
+        match feature.geometry.as_ref() {
+            Some(geometry) => {
+                let mut wkb = Vec::new();
+                encode_geometry(&mut wkb, &geometry.value, &mut self.bbox);
+                self.geometries.append_value(wkb);
+            }
+            None => self.geometries.append_null(),
+        }
+
+        self.rows += 1;
+        if self.rows == ROW_GROUP_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn buffer(&mut self) -> &mut Vec<u8> {
+        self.writer.inner_mut()
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<Vec<u8>> {
+        self.flush()?;
+
+        let mut column = json!({
+            "encoding": "WKB",
+            "geometry_types": [],
+        });
+        if let Some(crs) = self.crs.take() {
+            column["crs"] = crs;
+        }
+        if let Some(bbox) = self.bbox {
+            column["bbox"] = json!(bbox);
+        }
+        let metadata = json!({
+            "version": "1.1.0",
+            "primary_column": GEOMETRY,
+            "columns": { GEOMETRY: column },
+        });
+        self.writer
+            .append_key_value_metadata(KeyValue::new("geo".to_string(), metadata.to_string()));
+
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+impl Builder {
+    fn new(r#type: ColumnType) -> Self {
+        match r#type {
+            ColumnType::Boolean => Builder::Boolean(BooleanBuilder::new()),
+            ColumnType::Integer => Builder::Integer(Int64Builder::new()),
+            ColumnType::Number => Builder::Number(Float64Builder::new()),
+            ColumnType::String | ColumnType::DateTime | ColumnType::Json => {
+                Builder::String(StringBuilder::new())
+            }
+        }
+    }
+
+    fn append(&mut self, cell: Option<Cell>) {
+        match (self, cell) {
+            (Builder::Boolean(builder), Some(Cell::Boolean(value))) => builder.append_value(value),
+            (Builder::Integer(builder), Some(Cell::Integer(value))) => builder.append_value(value),
+            (Builder::Number(builder), Some(Cell::Number(value))) => builder.append_value(value),
+            (Builder::String(builder), Some(Cell::String(value))) => builder.append_value(value),
+            (Builder::Boolean(builder), _) => builder.append_null(),
+            (Builder::Integer(builder), _) => builder.append_null(),
+            (Builder::Number(builder), _) => builder.append_null(),
+            (Builder::String(builder), _) => builder.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Builder::Boolean(builder) => Arc::new(builder.finish()),
+            Builder::Integer(builder) => Arc::new(builder.finish()),
+            Builder::Number(builder) => Arc::new(builder.finish()),
+            Builder::String(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+/// Encode a geometry as little endian ISO WKB, extending the bounding box
+fn encode_geometry(wkb: &mut Vec<u8>, value: &GeometryValue, bbox: &mut Option<[f64; 4]>) {
+    let z = has_z(value);
+    match value {
+        GeometryValue::Point { coordinates } => {
+            header(wkb, 1, z);
+            position(wkb, coordinates, z, bbox);
+        }
+        GeometryValue::LineString { coordinates } => {
+            header(wkb, 2, z);
+            positions(wkb, coordinates, z, bbox);
+        }
+        GeometryValue::Polygon { coordinates } => {
+            header(wkb, 3, z);
+            rings(wkb, coordinates, z, bbox);
+        }
+        GeometryValue::MultiPoint { coordinates } => {
+            header(wkb, 4, z);
+            count(wkb, coordinates.len());
+            for point in coordinates {
+                header(wkb, 1, z);
+                position(wkb, point, z, bbox);
+            }
+        }
+        GeometryValue::MultiLineString { coordinates } => {
+            header(wkb, 5, z);
+            count(wkb, coordinates.len());
+            for line in coordinates {
+                header(wkb, 2, z);
+                positions(wkb, line, z, bbox);
+            }
+        }
+        GeometryValue::MultiPolygon { coordinates } => {
+            header(wkb, 6, z);
+            count(wkb, coordinates.len());
+            for polygon in coordinates {
+                header(wkb, 3, z);
+                rings(wkb, polygon, z, bbox);
+            }
+        }
+        GeometryValue::GeometryCollection { geometries } => {
+            header(wkb, 7, z);
+            count(wkb, geometries.len());
+            for geometry in geometries {
+                encode_geometry(wkb, &geometry.value, bbox);
+            }
+        }
+    }
+}
+
+/// Whether all positions of a geometry have a height
+fn has_z(value: &GeometryValue) -> bool {
+    let z = |position: &Position| position.len() > 2;
+    match value {
+        GeometryValue::Point { coordinates } => z(coordinates),
+        GeometryValue::LineString { coordinates } | GeometryValue::MultiPoint { coordinates } => {
+            coordinates.iter().all(z)
+        }
+        GeometryValue::Polygon { coordinates } | GeometryValue::MultiLineString { coordinates } => {
+            coordinates.iter().flatten().all(z)
+        }
+        GeometryValue::MultiPolygon { coordinates } => {
+            coordinates.iter().flatten().flatten().all(z)
+        }
+        GeometryValue::GeometryCollection { geometries } => {
+            geometries.iter().all(|geometry| has_z(&geometry.value))
+        }
+    }
+}
+
+/// Byte order and type of a geometry
+fn header(wkb: &mut Vec<u8>, r#type: u32, z: bool) {
+    wkb.push(1);
+    wkb.extend((if z { r#type + 1000 } else { r#type }).to_le_bytes());
+}
+
+fn count(wkb: &mut Vec<u8>, count: usize) {
+    wkb.extend((count as u32).to_le_bytes());
+}
+
+/// Coordinates of a position, `NaN` if it is empty
+fn position(wkb: &mut Vec<u8>, position: &Position, z: bool, bbox: &mut Option<[f64; 4]>) {
+    let position = position.as_slice();
+    if let [x, y, ..] = *position {
+        *bbox = Some(match *bbox {
+            Some([x1, y1, x2, y2]) => [x1.min(x), y1.min(y), x2.max(x), y2.max(y)],
+            None => [x, y, x, y],
+        });
+    }
+    for i in 0..if z { 3 } else { 2 } {
+        wkb.extend(position.get(i).copied().unwrap_or(f64::NAN).to_le_bytes());
+    }
+}
+
+fn positions(wkb: &mut Vec<u8>, positions: &[Position], z: bool, bbox: &mut Option<[f64; 4]>) {
+    count(wkb, positions.len());
+    for p in positions {
+        position(wkb, p, z, bbox);
+    }
+}
+
+fn rings(wkb: &mut Vec<u8>, rings: &[Vec<Position>], z: bool, bbox: &mut Option<[f64; 4]>) {
+    count(wkb, rings.len());
+    for ring in rings {
+        positions(wkb, ring, z, bbox);
+    }
+}
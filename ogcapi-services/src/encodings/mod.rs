@@ -0,0 +1,320 @@
+//! Streamed encodings of features
+//!
+//! Items are downloaded as FlatGeobuf or GeoParquet by streaming the features
+//! of a driver through a writer into the response body, without collecting
+//! them. The columns are derived from the schema of the collection up front,
+//! properties it does not describe are dropped.
+#[cfg(feature = "flatgeobuf")]
+mod flatgeobuf;
+#[cfg(feature = "geoparquet")]
+mod geoparquet;
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use futures::{
+    SinkExt, Stream, StreamExt, TryStreamExt,
+    channel::mpsc,
+    stream::{self, BoxStream},
+};
+use serde_json::Value;
+
+#[cfg(feature = "flatgeobuf")]
+use ogcapi_types::common::media_type::FLAT_GEOBUF;
+#[cfg(feature = "geoparquet")]
+use ogcapi_types::common::media_type::PARQUET;
+use ogcapi_types::{
+    common::Crs,
+    features::{Feature, Queryable},
+};
+
+/// Number of features buffered between a driver and a writer
+const CAPACITY: usize = 256;
+
+/// Size from which the encoded features are sent
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Encoder of a stream of features
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoder {
+    #[cfg(feature = "flatgeobuf")]
+    FlatGeobuf,
+    #[cfg(feature = "geoparquet")]
+    GeoParquet,
+}
+
+impl Encoder {
+    /// Encoder of a media type, `None` for documents
+    pub(crate) fn new(media_type: &str) -> Option<Self> {
+        match media_type {
+            #[cfg(feature = "flatgeobuf")]
+            FLAT_GEOBUF => Some(Encoder::FlatGeobuf),
+            #[cfg(feature = "geoparquet")]
+            PARQUET => Some(Encoder::GeoParquet),
+            _ => None,
+        }
+    }
+
+    /// Response with the features of a stream, encoded as they arrive
+    pub(crate) fn response<S>(
+        self,
+        mut headers: HeaderMap,
+        columns: Vec<Column>,
+        crs: &Crs,
+        features: S,
+    ) -> anyhow::Result<Response>
+    where
+        S: Stream<Item = anyhow::Result<Feature>> + Send + 'static,
+    {
+        let (media_type, writer): (_, Box<dyn Writer>) = match self {
+            #[cfg(feature = "flatgeobuf")]
+            Encoder::FlatGeobuf => (FLAT_GEOBUF, Box::new(flatgeobuf::Writer::new(columns, crs))),
+            #[cfg(feature = "geoparquet")]
+            Encoder::GeoParquet => (PARQUET, Box::new(geoparquet::Writer::new(columns, crs)?)),
+        };
+        headers.insert(CONTENT_TYPE, media_type.parse().unwrap());
+
+        let chunks = chunks(writer, features.boxed())
+            .inspect_err(|e| tracing::error!("Encoding error: {:?}", e));
+
+        Ok((headers, Body::from_stream(chunks)).into_response())
+    }
+}
+
+/// Writer of an encoding, which buffers the encoded features
+trait Writer: Send {
+    fn write(&mut self, feature: &Feature) -> anyhow::Result<()>;
+
+    /// Bytes encoded so far that have not been taken
+    fn buffer(&mut self) -> &mut Vec<u8>;
+
+    /// The remaining bytes
+    fn finish(self: Box<Self>) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Chunks of the bytes of a writer fed with the features of a stream
+fn chunks(
+    writer: Box<dyn Writer>,
+    features: BoxStream<'static, anyhow::Result<Feature>>,
+) -> impl Stream<Item = anyhow::Result<Vec<u8>>> {
+    stream::try_unfold(
+        (Some(writer), features),
+        |(writer, mut features)| async move {
+            let Some(mut writer) = writer else {
+                return Ok(None);
+            };
+
+            while let Some(feature) = features.next().await {
+                writer.write(&feature?)?;
+                if writer.buffer().len() >= CHUNK_SIZE {
+                    let chunk = std::mem::take(writer.buffer());
+                    return Ok(Some((chunk, (Some(writer), features))));
+                }
+            }
+
+            Ok(Some((writer.finish()?, (None, features))))
+        },
+    )
+}
+
+/// Stream of the features of a stream borrowing from `owner`, forwarded by a
+/// task so that it outlives the handler
+pub(crate) fn forward<T, F>(owner: T, features: F) -> impl Stream<Item = anyhow::Result<Feature>>
+where
+    T: Send + Sync + 'static,
+    F: for<'a> FnOnce(&'a T) -> BoxStream<'a, anyhow::Result<Feature>> + Send + 'static,
+{
+    let (mut tx, rx) = mpsc::channel(CAPACITY);
+
+    tokio::spawn(async move {
+        let mut features = features(&owner);
+        while let Some(feature) = features.next().await {
+            let failed = feature.is_err();
+            // stop once the response is dropped or the stream failed
+            if tx.send(feature).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// Column of the features
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Column {
+    pub(crate) name: String,
+    pub(crate) r#type: ColumnType,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    /// Whether the column holds the feature ids
+    id: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    Boolean,
+    Integer,
+    Number,
+    String,
+    DateTime,
+    /// Any other value, as JSON text
+    Json,
+}
+
+/// Value of a column
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl Column {
+    /// Value of a feature, `None` if it is missing or does not fit the type
+    fn cell(&self, feature: &Feature) -> Option<Cell> {
+        if self.id {
+            return feature.id.as_ref().map(|id| Cell::String(id.to_string()));
+        }
+
+        let value = feature.properties.as_ref()?.get(&self.name)?;
+        match (self.r#type, value) {
+            (_, Value::Null) => None,
+            (ColumnType::Boolean, value) => value.as_bool().map(Cell::Boolean),
+            (ColumnType::Integer, value) => value.as_i64().map(Cell::Integer),
+            (ColumnType::Number, value) => value.as_f64().map(Cell::Number),
+            (_, Value::String(value)) => Some(Cell::String(value.to_owned())),
+            (_, value) => Some(Cell::String(value.to_string())),
+        }
+    }
+}
+
+/// Columns of the ids and the properties of a schema, sorted by name and
+/// restricted to a selection of properties
+///
+/// Geometries are not columns, the primary one is encoded as the geometry of
+/// the features.
+pub(crate) fn columns(
+    properties: &HashMap<String, Queryable>,
+    selection: Option<&[String]>,
+) -> Vec<Column> {
+    let mut columns: Vec<Column> = properties
+        .iter()
+        .filter(|(_, property)| {
+            !property
+                .format
+                .as_deref()
+                .is_some_and(|format| format.starts_with("geometry-"))
+        })
+        .map(|(name, property)| {
+            let r#type = match (property.r#type.as_deref(), property.format.as_deref()) {
+                (Some("boolean"), _) => ColumnType::Boolean,
+                (Some("integer"), _) => ColumnType::Integer,
+                (Some("number"), _) => ColumnType::Number,
+                (Some("string"), Some("date-time")) => ColumnType::DateTime,
+                (Some("string"), _) => ColumnType::String,
+                _ => ColumnType::Json,
+            };
+            Column {
+                name: name.to_owned(),
+                r#type,
+                title: property.title.to_owned(),
+                description: property.description.to_owned(),
+                id: property.additional_properties.get("x-ogc-role") == Some(&"id".into()),
+            }
+        })
+        .filter(|column| column.id || selection.is_none_or(|names| names.contains(&column.name)))
+        .collect();
+
+    // the ids come first
+    columns.sort_by(|a, b| b.id.cmp(&a.id).then_with(|| a.name.cmp(&b.name)));
+    if !columns.first().is_some_and(|column| column.id)
+        && !columns.iter().any(|column| column.name == "id")
+    {
+        columns.insert(
+            0,
+            Column {
+                name: "id".to_string(),
+                r#type: ColumnType::String,
+                title: None,
+                description: None,
+                id: true,
+            },
+        );
+    }
+
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_columns() {
+        let mut id = Queryable::new("string");
+        id.additional_properties
+            .insert("x-ogc-role".to_string(), "id".into());
+        let properties = HashMap::from([
+            ("fid".to_string(), id),
+            ("name".to_string(), Queryable::new("string")),
+            ("population".to_string(), Queryable::new("integer")),
+            (
+                "updated".to_string(),
+                Queryable::new("string").format("date-time"),
+            ),
+            (
+                "geometry".to_string(),
+                Queryable::default().format("geometry-any"),
+            ),
+            ("tags".to_string(), Queryable::new("array")),
+        ]);
+
+        let columns = columns(&properties, None);
+        assert_eq!(
+            columns
+                .iter()
+                .map(|column| (column.name.as_str(), column.r#type))
+                .collect::<Vec<_>>(),
+            [
+                ("fid", ColumnType::String),
+                ("name", ColumnType::String),
+                ("population", ColumnType::Integer),
+                ("tags", ColumnType::Json),
+                ("updated", ColumnType::DateTime),
+            ]
+        );
+
+        let mut feature: Feature = serde_json::from_value(serde_json::json!({
+            "type": "Feature",
+            "id": 1,
+            "geometry": null,
+            "properties": { "population": "many", "tags": ["a"] }
+        }))
+        .unwrap();
+        assert_eq!(
+            columns[0].cell(&feature),
+            Some(Cell::String("1".to_string()))
+        );
+        assert_eq!(columns[2].cell(&feature), None);
+        assert_eq!(
+            columns[3].cell(&feature),
+            Some(Cell::String(r#"["a"]"#.to_string()))
+        );
+
+        // ids are always encoded
+        feature.properties = None;
+        let selection = ["name".to_string()];
+        let columns = super::columns(&HashMap::new(), Some(&selection));
+        assert_eq!(columns.len(), 1);
+        assert_eq!(
+            columns[0].cell(&feature),
+            Some(Cell::String("1".to_string()))
+        );
+    }
+}
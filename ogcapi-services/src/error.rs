@@ -18,7 +18,7 @@ pub enum Error {
 
     /// Return `500 Internal Server Error` on a `anyhow::Error`.
     #[error("an internal server error occurred")]
-    Anyhow(#[from] anyhow::Error),
+    Anyhow(anyhow::Error),
 
     /// Return `500 Internal Server Error` on a `url::ParseError`.
     #[error("an internal server error occurred")]
@@ -33,6 +33,16 @@ pub enum Error {
     ApiException(#[from] Exception),
 }
 
+/// Exceptions raised by drivers are passed through, other errors are internal.
+impl From<anyhow::Error> for Error {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<Exception>() {
+            Ok(exception) => Error::ApiException(exception),
+            Err(error) => Error::Anyhow(error),
+        }
+    }
+}
+
 impl Error {
     fn status_code(&self) -> StatusCode {
         match self {
@@ -0,0 +1,132 @@
+//! HTML encoding
+//!
+//! Resources are rendered with the [`minijinja`] templates of
+//! `assets/templates`, which are compiled into the binary. A template is
+//! rendered with the JSON representation of the resource as its context.
+use std::sync::LazyLock;
+
+use minijinja::Environment;
+use serde::Serialize;
+use url::Url;
+
+use crate::negotiation::set_f;
+
+static TEMPLATES: LazyLock<Environment<'static>> = LazyLock::new(|| {
+    let mut env = Environment::new();
+    for (name, source) in [
+        ("base.html", include_str!("../assets/templates/base.html")),
+        ("map.html", include_str!("../assets/templates/map.html")),
+        (
+            "landing.html",
+            include_str!("../assets/templates/landing.html"),
+        ),
+        (
+            "conformance.html",
+            include_str!("../assets/templates/conformance.html"),
+        ),
+        (
+            "collections.html",
+            include_str!("../assets/templates/collections.html"),
+        ),
+        (
+            "collection.html",
+            include_str!("../assets/templates/collection.html"),
+        ),
+        ("items.html", include_str!("../assets/templates/items.html")),
+        (
+            "feature.html",
+            include_str!("../assets/templates/feature.html"),
+        ),
+    ] {
+        env.add_template(name, source)
+            .unwrap_or_else(|e| panic!("Invalid template `{name}`: {e}"));
+    }
+    env.add_filter("f", f);
+    env
+});
+
+/// Render a resource with a template
+pub(crate) fn render<T: Serialize>(template: &str, resource: &T) -> anyhow::Result<String> {
+    Ok(TEMPLATES.get_template(template)?.render(resource)?)
+}
+
+/// Filter setting the `f` query parameter of a link, e.g.
+/// `{{ link.href | f("html") }}`
+fn f(href: String, f: String) -> String {
+    match Url::parse(&href) {
+        Ok(mut url) => {
+            set_f(&mut url, &f);
+            url.into()
+        }
+        Err(_) if href.contains('?') => format!("{href}&f={f}"),
+        Err(_) => format!("{href}?f={f}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ogcapi_types::{
+        common::{
+            Collection, Link,
+            link_rel::{ALTERNATE, ITEMS, SELF},
+            media_type::{GEO_JSON, HTML},
+        },
+        features::{Feature, FeatureCollection, Geometry},
+    };
+
+    use super::*;
+
+    /// Page with the slashes that are escaped in attributes restored
+    fn page<T: Serialize>(template: &str, resource: &T) -> String {
+        render(template, resource).unwrap().replace("&#x2f;", "/")
+    }
+
+    #[test]
+    fn templates() {
+        let collection = Collection {
+            id: "places".to_string(),
+            title: Some("Places & <names>".to_string()),
+            links: vec![Link::new(
+                "http://localhost/collections/places/items",
+                ITEMS,
+            )],
+            ..Default::default()
+        };
+        let html = page("collection.html", &collection);
+        assert!(html.contains("<h1>Places &amp; &lt;names&gt;</h1>"));
+        assert!(html.contains(r#"href="http://localhost/collections/places/items?f=html""#));
+
+        let mut feature = Feature::new(Geometry::new_point([7.44, 46.95]));
+        feature.links = vec![
+            Link::new("http://localhost/collections/places/items/1?f=html", SELF).mediatype(HTML),
+            Link::new(
+                "http://localhost/collections/places/items/1?f=json",
+                ALTERNATE,
+            )
+            .mediatype(GEO_JSON),
+        ];
+        let mut fc = FeatureCollection::new(vec![feature.clone()]);
+        fc.links = feature.links.clone();
+
+        // maps are fed by the GeoJSON link
+        for html in [page("feature.html", &feature), page("items.html", &fc)] {
+            assert!(
+                html.contains(
+                    r#"data-geojson="http://localhost/collections/places/items/1?f=json""#
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn filter() {
+        assert_eq!(
+            f(
+                "http://localhost/items?limit=1&f=json".to_string(),
+                "html".to_string()
+            ),
+            "http://localhost/items?limit=1&f=html"
+        );
+        assert_eq!(f(".".to_string(), "html".to_string()), ".?f=html");
+    }
+}
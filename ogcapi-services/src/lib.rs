@@ -1,6 +1,11 @@
 mod config;
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+mod encodings;
 mod error;
 mod extractors;
+#[cfg(feature = "html")]
+mod html;
+mod negotiation;
 mod openapi;
 #[cfg(feature = "processes")]
 mod processes;
@@ -9,11 +14,11 @@ mod service;
 mod state;
 pub mod telemetry;
 
-pub use config::Config;
+pub use config::{Config, DatabaseConfig};
 pub use error::Error;
 pub use openapi::ApiDoc;
 pub use service::Service;
-pub use state::{AppState, Drivers};
+pub use state::{AppState, Backend, Drivers};
 
 #[doc(hidden)]
 pub use clap::Parser as ConfigParser;
@@ -0,0 +1,506 @@
+//! Content negotiation
+//!
+//! Routes declare the encodings of their resources with an [`Encodings`]
+//! type. The [`Negotiated`] extractor selects one of them by the `f` query
+//! parameter or else the `Accept` header and rejects requests that accept none
+//! of them with `406 Not Acceptable`. Formats are added to a route by adding
+//! them to its encodings and to [`Negotiated::encode`], to
+//! [`Negotiated::render`] if they are rendered with templates, or to the
+//! [`Encoder`]s of [`Negotiated::encoder`] if features are streamed. Routers
+//! with negotiated routes add the [`vary`] layer.
+use std::marker::PhantomData;
+
+#[cfg(feature = "html")]
+use axum::response::Html;
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tower_http::set_header::SetResponseHeaderLayer;
+use url::Url;
+use utoipa::IntoParams;
+
+#[cfg(feature = "flatgeobuf")]
+use ogcapi_types::common::media_type::FLAT_GEOBUF;
+#[cfg(feature = "html")]
+use ogcapi_types::common::media_type::HTML;
+#[cfg(feature = "styles")]
+use ogcapi_types::common::media_type::MAPBOX_STYLE;
+#[cfg(feature = "tiles")]
+use ogcapi_types::common::media_type::MVT;
+#[cfg(feature = "geoparquet")]
+use ogcapi_types::common::media_type::PARQUET;
+#[cfg(feature = "features")]
+use ogcapi_types::common::media_type::SCHEMA_JSON;
+use ogcapi_types::common::{
+    Exception, Link,
+    link_rel::ALTERNATE,
+    media_type::{GEO_JSON, JSON},
+};
+
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use crate::encodings::Encoder;
+use crate::{Error, Result};
+
+/// Encoding of a resource
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Encoding {
+    /// Value of the `f` query parameter selecting the encoding
+    pub(crate) f: &'static str,
+    pub(crate) media_type: &'static str,
+    /// Title of the links to a resource in this encoding
+    pub(crate) title: &'static str,
+}
+
+pub(crate) const JSON_ENCODING: Encoding = Encoding {
+    f: "json",
+    media_type: JSON,
+    title: "This document as JSON",
+};
+
+pub(crate) const GEO_JSON_ENCODING: Encoding = Encoding {
+    f: "json",
+    media_type: GEO_JSON,
+    title: "This document as GeoJSON",
+};
+
+#[cfg(feature = "html")]
+pub(crate) const HTML_ENCODING: Encoding = Encoding {
+    f: "html",
+    media_type: HTML,
+    title: "This document as HTML",
+};
+
+#[cfg(feature = "flatgeobuf")]
+pub(crate) const FLAT_GEOBUF_ENCODING: Encoding = Encoding {
+    f: "fgb",
+    media_type: FLAT_GEOBUF,
+    title: "These features as FlatGeobuf",
+};
+
+#[cfg(feature = "geoparquet")]
+pub(crate) const GEO_PARQUET_ENCODING: Encoding = Encoding {
+    f: "parquet",
+    media_type: PARQUET,
+    title: "These features as GeoParquet",
+};
+
+/// Encodings of the resources of a route, the first one is the default
+pub(crate) trait Encodings {
+    const ENCODINGS: &'static [Encoding];
+}
+
+/// JSON documents
+#[cfg(any(
+    feature = "features",
+    feature = "processes",
+    feature = "styles",
+    feature = "tiles"
+))]
+pub(crate) struct Document;
+
+#[cfg(any(
+    feature = "features",
+    feature = "processes",
+    feature = "styles",
+    feature = "tiles"
+))]
+impl Encodings for Document {
+    const ENCODINGS: &'static [Encoding] = &[JSON_ENCODING];
+}
+
+/// JSON documents with an HTML page
+pub(crate) struct Page;
+
+impl Encodings for Page {
+    const ENCODINGS: &'static [Encoding] = &[
+        JSON_ENCODING,
+        #[cfg(feature = "html")]
+        HTML_ENCODING,
+    ];
+}
+
+/// Features and feature collections with an HTML page
+#[cfg(feature = "features")]
+pub(crate) struct Features;
+
+#[cfg(feature = "features")]
+impl Encodings for Features {
+    const ENCODINGS: &'static [Encoding] = &[
+        GEO_JSON_ENCODING,
+        #[cfg(feature = "html")]
+        HTML_ENCODING,
+    ];
+}
+
+/// Items of collections with an HTML page, which may be downloaded
+#[cfg(feature = "features")]
+pub(crate) struct Items;
+
+#[cfg(feature = "features")]
+impl Encodings for Items {
+    const ENCODINGS: &'static [Encoding] = &[
+        GEO_JSON_ENCODING,
+        #[cfg(feature = "html")]
+        HTML_ENCODING,
+        #[cfg(feature = "flatgeobuf")]
+        FLAT_GEOBUF_ENCODING,
+        #[cfg(feature = "geoparquet")]
+        GEO_PARQUET_ENCODING,
+    ];
+}
+
+/// Items of searches, which may be downloaded
+#[cfg(feature = "stac")]
+pub(crate) struct SearchResults;
+
+#[cfg(feature = "stac")]
+impl Encodings for SearchResults {
+    const ENCODINGS: &'static [Encoding] = &[
+        GEO_JSON_ENCODING,
+        #[cfg(feature = "flatgeobuf")]
+        FLAT_GEOBUF_ENCODING,
+        #[cfg(feature = "geoparquet")]
+        GEO_PARQUET_ENCODING,
+    ];
+}
+
+/// GeoJSON feature collections
+#[cfg(feature = "edr")]
+pub(crate) struct GeoJson;
+
+#[cfg(feature = "edr")]
+impl Encodings for GeoJson {
+    const ENCODINGS: &'static [Encoding] = &[GEO_JSON_ENCODING];
+}
+
+/// JSON schemas
+#[cfg(feature = "features")]
+pub(crate) struct JsonSchema;
+
+#[cfg(feature = "features")]
+impl Encodings for JsonSchema {
+    const ENCODINGS: &'static [Encoding] = &[Encoding {
+        f: "json",
+        media_type: SCHEMA_JSON,
+        title: "This document as JSON Schema",
+    }];
+}
+
+/// Vector tiles
+#[cfg(feature = "tiles")]
+pub(crate) struct VectorTile;
+
+#[cfg(feature = "tiles")]
+impl Encodings for VectorTile {
+    const ENCODINGS: &'static [Encoding] = &[Encoding {
+        f: "mvt",
+        media_type: MVT,
+        title: "This tile as Mapbox Vector Tile",
+    }];
+}
+
+/// Styles
+#[cfg(feature = "styles")]
+pub(crate) struct StyleDocument;
+
+#[cfg(feature = "styles")]
+impl Encodings for StyleDocument {
+    const ENCODINGS: &'static [Encoding] = &[Encoding {
+        f: "mapbox",
+        media_type: MAPBOX_STYLE,
+        title: "This style as Mapbox Style",
+    }];
+}
+
+/// The `f` query parameter, documented by negotiated routes
+#[allow(dead_code)]
+#[derive(IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct Format {
+    /// The encoding of the response, taking precedence over the `Accept`
+    /// header, e.g. `json`
+    #[param(nullable = false)]
+    f: Option<String>,
+}
+
+/// Extractor of the encoding of the response, negotiated among the
+/// encodings `E` of a route
+pub(crate) struct Negotiated<E> {
+    pub(crate) encoding: &'static Encoding,
+    encodings: PhantomData<E>,
+}
+
+impl<S, E> FromRequestParts<S> for Negotiated<E>
+where
+    S: Send + Sync,
+    E: Encodings,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let f = parts.uri.query().and_then(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "f")
+                .map(|(_, value)| value.into_owned())
+        });
+        let accept = parts
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        match negotiate(E::ENCODINGS, f.as_deref(), &accept) {
+            Some(encoding) => Ok(Negotiated {
+                encoding,
+                encodings: PhantomData,
+            }),
+            None => Err(Error::ApiException(
+                Exception::new_from_status(StatusCode::NOT_ACCEPTABLE.as_u16()).detail(format!(
+                    "The resource is available as {}",
+                    E::ENCODINGS
+                        .iter()
+                        .map(|encoding| format!("`{}` (f={})", encoding.media_type, encoding.f))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            )),
+        }
+    }
+}
+
+impl<E: Encodings> Default for Negotiated<E> {
+    /// The default encoding of the route
+    fn default() -> Self {
+        Negotiated {
+            encoding: &E::ENCODINGS[0],
+            encodings: PhantomData,
+        }
+    }
+}
+
+impl<E: Encodings> Negotiated<E> {
+    /// Media type of the negotiated encoding
+    pub(crate) fn media_type(&self) -> &'static str {
+        self.encoding.media_type
+    }
+
+    /// Links to the resource at `url` in the other encodings
+    pub(crate) fn alternates(&self, url: &Url) -> Vec<Link> {
+        E::ENCODINGS
+            .iter()
+            .filter(|encoding| encoding.f != self.encoding.f)
+            .map(|encoding| {
+                let mut url = url.to_owned();
+                set_f(&mut url, encoding.f);
+
+                Link::new(url, ALTERNATE)
+                    .mediatype(encoding.media_type)
+                    .title(encoding.title)
+            })
+            .collect()
+    }
+
+    /// Encoder of the features of a stream, `None` if the negotiated
+    /// encoding is one of documents
+    #[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+    pub(crate) fn encoder(&self) -> Option<Encoder> {
+        Encoder::new(self.encoding.media_type)
+    }
+
+    /// Response with a resource in the negotiated encoding
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn encode<T: Serialize>(
+        &self,
+        mut headers: HeaderMap,
+        resource: &T,
+    ) -> Result<Response> {
+        headers.insert(CONTENT_TYPE, self.encoding.media_type.parse().unwrap());
+
+        if is_json(self.encoding.media_type) {
+            Ok((headers, Json(resource)).into_response())
+        } else {
+            Err(Error::Anyhow(anyhow::anyhow!(
+                "No encoder for `{}`",
+                self.encoding.media_type
+            )))
+        }
+    }
+
+    /// Response with a resource in the negotiated encoding, rendered with a
+    /// template of [`crate::html`] if it is HTML
+    #[allow(clippy::result_large_err)]
+    #[cfg_attr(not(feature = "html"), allow(unused_variables))]
+    pub(crate) fn render<T: Serialize>(
+        &self,
+        headers: HeaderMap,
+        template: &str,
+        resource: &T,
+    ) -> Result<Response> {
+        #[cfg(feature = "html")]
+        if self.encoding.media_type == HTML {
+            let page = crate::html::render(template, resource)?;
+            return Ok((headers, Html(page)).into_response());
+        }
+
+        self.encode(headers, resource)
+    }
+}
+
+/// Layer of the routers with negotiated routes, telling caches that their
+/// responses depend on the `Accept` header
+pub(crate) fn vary() -> SetResponseHeaderLayer<HeaderValue> {
+    SetResponseHeaderLayer::appending(VARY, HeaderValue::from_static("accept"))
+}
+
+/// Replace the `f` query parameter of a URL
+pub(crate) fn set_f(url: &mut Url, f: &str) {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| key != "f")
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("f", f);
+}
+
+/// Encoding requested by the `f` parameter, or else the most preferred
+/// encoding of the `Accept` header, the first one if any is accepted
+fn negotiate(
+    encodings: &'static [Encoding],
+    f: Option<&str>,
+    accept: &str,
+) -> Option<&'static Encoding> {
+    if let Some(f) = f {
+        return encodings.iter().find(|encoding| encoding.f == f);
+    }
+
+    // media ranges by descending quality, in order of appearance
+    let mut ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media_range = params.next()?.trim();
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            (!media_range.is_empty()).then_some((media_range, quality))
+        })
+        .collect();
+    if ranges.is_empty() {
+        return encodings.first();
+    }
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    ranges
+        .into_iter()
+        .filter(|(_, quality)| *quality > 0.0)
+        .find_map(|(range, _)| {
+            encodings
+                .iter()
+                .find(|encoding| accepts(range, encoding.media_type))
+        })
+}
+
+/// Whether a media range of the `Accept` header includes a media type
+///
+/// JSON based media types (`+json`) are included in `application/json`.
+fn accepts(range: &str, media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default().trim();
+    let range = range.to_ascii_lowercase();
+
+    match range.strip_suffix("/*") {
+        Some("*") => true,
+        Some(r#type) => essence.split('/').next() == Some(r#type),
+        None => range == essence || (range == JSON && is_json(essence)),
+    }
+}
+
+/// Whether a media type is JSON based
+fn is_json(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or_default();
+    essence == JSON || essence.ends_with("+json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODINGS: &[Encoding] = &[
+        GEO_JSON_ENCODING,
+        Encoding {
+            f: "html",
+            media_type: "text/html",
+            title: "This document as HTML",
+        },
+    ];
+
+    #[test]
+    fn negotiation() {
+        let negotiated = |f, accept| negotiate(ENCODINGS, f, accept).map(|e| e.f);
+
+        // defaults
+        assert_eq!(negotiated(None, ""), Some("json"));
+        assert_eq!(negotiated(None, "*/*"), Some("json"));
+
+        // the `f` parameter takes precedence
+        assert_eq!(negotiated(Some("html"), GEO_JSON), Some("html"));
+        assert_eq!(negotiated(Some("xml"), ""), None);
+
+        // quality values
+        assert_eq!(
+            negotiated(None, "application/json;q=0.5, text/html"),
+            Some("html")
+        );
+        assert_eq!(
+            negotiated(
+                None,
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+            ),
+            Some("html")
+        );
+        assert_eq!(negotiated(None, "text/*, application/json"), Some("html"));
+        assert_eq!(negotiated(None, "application/json"), Some("json"));
+        assert_eq!(negotiated(None, "text/html;q=0, */*;q=0.1"), Some("json"));
+
+        // nothing acceptable
+        assert_eq!(negotiated(None, "image/png"), None);
+        assert_eq!(negotiated(None, "text/html;q=0"), None);
+    }
+
+    struct Both;
+
+    impl Encodings for Both {
+        const ENCODINGS: &'static [Encoding] = ENCODINGS;
+    }
+
+    #[test]
+    fn alternates() {
+        let negotiated: Negotiated<Both> = Negotiated {
+            encoding: &ENCODINGS[0],
+            encodings: PhantomData,
+        };
+        let url = Url::parse("http://localhost/collections/places/items?limit=10&f=json").unwrap();
+
+        let links = negotiated.alternates(&url);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].rel, ALTERNATE);
+        assert_eq!(
+            links[0].href,
+            "http://localhost/collections/places/items?limit=10&f=html"
+        );
+        assert_eq!(links[0].r#type.as_deref(), Some("text/html"));
+    }
+}
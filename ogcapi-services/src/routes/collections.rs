@@ -2,7 +2,10 @@ use axum::{
     Json,
     extract::{Path, State},
     http::{StatusCode, header::LOCATION},
+    response::Response,
 };
+use axum_extra::TypedHeader;
+use headers::{HeaderMapExt, IfMatch};
 use hyper::HeaderMap;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -13,21 +16,30 @@ use ogcapi_types::common::{
     link_rel::{DATA, ITEMS, ROOT, SELF},
     media_type::{GEO_JSON, JSON},
 };
+#[cfg(feature = "features")]
+use ogcapi_types::common::{
+    link_rel::{DESCRIBEDBY, QUERYABLES},
+    media_type::SCHEMA_JSON,
+};
 
 use crate::{
     AppState, Error, Result,
     extractors::{Qs, RemoteUrl},
+    negotiation::{Format, Negotiated, Page, vary},
+    routes,
 };
 
 const CONFORMANCE: [&str; 5] = [
     "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/core",
     "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/landingPage",
     "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/oas30",
-    // "http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/html",
     "http://www.opengis.net/spec/ogcapi_common-2/1.0/conf/json",
     "http://www.opengis.net/spec/ogcapi-common-2/1.0/conf/collections",
 ];
 
+#[cfg(feature = "html")]
+const HTML_CONFORMANCE: [&str; 1] = ["http://www.opengis.net/spec/ogcapi-common-1/1.0/conf/html"];
+
 /// Create new collection metadata
 #[utoipa::path(post, path = "/collections", tag = "Collections", 
     request_body = Collection,
@@ -88,7 +100,8 @@ async fn create(
 /// Describe the feature collection with id `collectionId`
 #[utoipa::path(get, path = "/collections/{collectionId}", tag = "Collections", 
     params(
-        ("collectionId" = String, Path, description = "local identifier of a collection")
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        Format
     ),
     responses(
         (
@@ -127,7 +140,16 @@ async fn read(
     RemoteUrl(url): RemoteUrl,
     State(state): State<AppState>,
     Path(collection_id): Path<String>,
-) -> Result<Json<Collection>> {
+    negotiated: Negotiated<Page>,
+) -> Result<Response> {
+    // read the entity tag first, so that it never claims a newer version
+    // than the one returned
+    let etag = state
+        .drivers
+        .collections
+        .collection_etag(&collection_id)
+        .await?;
+
     let mut collection = state
         .drivers
         .collections
@@ -136,9 +158,10 @@ async fn read(
         .ok_or(Error::NotFound)?;
 
     collection.links.insert_or_update(&[
-        Link::new(&url, SELF),
+        Link::new(&url, SELF).mediatype(negotiated.media_type()),
         Link::new(url.join("..")?, ROOT).mediatype(JSON),
     ]);
+    collection.links.extend(negotiated.alternates(&url));
 
     #[cfg(not(feature = "stac"))]
     {
@@ -147,6 +170,17 @@ async fn read(
         collection.links.insert_or_update(&[items_link]);
     }
 
+    #[cfg(feature = "features")]
+    {
+        let queryables_url = url.join(&format!("{}/queryables", collection.id))?;
+        let queryables_link = Link::new(queryables_url, QUERYABLES).mediatype(SCHEMA_JSON);
+        let schema_url = url.join(&format!("{}/schema", collection.id))?;
+        let schema_link = Link::new(schema_url, DESCRIBEDBY).mediatype(SCHEMA_JSON);
+        collection
+            .links
+            .insert_or_update(&[queryables_link, schema_link]);
+    }
+
     #[cfg(feature = "tiles")]
     {
         let tiles_url = url.join(&format!("{}/tiles", collection.id))?;
@@ -163,13 +197,24 @@ async fn read(
 
     collection.links.resolve_relative_links();
 
-    Ok(Json(collection))
+    // representations other than JSON have their own entity tag
+    let mut headers = HeaderMap::new();
+    if let Some(etag) = etag {
+        let mut variant = Vec::new();
+        if negotiated.media_type() != JSON {
+            variant.push(negotiated.media_type().to_string());
+        }
+        headers.typed_insert(routes::representation_etag(&etag, &variant)?);
+    }
+
+    negotiated.render(headers, "collection.html", &collection)
 }
 
 /// Update collection metadata
 #[utoipa::path(put, path = "/collections/{collectionId}", tag = "Collections",
     params(
-        ("collectionId" = String, Path, description = "local identifier of a collection")
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        ("If-Match" = Option<String>, Header, description = "entity tag of the version to modify")
     ),
     responses(
         (status = 204, description = "Successfuly updataed, no content."),
@@ -177,6 +222,11 @@ async fn read(
             status = 400, description = "General HTTP error response.", 
             body = Exception, example = json!(Exception::new_from_status(400))
         ),
+        (
+            status = 412, description = "The `If-Match` entity tag does not \
+            match the current version of the resource.",
+            body = Exception, example = json!(Exception::new_from_status(412))
+        ),
         (
             status = 500, description = "A server error occurred.", 
             body = Exception, example = json!(Exception::new_from_status(500))
@@ -186,14 +236,24 @@ async fn read(
 async fn update(
     State(state): State<AppState>,
     Path(collection_id): Path<String>,
+    if_match: Option<TypedHeader<IfMatch>>,
     Json(mut collection): Json<Collection>,
 ) -> Result<StatusCode> {
+    let if_match = routes::precondition(
+        if_match.as_deref(),
+        state
+            .drivers
+            .collections
+            .collection_etag(&collection_id)
+            .await?,
+    )?;
+
     collection.id = collection_id;
 
     state
         .drivers
         .collections
-        .update_collection(&collection)
+        .update_collection(&collection, if_match.as_deref())
         .await?;
 
     Ok(StatusCode::NO_CONTENT)
@@ -202,7 +262,8 @@ async fn update(
 /// Delete collection metadata
 #[utoipa::path(delete, path = "/collections/{collectionId}", tag = "Collections",
     params(
-        ("collectionId" = String, Path, description = "local identifier of a collection")
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        ("If-Match" = Option<String>, Header, description = "entity tag of the version to modify")
     ),
     request_body = Collection,
     responses(
@@ -211,6 +272,11 @@ async fn update(
             status = 400, description = "General HTTP error response.", 
             body = Exception, example = json!(Exception::new_from_status(400))
         ),
+        (
+            status = 412, description = "The `If-Match` entity tag does not \
+            match the current version of the resource.",
+            body = Exception, example = json!(Exception::new_from_status(412))
+        ),
         (
             status = 500, description = "A server error occurred.", 
             body = Exception, example = json!(Exception::new_from_status(500))
@@ -220,11 +286,21 @@ async fn update(
 async fn remove(
     Path(collection_id): Path<String>,
     State(state): State<AppState>,
+    if_match: Option<TypedHeader<IfMatch>>,
 ) -> Result<StatusCode> {
+    let if_match = routes::precondition(
+        if_match.as_deref(),
+        state
+            .drivers
+            .collections
+            .collection_etag(&collection_id)
+            .await?,
+    )?;
+
     state
         .drivers
         .collections
-        .delete_collection(&collection_id)
+        .delete_collection(&collection_id, if_match.as_deref())
         .await?;
 
     Ok(StatusCode::NO_CONTENT)
@@ -232,6 +308,7 @@ async fn remove(
 
 /// The feature collections in the dataset.
 #[utoipa::path(get, path = "/collections", tag = "Capabilities", 
+    params(Format),
     responses(
         (
             status = 200,
@@ -273,7 +350,8 @@ async fn collections(
     Qs(query): Qs<Query>,
     RemoteUrl(url): RemoteUrl,
     State(state): State<AppState>,
-) -> Result<Json<Collections>> {
+    negotiated: Negotiated<Page>,
+) -> Result<Response> {
     let mut collections = state.drivers.collections.list_collections(&query).await?;
 
     for collection in collections.collections.iter_mut() {
@@ -285,6 +363,18 @@ async fn collections(
                 ITEMS,
             )
             .mediatype(GEO_JSON),
+            #[cfg(feature = "features")]
+            Link::new(
+                url.join(&format!("collections/{}/queryables", collection.id))?,
+                QUERYABLES,
+            )
+            .mediatype(SCHEMA_JSON),
+            #[cfg(feature = "features")]
+            Link::new(
+                url.join(&format!("collections/{}/schema", collection.id))?,
+                DESCRIBEDBY,
+            )
+            .mediatype(SCHEMA_JSON),
             #[cfg(feature = "tiles")]
             Link::new(
                 url.join(&format!("collections/{}/tiles", collection.id))?,
@@ -297,13 +387,16 @@ async fn collections(
     }
 
     collections.links = vec![
-        Link::new(&url, SELF).mediatype(JSON).title("this document"),
+        Link::new(&url, SELF)
+            .mediatype(negotiated.media_type())
+            .title("this document"),
         Link::new(url.join(".")?, ROOT).mediatype(JSON),
     ];
+    collections.links.extend(negotiated.alternates(&url));
 
     collections.crs = vec![Crs::default2d(), Crs::from_epsg(3857)];
 
-    Ok(Json(collections))
+    negotiated.render(HeaderMap::new(), "collections.html", &collections)
 }
 
 pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
@@ -315,8 +408,11 @@ pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
     );
 
     state.conformance.write().unwrap().extend(&CONFORMANCE);
+    #[cfg(feature = "html")]
+    state.conformance.write().unwrap().extend(&HTML_CONFORMANCE);
 
     OpenApiRouter::new()
         .routes(routes!(collections, create))
         .routes(routes!(read, update, remove))
+        .layer(vary())
 }
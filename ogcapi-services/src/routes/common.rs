@@ -1,4 +1,4 @@
-use axum::{Json, extract::State};
+use axum::{Json, extract::State, response::Response};
 use hyper::{HeaderMap, header::CONTENT_TYPE};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -15,6 +15,7 @@ use ogcapi_types::common::{
 use crate::{
     AppState, Result,
     extractors::RemoteUrl,
+    negotiation::{Format, Negotiated, Page, vary},
     openapi::{OPENAPI, OpenAPI},
 };
 
@@ -24,6 +25,7 @@ use crate::{
 /// conformance statements for this API.
 #[utoipa::path(
     get, path = "/", tag = "Capabilities", 
+    params(Format),
     responses(
         (
             status = 200,
@@ -46,11 +48,13 @@ use crate::{
 pub async fn root(
     State(state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
-) -> Result<Json<LandingPage>> {
+    negotiated: Negotiated<Page>,
+) -> Result<Response> {
     let mut root = state.root.read().unwrap().to_owned();
 
     root.links.insert_or_update(&[
-        Link::new(format!("{}/", url.as_str().trim_end_matches('/')), SELF).mediatype(JSON),
+        Link::new(format!("{}/", url.as_str().trim_end_matches('/')), SELF)
+            .mediatype(negotiated.media_type()),
         Link::new(".", ROOT).mediatype(JSON),
         Link::new("api", SERVICE_DESC)
             .title("The Open API definition")
@@ -77,12 +81,13 @@ pub async fn root(
             .title("List of available vector tilesets for the dataset")
             .mediatype(JSON),
     ]);
+    root.links.extend(negotiated.alternates(&url));
     root.links.resolve_relative_links();
 
     #[cfg(feature = "stac")]
     let root = root.conforms_to(&state.conformance.read().unwrap().conforms_to[..]);
 
-    Ok(Json(root))
+    negotiated.render(HeaderMap::new(), "landing.html", &root)
 }
 
 /// API definition
@@ -125,6 +130,7 @@ pub(crate) async fn api(RemoteUrl(url): RemoteUrl) -> (HeaderMap, Json<openapiv3
 /// the server conforms to.
 #[utoipa::path(
     get, path = "/conformance", tag = "Capabilities", 
+    params(Format),
     responses(
         (
             status = 200,
@@ -145,8 +151,15 @@ pub(crate) async fn api(RemoteUrl(url): RemoteUrl) -> (HeaderMap, Json<openapiv3
         )
     )
 )]
-pub(crate) async fn conformance(State(state): State<AppState>) -> Json<Conformance> {
-    Json(state.conformance.read().unwrap().to_owned())
+pub(crate) async fn conformance(
+    State(state): State<AppState>,
+    negotiated: Negotiated<Page>,
+) -> Result<Response> {
+    negotiated.render(
+        HeaderMap::new(),
+        "conformance.html",
+        &*state.conformance.read().unwrap(),
+    )
 }
 
 pub(crate) fn router() -> OpenApiRouter<AppState> {
@@ -154,4 +167,5 @@ pub(crate) fn router() -> OpenApiRouter<AppState> {
         .routes(routes!(root))
         .routes(routes!(api))
         .routes(routes!(conformance))
+        .layer(vary())
 }
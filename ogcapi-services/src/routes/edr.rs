@@ -1,7 +1,6 @@
 use axum::{
-    Json,
     extract::{Path, State},
-    http::header::CONTENT_TYPE,
+    response::Response,
 };
 use hyper::HeaderMap;
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -15,6 +14,7 @@ use ogcapi_types::{
 use crate::{
     AppState, Result,
     extractors::{Qs, RemoteUrl},
+    negotiation::{GeoJson, Negotiated, vary},
 };
 
 const CONFORMANCE: [&str; 6] = [
@@ -69,7 +69,8 @@ async fn query(
     Qs(query): Qs<Query>,
     RemoteUrl(url): RemoteUrl,
     State(state): State<AppState>,
-) -> Result<(HeaderMap, Json<FeatureCollection>)> {
+    negotiated: Negotiated<GeoJson>,
+) -> Result<Response> {
     tracing::debug!("{:#?}", query);
 
     let (mut fc, crs) = state
@@ -92,10 +93,9 @@ async fn query(
     }
 
     let mut headers = HeaderMap::new();
-    headers.insert("Content-Crs", crs.to_string().parse().unwrap());
-    headers.insert(CONTENT_TYPE, GEO_JSON.parse().unwrap());
+    headers.insert("Content-Crs", format!("<{crs}>").parse().unwrap());
 
-    Ok((headers, Json(fc)))
+    negotiated.encode(headers, &fc)
 }
 
 // async fn instances() {}
@@ -105,8 +105,10 @@ async fn query(
 pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
     state.conformance.write().unwrap().extend(&CONFORMANCE);
 
-    OpenApiRouter::new().routes(routes!(query))
-    // .route("/collections/{collection_id}/instances", get(instances))
-    // .route("/collections/{collection_id}/instances/{instance_id}", get(instance))
-    // .route("/collections/{collection_id}/instances/{instance_id}/{query_type}", get(instance))
+    OpenApiRouter::new()
+        .routes(routes!(query))
+        // .route("/collections/{collection_id}/instances", get(instances))
+        // .route("/collections/{collection_id}/instances/{instance_id}", get(instance))
+        // .route("/collections/{collection_id}/instances/{instance_id}/{query_type}", get(instance))
+        .layer(vary())
 }
@@ -1,48 +1,109 @@
+use std::convert::Infallible;
+
 use anyhow::Context;
 use axum::{
     Json,
-    extract::{Path, State},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, State},
     http::{
         HeaderMap, StatusCode,
         header::{CONTENT_TYPE, LOCATION},
     },
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
+use axum_extra::TypedHeader;
+use futures::{Stream, StreamExt, future};
+use headers::{HeaderMapExt, IfMatch};
+use serde_json::{Map, Value};
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use ogcapi_types::{
     common::{
         Collection, Crs, Exception, Link, Linked,
-        link_rel::{COLLECTION, NEXT, PREV, ROOT, SELF},
-        media_type::{GEO_JSON, JSON},
+        link_rel::{COLLECTION, LATEST_VERSION, NEXT, PREV, ROOT, SELF, VERSION_HISTORY},
+        media_type::{GEO_JSON, GEO_JSON_SEQ, JSON, MERGE_PATCH_JSON, NDJSON},
+    },
+    features::{
+        Change, ChangeType, ChangesQuery, Feature, FeatureCollection, FeatureId, FeatureVersions,
+        Query, Queryables, Schema,
     },
-    features::{Feature, FeatureCollection, FeatureId, Query},
 };
 
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use crate::encodings;
 use crate::{
     AppState, Error, Result,
     extractors::{Qs, RemoteUrl},
+    negotiation::{Document, Features, Format, Items, JsonSchema, Negotiated, vary},
+    routes,
 };
 
-const CONFORMANCE: [&str; 4] = [
+const CONFORMANCE: [&str; 22] = [
     "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/core",
     "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/oas30",
     "http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/geojson",
     "http://www.opengis.net/spec/ogcapi-features-2/1.0/conf/crs",
+    "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/queryables",
+    "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/queryables-query-parameters",
+    "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/filter",
+    "http://www.opengis.net/spec/ogcapi-features-3/1.0/conf/features-filter",
+    "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/create-replace-delete",
+    "http://www.opengis.net/spec/ogcapi-features-4/1.0/conf/update",
+    "http://www.opengis.net/spec/ogcapi-features-5/1.0/conf/schemas",
+    "http://www.opengis.net/spec/ogcapi-features-8/1.0/conf/sorting",
+    "http://www.opengis.net/spec/cql2/1.0/conf/cql2-text",
+    "http://www.opengis.net/spec/cql2/1.0/conf/cql2-json",
+    "http://www.opengis.net/spec/cql2/1.0/conf/basic-cql2",
+    "http://www.opengis.net/spec/cql2/1.0/conf/advanced-comparison-operators",
+    "http://www.opengis.net/spec/cql2/1.0/conf/basic-spatial-functions",
+    "http://www.opengis.net/spec/cql2/1.0/conf/basic-spatial-functions-plus",
+    "http://www.opengis.net/spec/cql2/1.0/conf/spatial-functions",
+    "http://www.opengis.net/spec/cql2/1.0/conf/temporal-functions",
+    "http://www.opengis.net/spec/cql2/1.0/conf/array-functions",
+    "http://www.opengis.net/spec/cql2/1.0/conf/property-property",
 ];
 
-/// Create new item
+#[cfg(feature = "html")]
+const HTML_CONFORMANCE: [&str; 1] = ["http://www.opengis.net/spec/ogcapi-features-1/1.0/conf/html"];
+
+/// Maximum size of the request body for creating items in bulk
+const BULK_BODY_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Create new items
+///
+/// The request body is either a single feature, a feature collection or a
+/// stream of newline-delimited features (`application/x-ndjson` or
+/// `application/geo+json-seq`). Multiple features are created in a single
+/// transaction, either all of them or none.
 #[utoipa::path(post, path = "/collections/{collectionId}/items", tag = "Data",
     params(
         ("collectionId" = String, Path, description = "local identifier of a collection")
     ),
-    request_body = Feature,
+    request_body(
+        content(
+            (Feature = "application/geo+json"),
+            (FeatureCollection = "application/json"),
+            (String = "application/x-ndjson"),
+            (String = "application/geo+json-seq"),
+        )
+    ),
     responses(
         (
-            status = 201, description = "Created.", 
+            status = 201, description = "Created. The ids of multiple created \
+            features are listed in the response body.",
+            body = Vec<String>,
             headers(
                 ("Location", description = "URI of the newly added resource.")
             )
         ),
+        (
+            status = 400, description = "General HTTP error response, no \
+            features were created.",
+            body = Exception, example = json!(Exception::new_from_status(400))
+        ),
         (
             status = 409, description = "Already exists.", 
             body = Exception, example = json!(Exception::new_from_status(409))
@@ -57,18 +118,98 @@ async fn create(
     State(state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
     Path(collection_id): Path<String>,
-    Json(mut feature): Json<Feature>,
-) -> Result<(StatusCode, HeaderMap)> {
-    feature.collection = Some(collection_id);
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(GEO_JSON);
+
+    let mut features = if content_type.starts_with(NDJSON) || content_type.starts_with(GEO_JSON_SEQ)
+    {
+        parse_feature_sequence(&body).map_err(bad_request)?
+    } else {
+        let value: Value = serde_json::from_slice(&body).map_err(bad_request)?;
+        if value["type"] == "FeatureCollection" {
+            serde_json::from_value::<FeatureCollection>(value)
+                .map_err(bad_request)?
+                .features
+        } else {
+            let mut feature: Feature = serde_json::from_value(value).map_err(bad_request)?;
+            store_places(&state, &collection_id, std::slice::from_mut(&mut feature)).await?;
+            feature.collection = Some(collection_id);
 
-    let id = state.drivers.features.create_feature(&feature).await?;
+            let id = state.drivers.features.create_feature(&feature).await?;
 
-    let location = url.join(&format!("items/{id}"))?;
+            let location = url.join(&format!("items/{id}"))?;
 
-    let mut headers = HeaderMap::new();
-    headers.insert(LOCATION, location.as_str().parse().unwrap());
+            let mut headers = HeaderMap::new();
+            headers.insert(LOCATION, location.as_str().parse().unwrap());
+
+            return Ok((StatusCode::CREATED, headers).into_response());
+        }
+    };
+
+    store_places(&state, &collection_id, &mut features).await?;
+
+    let ids = state
+        .drivers
+        .features
+        .create_features(&collection_id, &features)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(ids)).into_response())
+}
+
+/// Parse newline-delimited features, optionally prefixed with the record
+/// separator of GeoJSON text sequences (RFC 8142)
+fn parse_feature_sequence(body: &[u8]) -> serde_json::Result<Vec<Feature>> {
+    body.split(|b| *b == b'\n')
+        .map(|line| line.strip_prefix(b"\x1e").unwrap_or(line).trim_ascii())
+        .filter(|line| !line.is_empty())
+        .map(serde_json::from_slice)
+        .collect()
+}
+
+fn bad_request(error: serde_json::Error) -> Error {
+    Error::ApiException(Exception::new_from_status(400).detail(error))
+}
+
+/// Store the JSON-FG `place` of features as the geometry property of the
+/// collection that is flagged as place
+async fn store_places(
+    state: &AppState,
+    collection_id: &str,
+    features: &mut [Feature],
+) -> Result<()> {
+    if features.iter().all(|feature| feature.place.is_none()) {
+        return Ok(());
+    }
+
+    let name = place_column(state, collection_id).await?;
+    for feature in features {
+        feature.place_to_property(&name).map_err(bad_request)?;
+    }
 
-    Ok((StatusCode::CREATED, headers))
+    Ok(())
+}
+
+/// Name of the geometry column of a collection that is the JSON-FG `place`
+async fn place_column(state: &AppState, collection_id: &str) -> Result<String> {
+    let collection = state
+        .drivers
+        .collections
+        .read_collection(collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    match collection.place() {
+        Some(column) => Ok(column.name.to_owned()),
+        None => Err(Error::ApiException(Exception::new_from_status(400).detail(
+            format!("Collection `{collection_id}` has no geometry for the place of features"),
+        ))),
+    }
 }
 
 /// Fetch a single feature
@@ -78,7 +219,8 @@ async fn create(
 #[utoipa::path(get, path = "/collections/{collectionId}/items/{featureId}", tag = "Data", 
     params(
         ("collectionId" = String, Path, description = "local identifier of a collection"),
-        ("featureId" = String, Path, description = "local identifier of a feature")
+        ("featureId" = String, Path, description = "local identifier of a feature"),
+        Format
     ),
     responses(
         (
@@ -102,7 +244,8 @@ async fn read(
     RemoteUrl(url): RemoteUrl,
     Path((collection_id, id)): Path<(String, String)>,
     Qs(query): Qs<Query>,
-) -> Result<(HeaderMap, Json<Feature>)> {
+    negotiated: Negotiated<Features>,
+) -> Result<Response> {
     let collection = state
         .drivers
         .collections
@@ -110,6 +253,14 @@ async fn read(
         .await?
         .ok_or(Error::NotFound)?;
 
+    // read the entity tag first, so that it never claims a newer version
+    // than the one returned
+    let etag = state
+        .drivers
+        .features
+        .feature_etag(&collection_id, &id)
+        .await?;
+
     let crs = if let Some(crs) = query.crs {
         is_supported_crs(&collection, &crs).await?;
         crs
@@ -117,18 +268,50 @@ async fn read(
         Crs::default2d()
     };
 
-    let mut feature = state
-        .drivers
-        .features
-        .read_feature(&collection_id, &id, &crs)
-        .await?
-        .ok_or(Error::NotFound)?;
+    let mut feature = match query.version_at.as_ref() {
+        Some(version_at) => {
+            let versions = state
+                .drivers
+                .features
+                .feature_versions(&collection_id, &id, &crs)
+                .await?
+                .ok_or_else(|| unversioned(&collection_id))?;
+            versions
+                .into_iter()
+                .find(|version| version.valid_at(version_at))
+                .map(|version| version.feature)
+        }
+        None => {
+            state
+                .drivers
+                .features
+                .read_feature(&collection_id, &id, &crs)
+                .await?
+        }
+    }
+    .ok_or(Error::NotFound)?;
+
+    if let Some(place) = collection.place() {
+        feature.property_to_place(&place.name);
+    }
 
     feature.links.insert_or_update(&[
-        Link::new(&url, SELF).mediatype(GEO_JSON),
+        Link::new(&url, SELF).mediatype(negotiated.media_type()),
         Link::new(url.join("../../..")?, ROOT).mediatype(JSON),
         Link::new(url.join(&format!("../../{collection_id}"))?, COLLECTION).mediatype(JSON),
     ]);
+    feature.links.extend(negotiated.alternates(&url));
+    if collection
+        .additional_properties
+        .get("versioning")
+        .is_some_and(|versioning| versioning == true)
+    {
+        feature.links.insert_or_update(&[Link::new(
+            url.join(&format!("{id}/versions"))?,
+            VERSION_HISTORY,
+        )
+        .mediatype(JSON)]);
+    }
     feature.links.resolve_relative_links();
 
     let mut headers = HeaderMap::new();
@@ -138,16 +321,122 @@ async fn read(
             .parse()
             .context("Unable to parse `Content-Crs` header value")?,
     );
-    headers.insert(CONTENT_TYPE, GEO_JSON.parse().unwrap());
+    // the entity tag is the one of the current version, representations
+    // other than GeoJSON in the default crs have their own
+    if let Some(etag) = etag.filter(|_| query.version_at.is_none()) {
+        let mut variant = Vec::new();
+        if negotiated.media_type() != GEO_JSON {
+            variant.push(negotiated.media_type().to_string());
+        }
+        if crs != Crs::default2d() {
+            variant.push(crs.to_string());
+        }
+        headers.typed_insert(routes::representation_etag(&etag, &variant)?);
+    }
 
-    Ok((headers, Json(feature)))
+    negotiated.render(headers, "feature.html", &feature)
+}
+
+/// Fetch the versions of a feature
+///
+/// Fetch the recorded versions of the feature with id `featureId` in the
+/// feature collection with id `collectionId`, oldest first. Versions are
+/// recorded for collections with `"versioning": true`.
+#[utoipa::path(get, path = "/collections/{collectionId}/items/{featureId}/versions", tag = "Data",
+    params(
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        ("featureId" = String, Path, description = "local identifier of a feature"),
+        ("crs" = Option<String>, Query, description = "coordinate reference system of the geometries"),
+        Format
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The versions of the feature with id `featureId` in \
+            the feature collection with id `collectionId`",
+            body = FeatureVersions),
+        (
+            status = 400, description = "The collection does not record the \
+            history of its features.",
+            body = Exception, example = json!(Exception::new_from_status(400))
+        ),
+        (
+            status = 404, description = "The requested resource does not exist \
+            on the server. For example, a path parameter had an incorrect value.",
+            body = Exception, example = json!(Exception::new_from_status(404))
+        ),
+        (
+            status = 500, description = "A server error occurred.",
+            body = Exception, example = json!(Exception::new_from_status(500))
+        )
+    )
+)]
+async fn versions(
+    State(state): State<AppState>,
+    RemoteUrl(url): RemoteUrl,
+    Path((collection_id, id)): Path<(String, String)>,
+    Qs(query): Qs<Query>,
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
+    let collection = state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let crs = if let Some(crs) = query.crs {
+        is_supported_crs(&collection, &crs).await?;
+        crs
+    } else {
+        Crs::default2d()
+    };
+
+    let mut versions = state
+        .drivers
+        .features
+        .feature_versions(&collection_id, &id, &crs)
+        .await?
+        .ok_or_else(|| unversioned(&collection_id))?;
+
+    if versions.is_empty() {
+        return Err(Error::NotFound);
+    }
+
+    if let Some(place) = collection.place() {
+        for version in versions.iter_mut() {
+            version.feature.property_to_place(&place.name);
+        }
+    }
+
+    let mut versions = FeatureVersions {
+        versions,
+        links: vec![
+            Link::new(&url, SELF).mediatype(negotiated.media_type()),
+            Link::new(url.join("../../../..")?, ROOT).mediatype(JSON),
+            Link::new(url.join(&format!("../{id}"))?, LATEST_VERSION).mediatype(GEO_JSON),
+            Link::new(url.join(&format!("../../../{collection_id}"))?, COLLECTION).mediatype(JSON),
+        ],
+    };
+    versions.links.extend(negotiated.alternates(&url));
+    versions.links.resolve_relative_links();
+
+    negotiated.encode(HeaderMap::new(), &versions)
+}
+
+/// Error of a collection that does not record the history of its features
+fn unversioned(collection_id: &str) -> Error {
+    Error::ApiException(Exception::new_from_status(400).detail(format!(
+        "Collection `{collection_id}` does not record the history of its features"
+    )))
 }
 
 /// Update collection item
 #[utoipa::path(put, path = "/collections/{collectionId}/items/{featureId}", tag = "Data",
     params(
         ("collectionId" = String, Path, description = "local identifier of a collection"),
-        ("featureId" = String, Path, description = "local identifier of a feature")
+        ("featureId" = String, Path, description = "local identifier of a feature"),
+        ("If-Match" = Option<String>, Header, description = "entity tag of the version to modify")
     ),
     request_body = Feature,
     responses(
@@ -156,6 +445,11 @@ async fn read(
             status = 400, description = "General HTTP error response.", 
             body = Exception, example = json!(Exception::new_from_status(400))
         ),
+        (
+            status = 412, description = "The `If-Match` entity tag does not \
+            match the current version of the resource.",
+            body = Exception, example = json!(Exception::new_from_status(412))
+        ),
         (
             status = 500, description = "A server error occurred.", 
             body = Exception, example = json!(Exception::new_from_status(500))
@@ -165,16 +459,120 @@ async fn read(
 async fn update(
     State(state): State<AppState>,
     Path((collection_id, id)): Path<(String, String)>,
+    if_match: Option<TypedHeader<IfMatch>>,
     Json(mut feature): Json<Feature>,
 ) -> Result<StatusCode> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let if_match = routes::precondition(
+        if_match.as_deref(),
+        state
+            .drivers
+            .features
+            .feature_etag(&collection_id, &id)
+            .await?,
+    )?;
+
     match feature.id {
         Some(ref fid) => assert_eq!(id, fid.to_string()),
         None => feature.id = Some(FeatureId::String(id)),
     }
 
+    store_places(&state, &collection_id, std::slice::from_mut(&mut feature)).await?;
     feature.collection = Some(collection_id);
 
-    state.drivers.features.update_feature(&feature).await?;
+    state
+        .drivers
+        .features
+        .update_feature(&feature, if_match.as_deref())
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Partially update collection item
+///
+/// The request body is a JSON Merge Patch (RFC 7396) of the feature, e.g.
+/// replacing the geometry or single properties.
+#[utoipa::path(patch, path = "/collections/{collectionId}/items/{featureId}", tag = "Data",
+    params(
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        ("featureId" = String, Path, description = "local identifier of a feature"),
+        ("If-Match" = Option<String>, Header, description = "entity tag of the version to modify")
+    ),
+    request_body(content = Object, content_type = MERGE_PATCH_JSON),
+    responses(
+        (status = 204, description = "Successfuly updated, no content."),
+        (
+            status = 400, description = "General HTTP error response.", 
+            body = Exception, example = json!(Exception::new_from_status(400))
+        ),
+        (
+            status = 404, description = "The requested resource does not exist \
+            on the server. For example, a path parameter had an incorrect value.", 
+            body = Exception, example = json!(Exception::new_from_status(404))
+        ),
+        (
+            status = 412, description = "The `If-Match` entity tag does not \
+            match the current version of the resource.",
+            body = Exception, example = json!(Exception::new_from_status(412))
+        ),
+        (
+            status = 500, description = "A server error occurred.", 
+            body = Exception, example = json!(Exception::new_from_status(500))
+        )
+    )
+)]
+async fn patch(
+    State(state): State<AppState>,
+    Path((collection_id, id)): Path<(String, String)>,
+    if_match: Option<TypedHeader<IfMatch>>,
+    Json(mut patch): Json<Map<String, Value>>,
+) -> Result<StatusCode> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let if_match = routes::precondition(
+        if_match.as_deref(),
+        state
+            .drivers
+            .features
+            .feature_etag(&collection_id, &id)
+            .await?,
+    )?;
+
+    // the place is patched as the geometry property
+    if let Some(place) = patch.remove("place") {
+        let name = place_column(&state, &collection_id).await?;
+        match patch
+            .entry("properties")
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(properties) => {
+                properties.insert(name, place);
+            }
+            _ => {
+                return Err(Error::ApiException(Exception::new_from_status(400).detail(
+                    "The place can not be patched with the removal of all properties",
+                )));
+            }
+        }
+    }
+
+    state
+        .drivers
+        .features
+        .patch_feature(&collection_id, &id, &patch, if_match.as_deref())
+        .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -183,7 +581,8 @@ async fn update(
 #[utoipa::path(delete, path = "/collections/{collectionId}/items/{featureId}", tag = "Data",
     params(
         ("collectionId" = String, Path, description = "local identifier of a collection"),
-        ("featureId" = String, Path, description = "local identifier of a feature")
+        ("featureId" = String, Path, description = "local identifier of a feature"),
+        ("If-Match" = Option<String>, Header, description = "entity tag of the version to modify")
     ),
     responses(
         (status = 204, description = "Successfuly deleted, no content."),
@@ -191,6 +590,11 @@ async fn update(
             status = 400, description = "General HTTP error response.", 
             body = Exception, example = json!(Exception::new_from_status(400))
         ),
+        (
+            status = 412, description = "The `If-Match` entity tag does not \
+            match the current version of the resource.",
+            body = Exception, example = json!(Exception::new_from_status(412))
+        ),
         (
             status = 500, description = "A server error occurred.", 
             body = Exception, example = json!(Exception::new_from_status(500))
@@ -200,11 +604,28 @@ async fn update(
 async fn remove(
     State(state): State<AppState>,
     Path((collection_id, id)): Path<(String, String)>,
+    if_match: Option<TypedHeader<IfMatch>>,
 ) -> Result<StatusCode> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let if_match = routes::precondition(
+        if_match.as_deref(),
+        state
+            .drivers
+            .features
+            .feature_etag(&collection_id, &id)
+            .await?,
+    )?;
+
     state
         .drivers
         .features
-        .delete_feature(&collection_id, &id)
+        .delete_feature(&collection_id, &id, if_match.as_deref())
         .await?;
 
     Ok(StatusCode::NO_CONTENT)
@@ -243,7 +664,11 @@ async fn remove(
             features that should be returned in the response, the page size. Each \
             page may include information about the number of selected and returned \
             features (`numberMatched` and `numberReturned`) as well as links to \
-            support paging (link relation next).", 
+            support paging (link relation next). \
+            \n \
+            Features encoded as FlatGeobuf or GeoParquet are not paged, all \
+            selected features are streamed unless `limit` is given, which is \
+            capped at 10000 as for pages.", 
             body = FeatureCollection),
         (
             status = 400, description = "General HTTP error response.", 
@@ -260,27 +685,10 @@ async fn items(
     RemoteUrl(mut url): RemoteUrl,
     Path(collection_id): Path<String>,
     Qs(mut query): Qs<Query>,
-) -> Result<(HeaderMap, Json<FeatureCollection>)> {
+    negotiated: Negotiated<Items>,
+) -> Result<Response> {
     tracing::debug!("{:#?}", query);
 
-    // limit
-    if let Some(limit) = query.limit {
-        // TODO: sync with opanapi specification
-        if limit > 10000 {
-            query.limit = Some(10000);
-        }
-        if limit == 0 {
-            query.limit = Some(1)
-        }
-    } else {
-        query.limit = Some(10);
-    }
-
-    // offset
-    if query.offset.is_none() {
-        query.offset = Some(0);
-    }
-
     // crs
     let collection = state
         .drivers
@@ -295,17 +703,74 @@ async fn items(
         &Crs::default2d()
     };
 
-    // queryables
-    let queryables = state.drivers.features.queryables(&collection_id).await?;
-    if !queryables.additional_properties {
-        for prop in query.additional_parameters.keys() {
-            if !queryables.queryables.contains_key(prop) {
-                return Err(Error::ApiException(
-                    Exception::new_from_status(StatusCode::BAD_REQUEST.as_u16())
-                        .detail(format!("Property {prop} is not queryable!")),
-                ));
+    // queryables, which may be sampled from the features, only if the query
+    // refers to properties
+    let filter = query.parse_filter()?;
+    if !query.additional_parameters.is_empty() || filter.is_some() || !query.sortby.is_empty() {
+        let queryables = state.drivers.features.queryables(&collection_id).await?;
+        if !queryables.additional_properties {
+            for prop in query.additional_parameters.keys() {
+                if !queryables.queryables.contains_key(prop) {
+                    return Err(Error::ApiException(
+                        Exception::new_from_status(StatusCode::BAD_REQUEST.as_u16())
+                            .detail(format!("Property {prop} is not queryable!")),
+                    ));
+                }
             }
         }
+
+        // filter
+        if let Some(filter) = filter.as_ref() {
+            filter.check_queryables(&queryables)?;
+        }
+
+        // sortby
+        for sortby in &query.sortby {
+            sortby.check_queryables(&queryables)?;
+        }
+    }
+
+    if filter.is_some()
+        && let Some(filter_crs) = query.filter_crs.as_ref()
+    {
+        is_supported_crs(&collection, filter_crs).await?;
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Crs", format!("<{}>", crs).parse().unwrap());
+
+    // limit
+    if let Some(limit) = query.limit {
+        // TODO: sync with opanapi specification
+        if limit > 10000 {
+            query.limit = Some(10000);
+        }
+        if limit == 0 {
+            query.limit = Some(1)
+        }
+    }
+
+    // streamed encodings are not paged, all features are streamed unless
+    // `limit` is given
+    #[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+    if let Some(encoder) = negotiated.encoder() {
+        let schema = state.drivers.features.schema(&collection_id).await?;
+        let columns = encodings::columns(&schema.properties, query.properties.as_deref());
+        let crs = crs.to_owned();
+        let features = encodings::forward(
+            (state.drivers.clone(), collection_id, query),
+            |(drivers, collection_id, query)| drivers.features.stream_items(collection_id, query),
+        );
+        return Ok(encoder.response(headers, columns, &crs, features)?);
+    }
+
+    if query.limit.is_none() {
+        query.limit = Some(10);
+    }
+
+    // offset
+    if query.offset.is_none() {
+        query.offset = Some(0);
     }
 
     let mut fc = state
@@ -315,19 +780,29 @@ async fn items(
         .await?;
 
     fc.links.insert_or_update(&[
-        Link::new(&url, SELF).mediatype(GEO_JSON),
+        Link::new(&url, SELF).mediatype(negotiated.media_type()),
         Link::new(url.join("../..")?, ROOT).mediatype(JSON),
         Link::new(url.join(".")?, COLLECTION).mediatype(JSON),
     ]);
+    fc.links.extend(negotiated.alternates(&url));
 
     // pagination
-    if let Some(limit) = query.limit
+    if let Some(token) = fc.next.take() {
+        // keyset pagination
+        query.next = Some(token);
+        query.offset = None;
+        url.set_query(serde_qs::to_string(&query).ok().as_deref());
+        let next = Link::new(&url, NEXT).mediatype(negotiated.media_type());
+        fc.links.insert_or_update(&[next]);
+    } else if !state.drivers.features.keyset_pagination(&collection_id)
+        && query.next.is_none()
+        && let Some(limit) = query.limit
         && let Some(offset) = query.offset
     {
         if offset != 0 && offset >= limit {
             query.offset = Some(offset - limit);
             url.set_query(serde_qs::to_string(&query).ok().as_deref());
-            let previous = Link::new(&url, PREV).mediatype(GEO_JSON);
+            let previous = Link::new(&url, PREV).mediatype(negotiated.media_type());
             fc.links.insert_or_update(&[previous]);
         }
 
@@ -345,12 +820,15 @@ async fn items(
         if next {
             query.offset = Some(offset + limit);
             url.set_query(serde_qs::to_string(&query).ok().as_deref());
-            let next = Link::new(&url, NEXT).mediatype(GEO_JSON);
+            let next = Link::new(&url, NEXT).mediatype(negotiated.media_type());
             fc.links.insert_or_update(&[next]);
         }
     }
 
     for feature in fc.features.iter_mut() {
+        if let Some(place) = collection.place() {
+            feature.property_to_place(&place.name);
+        }
         feature.links.insert_or_update(&[
             Link::new(
                 url.join(&format!("items/{}", feature.id.as_ref().unwrap()))?,
@@ -362,45 +840,171 @@ async fn items(
         ])
     }
 
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Crs", format!("<{}>", crs).parse().unwrap());
-    headers.insert(CONTENT_TYPE, GEO_JSON.parse().unwrap());
+    negotiated.render(headers, "items.html", &fc)
+}
+
+/// Stream the changes of items
+///
+/// Stream the created, updated and deleted features of the feature collection
+/// with id `collectionId` as Server-Sent Events, from the time of the request
+/// on. Each event is named after the type of the change and carries the
+/// change as JSON data.
+#[utoipa::path(get, path = "/collections/{collectionId}/items/changes", tag = "Data",
+    params(
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        ChangesQuery,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The stream of the changes of the features in the \
+            collection with id `collectionId`.",
+            body = Change, content_type = "text/event-stream"),
+        (
+            status = 404, description = "The requested resource does not exist \
+            on the server. For example, a path parameter had an incorrect value.",
+            body = Exception, example = json!(Exception::new_from_status(404))
+        ),
+        (
+            status = 500, description = "A server error occurred.",
+            body = Exception, example = json!(Exception::new_from_status(500))
+        )
+    )
+)]
+async fn changes(
+    State(state): State<AppState>,
+    Path(collection_id): Path<String>,
+    Qs(query): Qs<ChangesQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let changes = state.drivers.changes.changes(&collection_id).await?;
+
+    let events = changes.filter_map(move |change| {
+        let event = match change {
+            Ok(change) => query
+                .bbox
+                .as_ref()
+                .is_none_or(|bbox| change.intersects(bbox))
+                .then(|| {
+                    Event::default()
+                        .event(change_type(change.r#type))
+                        .id(&change.id)
+                        .json_data(&change)
+                        .unwrap()
+                }),
+            Err(e) => {
+                tracing::error!("Change notification error: {:?}", e);
+                Some(Event::default().event("error").data(e.to_string()))
+            }
+        };
+        future::ready(event.map(Ok))
+    });
 
-    Ok((headers, Json(fc)))
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
 }
 
-// /// Fetch queriables of a collection
-// ///
-// /// Fetch the feature with id `featureId` in the feature collection with id
-// /// `collectionId`.
-// #[utoipa::path(get, path = "/collections/{collectionId}/queryables", tag = "Schema",
-//     params(
-//         ("collectionId" = String, Path, description = "local identifier of a collection")
-//     ),
-//     responses(
-//         (
-//             status = 200,
-//             description = "Fetch the queryable properties of the collection with id `collectionId`",
-//             body = Queryables),
-//         (
-//             status = 404, description = "The requested resource does not exist \
-//             on the server. For example, a path parameter had an incorrect value.",
-//             body = Exception, example = json!(Exception::new_from_status(404))
-//         ),
-//         (
-//             status = 500, description = "A server error occurred.",
-//             body = Exception, example = json!(Exception::new_from_status(500))
-//         )
-//     )
-// )]
-// async fn queryables(
-//     State(state): State<AppState>,
-//     Path(collection_id): Path<String>,
-// ) -> Result<Json<Queryables>> {
-//     let queryables = state.drivers.features.queryables(&collection_id).await?;
-
-//     Ok(Json(queryables))
-// }
+/// Event name of a change type
+fn change_type(change_type: ChangeType) -> &'static str {
+    match change_type {
+        ChangeType::Created => "created",
+        ChangeType::Updated => "updated",
+        ChangeType::Deleted => "deleted",
+    }
+}
+
+/// Fetch queryables of a collection
+///
+/// Fetch the properties of the feature collection with id `collectionId` that
+/// may be used to construct filter expressions, as JSON Schema.
+#[utoipa::path(get, path = "/collections/{collectionId}/queryables", tag = "Schema",
+    params(
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        Format
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The queryable properties of the collection with id `collectionId`",
+            body = Queryables, content_type = "application/schema+json"),
+        (
+            status = 404, description = "The requested resource does not exist \
+            on the server. For example, a path parameter had an incorrect value.",
+            body = Exception, example = json!(Exception::new_from_status(404))
+        ),
+        (
+            status = 500, description = "A server error occurred.",
+            body = Exception, example = json!(Exception::new_from_status(500))
+        )
+    )
+)]
+async fn queryables(
+    State(state): State<AppState>,
+    RemoteUrl(url): RemoteUrl,
+    Path(collection_id): Path<String>,
+    negotiated: Negotiated<JsonSchema>,
+) -> Result<Response> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut queryables = state.drivers.features.queryables(&collection_id).await?;
+    queryables.id = Some(url.to_string());
+
+    negotiated.encode(HeaderMap::new(), &queryables)
+}
+
+/// Fetch the schema of a collection
+///
+/// Fetch the JSON Schema of the features in the feature collection with id
+/// `collectionId`.
+#[utoipa::path(get, path = "/collections/{collectionId}/schema", tag = "Schema",
+    params(
+        ("collectionId" = String, Path, description = "local identifier of a collection"),
+        Format
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The schema of the features in the collection with id `collectionId`",
+            body = Schema, content_type = "application/schema+json"),
+        (
+            status = 404, description = "The requested resource does not exist \
+            on the server. For example, a path parameter had an incorrect value.",
+            body = Exception, example = json!(Exception::new_from_status(404))
+        ),
+        (
+            status = 500, description = "A server error occurred.",
+            body = Exception, example = json!(Exception::new_from_status(500))
+        )
+    )
+)]
+async fn schema(
+    State(state): State<AppState>,
+    RemoteUrl(url): RemoteUrl,
+    Path(collection_id): Path<String>,
+    negotiated: Negotiated<JsonSchema>,
+) -> Result<Response> {
+    state
+        .drivers
+        .collections
+        .read_collection(&collection_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let mut schema = state.drivers.features.schema(&collection_id).await?;
+    schema.id = Some(url.to_string());
+
+    negotiated.encode(HeaderMap::new(), &schema)
+}
 
 async fn is_supported_crs(collection: &Collection, crs: &Crs) -> Result<(), Error> {
     if collection.crs.contains(crs) {
@@ -414,8 +1018,391 @@ async fn is_supported_crs(collection: &Collection, crs: &Crs) -> Result<(), Erro
 
 pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
     state.conformance.write().unwrap().extend(&CONFORMANCE);
+    #[cfg(feature = "html")]
+    state.conformance.write().unwrap().extend(&HTML_CONFORMANCE);
 
     OpenApiRouter::new()
         .routes(routes!(items, create))
-        .routes(routes!(read, update, remove))
+        .layer(DefaultBodyLimit::max(BULK_BODY_LIMIT))
+        .routes(routes!(read, update, patch, remove))
+        .routes(routes!(versions))
+        .routes(routes!(changes))
+        .routes(routes!(queryables))
+        .routes(routes!(schema))
+        .layer(vary())
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            Request,
+            header::{ACCEPT, ETAG, IF_MATCH, VARY},
+        },
+    };
+    use tower::ServiceExt;
+
+    use ogcapi_types::features::Geometry;
+
+    use super::*;
+    use crate::Drivers;
+
+    #[tokio::test]
+    async fn changes_stream() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+
+        let (router, _) = router(&state).split_for_parts();
+        let app = router.with_state(state.clone());
+
+        let response = app
+            .oneshot(
+                Request::get("/collections/places/items/changes?bbox=7,46,8,47")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut events = response.into_body().into_data_stream();
+
+        // outside and inside of the bounding box
+        for (x, y) in [(8.54, 47.37), (7.44, 46.95)] {
+            let mut feature = Feature::new(Geometry::new_point([x, y]));
+            feature.collection = Some("places".to_string());
+            state
+                .drivers
+                .features
+                .create_feature(&feature)
+                .await
+                .unwrap();
+        }
+
+        let event = events.next().await.unwrap().unwrap();
+        let event = std::str::from_utf8(&event).unwrap();
+        assert!(event.starts_with("event: created\n"));
+        assert!(event.contains("[7.44,46.95,7.44,46.95]"));
+    }
+
+    #[tokio::test]
+    async fn negotiation() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+
+        let (router, _) = router(&state).split_for_parts();
+        let app = router.with_state(state);
+
+        let request = |uri: &str, accept: &str| {
+            Request::get(uri)
+                .header(ACCEPT, accept)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let items = "http://localhost/collections/places/items";
+
+        for (uri, accept, content_type) in [
+            (items.to_string(), "application/json", Some(GEO_JSON)),
+            (
+                items.to_string(),
+                "image/png;q=0.9, */*;q=0.1",
+                Some(GEO_JSON),
+            ),
+            (format!("{items}?f=json"), "text/html", Some(GEO_JSON)),
+            #[cfg(feature = "html")]
+            (
+                items.to_string(),
+                "text/html,*/*;q=0.8",
+                Some("text/html; charset=utf-8"),
+            ),
+            #[cfg(feature = "html")]
+            (
+                format!("{items}?f=html"),
+                GEO_JSON,
+                Some("text/html; charset=utf-8"),
+            ),
+            (items.to_string(), "image/png", None),
+            (format!("{items}?f=xml"), "*/*", None),
+        ] {
+            let response = app.clone().oneshot(request(&uri, accept)).await.unwrap();
+            assert_eq!(response.headers()[VARY], "accept");
+
+            match content_type {
+                Some(content_type) => {
+                    assert_eq!(response.status(), StatusCode::OK, "{uri} {accept}");
+                    assert_eq!(response.headers()[CONTENT_TYPE], content_type);
+                }
+                None => assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE),
+            }
+        }
+
+        // the `f` parameter is not a property filter and is kept by the links
+        let response = app
+            .oneshot(request(&format!("{items}?limit=1&f=json"), "*/*"))
+            .await
+            .unwrap();
+        let fc: FeatureCollection = serde_json::from_slice(
+            &axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let link = fc.links.iter().find(|link| link.rel == SELF).unwrap();
+        assert_eq!(link.href, format!("{items}?limit=1&f=json"));
+        assert_eq!(link.r#type.as_deref(), Some(GEO_JSON));
+    }
+
+    #[tokio::test]
+    async fn pagination() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+        for (x, y) in [(8.54, 47.37), (7.44, 46.95)] {
+            let mut feature = Feature::new(Geometry::new_point([x, y]));
+            feature.collection = Some("places".to_string());
+            state
+                .drivers
+                .features
+                .create_feature(&feature)
+                .await
+                .unwrap();
+        }
+
+        let (router, _) = router(&state).split_for_parts();
+        let app = router.with_state(state);
+
+        // the driver's token tells whether there is a next page
+        for (query, next) in [("limit=1&offset=0", true), ("limit=2&offset=0", false)] {
+            let uri = format!("http://localhost/collections/places/items?{query}");
+            let request = Request::get(&uri).body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            let fc: FeatureCollection = serde_json::from_slice(
+                &axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(
+                fc.links.iter().any(|link| link.rel == NEXT),
+                next,
+                "{query}"
+            );
+        }
+    }
+
+    #[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+    #[tokio::test]
+    async fn downloads() {
+        use ogcapi_drivers::{FeatureTransactions, files::Files};
+
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+
+        let geometries = [
+            Geometry::new_point([7.44, 46.95]),
+            Geometry::new(geojson::GeometryValue::MultiPolygon {
+                coordinates: vec![
+                    vec![vec![
+                        [0.0, 0.0].into(),
+                        [1.0, 0.0].into(),
+                        [1.0, 1.0].into(),
+                        [0.0, 0.0].into(),
+                    ]],
+                    vec![vec![
+                        [2.0, 2.0].into(),
+                        [3.0, 2.0].into(),
+                        [3.0, 3.0].into(),
+                        [2.0, 2.0].into(),
+                    ]],
+                ],
+            }),
+        ];
+        let features: Vec<Feature> = geometries
+            .iter()
+            .zip([("Bern", 134591), ("Islands", 0)])
+            .map(|(geometry, (name, population))| {
+                let mut feature = Feature::new(geometry.to_owned());
+                feature.properties = Some(Map::from_iter([
+                    ("name".to_string(), name.into()),
+                    ("population".to_string(), population.into()),
+                ]));
+                feature
+            })
+            .collect();
+        state
+            .drivers
+            .features
+            .create_features("places", &features)
+            .await
+            .unwrap();
+
+        let (router, _) = router(&state).split_for_parts();
+        let app = router.with_state(state);
+
+        // the files driver reads the downloads back
+        let dir = std::env::temp_dir().join(format!("ogcapi-downloads-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = Files::new(&dir);
+
+        for (f, content_type) in [
+            #[cfg(feature = "flatgeobuf")]
+            ("fgb", "application/flatgeobuf"),
+            #[cfg(feature = "geoparquet")]
+            ("parquet", "application/vnd.apache.parquet"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get(format!("http://localhost/collections/places/items?f={f}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[CONTENT_TYPE], content_type);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            std::fs::write(dir.join(format!("{f}.{f}")), body).unwrap();
+
+            let fc = files.list_items(f, &Query::default()).await.unwrap();
+            assert_eq!(fc.features.len(), 2);
+            for (feature, geometry) in fc.features.iter().zip(&geometries) {
+                assert_eq!(feature.geometry.as_ref(), Some(geometry), "{f}");
+            }
+            let properties = fc.features[0].properties.as_ref().unwrap();
+            assert_eq!(properties["name"], "Bern");
+            assert_eq!(properties["population"].as_f64(), Some(134591.0));
+            assert!(properties["id"].is_string());
+
+            // limits are capped as for pages
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get(format!(
+                        "http://localhost/collections/places/items?f={f}&limit=0"
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            std::fs::write(dir.join(format!("{f}-limit.{f}")), body).unwrap();
+
+            let fc = files
+                .list_items(&format!("{f}-limit"), &Query::default())
+                .await
+                .unwrap();
+            assert_eq!(fc.features.len(), 1);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn etags() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+        let mut feature = Feature::new(Geometry::new_point([7.44, 46.95]));
+        feature.collection = Some("places".to_string());
+        let id = state
+            .drivers
+            .features
+            .create_feature(&feature)
+            .await
+            .unwrap();
+
+        let (router, _) = router(&state).split_for_parts();
+        let app = router.with_state(state);
+
+        let etag = |query: &'static str| {
+            let app = app.clone();
+            let uri = format!("http://localhost/collections/places/items/{id}{query}");
+            async move {
+                let response = app
+                    .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK, "{query}");
+                response.headers()[ETAG].to_str().unwrap().to_owned()
+            }
+        };
+
+        // the GeoJSON representation is tagged with the version
+        let tag = etag("").await;
+        assert_eq!(tag, etag("?f=json").await);
+        // single features are not projected
+        assert_eq!(tag, etag("?properties=name").await);
+        #[cfg(feature = "html")]
+        assert_ne!(tag, etag("?f=html").await);
+
+        // unknown collections are not found
+        for method in ["GET", "PUT", "PATCH", "DELETE"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri("http://localhost/collections/nope/items/1")
+                        .header(CONTENT_TYPE, "application/json")
+                        .header(IF_MATCH, tag.as_str())
+                        .body(Body::from(
+                            r#"{"type":"Feature","geometry":null,"properties":{}}"#,
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{method}");
+        }
+    }
 }
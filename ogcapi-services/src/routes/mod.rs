@@ -12,3 +12,56 @@ pub(crate) mod stac;
 pub(crate) mod styles;
 #[cfg(feature = "tiles")]
 pub(crate) mod tiles;
+
+use axum::http::StatusCode;
+use headers::{ETag, IfMatch};
+
+use ogcapi_types::common::Exception;
+
+/// Strong entity tag for the version tag of a driver
+pub(crate) fn etag(tag: &str) -> anyhow::Result<ETag> {
+    format!("\"{tag}\"")
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid entity tag `{tag}`"))
+}
+
+/// Strong entity tag of a representation of a resource
+///
+/// The representation that is written, with an empty `variant`, is tagged
+/// with the version tag of a driver, which is matched by `If-Match`. Other
+/// representations, e.g. HTML or another crs, have the hash of their variant
+/// appended.
+pub(crate) fn representation_etag(tag: &str, variant: &[String]) -> anyhow::Result<ETag> {
+    if variant.is_empty() {
+        return etag(tag);
+    }
+
+    // FNV-1a, which is stable across platforms and releases
+    let hash = variant
+        .join("\n")
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    etag(&format!("{tag}-{hash:016x}"))
+}
+
+/// Evaluate the `If-Match` precondition against the current entity tag of a
+/// resource, returning the tag a conditional write has to match
+pub(crate) fn precondition(
+    if_match: Option<&IfMatch>,
+    current: Option<String>,
+) -> anyhow::Result<Option<String>> {
+    let Some(if_match) = if_match else {
+        return Ok(None);
+    };
+
+    match current {
+        Some(tag) if if_match.precondition_passes(&etag(&tag)?) => Ok(Some(tag)),
+        _ => Err(
+            Exception::new_from_status(StatusCode::PRECONDITION_FAILED.as_u16())
+                .detail("The entity tag does not match the current version of the resource")
+                .into(),
+        ),
+    }
+}
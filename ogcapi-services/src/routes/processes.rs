@@ -28,6 +28,7 @@ use ogcapi_types::{
 use crate::{
     AppState, Error, Result,
     extractors::RemoteUrl,
+    negotiation::{Document, Negotiated, vary},
     processes::{ProcessExecuteResponse, ProcessResultsResponse, ValidParams},
 };
 
@@ -65,7 +66,8 @@ async fn processes(
     State(state): State<AppState>,
     RemoteUrl(mut url): RemoteUrl,
     Query(mut query): Query<LimitOffsetPagination>,
-) -> Result<Json<ProcessList>> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let processors = read_lock(&state.processors);
     let limit = query.limit.unwrap_or_else(|| processors.len());
     let offset = query.offset.unwrap_or(0);
@@ -82,7 +84,8 @@ async fn processes(
         })
         .collect();
 
-    let mut links = vec![Link::new(url.clone(), SELF).mediatype(JSON)];
+    let mut links = vec![Link::new(url.clone(), SELF).mediatype(negotiated.media_type())];
+    links.extend(negotiated.alternates(&url));
 
     if query.limit.is_some() {
         if offset != 0 && offset >= limit {
@@ -124,7 +127,7 @@ async fn processes(
         links,
     };
 
-    Ok(Json(process_list))
+    negotiated.encode(HeaderMap::new(), &process_list)
 }
 
 /// Retrieve a processes description
@@ -154,7 +157,8 @@ async fn process(
     State(state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
     Path(process_id): Path<String>,
-) -> Result<Json<Process>> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     match read_lock(&state.processors)
         .get(&process_id)
         .and_then(|processor| processor.process().ok())
@@ -162,13 +166,13 @@ async fn process(
         Some(mut process) => {
             process.summary.links.insert_or_update(&[
                 Link::new(url.clone(), SELF)
-                    .mediatype(JSON)
+                    .mediatype(negotiated.media_type())
                     .title("Process description"),
                 Link::new(url_plus_segments(url, &["execution"])?, EXECUTE)
                     .title("Execute endpoint"),
             ]);
 
-            Ok(Json(process))
+            negotiated.encode(HeaderMap::new(), &process)
         }
         None => Err(Error::ApiException(
             (
@@ -411,7 +415,8 @@ async fn jobs(
     State(state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
     Query(query): Query<LimitOffsetPagination>,
-) -> Result<Json<JobList>> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     const DEFAULT_LIMIT: usize = 10;
     const MAX_LIMIT: usize = 100;
 
@@ -420,7 +425,8 @@ async fn jobs(
 
     let jobs = state.drivers.jobs.status_list(offset, limit).await?;
 
-    let mut links = vec![Link::new(url.clone(), SELF).mediatype(JSON)];
+    let mut links = vec![Link::new(url.clone(), SELF).mediatype(negotiated.media_type())];
+    links.extend(negotiated.alternates(&url));
 
     if jobs.len() >= limit {
         let mut next_url = url.clone();
@@ -429,7 +435,7 @@ async fn jobs(
         links.push(Link::new(&next_url, NEXT).mediatype(JSON));
     }
 
-    Ok(Json(JobList { jobs, links }))
+    negotiated.encode(HeaderMap::new(), &JobList { jobs, links })
 }
 
 /// Retrieve the status of a job
@@ -454,6 +460,7 @@ async fn status(
     State(state): State<AppState>,
     Path(job_id): Path<String>,
     RemoteUrl(url): RemoteUrl,
+    negotiated: Negotiated<Document>,
 ) -> Result<Response> {
     let status = state.drivers.jobs.status(&job_id).await?;
 
@@ -469,13 +476,13 @@ async fn status(
     };
 
     info.links.insert_or_update(&[
-        Link::new(url.clone(), SELF).mediatype(JSON),
+        Link::new(url.clone(), SELF).mediatype(negotiated.media_type()),
         Link::new(url_plus_segments(url, &["results"])?, RESULTS)
             .mediatype(JSON)
             .title("Job results"),
     ]);
 
-    Ok(Json(info).into_response())
+    negotiated.encode(HeaderMap::new(), &info)
 }
 
 /// Cancel a job execution, remove finished job
@@ -623,6 +630,7 @@ pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
         .routes(routes!(jobs))
         .routes(routes!(status, delete))
         .routes(routes!(results))
+        .layer(vary())
 }
 
 /// Helper function to add a path segment to a URL, returning an error if the URL cannot be modified.
@@ -666,6 +674,16 @@ mod tests {
     use ogcapi_types::common::link_rel::EXECUTE;
     use tokio::task_local;
 
+    /// Deserialized body of a response
+    async fn body<T: serde::de::DeserializeOwned>(response: Response) -> T {
+        serde_json::from_slice(
+            &axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
     /// Test that we can pass task-local context into spawned tasks.
     #[tokio::test]
     async fn it_allows_passing_scope_in_spawn() {
@@ -877,17 +895,20 @@ mod tests {
         let base_url = Url::parse("http://example.org/subdir/").unwrap();
 
         // Call `processes` and assert the self link is the base URL
-        let processes_response = processes(
-            State(state.clone()),
-            RemoteUrl(base_url.join("processes").unwrap()),
-            Query(LimitOffsetPagination {
-                limit: Some(10),
-                offset: Some(0),
-            }),
+        let processes_response: ProcessList = body(
+            processes(
+                State(state.clone()),
+                RemoteUrl(base_url.join("processes").unwrap()),
+                Query(LimitOffsetPagination {
+                    limit: Some(10),
+                    offset: Some(0),
+                }),
+                Negotiated::default(),
+            )
+            .await
+            .unwrap(),
         )
-        .await
-        .unwrap()
-        .0;
+        .await;
 
         assert_eq!(
             processes_response.links,
@@ -909,14 +930,17 @@ mod tests {
         );
 
         // Call `process` and assert the process description contains the self link
-        let process_response = process(
-            State(state.clone()),
-            RemoteUrl(base_url.join("processes/echo").unwrap()),
-            Path("echo".to_string()),
+        let process_response: Process = body(
+            process(
+                State(state.clone()),
+                RemoteUrl(base_url.join("processes/echo").unwrap()),
+                Path("echo".to_string()),
+                Negotiated::default(),
+            )
+            .await
+            .unwrap(),
         )
-        .await
-        .unwrap()
-        .0;
+        .await;
 
         assert_eq!(
             process_response.summary.links,
@@ -933,17 +957,20 @@ mod tests {
         );
 
         // Call `jobs` and assert the self link
-        let jobs_response = jobs(
-            State(state.clone()),
-            RemoteUrl(base_url.join("jobs?limit=10&offset=0").unwrap()),
-            Query(LimitOffsetPagination {
-                limit: Some(10),
-                offset: Some(0),
-            }),
+        let jobs_response: JobList = body(
+            jobs(
+                State(state.clone()),
+                RemoteUrl(base_url.join("jobs?limit=10&offset=0").unwrap()),
+                Query(LimitOffsetPagination {
+                    limit: Some(10),
+                    offset: Some(0),
+                }),
+                Negotiated::default(),
+            )
+            .await
+            .unwrap(),
         )
-        .await
-        .unwrap()
-        .0;
+        .await;
 
         assert_eq!(
             jobs_response.links,
@@ -996,19 +1023,17 @@ mod tests {
         );
 
         // call status route and assert
-        let status_response = status(
-            State(state.clone()),
-            Path("job1".to_string()),
-            RemoteUrl(base_url.join("jobs/job1").unwrap()),
-        )
-        .await
-        .unwrap();
-        let status_info: StatusInfo = serde_json::from_slice(
-            &axum::body::to_bytes(status_response.into_body(), usize::MAX)
-                .await
-                .unwrap(),
+        let status_info: StatusInfo = body(
+            status(
+                State(state.clone()),
+                Path("job1".to_string()),
+                RemoteUrl(base_url.join("jobs/job1").unwrap()),
+                Negotiated::default(),
+            )
+            .await
+            .unwrap(),
         )
-        .unwrap();
+        .await;
         assert_eq!(
             status_info.links,
             &[
@@ -1020,19 +1045,16 @@ mod tests {
         );
 
         // call delete (dismiss) route and assert
-        let delete_response = delete(
-            State(state.clone()),
-            Path("job1".to_string()),
-            RemoteUrl(base_url.join("jobs/job1").unwrap()),
-        )
-        .await
-        .unwrap();
-        let status_info: StatusInfo = serde_json::from_slice(
-            &axum::body::to_bytes(delete_response.into_body(), usize::MAX)
-                .await
-                .unwrap(),
+        let status_info: StatusInfo = body(
+            delete(
+                State(state.clone()),
+                Path("job1".to_string()),
+                RemoteUrl(base_url.join("jobs/job1").unwrap()),
+            )
+            .await
+            .unwrap(),
         )
-        .unwrap();
+        .await;
         assert_eq!(
             status_info.links,
             &[Link::new("http://example.org/subdir/jobs/job1", SELF).mediatype(JSON)]
@@ -1,12 +1,23 @@
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use std::collections::HashMap;
+
 use axum::{
     Json,
     extract::State,
     http::{HeaderMap, StatusCode},
+    response::Response,
 };
-use hyper::header::CONTENT_TYPE;
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use futures::{StreamExt, TryStreamExt};
 use url::Url;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use ogcapi_types::{
+    common::Crs,
+    features::{Feature, Queryable},
+};
+
 use ogcapi_types::{
     common::{
         Bbox, Exception, Link, Linked,
@@ -17,9 +28,12 @@ use ogcapi_types::{
     stac::{SearchBody, SearchParams},
 };
 
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+use crate::encodings;
 use crate::{
     AppState, Error, Result,
     extractors::{Qs, RemoteUrl},
+    negotiation::{Negotiated, SearchResults, vary},
 };
 
 /// Search STAC items with simple filtering.
@@ -51,8 +65,9 @@ pub(crate) async fn search_get(
     State(state): State<AppState>,
     Qs(params): Qs<SearchParams>,
     RemoteUrl(url): RemoteUrl,
-) -> Result<(HeaderMap, Json<FeatureCollection>)> {
-    search(params, url, state).await
+    negotiated: Negotiated<SearchResults>,
+) -> Result<Response> {
+    search(params, url, state, negotiated).await
 }
 
 /// Search STAC items with full-featured filtering.
@@ -82,39 +97,30 @@ pub(crate) async fn search_get(
 pub(crate) async fn search_post(
     State(state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
+    negotiated: Negotiated<SearchResults>,
     Json(params): Json<SearchBody>,
-) -> Result<(HeaderMap, Json<FeatureCollection>)> {
-    search(params.into(), url, state).await
+) -> Result<Response> {
+    search(params.into(), url, state, negotiated).await
 }
 
 pub(crate) async fn search(
     mut params: SearchParams,
     mut url: Url,
     state: AppState,
-) -> Result<(HeaderMap, Json<FeatureCollection>)> {
+    negotiated: Negotiated<SearchResults>,
+) -> Result<Response> {
     tracing::debug!("{:#?}", params);
 
-    // Limit
-    if let Some(limit) = params.limit {
-        if !(1..10001).contains(&limit) {
-            return Err(Error::ApiException(
-                (
-                    StatusCode::BAD_REQUEST,
-                    "query parameter `limit` not in range 1 to 10000".to_string(),
-                )
-                    .into(),
-            ));
-        }
-    } else {
-        // default
-        params.limit = Some(100);
+    // Fields
+    if let Some(fields) = params.fields.clone() {
+        fields.apply(&mut params)?;
     }
 
-    // Bbox
+    // Bbox, the west edge is larger than the east edge if it spans the antimeridian
     if let Some(bbox) = params.bbox.as_ref() {
         match bbox {
             Bbox::Bbox2D(bbox) => {
-                if bbox[0] > bbox[2] || bbox[1] > bbox[3] {
+                if bbox[1] > bbox[3] {
                     return Err(Error::ApiException(
                         (
                             StatusCode::BAD_REQUEST,
@@ -125,7 +131,7 @@ pub(crate) async fn search(
                 }
             }
             Bbox::Bbox3D(bbox) => {
-                if bbox[0] > bbox[3] || bbox[1] > bbox[4] || bbox[2] > bbox[5] {
+                if bbox[1] > bbox[4] || bbox[2] > bbox[5] {
                     return Err(Error::ApiException(
                         (
                             StatusCode::BAD_REQUEST,
@@ -138,12 +144,69 @@ pub(crate) async fn search(
         }
     }
 
+    // Sortby, the fields of items and the queryables of a searched collection
+    if !params.sortby.is_empty() {
+        let mut queryables = Vec::new();
+        for collection_id in searched(&state, &params).await? {
+            queryables.push(state.drivers.features.queryables(&collection_id).await?);
+        }
+        for sortby in &params.sortby {
+            if sortby.property() != "collection"
+                && !queryables
+                    .iter()
+                    .any(|queryables| sortby.check_queryables(queryables).is_ok())
+            {
+                return Err(Error::ApiException(
+                    Exception::new_from_status(StatusCode::BAD_REQUEST.as_u16())
+                        .detail(format!("Property {} is not sortable!", sortby.property())),
+                ));
+            }
+        }
+    }
+
+    // Limit
+    if let Some(limit) = params.limit
+        && !(1..10001).contains(&limit)
+    {
+        return Err(Error::ApiException(
+            (
+                StatusCode::BAD_REQUEST,
+                "query parameter `limit` not in range 1 to 10000".to_string(),
+            )
+                .into(),
+        ));
+    }
+
+    // streamed encodings are not paged, all features are streamed unless
+    // `limit` is given
+    #[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+    if let Some(encoder) = negotiated.encoder() {
+        return Ok(encoder.response(
+            HeaderMap::new(),
+            columns(&state, &params).await?,
+            &Crs::default2d(),
+            encodings::forward((state.drivers.clone(), params), |(drivers, params)| {
+                drivers
+                    .stac
+                    .stream_search(params)
+                    .map_ok(collection)
+                    .boxed()
+            }),
+        )?);
+    }
+
+    // default limit of pages
+    if params.limit.is_none() {
+        params.limit = Some(100);
+    }
+
     let mut fc = state.drivers.stac.search(&params).await?;
 
     fc.links.insert_or_update(&[
-        Link::new(&url, SELF).mediatype(GEO_JSON),
+        Link::new(&url, SELF).mediatype(negotiated.media_type()),
         Link::new(url.join("../..")?, ROOT).mediatype(JSON),
     ]);
+    fc.links.extend(negotiated.alternates(&url));
 
     // pagination
     if let Some(limit) = params.limit {
@@ -155,7 +218,7 @@ pub(crate) async fn search(
             if offset != 0 && offset >= limit {
                 params.offset = Some(offset - limit);
                 url.set_query(serde_qs::to_string(&params).ok().as_deref());
-                let previous = Link::new(&url, PREV).mediatype(GEO_JSON);
+                let previous = Link::new(&url, PREV).mediatype(negotiated.media_type());
                 fc.links.insert_or_update(&[previous]);
             }
 
@@ -164,7 +227,7 @@ pub(crate) async fn search(
             {
                 params.offset = Some(offset + limit);
                 url.set_query(serde_qs::to_string(&params).ok().as_deref());
-                let next = Link::new(&url, NEXT).mediatype(GEO_JSON);
+                let next = Link::new(&url, NEXT).mediatype(negotiated.media_type());
                 fc.links.insert_or_update(&[next]);
             }
         }
@@ -187,12 +250,125 @@ pub(crate) async fn search(
         ])
     }
 
-    let mut headers = HeaderMap::new();
-    headers.insert(CONTENT_TYPE, GEO_JSON.parse().unwrap());
+    negotiated.encode(HeaderMap::new(), &fc)
+}
+
+/// Ids of the searched collections, or else of all collections
+async fn searched(state: &AppState, params: &SearchParams) -> Result<Vec<String>> {
+    Ok(if params.collections.is_empty() {
+        state
+            .drivers
+            .collections
+            .list_collections(&Default::default())
+            .await?
+            .collections
+            .into_iter()
+            .map(|collection| collection.id)
+            .collect()
+    } else {
+        params.collections.to_owned()
+    })
+}
+
+/// Columns of the items of the searched collections, with their collection
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+async fn columns(state: &AppState, params: &SearchParams) -> Result<Vec<encodings::Column>> {
+    let mut properties = HashMap::from([("collection".to_string(), Queryable::new("string"))]);
+    for collection_id in searched(state, params).await? {
+        let schema = state.drivers.features.schema(&collection_id).await?;
+        for (name, property) in schema.properties {
+            properties.entry(name).or_insert(property);
+        }
+    }
+
+    Ok(encodings::columns(
+        &properties,
+        params.properties.as_deref(),
+    ))
+}
 
-    Ok((headers, Json(fc)))
+/// Item with its collection as a property
+#[cfg(any(feature = "flatgeobuf", feature = "geoparquet"))]
+fn collection(mut feature: Feature) -> Feature {
+    if let Some(collection) = feature.collection.to_owned() {
+        feature
+            .properties
+            .get_or_insert_default()
+            .insert("collection".to_string(), collection.into());
+    }
+    feature
 }
 
 pub(crate) fn router() -> OpenApiRouter<AppState> {
-    OpenApiRouter::new().routes(routes!(search_get, search_post))
+    OpenApiRouter::new()
+        .routes(routes!(search_get, search_post))
+        .layer(vary())
+}
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    use ogcapi_types::{
+        common::Collection,
+        features::{Feature, Geometry},
+    };
+
+    use super::*;
+    use crate::Drivers;
+
+    #[tokio::test]
+    async fn validation() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let collection = Collection {
+            id: "places".to_string(),
+            ..Default::default()
+        };
+        state
+            .drivers
+            .collections
+            .create_collection(&collection)
+            .await
+            .unwrap();
+        let mut feature = Feature::new(Geometry::new_point([7.44, 46.95]));
+        feature.collection = Some("places".to_string());
+        feature.properties = Some(serde_json::Map::from_iter([(
+            "name".to_string(),
+            "Bern".into(),
+        )]));
+        state
+            .drivers
+            .features
+            .create_feature(&feature)
+            .await
+            .unwrap();
+
+        let (router, _) = router().split_for_parts();
+        let app = router.with_state(state);
+
+        for (query, status) in [
+            ("sortby=-properties.name", StatusCode::OK),
+            ("sortby=collection,-id", StatusCode::OK),
+            ("sortby=properties.population", StatusCode::BAD_REQUEST),
+            ("fields=properties.name,-geometry", StatusCode::OK),
+            ("fields=id,assets,properties.name", StatusCode::OK),
+            ("fields=-assets", StatusCode::BAD_REQUEST),
+            ("fields=-properties.name", StatusCode::BAD_REQUEST),
+            ("limit=0", StatusCode::BAD_REQUEST),
+            #[cfg(feature = "flatgeobuf")]
+            ("limit=0&f=fgb", StatusCode::BAD_REQUEST),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get(format!("http://localhost/search?{query}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{query}");
+        }
+    }
 }
@@ -1,28 +1,37 @@
 use axum::{
-    Json,
     extract::{Path, State},
+    http::HeaderMap,
+    response::Response,
     routing::get,
 };
-use serde_json::Value;
 use utoipa_axum::router::OpenApiRouter;
 
-use ogcapi_types::styles::Styles;
-
-use crate::{AppState, Error, Result};
+use crate::{
+    AppState, Error, Result,
+    negotiation::{Document, Negotiated, StyleDocument, vary},
+};
 
-async fn styles(State(state): State<AppState>) -> Result<Json<Styles>> {
+async fn styles(
+    State(state): State<AppState>,
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let styles = state.drivers.styles.list_styles().await?;
-    Ok(Json(styles))
+    negotiated.encode(HeaderMap::new(), &styles)
 }
 
-async fn read_style(Path(id): Path<String>, State(state): State<AppState>) -> Result<Json<Value>> {
+async fn read_style(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    negotiated: Negotiated<StyleDocument>,
+) -> Result<Response> {
     let style = state.drivers.styles.read_style(&id).await?;
 
-    style.map(Json).ok_or(Error::NotFound)
+    negotiated.encode(HeaderMap::new(), &style.ok_or(Error::NotFound)?)
 }
 
 pub(crate) fn router(_state: &AppState) -> OpenApiRouter<AppState> {
     OpenApiRouter::new()
         .route("/styles", get(styles))
         .route("/styles/{id}", get(read_style))
+        .layer(vary())
 }
@@ -1,9 +1,12 @@
 use std::sync::{Arc, OnceLock};
 
 use axum::{
-    Json,
     extract::{Path, State},
-    http::HeaderMap,
+    http::{
+        HeaderMap,
+        header::{CONTENT_TYPE, HeaderName},
+    },
+    response::Response,
 };
 use dashmap::DashMap;
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -23,6 +26,7 @@ use ogcapi_types::{
 use crate::{
     AppState, Result,
     extractors::{Qs, RemoteUrl},
+    negotiation::{Document, Negotiated, VectorTile, vary},
 };
 
 const CONFORMANCE: [&str; 7] = [
@@ -58,7 +62,10 @@ static TMS: OnceLock<Arc<DashMap<TileMatrixSetId, TileMatrixSet>>> = OnceLock::n
         )
     )
 )]
-async fn tile_matrix_sets(RemoteUrl(url): RemoteUrl) -> Result<Json<TileMatrixSets>> {
+async fn tile_matrix_sets(
+    RemoteUrl(url): RemoteUrl,
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let registry = TMS.get().expect("TMS cell to be inizialized");
 
     let tile_matrix_sets = registry
@@ -76,7 +83,7 @@ async fn tile_matrix_sets(RemoteUrl(url): RemoteUrl) -> Result<Json<TileMatrixSe
         })
         .collect();
 
-    Ok(Json(TileMatrixSets { tile_matrix_sets }))
+    negotiated.encode(HeaderMap::new(), &TileMatrixSets { tile_matrix_sets })
 }
 
 /// Retrieve the definition of the specified tiling scheme (tile matrix set)
@@ -104,11 +111,14 @@ async fn tile_matrix_sets(RemoteUrl(url): RemoteUrl) -> Result<Json<TileMatrixSe
         )
     )
 )]
-async fn tile_matrix_set(Path(id): Path<TileMatrixSetId>) -> Result<Json<TileMatrixSet>> {
+async fn tile_matrix_set(
+    Path(id): Path<TileMatrixSetId>,
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let registry = TMS.get().expect("TMS cell to be inizialized");
 
     if let Some(tms) = registry.get(&id) {
-        Ok(Json(tms.to_owned()))
+        negotiated.encode(HeaderMap::new(), &*tms)
     } else {
         // fetch online
         let url = format!(
@@ -118,7 +128,7 @@ async fn tile_matrix_set(Path(id): Path<TileMatrixSetId>) -> Result<Json<TileMat
             Ok(r) => match r.json::<TileMatrixSet>().await {
                 Ok(tms) => {
                     // registry.insert(tms.id.to_owned(), tms);
-                    Ok(Json(tms.to_owned()))
+                    negotiated.encode(HeaderMap::new(), &tms)
                 }
                 Err(e) => Err(Exception::new_from_status(500).detail(e.to_string()).into()),
             },
@@ -146,7 +156,7 @@ async fn tile_matrix_set(Path(id): Path<TileMatrixSetId>) -> Result<Json<TileMat
         )
     )
 )]
-async fn tiles(RemoteUrl(url): RemoteUrl) -> Result<Json<TileSets>> {
+async fn tiles(RemoteUrl(url): RemoteUrl, negotiated: Negotiated<Document>) -> Result<Response> {
     let registry = TMS.get().expect("TMS cell to be inizialized");
 
     let mut tilesets = Vec::new();
@@ -183,7 +193,7 @@ async fn tiles(RemoteUrl(url): RemoteUrl) -> Result<Json<TileSets>> {
         links: vec![],
     };
 
-    Ok(Json(tile_sets))
+    negotiated.encode(HeaderMap::new(), &tile_sets)
 }
 
 /// Retrieve the vector tileset metadata for the whole dataset and the
@@ -210,7 +220,8 @@ async fn tiles(RemoteUrl(url): RemoteUrl) -> Result<Json<TileSets>> {
 async fn tiles_tile_set(
     RemoteUrl(url): RemoteUrl,
     Path(tms_id): Path<TileMatrixSetId>,
-) -> Result<(HeaderMap, Json<TileSet>)> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let mut headers = HeaderMap::new();
 
     // tms
@@ -226,7 +237,7 @@ async fn tiles_tile_set(
     let tms_url = url.join(&tms_path).expect("failed to parse url");
 
     // links
-    let self_link = Link::new(url.clone(), SELF).mediatype(JSON);
+    let self_link = Link::new(url.clone(), SELF).mediatype(negotiated.media_type());
 
     let tms_link = Link::new(tms_url.clone(), TILING_SCHEME).mediatype(JSON);
 
@@ -268,7 +279,7 @@ async fn tiles_tile_set(
         media_types: Default::default(),
     };
 
-    Ok((headers, Json(tile_set)))
+    negotiated.encode(headers, &tile_set)
 }
 
 /// Retrieve a vector tile including one or more collections from the dataset.
@@ -295,7 +306,8 @@ async fn tiles_tile(
     Path(params): Path<TileParams>,
     Qs(query): Qs<TileQuery>,
     State(state): State<AppState>,
-) -> Result<Vec<u8>> {
+    negotiated: Negotiated<VectorTile>,
+) -> Result<([(HeaderName, &'static str); 1], Vec<u8>)> {
     // tile matrix set
     let tms_id = &params.tile_matrix_set_id;
     let Some(tms) = TMS.get().and_then(|tms| tms.get(tms_id)) else {
@@ -333,10 +345,11 @@ async fn tiles_tile(
             &params.tile_matrix,
             params.tile_row,
             params.tile_col,
+            query.geometry.as_deref(),
         )
         .await?;
 
-    Ok(tiles)
+    Ok(([(CONTENT_TYPE, negotiated.media_type())], tiles))
 }
 
 /// Retrieve a list of available vector tilesets for the collection
@@ -359,7 +372,8 @@ async fn tiles_tile(
 async fn collection_tiles(
     RemoteUrl(url): RemoteUrl,
     Path(collection_id): Path<String>,
-) -> Result<Json<TileSets>> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let registry = TMS.get().expect("TMS cell to be inizialized");
 
     let mut tilesets = Vec::new();
@@ -396,7 +410,7 @@ async fn collection_tiles(
         links: vec![],
     };
 
-    Ok(Json(tile_sets))
+    negotiated.encode(HeaderMap::new(), &tile_sets)
 }
 
 /// Retrieve the vector tileset metadata for a specific collection and the
@@ -425,7 +439,8 @@ async fn collection_tile_set(
     State(_state): State<AppState>,
     RemoteUrl(url): RemoteUrl,
     Path((collection_id, tms_id)): Path<(String, TileMatrixSetId)>,
-) -> Result<(HeaderMap, Json<TileSet>)> {
+    negotiated: Negotiated<Document>,
+) -> Result<Response> {
     let mut headers = HeaderMap::new();
 
     // tms
@@ -442,7 +457,7 @@ async fn collection_tile_set(
 
     // links
     let self_url = url.join(&tms_id.to_string()).expect("failed to parse url");
-    let self_link = Link::new(self_url, SELF).mediatype(JSON);
+    let self_link = Link::new(self_url, SELF).mediatype(negotiated.media_type());
 
     let tms_link = Link::new(tms_url.clone(), TILING_SCHEME).mediatype(JSON);
 
@@ -486,7 +501,7 @@ async fn collection_tile_set(
         media_types: Default::default(),
     };
 
-    Ok((headers, Json(tile_set)))
+    negotiated.encode(headers, &tile_set)
 }
 
 /// Retrieve a vector tile from a collection.
@@ -513,7 +528,8 @@ async fn collection_tile(
     Path(params): Path<CollectionTileParams>,
     Qs(mut query): Qs<TileQuery>,
     State(state): State<AppState>,
-) -> Result<Vec<u8>> {
+    negotiated: Negotiated<VectorTile>,
+) -> Result<([(HeaderName, &'static str); 1], Vec<u8>)> {
     // tile matrix set
     let tms_id = &params.tile_params.tile_matrix_set_id;
     let Some(tms) = TMS.get().and_then(|tms| tms.get(tms_id)) else {
@@ -560,10 +576,11 @@ async fn collection_tile(
             &params.tile_params.tile_matrix,
             params.tile_params.tile_row,
             params.tile_params.tile_col,
+            query.geometry.as_deref(),
         )
         .await?;
 
-    Ok(tiles)
+    Ok(([(CONTENT_TYPE, negotiated.media_type())], tiles))
 }
 
 pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
@@ -590,4 +607,5 @@ pub(crate) fn router(state: &AppState) -> OpenApiRouter<AppState> {
         .routes(routes!(collection_tiles))
         .routes(routes!(collection_tile_set))
         .routes(routes!(collection_tile))
+        .layer(vary())
 }
@@ -49,7 +49,8 @@ impl Service {
         let config = Config::parse();
 
         // drivers
-        let drivers = Drivers::try_new_from_env().await?;
+        let url = std::env::var("DATABASE_URL")?;
+        let drivers = Drivers::try_new_db_with(&url, &config.database).await?;
 
         // state
         let state = AppState::new(drivers).await;
@@ -4,8 +4,6 @@ use std::sync::{Arc, RwLock};
 
 #[cfg(feature = "edr")]
 use ogcapi_drivers::EdrQuerier;
-#[cfg(feature = "features")]
-use ogcapi_drivers::FeatureTransactions;
 #[cfg(feature = "processes")]
 use ogcapi_drivers::JobHandler;
 #[cfg(feature = "stac")]
@@ -14,13 +12,23 @@ use ogcapi_drivers::StacSearch;
 use ogcapi_drivers::StyleTransactions;
 #[cfg(feature = "tiles")]
 use ogcapi_drivers::TileTransactions;
+#[cfg(feature = "features")]
+use ogcapi_drivers::{FeatureChanges, FeatureTransactions};
 
-use ogcapi_drivers::{CollectionTransactions, postgres::Db};
+#[cfg(feature = "memory")]
+use ogcapi_drivers::memory::Memory;
+use ogcapi_drivers::{
+    CollectionTransactions,
+    postgres::Db,
+    routing::{Route, Routing},
+};
 #[cfg(feature = "processes")]
 use ogcapi_processes::Processor;
 use ogcapi_types::common::{Conformance, LandingPage};
 use url::Url;
 
+use crate::config::DatabaseConfig;
+
 /// Application state
 #[derive(Clone)]
 pub struct AppState {
@@ -38,6 +46,8 @@ pub struct Drivers {
     pub collections: Box<dyn CollectionTransactions>,
     #[cfg(feature = "features")]
     pub features: Box<dyn FeatureTransactions>,
+    #[cfg(feature = "features")]
+    pub changes: Box<dyn FeatureChanges>,
     #[cfg(feature = "edr")]
     pub edr: Box<dyn EdrQuerier>,
     #[cfg(feature = "processes")]
@@ -57,15 +67,31 @@ impl Drivers {
         Self::try_new_db(&var).await
     }
 
-    /// Try to setup db driver from database url.
+    /// Try to setup db driver from database url, with the pool configured by
+    /// the environment.
     pub async fn try_new_db(url: &str) -> Result<Self, anyhow::Error> {
+        Self::try_new_db_with(url, &DatabaseConfig::from_env()?).await
+    }
+
+    /// Try to setup db driver from database url and configuration.
+    pub async fn try_new_db_with(
+        url: &str,
+        config: &DatabaseConfig,
+    ) -> Result<Self, anyhow::Error> {
         let database_url = Url::parse(url)?;
-        let db = Db::setup(&database_url).await?;
+        let pool = config.pool();
+        let mut db = Db::setup_with(&database_url, &pool).await?;
+        if let Some(replica_url) = config.database_replica_url.as_ref() {
+            db = db.replica(replica_url, &pool).await?;
+        }
+        let db = db.cache_ttl(config.cache_ttl());
 
         let drivers = Drivers {
             collections: Box::new(db.clone()),
             #[cfg(feature = "features")]
             features: Box::new(db.clone()),
+            #[cfg(feature = "features")]
+            changes: Box::new(db.clone()),
             #[cfg(feature = "edr")]
             edr: Box::new(db.clone()),
             #[cfg(feature = "processes")]
@@ -80,6 +106,133 @@ impl Drivers {
 
         Ok(drivers)
     }
+
+    /// Serve the collections of a route from a backend, taking precedence
+    /// over earlier routes.
+    pub fn route(mut self, route: Route, backend: Backend) -> Self {
+        if let Some(collections) = backend.collections {
+            self.collections = Box::new(
+                Routing::new(Arc::from(self.collections))
+                    .route(route.clone(), Arc::from(collections)),
+            );
+        }
+        #[cfg(feature = "features")]
+        if let Some(features) = backend.features {
+            self.features = Box::new(
+                Routing::new(Arc::from(self.features)).route(route.clone(), Arc::from(features)),
+            );
+        }
+        #[cfg(feature = "features")]
+        if let Some(changes) = backend.changes {
+            self.changes = Box::new(
+                Routing::new(Arc::from(self.changes)).route(route.clone(), Arc::from(changes)),
+            );
+        }
+        #[cfg(feature = "edr")]
+        if let Some(edr) = backend.edr {
+            self.edr =
+                Box::new(Routing::new(Arc::from(self.edr)).route(route.clone(), Arc::from(edr)));
+        }
+        #[cfg(feature = "tiles")]
+        if let Some(tiles) = backend.tiles {
+            self.tiles = Box::new(
+                Routing::new(Arc::from(self.tiles)).route(route.clone(), Arc::from(tiles)),
+            );
+        }
+        #[cfg(feature = "stac")]
+        if let Some(stac) = backend.stac {
+            self.stac =
+                Box::new(Routing::new(Arc::from(self.stac)).route(route.clone(), Arc::from(stac)));
+        }
+        self
+    }
+
+    /// Serve a collection from a backend.
+    pub fn route_collection(self, id: impl ToString, backend: Backend) -> Self {
+        self.route(Route::Collection(id.to_string()), backend)
+    }
+
+    /// Serve the collections with an id prefix from a backend.
+    pub fn route_prefix(self, prefix: impl ToString, backend: Backend) -> Self {
+        self.route(Route::Prefix(prefix.to_string()), backend)
+    }
+
+    /// Setup drivers backed by a shared in-memory store.
+    #[cfg(feature = "memory")]
+    pub fn in_memory() -> Self {
+        let memory = Memory::new();
+
+        Drivers {
+            collections: Box::new(memory.clone()),
+            #[cfg(feature = "features")]
+            features: Box::new(memory.clone()),
+            #[cfg(feature = "features")]
+            changes: Box::new(memory.clone()),
+            #[cfg(feature = "edr")]
+            edr: Box::new(memory.clone()),
+            #[cfg(feature = "processes")]
+            jobs: Box::new(memory.clone()),
+            #[cfg(feature = "styles")]
+            styles: Box::new(memory.clone()),
+            #[cfg(feature = "tiles")]
+            tiles: Box::new(memory.clone()),
+            #[cfg(feature = "stac")]
+            stac: Box::new(memory.clone()),
+        }
+    }
+}
+
+/// Drivers of routed collections
+///
+/// Traits without a driver are served by the drivers the collections are
+/// routed from.
+#[derive(Default)]
+pub struct Backend {
+    pub collections: Option<Box<dyn CollectionTransactions>>,
+    #[cfg(feature = "features")]
+    pub features: Option<Box<dyn FeatureTransactions>>,
+    #[cfg(feature = "features")]
+    pub changes: Option<Box<dyn FeatureChanges>>,
+    #[cfg(feature = "edr")]
+    pub edr: Option<Box<dyn EdrQuerier>>,
+    #[cfg(feature = "tiles")]
+    pub tiles: Option<Box<dyn TileTransactions>>,
+    #[cfg(feature = "stac")]
+    pub stac: Option<Box<dyn StacSearch>>,
+}
+
+impl Backend {
+    /// Backend of a driver for collections and features, such as the S3 or
+    /// file drivers
+    #[cfg(feature = "features")]
+    pub fn new<D>(driver: D) -> Self
+    where
+        D: CollectionTransactions + FeatureTransactions + Clone + 'static,
+    {
+        Backend {
+            collections: Some(Box::new(driver.clone())),
+            features: Some(Box::new(driver)),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Drivers> for Backend {
+    fn from(drivers: Drivers) -> Self {
+        Backend {
+            collections: Some(drivers.collections),
+            #[cfg(feature = "features")]
+            features: Some(drivers.features),
+            #[cfg(feature = "features")]
+            changes: Some(drivers.changes),
+            #[cfg(feature = "edr")]
+            edr: Some(drivers.edr),
+            #[cfg(feature = "tiles")]
+            tiles: Some(drivers.tiles),
+            #[cfg(feature = "stac")]
+            stac: Some(drivers.stac),
+        }
+    }
 }
 
 impl AppState {
@@ -94,6 +247,7 @@ impl AppState {
             "https://api.stacspec.org/v1.0.0-rc.1/collections",
             "https://api.stacspec.org/v1.0.0-rc.1/ogcapi-features",
             "https://api.stacspec.org/v1.0.0-rc.1/browseable",
+            "https://api.stacspec.org/v1.0.0-rc.1/item-search#sort",
         ]);
 
         AppState {
@@ -112,6 +266,34 @@ impl AppState {
         self
     }
 
+    /// Serve the collections of a route from a backend, see [`Drivers::route`].
+    ///
+    /// Fails if the state has been cloned, routes must be registered before.
+    pub fn route(mut self, route: Route, backend: Backend) -> Result<Self, anyhow::Error> {
+        let drivers = Arc::try_unwrap(self.drivers)
+            .map_err(|_| anyhow::anyhow!("Routes must be registered before the state is cloned"))?;
+        self.drivers = Arc::new(drivers.route(route, backend));
+        Ok(self)
+    }
+
+    /// Serve a collection from a backend.
+    pub fn route_collection(
+        self,
+        id: impl ToString,
+        backend: Backend,
+    ) -> Result<Self, anyhow::Error> {
+        self.route(Route::Collection(id.to_string()), backend)
+    }
+
+    /// Serve the collections with an id prefix from a backend.
+    pub fn route_prefix(
+        self,
+        prefix: impl ToString,
+        backend: Backend,
+    ) -> Result<Self, anyhow::Error> {
+        self.route(Route::Prefix(prefix.to_string()), backend)
+    }
+
     #[cfg(feature = "processes")]
     pub fn processors(self, processors: Vec<Box<dyn Processor>>) -> Self {
         for p in processors {
@@ -132,3 +314,25 @@ impl AppState {
         self
     }
 }
+
+#[cfg(all(test, feature = "memory"))]
+mod tests {
+    use ogcapi_drivers::memory::Memory;
+
+    use super::{AppState, Backend, Drivers};
+
+    #[tokio::test]
+    async fn route() {
+        let state = AppState::new(Drivers::in_memory()).await;
+        let state = state
+            .route_prefix("ch-", Backend::new(Memory::new()))
+            .unwrap();
+
+        let _clone = state.clone();
+        assert!(
+            state
+                .route_collection("lakes", Backend::new(Memory::new()))
+                .is_err()
+        );
+    }
+}
@@ -58,6 +58,76 @@ impl fmt::Display for Bbox {
     }
 }
 
+impl Bbox {
+    /// Lower and upper values of the horizontal axes, without the vertical axis
+    pub fn horizontal(&self) -> [f64; 4] {
+        match self {
+            Bbox::Bbox2D(bbox) => *bbox,
+            Bbox::Bbox3D([x1, y1, _, x2, y2, _]) => [*x1, *y1, *x2, *y2],
+        }
+    }
+
+    /// Whether a box in longitude/latitude spans the antimeridian, its west
+    /// edge being larger than its east edge
+    pub fn crosses_antimeridian(&self) -> bool {
+        let [west, _, east, _] = self.horizontal();
+        west > east
+    }
+
+    /// Box in longitude/latitude with the longitudes wrapped to [-180, 180]
+    ///
+    /// Boxes spanning 360 degrees or more cover all longitudes, boxes whose
+    /// east edge is wrapped past the antimeridian cross it.
+    pub fn normalize(&self) -> Bbox {
+        let [west, _, east, _] = self.horizontal();
+        let (west, east) = if west <= east && east - west >= 360.0 {
+            (-180.0, 180.0)
+        } else {
+            (wrap_longitude(west), wrap_longitude(east))
+        };
+        self.with_longitudes(west, east)
+    }
+
+    /// Box in longitude/latitude split at the antimeridian into boxes whose
+    /// west edge is not larger than their east edge
+    pub fn split_antimeridian(&self) -> Vec<Bbox> {
+        if self.crosses_antimeridian() {
+            let [west, _, east, _] = self.horizontal();
+            vec![
+                self.with_longitudes(west, 180.0),
+                self.with_longitudes(-180.0, east),
+            ]
+        } else {
+            vec![self.to_owned()]
+        }
+    }
+
+    fn with_longitudes(&self, west: f64, east: f64) -> Bbox {
+        match self {
+            Bbox::Bbox2D([_, y1, _, y2]) => Bbox::Bbox2D([west, *y1, east, *y2]),
+            Bbox::Bbox3D([_, y1, z1, _, y2, z2]) => Bbox::Bbox3D([west, *y1, *z1, east, *y2, *z2]),
+        }
+    }
+
+    /// Bounding box with the first two axes swapped, like latitude/longitude
+    /// to longitude/latitude
+    pub fn swap_axes(&self) -> Bbox {
+        match self {
+            Bbox::Bbox2D([x1, y1, x2, y2]) => Bbox::Bbox2D([*y1, *x1, *y2, *x2]),
+            Bbox::Bbox3D([x1, y1, z1, x2, y2, z2]) => Bbox::Bbox3D([*y1, *x1, *z1, *y2, *x2, *z2]),
+        }
+    }
+}
+
+/// Longitude wrapped to [-180, 180]
+fn wrap_longitude(longitude: f64) -> f64 {
+    if (-180.0..=180.0).contains(&longitude) {
+        longitude
+    } else {
+        (longitude + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
 impl From<[f64; 4]> for Bbox {
     fn from(slice: [f64; 4]) -> Self {
         Bbox::Bbox2D(slice)
@@ -142,6 +212,43 @@ mod tests {
         let _bbox: Bbox = numbers.as_slice().try_into().unwrap();
     }
 
+    #[test]
+    fn swap_axes() {
+        let bbox = Bbox::Bbox2D([46.8, 7.4, 47.0, 7.5]);
+        assert_eq!(bbox.swap_axes(), Bbox::Bbox2D([7.4, 46.8, 7.5, 47.0]));
+
+        let bbox = Bbox::Bbox3D([46.8, 7.4, 500.0, 47.0, 7.5, 600.0]);
+        assert_eq!(bbox.swap_axes().swap_axes(), bbox);
+    }
+
+    #[test]
+    fn antimeridian() {
+        let bbox = Bbox::Bbox2D([160.6, -55.95, -170.0, -25.89]);
+        assert!(bbox.crosses_antimeridian());
+        assert_eq!(
+            bbox.split_antimeridian(),
+            [
+                Bbox::Bbox2D([160.6, -55.95, 180.0, -25.89]),
+                Bbox::Bbox2D([-180.0, -55.95, -170.0, -25.89])
+            ]
+        );
+
+        // wrapped longitudes
+        let bbox = Bbox::Bbox3D([170.0, -10.0, 0.0, 190.0, 10.0, 100.0]);
+        assert!(!bbox.crosses_antimeridian());
+        assert_eq!(
+            bbox.normalize(),
+            Bbox::Bbox3D([170.0, -10.0, 0.0, -170.0, 10.0, 100.0])
+        );
+        assert_eq!(bbox.normalize().split_antimeridian().len(), 2);
+
+        let bbox = Bbox::Bbox2D([-200.0, -90.0, 200.0, 90.0]);
+        assert_eq!(bbox.normalize(), Bbox::Bbox2D([-180.0, -90.0, 180.0, 90.0]));
+
+        let bbox = Bbox::Bbox2D([7.0, 46.0, 8.0, 47.0]);
+        assert_eq!(bbox.split_antimeridian(), [bbox]);
+    }
+
     #[test]
     fn from_str() {
         let s = "160.6,-55.95, -170, -25.89";
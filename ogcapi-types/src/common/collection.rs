@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use utoipa::ToSchema;
 
-use super::{Crs, Extent, Link};
+use super::{Crs, Exception, Extent, Link};
 
 // const CRS_REF: &str = "#/crs";
 
@@ -38,6 +38,10 @@ pub struct Collection {
     pub storage_crs: Option<Crs>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub storage_crs_coordinate_epoch: Option<f32>,
+    /// Named geometries of the features in addition to their primary
+    /// geometry, held as GeoJSON geometries in the feature properties.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub geometry_columns: Vec<GeometryColumn>,
     #[serde(default)]
     pub links: Vec<Link>,
     /// Detailed information relevant to individual query types
@@ -100,6 +104,42 @@ pub struct Collection {
     pub additional_properties: Map<String, Value>,
 }
 
+/// Named geometry of the features of a collection
+#[derive(Serialize, Deserialize, ToSchema, Debug, PartialEq, Eq, Clone)]
+pub struct GeometryColumn {
+    /// Name of the feature property holding the geometry
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Whether the geometry is the JSON-FG `place` of the features, which
+    /// is encoded as `place` member instead of a property.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub place: bool,
+}
+
+impl Collection {
+    /// Named geometry column of the features
+    #[allow(clippy::result_large_err)]
+    pub fn geometry_column(&self, name: &str) -> Result<&GeometryColumn, Exception> {
+        self.geometry_columns
+            .iter()
+            .find(|column| column.name == name)
+            .ok_or_else(|| {
+                Exception::new_from_status(400)
+                    .detail(format!("Collection `{}` has no geometry `{name}`", self.id))
+            })
+    }
+
+    /// Geometry column of the JSON-FG `place` of the features
+    pub fn place(&self) -> Option<&GeometryColumn> {
+        self.geometry_columns.iter().find(|column| column.place)
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
 fn feature() -> String {
     "feature".to_string()
 }
@@ -125,6 +165,7 @@ impl Default for Collection {
             crs: vec![Crs::default2d()],
             storage_crs: Default::default(),
             storage_crs_coordinate_epoch: Default::default(),
+            geometry_columns: Default::default(),
             links: Default::default(),
             #[cfg(feature = "edr")]
             data_queries: Default::default(),
@@ -11,6 +11,44 @@ pub const OGC_CRS84: &str = "http://www.opengis.net/def/crs/OGC/1.3/CRS84";
 /// Default CRS for coordinates with height
 pub const OGC_CRS84H: &str = "http://www.opengis.net/def/crs/OGC/0/CRS84h";
 
+/// EPSG codes of coordinate reference systems whose first horizontal axis is
+/// the latitude or northing
+///
+/// Covers the geographic CRSs of the EPSG dataset and common projected CRSs
+/// with northing first. Other EPSG codes are assumed to be easting first.
+const NORTH_EAST: &[(i32, i32)] = &[
+    (2180, 2180),   // ETRS89 / Poland CS92
+    (3006, 3006),   // SWEREF99 TM
+    (3035, 3035),   // ETRS89-extended / LAEA Europe
+    (3059, 3059),   // LKS92 / Latvia TM
+    (3067, 3067),   // ETRS89 / TM35FIN(E,N)
+    (3301, 3301),   // Estonian Coordinate System of 1997
+    (3346, 3346),   // LKS94 / Lithuania TM
+    (4001, 4047),   // geographic CRSs of ellipsoids
+    (4120, 4327),   // geographic 2D CRSs, including WGS 84 and ETRS89
+    (4329, 4329),   // WGS 84 (3D), deprecated
+    (4600, 4765),   // geographic 2D CRSs
+    (4801, 4811),   // geographic 2D CRSs with Paris meridian
+    (4813, 4821),   // geographic 2D CRSs
+    (4823, 4824),   // geographic 2D CRSs
+    (4839, 4839),   // ETRS89 / LCC Germany (N-E)
+    (4901, 4904),   // geographic 2D CRSs with Paris meridian
+    (4937, 4937),   // ETRS89 (3D)
+    (4979, 4979),   // WGS 84 (3D)
+    (6318, 6319),   // NAD83(2011)
+    (7843, 7844),   // GDA2020
+    (31466, 31469), // DHDN / 3-degree Gauss-Kruger
+];
+
+/// Order of the horizontal axes of a coordinate reference system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisOrder {
+    /// Longitude or easting first, like `OGC:CRS84` and GeoJSON
+    EastNorth,
+    /// Latitude or northing first, like `EPSG:4326`
+    NorthEast,
+}
+
 /// Coordinate Reference System (CRS)
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Crs {
@@ -92,6 +130,26 @@ impl Crs {
     pub fn as_known_crs(&self) -> String {
         format!("{}:{}", self.authority, self.code)
     }
+
+    /// Order of the horizontal axes as defined by the authority
+    ///
+    /// OGC CRSs are longitude first, EPSG CRSs are looked up in a built-in
+    /// table of northing first codes.
+    pub fn axis_order(&self) -> AxisOrder {
+        match self.authority {
+            Authority::OGC => AxisOrder::EastNorth,
+            Authority::EPSG => match self.code.parse::<i32>() {
+                Ok(code)
+                    if NORTH_EAST
+                        .iter()
+                        .any(|(first, last)| (*first..=*last).contains(&code)) =>
+                {
+                    AxisOrder::NorthEast
+                }
+                _ => AxisOrder::EastNorth,
+            },
+        }
+    }
 }
 
 impl fmt::Display for Crs {
@@ -203,7 +261,7 @@ impl FromStr for Authority {
 mod tests {
     use std::str::FromStr;
 
-    use crate::common::{Crs, OGC_CRS84};
+    use crate::common::{AxisOrder, Crs, OGC_CRS84};
 
     #[test]
     fn parse_crs() {
@@ -241,6 +299,19 @@ mod tests {
         )
     }
 
+    #[test]
+    fn axis_order() {
+        assert_eq!(Crs::default2d().axis_order(), AxisOrder::EastNorth);
+        assert_eq!(Crs::default3d().axis_order(), AxisOrder::EastNorth);
+        assert_eq!(Crs::from_epsg(4326).axis_order(), AxisOrder::NorthEast);
+        assert_eq!(Crs::from_epsg(4258).axis_order(), AxisOrder::NorthEast);
+        assert_eq!(Crs::from_epsg(4979).axis_order(), AxisOrder::NorthEast);
+        assert_eq!(Crs::from_epsg(3035).axis_order(), AxisOrder::NorthEast);
+        assert_eq!(Crs::from_epsg(3857).axis_order(), AxisOrder::EastNorth);
+        assert_eq!(Crs::from_epsg(2056).axis_order(), AxisOrder::EastNorth);
+        assert_eq!(Crs::from_epsg(4978).axis_order(), AxisOrder::EastNorth);
+    }
+
     #[test]
     fn to_epsg() {
         let crs = Crs::from_str("http://www.opengis.net/def/crs/EPSG/0/4979").unwrap();
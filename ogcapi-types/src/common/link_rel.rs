@@ -6,7 +6,11 @@
 pub const ABOUT: &str = "about";
 
 /// Refers to a substitute for the link’s context.
-pub const ATERNATE: &str = "alternate";
+pub const ALTERNATE: &str = "alternate";
+
+/// Refers to a substitute for the link’s context.
+#[deprecated(note = "misspelled, use `ALTERNATE`")]
+pub const ATERNATE: &str = ALTERNATE;
 
 pub const CHILD: &str = "child";
 
@@ -50,6 +54,9 @@ pub const JOB_LIST: &str = "http://www.opengis.net/def/rel/ogc/1.0/job-list";
 
 pub const LAST: &str = "last";
 
+/// Points to a resource containing the latest version (RFC 5829).
+pub const LATEST_VERSION: &str = "latest-version";
+
 /// Refers to a license associated with the link’s context.
 pub const LICENSE: &str = "license";
 
@@ -69,6 +76,11 @@ pub const PREV: &str = "prev";
 /// See: <http://www.opengis.net/def/rel/ogc/1.0/processes>
 pub const PROCESSES: &str = "http://www.opengis.net/def/rel/ogc/1.0/processes";
 
+/// The target URI points to the queryables of the link's context.
+///
+/// See: <http://www.opengis.net/def/rel/ogc/1.0/queryables>
+pub const QUERYABLES: &str = "http://www.opengis.net/def/rel/ogc/1.0/queryables";
+
 pub const RELATED: &str = "related";
 
 /// The target URI points to the results of a job.
@@ -121,3 +133,6 @@ pub const OVERVIEW: &str = "overview";
 
 /// Refers to a parent document in a hierarchy of documents.
 pub const UP: &str = "up";
+
+/// Points to a resource containing the version history for the context (RFC 5829).
+pub const VERSION_HISTORY: &str = "version-history";
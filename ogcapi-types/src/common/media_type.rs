@@ -3,15 +3,30 @@
 /// Media Type for `application/prs.coverage+json`
 pub const COVERAGE_JSON: &str = "application/prs.coverage+json";
 
+/// Media Type for `text/event-stream`
+pub const EVENT_STREAM: &str = "text/event-stream";
+
+/// Media Type for `application/flatgeobuf`
+pub const FLAT_GEOBUF: &str = "application/flatgeobuf";
+
 /// Media Type for `application/geo+json`
 pub const GEO_JSON: &str = "application/geo+json";
 
+/// Media Type for `application/geo+json-seq`
+pub const GEO_JSON_SEQ: &str = "application/geo+json-seq";
+
 /// Media Type for `text/html`
 pub const HTML: &str = "text/html";
 
 /// Media Type for `application/json`
 pub const JSON: &str = "application/json";
 
+/// Media Type for `application/merge-patch+json`
+pub const MERGE_PATCH_JSON: &str = "application/merge-patch+json";
+
+/// Media Type for `application/x-ndjson`
+pub const NDJSON: &str = "application/x-ndjson";
+
 /// Media Type for `application/vnd.oai.openapi;version=3.0`
 pub const OPEN_API: &str = "application/vnd.oai.openapi;version=3.0";
 
@@ -27,11 +42,17 @@ pub const MAPBOX_STYLE: &str = "application/vnd.mapbox.style+json";
 /// Media Type for `application/vnd.mapbox-vector-tile`
 pub const MVT: &str = "application/vnd.mapbox-vector-tile";
 
+/// Media Type for `application/vnd.apache.parquet`
+pub const PARQUET: &str = "application/vnd.apache.parquet";
+
 /// Media Type for `image/png`
 pub const PNG: &str = "image/png";
 
 /// Media Type for `application/problem+json`
 pub const PROBLEM_JSON: &str = "application/problem+json";
 
+/// Media Type for `application/schema+json`
+pub const SCHEMA_JSON: &str = "application/schema+json";
+
 /// Media Type for `application/vnd.ogc.sld+xml;version=1.0`
 pub const SLD: &str = "application/vnd.ogc.sld+xml;version=1.0";
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use serde_with::DisplayFromStr;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::common::Bbox;
+
+use super::Feature;
+
+/// Change of a feature, as notified to the subscribers of a collection
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub r#type: ChangeType,
+    pub collection: String,
+    pub id: String,
+    /// Bounds of the geometry in WGS 84 longitude/latitude, of the previous
+    /// geometry for deleted features
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+    /// The feature as created or updated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<Feature>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Query parameters of the changes of a collection
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, IntoParams, Debug, Default)]
+pub struct ChangesQuery {
+    /// Only changes of features whose geometry intersects the bounding box,
+    /// in WGS 84 longitude/latitude, are streamed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[param(value_type = Bbox, style = Form, explode = false, nullable = false)]
+    pub bbox: Option<Bbox>,
+}
+
+impl Change {
+    /// Whether the bounds of the geometry intersect a bounding box in WGS 84
+    /// longitude/latitude, changes without bounds never do
+    pub fn intersects(&self, bbox: &Bbox) -> bool {
+        self.bbox.is_some_and(|[minx, miny, maxx, maxy]| {
+            bbox.normalize().split_antimeridian().iter().any(|bbox| {
+                let [x1, y1, x2, y2] = bbox.horizontal();
+                minx <= x2 && maxx >= x1 && miny <= y2 && maxy >= y1
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersects() {
+        let change: Change = serde_json::from_str(
+            r#"{"type":"deleted","collection":"places","id":"1","bbox":[7.0,46.0,8.0,47.0]}"#,
+        )
+        .unwrap();
+        assert_eq!(change.r#type, ChangeType::Deleted);
+
+        assert!(change.intersects(&Bbox::Bbox2D([7.5, 46.5, 9.0, 48.0])));
+        assert!(change.intersects(&Bbox::Bbox3D([0.0, 0.0, -1.0, 7.0, 46.0, 1.0])));
+        assert!(!change.intersects(&Bbox::Bbox2D([8.5, 46.0, 9.0, 47.0])));
+
+        // spanning the antimeridian
+        assert!(change.intersects(&Bbox::Bbox2D([170.0, 46.0, 7.5, 47.0])));
+        assert!(!change.intersects(&Bbox::Bbox2D([170.0, 46.0, 6.5, 47.0])));
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat};
+use geojson::Geometry;
+use serde_json::{Value, json};
+
+use crate::common::Exception;
+
+use super::{Expr, Operator, invalid};
+
+/// Parse a CQL2 JSON expression
+pub(super) fn from_value(value: &Value) -> Result<Expr, Exception> {
+    match value {
+        Value::Null => Ok(Expr::Null),
+        Value::Bool(b) => Ok(Expr::Bool(*b)),
+        Value::Number(n) => n
+            .as_f64()
+            .map(Expr::Number)
+            .ok_or_else(|| invalid(format!("invalid number `{n}`"))),
+        Value::String(s) => Ok(Expr::String(s.to_owned())),
+        Value::Array(items) => items
+            .iter()
+            .map(from_value)
+            .collect::<Result<Vec<Expr>, Exception>>()
+            .map(Expr::Array),
+        Value::Object(object) => {
+            if let Some(op) = object.get("op") {
+                let name = op
+                    .as_str()
+                    .ok_or_else(|| invalid("`op` must be a string"))?;
+                let args = match object.get("args") {
+                    Some(Value::Array(args)) => {
+                        args.iter()
+                            .map(from_value)
+                            .collect::<Result<Vec<Expr>, Exception>>()?
+                    }
+                    Some(arg) => vec![from_value(arg)?],
+                    None => Vec::new(),
+                };
+                Ok(match name.parse::<Operator>() {
+                    Ok(op) => Expr::Op { op, args },
+                    Err(_) => Expr::Function {
+                        name: name.to_lowercase(),
+                        args,
+                    },
+                })
+            } else if let Some(property) = object.get("property") {
+                property
+                    .as_str()
+                    .map(|p| Expr::Property(p.to_owned()))
+                    .ok_or_else(|| invalid("`property` must be a string"))
+            } else if let Some(date) = object.get("date") {
+                let date = date.as_str().unwrap_or_default();
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .map(Expr::Date)
+                    .map_err(|e| invalid(format!("invalid date `{date}`: {e}")))
+            } else if let Some(timestamp) = object.get("timestamp") {
+                let timestamp = timestamp.as_str().unwrap_or_default();
+                DateTime::parse_from_rfc3339(timestamp)
+                    .map(|d| Expr::Timestamp(d.into()))
+                    .map_err(|e| invalid(format!("invalid timestamp `{timestamp}`: {e}")))
+            } else if let Some(interval) = object.get("interval") {
+                match interval.as_array().map(|i| i.as_slice()) {
+                    Some([start, end]) => Ok(Expr::Interval(
+                        Box::new(instant(start)?),
+                        Box::new(instant(end)?),
+                    )),
+                    _ => Err(invalid("interval requires exactly two bounds")),
+                }
+            } else if let Some(bbox) = object.get("bbox") {
+                let numbers = bbox
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|n| n.as_f64())
+                    .collect::<Option<Vec<f64>>>()
+                    .ok_or_else(|| invalid("bbox requires numeric coordinates"))?;
+                if numbers.len() == 4 || numbers.len() == 6 {
+                    Ok(Expr::Bbox(numbers))
+                } else {
+                    Err(invalid("bbox requires 4 or 6 numbers"))
+                }
+            } else if object.contains_key("type") {
+                serde_json::from_value::<Geometry>(value.to_owned())
+                    .map(Expr::Geometry)
+                    .map_err(|e| invalid(format!("invalid geometry: {e}")))
+            } else {
+                Err(invalid(format!("unknown expression `{value}`")))
+            }
+        }
+    }
+}
+
+/// Interval bounds are either strings (dates, timestamps or `..`) or properties
+fn instant(value: &Value) -> Result<Expr, Exception> {
+    match value {
+        Value::String(s) if s == ".." => Ok(Expr::String(s.to_owned())),
+        Value::String(s) if s.contains('T') => DateTime::parse_from_rfc3339(s)
+            .map(|d| Expr::Timestamp(d.into()))
+            .map_err(|e| invalid(format!("invalid timestamp `{s}`: {e}"))),
+        Value::String(s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map(Expr::Date)
+            .map_err(|e| invalid(format!("invalid date `{s}`: {e}"))),
+        value => from_value(value),
+    }
+}
+
+/// Encode an expression as CQL2 JSON
+pub(super) fn to_value(expr: &Expr) -> Value {
+    match expr {
+        Expr::Null => Value::Null,
+        Expr::Bool(b) => json!(b),
+        Expr::Number(n) => json!(n),
+        Expr::String(s) => json!(s),
+        Expr::Property(p) => json!({ "property": p }),
+        Expr::Date(d) => json!({ "date": d.format("%Y-%m-%d").to_string() }),
+        Expr::Timestamp(t) => {
+            json!({ "timestamp": t.to_rfc3339_opts(SecondsFormat::AutoSi, true) })
+        }
+        Expr::Interval(start, end) => {
+            let bound = |e: &Expr| match e {
+                Expr::Date(d) => json!(d.format("%Y-%m-%d").to_string()),
+                Expr::Timestamp(t) => json!(t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                e => to_value(e),
+            };
+            json!({ "interval": [bound(start), bound(end)] })
+        }
+        Expr::Geometry(g) => serde_json::to_value(g).unwrap_or_default(),
+        Expr::Bbox(b) => json!({ "bbox": b }),
+        Expr::Array(items) => Value::Array(items.iter().map(to_value).collect()),
+        Expr::Function { name, args } => json!({
+            "op": name,
+            "args": args.iter().map(to_value).collect::<Vec<_>>()
+        }),
+        Expr::Op { op, args } => json!({
+            "op": op.as_str(),
+            "args": args.iter().map(to_value).collect::<Vec<_>>()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let value = json!({
+            "op": "and",
+            "args": [
+                {
+                    "op": "s_intersects",
+                    "args": [
+                        {"property": "geometry"},
+                        {"type": "Point", "coordinates": [7.0, 46.0]}
+                    ]
+                },
+                {
+                    "op": "t_during",
+                    "args": [
+                        {"property": "datetime"},
+                        {"interval": ["2020-01-01", ".."]}
+                    ]
+                },
+                {
+                    "op": "in",
+                    "args": [{"property": "name"}, ["Bern", "Basel"]]
+                },
+                {
+                    "op": "casei",
+                    "args": [{"property": "canton"}]
+                }
+            ]
+        });
+
+        let expr = from_value(&value).unwrap();
+        assert_eq!(to_value(&expr), value);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(from_value(&json!({"op": 1})).is_err());
+        assert!(from_value(&json!({"interval": ["2020-01-01"]})).is_err());
+        assert!(from_value(&json!({"bbox": [1, 2, 3]})).is_err());
+        assert!(from_value(&json!({"foo": "bar"})).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! Common Query Language (CQL2)
+//!
+//! Abstract syntax tree for the `filter` parameter as specified in
+//! [OGC API - Features - Part 3: Filtering](https://docs.ogc.org/is/19-079r2/19-079r2.html)
+//! and [CQL2](https://docs.ogc.org/is/21-065r2/21-065r2.html), with parsers
+//! for the text (`cql2-text`) and the JSON (`cql2-json`) encoding.
+
+// Errors are reported as `Exception`, as for the other query parameters
+#![allow(clippy::result_large_err)]
+
+mod json;
+mod text;
+
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use geojson::Geometry;
+
+use crate::common::Exception;
+
+use super::Queryables;
+
+/// CQL2 expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// Reference to a queryable property
+    Property(String),
+    Date(NaiveDate),
+    Timestamp(DateTime<Utc>),
+    /// Temporal interval, an open bound is represented by the string `..`
+    Interval(Box<Expr>, Box<Expr>),
+    Geometry(Geometry),
+    Bbox(Vec<f64>),
+    Array(Vec<Expr>),
+    /// Function call that is not a standardized operator (e.g. `casei`)
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    Op {
+        op: Operator,
+        args: Vec<Expr>,
+    },
+}
+
+/// CQL2 operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    // logical
+    And,
+    Or,
+    Not,
+    // comparison
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Like,
+    Between,
+    In,
+    IsNull,
+    // spatial
+    SContains,
+    SCrosses,
+    SDisjoint,
+    SEquals,
+    SIntersects,
+    SOverlaps,
+    STouches,
+    SWithin,
+    // temporal
+    TAfter,
+    TBefore,
+    TContains,
+    TDisjoint,
+    TDuring,
+    TEquals,
+    TFinishedBy,
+    TFinishes,
+    TIntersects,
+    TMeets,
+    TMetBy,
+    TOverlappedBy,
+    TOverlaps,
+    TStartedBy,
+    TStarts,
+    // array
+    AContainedBy,
+    AContains,
+    AEquals,
+    AOverlaps,
+}
+
+const OPERATORS: [(Operator, &str); 40] = [
+    (Operator::And, "and"),
+    (Operator::Or, "or"),
+    (Operator::Not, "not"),
+    (Operator::Eq, "="),
+    (Operator::Neq, "<>"),
+    (Operator::Lt, "<"),
+    (Operator::Lte, "<="),
+    (Operator::Gt, ">"),
+    (Operator::Gte, ">="),
+    (Operator::Like, "like"),
+    (Operator::Between, "between"),
+    (Operator::In, "in"),
+    (Operator::IsNull, "isNull"),
+    (Operator::SContains, "s_contains"),
+    (Operator::SCrosses, "s_crosses"),
+    (Operator::SDisjoint, "s_disjoint"),
+    (Operator::SEquals, "s_equals"),
+    (Operator::SIntersects, "s_intersects"),
+    (Operator::SOverlaps, "s_overlaps"),
+    (Operator::STouches, "s_touches"),
+    (Operator::SWithin, "s_within"),
+    (Operator::TAfter, "t_after"),
+    (Operator::TBefore, "t_before"),
+    (Operator::TContains, "t_contains"),
+    (Operator::TDisjoint, "t_disjoint"),
+    (Operator::TDuring, "t_during"),
+    (Operator::TEquals, "t_equals"),
+    (Operator::TFinishedBy, "t_finishedBy"),
+    (Operator::TFinishes, "t_finishes"),
+    (Operator::TIntersects, "t_intersects"),
+    (Operator::TMeets, "t_meets"),
+    (Operator::TMetBy, "t_metBy"),
+    (Operator::TOverlappedBy, "t_overlappedBy"),
+    (Operator::TOverlaps, "t_overlaps"),
+    (Operator::TStartedBy, "t_startedBy"),
+    (Operator::TStarts, "t_starts"),
+    (Operator::AContainedBy, "a_containedBy"),
+    (Operator::AContains, "a_contains"),
+    (Operator::AEquals, "a_equals"),
+    (Operator::AOverlaps, "a_overlaps"),
+];
+
+impl Operator {
+    /// Operator name as used in the JSON encoding
+    pub fn as_str(&self) -> &'static str {
+        OPERATORS
+            .iter()
+            .find(|(op, _)| op == self)
+            .map(|(_, name)| *name)
+            .expect("all operators to be listed")
+    }
+
+    /// Whether the operator is a spatial comparison function (`S_*`)
+    pub fn is_spatial(&self) -> bool {
+        self.as_str().starts_with("s_")
+    }
+
+    /// Whether the operator is a temporal comparison function (`T_*`)
+    pub fn is_temporal(&self) -> bool {
+        self.as_str().starts_with("t_")
+    }
+
+    /// Whether the operator is an array comparison function (`A_*`)
+    pub fn is_array(&self) -> bool {
+        self.as_str().starts_with("a_")
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Operator {
+    type Err = Exception;
+
+    /// Operator names are matched case-insensitively
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OPERATORS
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(op, _)| *op)
+            .ok_or_else(|| invalid(format!("unknown operator `{s}`")))
+    }
+}
+
+impl Expr {
+    /// Parse an expression from the JSON encoding
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, Exception> {
+        json::from_value(value)
+    }
+
+    /// Encode the expression in the JSON encoding
+    pub fn to_json(&self) -> serde_json::Value {
+        json::to_value(self)
+    }
+
+    /// Names of all properties referenced in the expression
+    pub fn properties(&self) -> BTreeSet<&str> {
+        let mut properties = BTreeSet::new();
+        self.collect_properties(&mut properties);
+        properties
+    }
+
+    fn collect_properties<'a>(&'a self, properties: &mut BTreeSet<&'a str>) {
+        match self {
+            Expr::Property(name) => {
+                properties.insert(name);
+            }
+            Expr::Interval(start, end) => {
+                start.collect_properties(properties);
+                end.collect_properties(properties);
+            }
+            Expr::Array(items)
+            | Expr::Function { args: items, .. }
+            | Expr::Op { args: items, .. } => {
+                for item in items {
+                    item.collect_properties(properties);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check that all referenced properties are queryable
+    pub fn check_queryables(&self, queryables: &Queryables) -> Result<(), Exception> {
+        if queryables.additional_properties {
+            return Ok(());
+        }
+        match self
+            .properties()
+            .into_iter()
+            .find(|p| !queryables.queryables.contains_key(*p))
+        {
+            Some(property) => Err(invalid(format!("Property {property} is not queryable!"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Exception;
+
+    /// Parse an expression from the text encoding
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        text::parse(s)
+    }
+}
+
+impl serde::Serialize for Expr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.to_json().serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Expr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Expr::from_json(&value).map_err(|e| serde::de::Error::custom(e.detail.unwrap_or_default()))
+    }
+}
+
+/// Exception for invalid filter expressions
+pub fn invalid(detail: impl ToString) -> Exception {
+    Exception::new("http://www.opengis.net/def/exceptions/ogcapi-features-3/1.0/invalid-filter")
+        .status(400)
+        .title("Invalid filter")
+        .detail(detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::{Queryable, Queryables};
+
+    use super::*;
+
+    #[test]
+    fn text_and_json_are_equivalent() {
+        let text: Expr = "landsat:scene_id = 'LC82030282019133LGN00' AND eo:cloud_cover < 10"
+            .parse()
+            .unwrap();
+        let json = Expr::from_json(&serde_json::json!({
+            "op": "and",
+            "args": [
+                {"op": "=", "args": [{"property": "landsat:scene_id"}, "LC82030282019133LGN00"]},
+                {"op": "<", "args": [{"property": "eo:cloud_cover"}, 10]}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(text, json);
+    }
+
+    #[test]
+    fn queryables() {
+        let expr: Expr = "name = 'Bern' AND pop > 1000".parse().unwrap();
+        assert_eq!(expr.properties(), BTreeSet::from(["name", "pop"]));
+
+        let mut queryables = Queryables {
+            additional_properties: false,
+            ..Default::default()
+        };
+        queryables
+            .queryables
+            .insert("name".to_string(), Queryable::default());
+        assert!(expr.check_queryables(&queryables).is_err());
+
+        queryables.additional_properties = true;
+        assert!(expr.check_queryables(&queryables).is_ok());
+    }
+}
@@ -0,0 +1,651 @@
+use chrono::{DateTime, NaiveDate};
+use geojson::{Geometry, GeometryValue, Position};
+
+use crate::common::Exception;
+
+use super::{Expr, Operator, invalid};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    QuotedIdent(String),
+    String(String),
+    Number(f64),
+    LParen,
+    RParen,
+    Comma,
+    Comparison(Operator),
+}
+
+/// Parse a CQL2 text expression
+pub(super) fn parse(s: &str) -> Result<Expr, Exception> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?,
+        pos: 0,
+    };
+    let expr = parser.boolean_expression()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(invalid(format!("unexpected token `{token:?}`"))),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, Exception> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            '=' => {
+                chars.next();
+                tokens.push(Token::Comparison(Operator::Eq));
+            }
+            '<' => {
+                chars.next();
+                let op = match chars.peek() {
+                    Some('=') => Operator::Lte,
+                    Some('>') => Operator::Neq,
+                    _ => Operator::Lt,
+                };
+                if op != Operator::Lt {
+                    chars.next();
+                }
+                tokens.push(Token::Comparison(op));
+            }
+            '>' => {
+                chars.next();
+                let op = if chars.peek() == Some(&'=') {
+                    chars.next();
+                    Operator::Gte
+                } else {
+                    Operator::Gt
+                };
+                tokens.push(Token::Comparison(op));
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        // quotes are escaped by doubling them
+                        Some(q) if q == c && chars.peek() == Some(&c) => {
+                            chars.next();
+                            value.push(c);
+                        }
+                        Some(q) if q == c => break,
+                        Some(other) => value.push(other),
+                        None => return Err(invalid("unterminated string literal")),
+                    }
+                }
+                tokens.push(if c == '\'' {
+                    Token::String(value)
+                } else {
+                    Token::QuotedIdent(value)
+                });
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut number = String::new();
+                number.push(c);
+                chars.next();
+                while let Some(&c) = chars.peek() {
+                    let exponent_sign = (c == '-' || c == '+') && number.ends_with(['e', 'E']);
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign {
+                        number.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let number = number
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid number `{number}`")))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || matches!(c, '_' | '.' | ':') {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => return Err(invalid(format!("unexpected character `{c}`"))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if i.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next(&mut self) -> Result<Token, Exception> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| invalid("unexpected end of expression"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let matched = self.peek_keyword(keyword);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Exception> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(invalid(format!(
+                "expected `{expected:?}`, found `{token:?}`"
+            )))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Exception> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(invalid(format!("expected `{keyword}`")))
+        }
+    }
+
+    fn boolean_expression(&mut self) -> Result<Expr, Exception> {
+        let mut args = vec![self.boolean_term()?];
+        while self.eat_keyword("OR") {
+            args.push(self.boolean_term()?);
+        }
+        Ok(fold(Operator::Or, args))
+    }
+
+    fn boolean_term(&mut self) -> Result<Expr, Exception> {
+        let mut args = vec![self.boolean_factor()?];
+        while self.eat_keyword("AND") {
+            args.push(self.boolean_factor()?);
+        }
+        Ok(fold(Operator::And, args))
+    }
+
+    fn boolean_factor(&mut self) -> Result<Expr, Exception> {
+        if self.eat_keyword("NOT") {
+            Ok(not(self.boolean_factor()?))
+        } else if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.boolean_expression()?;
+            self.expect(Token::RParen)?;
+            Ok(expr)
+        } else {
+            self.predicate()
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Expr, Exception> {
+        let left = self.scalar()?;
+
+        // spatial, temporal and array functions as well as boolean literals
+        if matches!(left, Expr::Op { .. } | Expr::Bool(_)) {
+            return Ok(left);
+        }
+
+        let negated = self.eat_keyword("NOT");
+
+        let expr = match self.peek().cloned() {
+            Some(Token::Comparison(op)) if !negated => {
+                self.pos += 1;
+                Expr::Op {
+                    op,
+                    args: vec![left, self.scalar()?],
+                }
+            }
+            Some(Token::Ident(i)) if i.eq_ignore_ascii_case("LIKE") => {
+                self.pos += 1;
+                Expr::Op {
+                    op: Operator::Like,
+                    args: vec![left, self.scalar()?],
+                }
+            }
+            Some(Token::Ident(i)) if i.eq_ignore_ascii_case("BETWEEN") => {
+                self.pos += 1;
+                let low = self.scalar()?;
+                self.expect_keyword("AND")?;
+                let high = self.scalar()?;
+                Expr::Op {
+                    op: Operator::Between,
+                    args: vec![left, low, high],
+                }
+            }
+            Some(Token::Ident(i)) if i.eq_ignore_ascii_case("IN") => {
+                self.pos += 1;
+                self.expect(Token::LParen)?;
+                let list = self.list(Self::scalar)?;
+                Expr::Op {
+                    op: Operator::In,
+                    args: vec![left, Expr::Array(list)],
+                }
+            }
+            Some(Token::Ident(i)) if i.eq_ignore_ascii_case("IS") && !negated => {
+                self.pos += 1;
+                let negated = self.eat_keyword("NOT");
+                self.expect_keyword("NULL")?;
+                let expr = Expr::Op {
+                    op: Operator::IsNull,
+                    args: vec![left],
+                };
+                return Ok(if negated { not(expr) } else { expr });
+            }
+            token => return Err(invalid(format!("expected predicate, found `{token:?}`"))),
+        };
+
+        Ok(if negated { not(expr) } else { expr })
+    }
+
+    /// Parse comma separated items until the closing parenthesis
+    fn list(
+        &mut self,
+        item: fn(&mut Self) -> Result<Expr, Exception>,
+    ) -> Result<Vec<Expr>, Exception> {
+        let mut items = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            match self.next()? {
+                Token::Comma => continue,
+                Token::RParen => break,
+                token => return Err(invalid(format!("expected `,` or `)`, found `{token:?}`"))),
+            }
+        }
+        Ok(items)
+    }
+
+    /// Function arguments may also be arrays
+    fn argument(&mut self) -> Result<Expr, Exception> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            Ok(Expr::Array(self.list(Self::argument)?))
+        } else {
+            self.scalar()
+        }
+    }
+
+    fn scalar(&mut self) -> Result<Expr, Exception> {
+        match self.next()? {
+            Token::String(s) => Ok(Expr::String(s)),
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::QuotedIdent(name) => Ok(Expr::Property(name)),
+            Token::Ident(ident) => {
+                let upper = ident.to_uppercase();
+                match upper.as_str() {
+                    "TRUE" => Ok(Expr::Bool(true)),
+                    "FALSE" => Ok(Expr::Bool(false)),
+                    "NULL" => Ok(Expr::Null),
+                    "DATE" => {
+                        let s = self.string_argument()?;
+                        NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+                            .map(Expr::Date)
+                            .map_err(|e| invalid(format!("invalid date `{s}`: {e}")))
+                    }
+                    "TIMESTAMP" => {
+                        let s = self.string_argument()?;
+                        timestamp(&s)
+                    }
+                    "INTERVAL" => {
+                        self.expect(Token::LParen)?;
+                        let bounds = self.list(Self::instant)?;
+                        match <[Expr; 2]>::try_from(bounds) {
+                            Ok([start, end]) => Ok(Expr::Interval(Box::new(start), Box::new(end))),
+                            Err(_) => Err(invalid("interval requires exactly two bounds")),
+                        }
+                    }
+                    "BBOX" => {
+                        self.expect(Token::LParen)?;
+                        let numbers = self
+                            .list(Self::scalar)?
+                            .into_iter()
+                            .map(|n| match n {
+                                Expr::Number(n) => Ok(n),
+                                _ => Err(invalid("bbox requires numeric coordinates")),
+                            })
+                            .collect::<Result<Vec<f64>, Exception>>()?;
+                        if numbers.len() == 4 || numbers.len() == 6 {
+                            Ok(Expr::Bbox(numbers))
+                        } else {
+                            Err(invalid("bbox requires 4 or 6 numbers"))
+                        }
+                    }
+                    "POINT" | "LINESTRING" | "POLYGON" | "MULTIPOINT" | "MULTILINESTRING"
+                    | "MULTIPOLYGON" | "GEOMETRYCOLLECTION" => {
+                        self.pos -= 1;
+                        self.geometry().map(Expr::Geometry)
+                    }
+                    _ if self.peek() == Some(&Token::LParen) => {
+                        self.pos += 1;
+                        let args = self.list(Self::argument)?;
+                        match upper.parse::<Operator>() {
+                            Ok(op) if op.is_spatial() || op.is_temporal() || op.is_array() => {
+                                if args.len() != 2 {
+                                    return Err(invalid(format!("{ident} requires two arguments")));
+                                }
+                                Ok(Expr::Op { op, args })
+                            }
+                            _ => Ok(Expr::Function {
+                                name: ident.to_lowercase(),
+                                args,
+                            }),
+                        }
+                    }
+                    _ => Ok(Expr::Property(ident)),
+                }
+            }
+            token => Err(invalid(format!("unexpected token `{token:?}`"))),
+        }
+    }
+
+    fn string_argument(&mut self) -> Result<String, Exception> {
+        self.expect(Token::LParen)?;
+        let s = match self.next()? {
+            Token::String(s) => s,
+            token => return Err(invalid(format!("expected string, found `{token:?}`"))),
+        };
+        self.expect(Token::RParen)?;
+        Ok(s)
+    }
+
+    /// Interval bounds are instants, properties, or `..`
+    fn instant(&mut self) -> Result<Expr, Exception> {
+        match self.scalar()? {
+            Expr::String(s) if s == ".." => Ok(Expr::String(s)),
+            Expr::String(s) if s.contains('T') => timestamp(&s),
+            Expr::String(s) => NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+                .map(Expr::Date)
+                .map_err(|e| invalid(format!("invalid date `{s}`: {e}"))),
+            expr @ (Expr::Date(_) | Expr::Timestamp(_) | Expr::Property(_)) => Ok(expr),
+            expr => Err(invalid(format!("invalid interval bound `{expr:?}`"))),
+        }
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, Exception> {
+        let kind = match self.next()? {
+            Token::Ident(kind) => kind.to_uppercase(),
+            token => return Err(invalid(format!("expected geometry, found `{token:?}`"))),
+        };
+        // optional dimension marker
+        self.eat_keyword("Z");
+
+        self.expect(Token::LParen)?;
+        let value = match kind.as_str() {
+            "POINT" => {
+                let position = self.position()?;
+                self.expect(Token::RParen)?;
+                GeometryValue::Point {
+                    coordinates: position,
+                }
+            }
+            "LINESTRING" => GeometryValue::LineString {
+                coordinates: self.positions()?,
+            },
+            "POLYGON" => GeometryValue::Polygon {
+                coordinates: self.rings()?,
+            },
+            "MULTIPOINT" => {
+                // points may or may not be enclosed in parentheses
+                let mut coordinates = Vec::new();
+                loop {
+                    if self.peek() == Some(&Token::LParen) {
+                        self.pos += 1;
+                        coordinates.push(self.position()?);
+                        self.expect(Token::RParen)?;
+                    } else {
+                        coordinates.push(self.position()?);
+                    }
+                    match self.next()? {
+                        Token::Comma => continue,
+                        Token::RParen => break,
+                        token => return Err(invalid(format!("unexpected token `{token:?}`"))),
+                    }
+                }
+                GeometryValue::MultiPoint { coordinates }
+            }
+            "MULTILINESTRING" => GeometryValue::MultiLineString {
+                coordinates: self.rings()?,
+            },
+            "MULTIPOLYGON" => {
+                let mut coordinates = Vec::new();
+                loop {
+                    self.expect(Token::LParen)?;
+                    coordinates.push(self.rings()?);
+                    match self.next()? {
+                        Token::Comma => continue,
+                        Token::RParen => break,
+                        token => return Err(invalid(format!("unexpected token `{token:?}`"))),
+                    }
+                }
+                GeometryValue::MultiPolygon { coordinates }
+            }
+            "GEOMETRYCOLLECTION" => {
+                let mut geometries = Vec::new();
+                loop {
+                    geometries.push(self.geometry()?);
+                    match self.next()? {
+                        Token::Comma => continue,
+                        Token::RParen => break,
+                        token => return Err(invalid(format!("unexpected token `{token:?}`"))),
+                    }
+                }
+                GeometryValue::GeometryCollection { geometries }
+            }
+            kind => return Err(invalid(format!("unknown geometry type `{kind}`"))),
+        };
+
+        Ok(Geometry::new(value))
+    }
+
+    fn position(&mut self) -> Result<Position, Exception> {
+        let mut coordinates = Vec::new();
+        while let Some(Token::Number(n)) = self.peek() {
+            coordinates.push(*n);
+            self.pos += 1;
+        }
+        if coordinates.len() < 2 {
+            return Err(invalid("a position requires at least two coordinates"));
+        }
+        Ok(Position::from(coordinates))
+    }
+
+    /// Comma separated positions, consumes the closing parenthesis
+    fn positions(&mut self) -> Result<Vec<Position>, Exception> {
+        let mut positions = Vec::new();
+        loop {
+            positions.push(self.position()?);
+            match self.next()? {
+                Token::Comma => continue,
+                Token::RParen => break,
+                token => return Err(invalid(format!("unexpected token `{token:?}`"))),
+            }
+        }
+        Ok(positions)
+    }
+
+    /// Comma separated parenthesized positions, consumes the closing parenthesis
+    fn rings(&mut self) -> Result<Vec<Vec<Position>>, Exception> {
+        let mut rings = Vec::new();
+        loop {
+            self.expect(Token::LParen)?;
+            rings.push(self.positions()?);
+            match self.next()? {
+                Token::Comma => continue,
+                Token::RParen => break,
+                token => return Err(invalid(format!("unexpected token `{token:?}`"))),
+            }
+        }
+        Ok(rings)
+    }
+}
+
+fn timestamp(s: &str) -> Result<Expr, Exception> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|d| Expr::Timestamp(d.into()))
+        .map_err(|e| invalid(format!("invalid timestamp `{s}`: {e}")))
+}
+
+fn fold(op: Operator, mut args: Vec<Expr>) -> Expr {
+    if args.len() == 1 {
+        args.remove(0)
+    } else {
+        Expr::Op { op, args }
+    }
+}
+
+fn not(expr: Expr) -> Expr {
+    Expr::Op {
+        op: Operator::Not,
+        args: vec![expr],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(name: &str) -> Expr {
+        Expr::Property(name.to_string())
+    }
+
+    #[test]
+    fn comparison() {
+        let expr = parse("\"eo:cloud_cover\" <= -1.5e1").unwrap();
+        assert_eq!(
+            expr,
+            Expr::Op {
+                op: Operator::Lte,
+                args: vec![property("eo:cloud_cover"), Expr::Number(-15.0)]
+            }
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let expr = parse("a = 1 OR b = 'x' AND NOT (c IS NULL)").unwrap();
+        let Expr::Op { op, args } = expr else {
+            panic!("expected operation")
+        };
+        assert_eq!(op, Operator::Or);
+        assert!(matches!(
+            args[1],
+            Expr::Op {
+                op: Operator::And,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn advanced_comparison() {
+        let expr = parse("name NOT LIKE 'Ber%' AND pop BETWEEN 1 AND 10 AND id IN ('a', 'b')");
+        assert!(expr.is_ok());
+
+        let expr = parse("name IS NOT NULL").unwrap();
+        assert_eq!(
+            expr,
+            not(Expr::Op {
+                op: Operator::IsNull,
+                args: vec![property("name")]
+            })
+        );
+    }
+
+    #[test]
+    fn spatial() {
+        let expr =
+            parse("S_INTERSECTS(geometry, POLYGON((6 45, 6 49, 9 49, 9 45, 6 45)))").unwrap();
+        let Expr::Op { op, args } = expr else {
+            panic!("expected operation")
+        };
+        assert_eq!(op, Operator::SIntersects);
+        assert!(matches!(args[1], Expr::Geometry(_)));
+
+        assert!(parse("s_within(geometry, BBOX(6, 45, 9, 49))").is_ok());
+        assert!(parse("S_CROSSES(geometry, MULTIPOINT((1 2), (3 4)))").is_ok());
+    }
+
+    #[test]
+    fn temporal() {
+        let expr = parse(
+            "T_INTERSECTS(INTERVAL(start_datetime, end_datetime), INTERVAL('2020-01-01', '..'))",
+        )
+        .unwrap();
+        let Expr::Op { args, .. } = expr else {
+            panic!("expected operation")
+        };
+        assert_eq!(
+            args[1],
+            Expr::Interval(
+                Box::new(Expr::Date(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap())),
+                Box::new(Expr::String("..".to_string()))
+            )
+        );
+
+        assert!(parse("T_AFTER(datetime, TIMESTAMP('2020-01-01T00:00:00Z'))").is_ok());
+    }
+
+    #[test]
+    fn array() {
+        let expr = parse("A_CONTAINS(layer:ids, ('layers-ca', 'layers-us'))").unwrap();
+        let Expr::Op { args, .. } = expr else {
+            panic!("expected operation")
+        };
+        assert_eq!(
+            args[1],
+            Expr::Array(vec![
+                Expr::String("layers-ca".to_string()),
+                Expr::String("layers-us".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(parse("name = ").is_err());
+        assert!(parse("name = 'unterminated").is_err());
+        assert!(parse("name ! 3").is_err());
+        assert!(parse("S_INTERSECTS(geometry)").is_err());
+    }
+}
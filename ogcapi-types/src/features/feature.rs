@@ -60,8 +60,16 @@ pub struct Feature {
     pub r#type: Type,
     #[serde(default)]
     pub properties: Option<Map<String, Value>>,
+    /// Geometry of the feature, `null` for unlocated features or if the
+    /// geometry was skipped with `skipGeometry`
+    #[serde(default)]
+    #[schema(schema_with = geometry)]
+    pub geometry: Option<Geometry>,
+    /// Primary place of the feature (JSON-FG), for collections with a
+    /// geometry column flagged as place
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     #[schema(schema_with = geometry)]
-    pub geometry: Geometry,
+    pub place: Option<Geometry>,
     /// Bounding Box of the asset represented by this Item, formatted according to RFC 7946, section 5.
     #[cfg(feature = "stac")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -118,7 +126,8 @@ impl Feature {
             collection: Default::default(),
             r#type: Default::default(),
             properties: Default::default(),
-            geometry,
+            geometry: Some(geometry),
+            place: Default::default(),
             #[cfg(feature = "stac")]
             bbox: Default::default(),
             links: Default::default(),
@@ -148,4 +157,114 @@ impl Feature {
             self.properties = Some(other);
         }
     }
+
+    /// Move the geometry property `name` to the JSON-FG `place`
+    ///
+    /// Properties that are not a GeoJSON geometry are kept.
+    pub fn property_to_place(&mut self, name: &str) {
+        let Some(properties) = self.properties.as_mut() else {
+            return;
+        };
+        if let Some(Ok(place)) = properties.get(name).map(Geometry::deserialize) {
+            properties.remove(name);
+            self.place = Some(place);
+        }
+    }
+
+    /// Move the JSON-FG `place` to the geometry property `name`
+    pub fn place_to_property(&mut self, name: &str) -> Result<(), serde_json::Error> {
+        if let Some(place) = self.place.take() {
+            self.properties
+                .get_or_insert_default()
+                .insert(name.to_owned(), serde_json::to_value(place)?);
+        }
+        Ok(())
+    }
+
+    /// Apply a JSON Merge Patch ([RFC 7396](https://www.rfc-editor.org/rfc/rfc7396))
+    pub fn merge_patch(&mut self, patch: &Map<String, Value>) -> Result<(), serde_json::Error> {
+        let mut value = serde_json::to_value(&*self)?;
+        merge_patch(&mut value, &Value::Object(patch.to_owned()));
+        *self = serde_json::from_value(value)?;
+        Ok(())
+    }
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.to_owned();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merge_patch() {
+        let mut feature = Feature::new(Geometry::new_point([7.0, 46.0]));
+        feature.properties = Some(Map::from_iter([
+            ("name".to_string(), json!("Bern")),
+            ("population".to_string(), json!(134591)),
+            ("tags".to_string(), json!({"capital": true, "canton": "BE"})),
+        ]));
+
+        let patch = json!({
+            "properties": {"population": null, "tags": {"canton": null}},
+            "geometry": {"type": "Point", "coordinates": [7.4, 46.9]}
+        });
+        feature.merge_patch(patch.as_object().unwrap()).unwrap();
+
+        assert_eq!(
+            feature.properties,
+            Some(Map::from_iter([
+                ("name".to_string(), json!("Bern")),
+                ("tags".to_string(), json!({"capital": true})),
+            ]))
+        );
+        assert_eq!(feature.geometry, Some(Geometry::new_point([7.4, 46.9])));
+    }
+
+    #[test]
+    fn place() {
+        let mut feature = Feature::new(Geometry::new_point([7.4, 46.9]));
+        feature.properties = Some(Map::from_iter([
+            ("name".to_string(), json!("Bern")),
+            (
+                "area".to_string(),
+                json!({"type": "Polygon", "coordinates": [[[7.3, 46.9], [7.5, 46.9], [7.5, 47.0], [7.3, 46.9]]]}),
+            ),
+        ]));
+
+        feature.property_to_place("name");
+        assert!(feature.place.is_none());
+
+        feature.property_to_place("area");
+        assert!(matches!(
+            feature.place.as_ref().map(|place| &place.value),
+            Some(geojson::GeometryValue::Polygon { .. })
+        ));
+        assert!(!feature.properties.as_ref().unwrap().contains_key("area"));
+
+        feature.place_to_property("area").unwrap();
+        assert!(feature.place.is_none());
+        assert_eq!(
+            feature.properties.as_ref().unwrap()["area"]["type"],
+            "Polygon"
+        );
+    }
 }
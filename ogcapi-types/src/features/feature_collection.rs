@@ -41,6 +41,11 @@ pub struct FeatureCollection {
     /// of items in the "features" array.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub number_returned: Option<u64>,
+    /// Opaque token to fetch the next page, if there are more features.
+    ///
+    /// Not part of the encoding, it is used to construct the `next` link.
+    #[serde(skip)]
+    pub next: Option<String>,
     #[cfg(feature = "movingfeatures")]
     #[serde(default)]
     pub crs: crate::movingfeatures::crs::Crs,
@@ -1,11 +1,20 @@
+mod change;
+pub mod cql2;
 mod feature;
 mod feature_collection;
 mod query;
 mod queryables;
+mod schema;
+mod sortby;
+mod version;
 
+pub use change::{Change, ChangeType, ChangesQuery};
 pub use feature::{Feature, FeatureId, geometry};
 pub use feature_collection::FeatureCollection;
-pub use query::Query;
-pub use queryables::Queryables;
+pub use query::{NumberMatched, Query};
+pub use queryables::{Queryable, Queryables};
+pub use schema::Schema;
+pub use sortby::{Direction, SortBy};
+pub use version::{FeatureVersion, FeatureVersions};
 
 pub use geojson::Geometry;
@@ -1,13 +1,16 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_with::DisplayFromStr;
+use serde_with::{DisplayFromStr, StringWithSeparator, formats::CommaSeparator};
 use utoipa::{IntoParams, ToSchema};
 
-use crate::common::{Bbox, Crs, Datetime};
+use crate::common::{Bbox, Crs, Datetime, Exception};
+
+use super::{SortBy, cql2::Expr};
 
 #[serde_with::serde_as]
-#[derive(Serialize, Deserialize, IntoParams, Debug, Default)]
+#[derive(Serialize, Deserialize, IntoParams, Debug, Default, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct Query {
     /// The optional limit parameter limits the number of items that are
@@ -22,6 +25,16 @@ pub struct Query {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[param(nullable = false)]
     pub offset: Option<usize>,
+    /// Opaque token to continue with the next page, as provided by the
+    /// `next` link of a previous response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(nullable = false)]
+    pub next: Option<String>,
+    /// How the number of matched features is determined. Counting exactly
+    /// may be expensive on large collections.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(inline, nullable = false)]
+    pub number_matched: Option<NumberMatched>,
     /// Only features that have a geometry that intersects the bounding box
     /// are selected. The bounding box is provided as four or six numbers,
     /// depending on whether the coordinate reference system includes a
@@ -66,6 +79,12 @@ pub struct Query {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[param(value_type = String, nullable = false)]
     pub bbox_crs: Option<Crs>,
+    /// Name of the geometry of the features that the `bbox` is applied to,
+    /// one of the geometry columns of the collection. The primary geometry
+    /// is used if the parameter is omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(nullable = false)]
+    pub bbox_geometry: Option<String>,
     /// Either a date-time or an interval. Date and time expressions adhere to
     /// RFC 3339. Intervals may be bounded or half-bounded (double-dots at start or end).
     ///
@@ -97,15 +116,112 @@ pub struct Query {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[param(value_type = String)]
     pub filter_crs: Option<Crs>,
+    /// Comma separated list of the properties to include in the response.
+    /// All properties are included if the parameter is omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<StringWithSeparator::<CommaSeparator, String>>")]
+    #[param(value_type = String, nullable = false, required = false)]
+    pub properties: Option<Vec<String>>,
+    /// Omit the geometry of the features in the response
+    #[serde(
+        default,
+        rename = "skipGeometry",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[param(rename = "skipGeometry", value_type = bool, nullable = false, required = false)]
+    pub skip_geometry: Option<bool>,
+    /// Comma separated list of properties to sort by, each prefixed with
+    /// `+` (ascending, default) or `-` (descending), e.g. `-datetime,id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, SortBy>")]
+    #[param(value_type = String, nullable = false, required = false)]
+    pub sortby: Vec<SortBy>,
+    /// Only the versions of the features that were current at the date-time
+    /// are selected, for collections that record the history of their
+    /// features. Date and time expressions adhere to RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(value_type = String, nullable = false)]
+    pub version_at: Option<DateTime<Utc>>,
+    /// The encoding of the response, taking precedence over the `Accept`
+    /// header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(nullable = false)]
+    pub f: Option<String>,
     /// Parameters for filtering on feature properties
     #[serde(default, flatten)]
     pub additional_parameters: HashMap<String, String>,
 }
 
-#[derive(Serialize, Deserialize, ToSchema, Default, Debug)]
-#[serde(rename_all = "kebab-case")]
+impl Query {
+    /// Parse the `filter` parameter according to the `filter-lang` parameter
+    #[allow(clippy::result_large_err)]
+    pub fn parse_filter(&self) -> Result<Option<Expr>, Exception> {
+        let Some(filter) = self.filter.as_ref() else {
+            return Ok(None);
+        };
+
+        let expr = match self.filter_lang.unwrap_or_default() {
+            FilterLang::CqlText => filter.parse()?,
+            FilterLang::CqlJson => {
+                let value: serde_json::Value = serde_json::from_str(filter)
+                    .map_err(|e| Exception::new_from_status(400).detail(e))?;
+                Expr::from_json(&value)?
+            }
+        };
+
+        Ok(Some(expr))
+    }
+}
+
+/// Mode for determining `numberMatched`
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberMatched {
+    /// Count all matching features
+    #[default]
+    Exact,
+    /// Estimate the number of matching features from table statistics
+    Estimate,
+    /// Omit `numberMatched`
+    Skip,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FilterLang {
     #[default]
+    #[serde(rename = "cql2-text", alias = "cql-text")]
     CqlText,
+    #[serde(rename = "cql2-json", alias = "cql-json")]
     CqlJson,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn property_selection() {
+        let query: Query =
+            serde_qs::from_str("properties=name,population&skipGeometry=true").unwrap();
+        assert_eq!(
+            query.properties,
+            Some(vec!["name".to_string(), "population".to_string()])
+        );
+        assert_eq!(query.skip_geometry, Some(true));
+        assert!(query.additional_parameters.is_empty());
+
+        let query: Query = serde_qs::from_str("properties=").unwrap();
+        assert_eq!(query.properties, Some(vec![]));
+    }
+
+    #[test]
+    fn version_at() {
+        let query: Query = serde_qs::from_str("version-at=2024-03-01T12:00:00Z").unwrap();
+        assert_eq!(
+            query.version_at,
+            Some("2024-03-01T12:00:00Z".parse().unwrap())
+        );
+        assert!(query.additional_parameters.is_empty());
+    }
+}
@@ -1,23 +1,115 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, ToSchema, Debug, Default)]
+/// Queryable properties of a collection, encoded as JSON Schema
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
 pub struct Queryables {
-    #[serde(flatten)]
+    #[serde(rename = "$schema", default = "json_schema")]
+    pub schema: String,
+    #[serde(rename = "$id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default = "object")]
+    pub r#type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(rename = "properties", default)]
     pub queryables: HashMap<String, Queryable>,
     #[serde(rename = "additionalProperties", default = "default_true")]
     pub additional_properties: bool,
 }
 
-#[derive(Serialize, Deserialize, ToSchema, Debug)]
+impl Default for Queryables {
+    fn default() -> Self {
+        Self {
+            schema: json_schema(),
+            id: None,
+            r#type: object(),
+            title: None,
+            queryables: Default::default(),
+            additional_properties: true,
+        }
+    }
+}
+
+/// Schema of a single queryable property
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, Clone, PartialEq)]
 pub struct Queryable {
-    title: Option<String>,
-    description: Option<String>,
-    r#type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Further JSON Schema keywords, e.g. `enum`, `items` or `x-ogc-role`
+    #[serde(flatten, default, skip_serializing_if = "Map::is_empty")]
+    pub additional_properties: Map<String, Value>,
+}
+
+impl Queryable {
+    /// Create queryable of the given JSON Schema `type`
+    pub fn new(r#type: impl ToString) -> Self {
+        Queryable {
+            r#type: Some(r#type.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn title(mut self, title: impl ToString) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn format(mut self, format: impl ToString) -> Self {
+        self.format = Some(format.to_string());
+        self
+    }
+}
+
+fn json_schema() -> String {
+    "https://json-schema.org/draft/2020-12/schema".to_string()
+}
+
+fn object() -> String {
+    "object".to_string()
 }
 
 fn default_true() -> bool {
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_schema_encoding() {
+        let mut queryables = Queryables {
+            additional_properties: false,
+            ..Default::default()
+        };
+        queryables
+            .queryables
+            .insert("name".to_string(), Queryable::new("string").title("Name"));
+
+        let value = serde_json::to_value(&queryables).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "type": "object",
+                "properties": {
+                    "name": { "title": "Name", "type": "string" }
+                },
+                "additionalProperties": false
+            })
+        );
+
+        let parsed: Queryables = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, queryables);
+    }
+}
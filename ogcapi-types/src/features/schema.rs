@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{Queryable, Queryables};
+
+/// Schema of the features of a collection, encoded as JSON Schema
+///
+/// See [OGC API - Features - Part 5: Schemas](https://docs.ogc.org/DRAFTS/23-058r1.html)
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Schema {
+    #[serde(rename = "$schema", default = "json_schema")]
+    pub schema: String,
+    #[serde(rename = "$id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default = "object")]
+    pub r#type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub properties: HashMap<String, Queryable>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    #[serde(rename = "additionalProperties", default = "default_true")]
+    pub additional_properties: bool,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            schema: json_schema(),
+            id: None,
+            r#type: object(),
+            title: None,
+            description: None,
+            properties: Default::default(),
+            required: Default::default(),
+            additional_properties: true,
+        }
+    }
+}
+
+impl From<Queryables> for Schema {
+    fn from(queryables: Queryables) -> Self {
+        Self {
+            schema: queryables.schema,
+            id: queryables.id,
+            r#type: queryables.r#type,
+            title: queryables.title,
+            properties: queryables.queryables,
+            ..Default::default()
+        }
+    }
+}
+
+fn json_schema() -> String {
+    "https://json-schema.org/draft/2020-12/schema".to_string()
+}
+
+fn object() -> String {
+    "object".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
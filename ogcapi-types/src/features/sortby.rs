@@ -0,0 +1,155 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::Exception;
+
+use super::Queryables;
+
+/// Sort criterion of the `sortby` parameter
+///
+/// The text encoding is a property name with an optional `+` (ascending) or
+/// `-` (descending) prefix, e.g. `-properties.datetime`. The JSON encoding of
+/// the STAC sort extension is `{"field": "properties.datetime", "direction": "desc"}`.
+///
+/// See [OGC API - Features - Part 8: Sorting](https://docs.ogc.org/DRAFTS/24-030.html)
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct SortBy {
+    pub field: String,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortBy {
+    pub fn asc(field: impl ToString) -> Self {
+        SortBy {
+            field: field.to_string(),
+            direction: Direction::Asc,
+        }
+    }
+
+    pub fn desc(field: impl ToString) -> Self {
+        SortBy {
+            field: field.to_string(),
+            direction: Direction::Desc,
+        }
+    }
+
+    /// Name of the sorted property, without the `properties.` prefix of STAC
+    pub fn property(&self) -> &str {
+        self.field
+            .strip_prefix("properties.")
+            .unwrap_or(&self.field)
+    }
+
+    /// Check that the sorted property is a queryable
+    #[allow(clippy::result_large_err)]
+    pub fn check_queryables(&self, queryables: &Queryables) -> Result<(), Exception> {
+        let property = self.property();
+        if property == "id"
+            || queryables.additional_properties
+            || queryables.queryables.contains_key(property)
+        {
+            Ok(())
+        } else {
+            Err(Exception::new_from_status(400)
+                .detail(format!("Property {property} is not sortable!")))
+        }
+    }
+}
+
+impl FromStr for SortBy {
+    type Err = Exception;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // an unencoded `+` in a query string is decoded as space
+        let s = s.trim();
+        let (field, direction) = match s.strip_prefix('-') {
+            Some(field) => (field, Direction::Desc),
+            None => (s.strip_prefix('+').unwrap_or(s), Direction::Asc),
+        };
+        if field.is_empty() {
+            return Err(Exception::new_from_status(400).detail("Invalid `sortby` parameter"));
+        }
+        Ok(SortBy {
+            field: field.to_string(),
+            direction,
+        })
+    }
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            Direction::Asc => write!(f, "{}", self.field),
+            Direction::Desc => write!(f, "-{}", self.field),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_encoding() {
+        let sortby: Vec<SortBy> = "+properties.datetime,-id, name"
+            .split(',')
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(
+            sortby,
+            vec![
+                SortBy::asc("properties.datetime"),
+                SortBy::desc("id"),
+                SortBy::asc("name")
+            ]
+        );
+        assert_eq!(sortby[0].property(), "datetime");
+        assert_eq!(sortby[1].to_string(), "-id");
+        assert!("-".parse::<SortBy>().is_err());
+    }
+
+    #[test]
+    fn query_parameter() {
+        let query: crate::features::Query =
+            serde_qs::from_str("sortby=+name,-id&name=Bern").unwrap();
+        assert_eq!(query.sortby, vec![SortBy::asc("name"), SortBy::desc("id")]);
+        assert_eq!(query.additional_parameters.len(), 1);
+        assert_eq!(
+            serde_qs::to_string(&query).unwrap(),
+            "sortby=name,-id&name=Bern"
+        );
+    }
+
+    #[test]
+    fn json_encoding() {
+        let sortby: Vec<SortBy> = serde_json::from_str(
+            r#"[{"field": "properties.datetime", "direction": "desc"}, {"field": "id"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sortby,
+            vec![SortBy::desc("properties.datetime"), SortBy::asc("id")]
+        );
+    }
+
+    #[test]
+    fn queryables() {
+        let queryables = Queryables {
+            additional_properties: false,
+            ..Default::default()
+        };
+        assert!(SortBy::asc("id").check_queryables(&queryables).is_ok());
+        assert!(SortBy::asc("name").check_queryables(&queryables).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::Link;
+
+use super::Feature;
+
+/// Version of a feature, valid from its creation or update until it was
+/// replaced or deleted
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureVersion {
+    /// Version of the feature, as used in entity tags
+    pub version: u64,
+    pub valid_from: DateTime<Utc>,
+    /// End of the validity, unless it is the current version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<DateTime<Utc>>,
+    pub feature: Feature,
+}
+
+impl FeatureVersion {
+    /// Whether the version was current at a date-time
+    pub fn valid_at(&self, datetime: &DateTime<Utc>) -> bool {
+        &self.valid_from <= datetime && self.valid_to.is_none_or(|to| &to > datetime)
+    }
+}
+
+/// Versions of a feature, oldest first
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct FeatureVersions {
+    pub versions: Vec<FeatureVersion>,
+    #[serde(default)]
+    pub links: Vec<Link>,
+}
@@ -1,13 +1,18 @@
+use std::{fmt, str::FromStr};
+
 use geojson::Geometry;
 use serde::{Deserialize, Serialize};
-use serde_with::DisplayFromStr;
+use serde_with::{DisplayFromStr, StringWithSeparator, formats::CommaSeparator};
 use utoipa::{IntoParams, ToSchema};
 
-use crate::common::{Bbox, Datetime};
+use crate::{
+    common::{Bbox, Datetime, Exception},
+    features::SortBy,
+};
 
 /// Search parameters for searching a SpatioTemporal Asset Catalog.
 #[serde_with::serde_as]
-#[derive(Serialize, Deserialize, IntoParams, Default, Debug)]
+#[derive(Serialize, Deserialize, IntoParams, Default, Debug, Clone)]
 #[into_params(parameter_in = Query)]
 pub struct SearchParams {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -42,6 +47,37 @@ pub struct SearchParams {
     )]
     #[param(style = Form, explode = false, required = false)]
     pub collections: Vec<String>,
+    /// Comma separated list of the properties to include in the features.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<StringWithSeparator::<CommaSeparator, String>>")]
+    #[param(value_type = String, nullable = false, required = false)]
+    pub properties: Option<Vec<String>>,
+    /// Omit the geometry of the features
+    #[serde(
+        default,
+        rename = "skipGeometry",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[param(rename = "skipGeometry", value_type = bool, nullable = false, required = false)]
+    pub skip_geometry: Option<bool>,
+    /// Comma separated list of fields to sort by, each prefixed with
+    /// `+` (ascending, default) or `-` (descending), e.g. `-properties.datetime,id`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "StringWithSeparator::<CommaSeparator, SortBy>")]
+    #[param(value_type = String, nullable = false, required = false)]
+    pub sortby: Vec<SortBy>,
+    /// Comma separated list of fields to include, or to exclude if prefixed
+    /// with `-` (STAC fields extension), e.g. `properties.name,-geometry`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[param(value_type = String, nullable = false, required = false)]
+    pub fields: Option<Fields>,
+    /// The encoding of the response, taking precedence over the `Accept`
+    /// header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(nullable = false)]
+    pub f: Option<String>,
 }
 
 impl SearchParams {
@@ -99,6 +135,12 @@ impl SearchParams {
         self.collections = collections.into_iter().map(|c| c.to_string()).collect();
         self
     }
+
+    /// Set the `sortby` property
+    pub fn with_sortby(mut self, sortby: impl IntoIterator<Item = SortBy>) -> Self {
+        self.sortby = sortby.into_iter().collect();
+        self
+    }
 }
 
 /// Search body for searching a SpatioTemporal Asset Catalog.
@@ -119,6 +161,88 @@ pub struct SearchBody {
     pub ids: Vec<String>,
     #[serde(default)]
     pub collections: Vec<String>,
+    #[serde(default)]
+    pub sortby: Vec<SortBy>,
+    #[serde(default)]
+    pub fields: Option<Fields>,
+}
+
+/// Fields to include or exclude (STAC fields extension)
+///
+/// Only the properties of items can be selected, by including
+/// `properties.<name>`, and the geometry can be excluded. Other fields are
+/// always returned, including them has no effect.
+#[derive(Serialize, Deserialize, ToSchema, Default, Debug, Clone, PartialEq, Eq)]
+pub struct Fields {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Fields {
+    /// Select the properties and geometry of the search results
+    #[allow(clippy::result_large_err)]
+    pub fn apply(&self, params: &mut SearchParams) -> Result<(), Exception> {
+        let mut properties = Vec::new();
+        let mut all_properties = false;
+        for field in &self.include {
+            match field.strip_prefix("properties.") {
+                Some(property) => properties.push(property.to_owned()),
+                None => all_properties |= field == "properties",
+            }
+        }
+        for field in &self.exclude {
+            match field.as_str() {
+                // includes take precedence
+                field if self.include.iter().any(|include| include == field) => {}
+                "geometry" => params.skip_geometry = Some(true),
+                field => {
+                    return Err(Exception::new_from_status(400)
+                        .detail(format!("Field {field} can not be excluded")));
+                }
+            }
+        }
+        if !properties.is_empty() && !all_properties {
+            params.properties = Some(properties);
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Fields {
+    type Err = Exception;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = Fields::default();
+        // an unencoded `+` in a query string is decoded as space
+        for field in s
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+        {
+            match field.strip_prefix('-') {
+                Some(field) => fields.exclude.push(field.to_owned()),
+                None => fields
+                    .include
+                    .push(field.strip_prefix('+').unwrap_or(field).to_owned()),
+            }
+        }
+        Ok(fields)
+    }
+}
+
+impl fmt::Display for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = self
+            .include
+            .iter()
+            .cloned()
+            .chain(self.exclude.iter().map(|field| format!("-{field}")))
+            .collect();
+        write!(f, "{}", fields.join(","))
+    }
 }
 
 impl From<SearchBody> for SearchParams {
@@ -131,6 +255,57 @@ impl From<SearchBody> for SearchParams {
             intersects: body.intersects,
             ids: body.ids,
             collections: body.collections,
+            properties: None,
+            skip_geometry: None,
+            sortby: body.sortby,
+            fields: body.fields,
+            f: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let params: SearchParams =
+            serde_qs::from_str("fields=properties.name,+properties.datetime,-geometry").unwrap();
+        let fields = params.fields.clone().unwrap();
+        assert_eq!(fields.include, ["properties.name", "properties.datetime"]);
+        assert_eq!(fields.exclude, ["geometry"]);
+        assert_eq!(
+            fields.to_string(),
+            "properties.name,properties.datetime,-geometry"
+        );
+
+        let mut params = SearchParams::new();
+        fields.apply(&mut params).unwrap();
+        assert_eq!(
+            params.properties,
+            Some(vec!["name".to_string(), "datetime".to_string()])
+        );
+        assert_eq!(params.skip_geometry, Some(true));
+
+        // fields that are always returned
+        let fields: Fields =
+            "id,type,geometry,bbox,assets,links,collection,properties.name,-geometry"
+                .parse()
+                .unwrap();
+        let mut params = SearchParams::new();
+        fields.apply(&mut params).unwrap();
+        assert_eq!(params.properties, Some(vec!["name".to_string()]));
+        assert_eq!(params.skip_geometry, None);
+
+        let fields: Fields = "properties,properties.name".parse().unwrap();
+        let mut params = SearchParams::new();
+        fields.apply(&mut params).unwrap();
+        assert_eq!(params.properties, None);
+
+        for fields in ["-assets", "-properties.name"] {
+            let fields: Fields = fields.parse().unwrap();
+            assert!(fields.apply(&mut SearchParams::new()).is_err());
         }
     }
 }
@@ -97,6 +97,12 @@ pub struct TileQuery {
     )]
     #[param(style = Form, explode = false, required = false)]
     pub collections: Vec<String>,
+    /// Name of the geometry of the features that is rendered, one of the
+    /// geometry columns of the collections. Collections without this
+    /// geometry are rendered with their primary geometry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[param(nullable = false)]
+    pub geometry: Option<String>,
     // /// Retrieve only part of the data by slicing or trimming along one or more
     // /// axis For trimming: {axisAbbrev}({low}:{high}) (preserves dimensionality)
     // /// An asterisk (*) can be used instead of {low} or {high} to indicate the